@@ -0,0 +1,4 @@
+// Embedded migrations must be recompiled whenever they change
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+}
@@ -0,0 +1,28 @@
+CREATE TABLE subjects(
+       id BIGINT NOT NULL UNIQUE PRIMARY KEY,
+       title TEXT NOT NULL,
+       gang TEXT NOT NULL,
+       optional BIGINT NOT NULL
+);
+
+CREATE TABLE meetings(
+       id BIGINT NOT NULL UNIQUE PRIMARY KEY,
+       name TEXT NOT NULL,
+       gang TEXT NOT NULL,
+       link TEXT NOT NULL
+);
+
+CREATE TABLE schedule(
+       day BIGINT NOT NULL,
+       repeat BIGINT NOT NULL,
+       slot BIGINT NOT NULL,
+       subject_id BIGINT NOT NULL,
+       FOREIGN KEY(subject_id) REFERENCES subjects(id)
+);
+
+CREATE TABLE assigned(
+       meeting_id BIGINT NOT NULL,
+       subject_id BIGINT NOT NULL,
+       FOREIGN KEY(meeting_id) REFERENCES meetings(id),
+       FOREIGN KEY(subject_id) REFERENCES subjects(id)
+);
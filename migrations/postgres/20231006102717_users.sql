@@ -0,0 +1,4 @@
+CREATE TABLE users(
+       chat_id BIGINT NOT NULL UNIQUE PRIMARY KEY,
+       gang TEXT NOT NULL
+);
@@ -0,0 +1,65 @@
+CREATE TABLE institutions(
+       id BIGINT NOT NULL UNIQUE PRIMARY KEY,
+       code TEXT NOT NULL UNIQUE,
+       name TEXT NOT NULL,
+       timezone TEXT NOT NULL
+);
+
+INSERT INTO institutions(id, code, name, timezone) VALUES(0, 'default', 'Default', 'Europe/Kiev');
+
+CREATE TABLE bells(
+       institution_id BIGINT NOT NULL,
+       slot BIGINT NOT NULL,
+       starts TEXT NOT NULL,
+       ends TEXT NOT NULL,
+       PRIMARY KEY(institution_id, slot),
+       FOREIGN KEY(institution_id) REFERENCES institutions(id)
+);
+
+CREATE TABLE gangs(
+       institution_id BIGINT NOT NULL,
+       name TEXT NOT NULL,
+       PRIMARY KEY(institution_id, name),
+       FOREIGN KEY(institution_id) REFERENCES institutions(id)
+);
+
+CREATE TABLE admins(
+       institution_id BIGINT NOT NULL,
+       chat_id BIGINT NOT NULL,
+       PRIMARY KEY(institution_id, chat_id),
+       FOREIGN KEY(institution_id) REFERENCES institutions(id)
+);
+
+ALTER TABLE schedule DROP CONSTRAINT schedule_subject_id_fkey;
+ALTER TABLE assigned DROP CONSTRAINT assigned_meeting_id_fkey;
+ALTER TABLE assigned DROP CONSTRAINT assigned_subject_id_fkey;
+
+ALTER TABLE subjects DROP CONSTRAINT subjects_pkey;
+ALTER TABLE subjects ADD COLUMN institution_id BIGINT NOT NULL DEFAULT 0 REFERENCES institutions(id);
+ALTER TABLE subjects ALTER COLUMN institution_id DROP DEFAULT;
+ALTER TABLE subjects ADD PRIMARY KEY(institution_id, id);
+
+ALTER TABLE meetings DROP CONSTRAINT meetings_pkey;
+ALTER TABLE meetings ADD COLUMN institution_id BIGINT NOT NULL DEFAULT 0 REFERENCES institutions(id);
+ALTER TABLE meetings ALTER COLUMN institution_id DROP DEFAULT;
+ALTER TABLE meetings ADD PRIMARY KEY(institution_id, id);
+
+ALTER TABLE schedule ADD COLUMN institution_id BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE schedule ALTER COLUMN institution_id DROP DEFAULT;
+ALTER TABLE schedule ADD FOREIGN KEY(institution_id, subject_id) REFERENCES subjects(institution_id, id);
+
+ALTER TABLE assigned ADD COLUMN institution_id BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE assigned ALTER COLUMN institution_id DROP DEFAULT;
+ALTER TABLE assigned ADD FOREIGN KEY(institution_id, meeting_id) REFERENCES meetings(institution_id, id);
+ALTER TABLE assigned ADD FOREIGN KEY(institution_id, subject_id) REFERENCES subjects(institution_id, id);
+
+ALTER TABLE calendar DROP CONSTRAINT calendar_pkey;
+ALTER TABLE calendar ADD COLUMN institution_id BIGINT NOT NULL DEFAULT 0 REFERENCES institutions(id);
+ALTER TABLE calendar ALTER COLUMN institution_id DROP DEFAULT;
+ALTER TABLE calendar ADD PRIMARY KEY(institution_id, date);
+
+ALTER TABLE users ADD COLUMN institution_id BIGINT NOT NULL DEFAULT 0 REFERENCES institutions(id);
+
+INSERT INTO gangs(institution_id, name)
+       SELECT DISTINCT 0, gang FROM subjects
+       UNION SELECT DISTINCT 0, gang FROM users;
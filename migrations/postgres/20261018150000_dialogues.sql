@@ -0,0 +1,4 @@
+CREATE TABLE dialogues(
+       chat_id BIGINT NOT NULL UNIQUE PRIMARY KEY,
+       state TEXT NOT NULL
+);
@@ -0,0 +1,5 @@
+CREATE TABLE pinned(
+       chat_id BIGINT NOT NULL UNIQUE PRIMARY KEY,
+       message_id BIGINT NOT NULL,
+       date TEXT NOT NULL
+);
@@ -0,0 +1,33 @@
+-- An empty gang grants the role over the whole institution
+CREATE TABLE roles(
+       institution_id BIGINT NOT NULL,
+       gang TEXT NOT NULL,
+       chat_id BIGINT NOT NULL,
+       role TEXT NOT NULL,
+       PRIMARY KEY(institution_id, gang, chat_id),
+       FOREIGN KEY(institution_id) REFERENCES institutions(id)
+);
+
+INSERT INTO roles(institution_id, gang, chat_id, role)
+       SELECT institution_id, '', chat_id, 'owner' FROM admins;
+
+DROP TABLE admins;
+
+-- A NULL subject cancels the classes of the slot
+CREATE TABLE changes(
+       institution_id BIGINT NOT NULL,
+       gang TEXT NOT NULL,
+       date TEXT NOT NULL,
+       slot BIGINT NOT NULL,
+       subject_id BIGINT,
+       PRIMARY KEY(institution_id, gang, date, slot),
+       FOREIGN KEY(institution_id) REFERENCES institutions(id)
+);
+
+CREATE TABLE rooms(
+       institution_id BIGINT NOT NULL,
+       subject_id BIGINT NOT NULL,
+       room TEXT NOT NULL,
+       PRIMARY KEY(institution_id, subject_id),
+       FOREIGN KEY(institution_id, subject_id) REFERENCES subjects(institution_id, id)
+);
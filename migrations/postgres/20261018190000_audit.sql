@@ -0,0 +1,24 @@
+-- Append-only: entries are never updated nor deleted. Values are empty
+-- when there was none before or after the change.
+CREATE TABLE audit(
+       id BIGSERIAL PRIMARY KEY,
+       made_at TEXT NOT NULL,
+       source TEXT NOT NULL,
+       actor TEXT NOT NULL,
+       institution_id BIGINT,
+       action TEXT NOT NULL,
+       target TEXT NOT NULL,
+       before_value TEXT NOT NULL,
+       after_value TEXT NOT NULL
+);
+
+CREATE INDEX audit_by_institution ON audit(institution_id, id);
+
+CREATE FUNCTION audit_append_only() RETURNS trigger AS $$
+BEGIN
+       RAISE EXCEPTION 'audit is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_append_only BEFORE UPDATE OR DELETE ON audit
+       FOR EACH ROW EXECUTE FUNCTION audit_append_only();
@@ -0,0 +1,5 @@
+CREATE TABLE uploads(
+       chat_id BIGINT NOT NULL UNIQUE PRIMARY KEY,
+       name TEXT NOT NULL,
+       content TEXT NOT NULL
+);
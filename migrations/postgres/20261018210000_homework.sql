@@ -0,0 +1,22 @@
+-- Shared homework is seen by the whole group of the subject, the rest only
+-- by the chat that added it
+CREATE TABLE homework(
+       id BIGSERIAL PRIMARY KEY,
+       institution_id BIGINT NOT NULL,
+       subject_id BIGINT NOT NULL,
+       due TEXT NOT NULL,
+       content TEXT NOT NULL,
+       author BIGINT NOT NULL,
+       shared BIGINT NOT NULL,
+       reminded BIGINT NOT NULL DEFAULT 0,
+       FOREIGN KEY(institution_id, subject_id) REFERENCES subjects(institution_id, id)
+);
+
+CREATE INDEX homework_by_due ON homework(institution_id, due);
+
+CREATE TABLE homework_done(
+       homework_id BIGINT NOT NULL,
+       chat_id BIGINT NOT NULL,
+       PRIMARY KEY(homework_id, chat_id),
+       FOREIGN KEY(homework_id) REFERENCES homework(id)
+);
@@ -0,0 +1,16 @@
+-- One-off events of the exam session, at a local time of the institution.
+-- The countdown is how many days before the exam the last reminder went out,
+-- -1 before the first one and 0 once there are no more to send
+CREATE TABLE exams(
+       id BIGSERIAL PRIMARY KEY,
+       institution_id BIGINT NOT NULL,
+       subject_id BIGINT NOT NULL,
+       kind TEXT NOT NULL,
+       starts TEXT NOT NULL,
+       room TEXT NOT NULL,
+       lecturer TEXT NOT NULL,
+       countdown BIGINT NOT NULL DEFAULT -1,
+       FOREIGN KEY(institution_id, subject_id) REFERENCES subjects(institution_id, id)
+);
+
+CREATE INDEX exams_by_start ON exams(institution_id, starts);
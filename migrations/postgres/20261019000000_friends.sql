@@ -0,0 +1,9 @@
+-- Telegram username of a private chat, lowercase, to be found by
+ALTER TABLE users ADD COLUMN username TEXT;
+
+-- Chats that share their free time with others, linked both ways once agreed
+CREATE TABLE friends(
+       chat_id BIGINT NOT NULL,
+       friend_id BIGINT NOT NULL,
+       PRIMARY KEY(chat_id, friend_id)
+);
@@ -0,0 +1,8 @@
+-- Optional subjects a chat takes
+CREATE TABLE enrollments(
+       chat_id BIGINT NOT NULL,
+       institution_id BIGINT NOT NULL,
+       subject_id BIGINT NOT NULL,
+       PRIMARY KEY(chat_id, institution_id, subject_id),
+       FOREIGN KEY(institution_id, subject_id) REFERENCES subjects(institution_id, id)
+);
@@ -0,0 +1,13 @@
+-- Classes a user marked as attended (1) or missed (0), and how many
+-- absences per subject an institution allows
+CREATE TABLE attendance(
+       chat_id BIGINT NOT NULL,
+       institution_id BIGINT NOT NULL,
+       subject_id BIGINT NOT NULL,
+       date TEXT NOT NULL,
+       attended BIGINT NOT NULL,
+       PRIMARY KEY(chat_id, institution_id, subject_id, date),
+       FOREIGN KEY(institution_id, subject_id) REFERENCES subjects(institution_id, id)
+);
+
+ALTER TABLE institutions ADD COLUMN absences BIGINT NOT NULL DEFAULT 3;
@@ -0,0 +1,2 @@
+-- Month and day the autumn and spring semesters of an institution start on
+ALTER TABLE institutions ADD COLUMN semesters TEXT NOT NULL DEFAULT '09-01 02-01';
@@ -0,0 +1,4 @@
+CREATE TABLE calendar(
+       date TEXT NOT NULL UNIQUE PRIMARY KEY,
+       title TEXT NOT NULL
+);
@@ -0,0 +1,107 @@
+CREATE TABLE institutions(
+       id INT NOT NULL UNIQUE PRIMARY KEY,
+       code TEXT NOT NULL UNIQUE,
+       name TEXT NOT NULL,
+       timezone TEXT NOT NULL
+);
+
+INSERT INTO institutions(id, code, name, timezone) VALUES(0, 'default', 'Default', 'Europe/Kiev');
+
+CREATE TABLE bells(
+       institution_id INT NOT NULL,
+       slot INT NOT NULL,
+       starts TEXT NOT NULL,
+       ends TEXT NOT NULL,
+       PRIMARY KEY(institution_id, slot),
+       FOREIGN KEY(institution_id) REFERENCES institutions(id)
+);
+
+CREATE TABLE gangs(
+       institution_id INT NOT NULL,
+       name TEXT NOT NULL,
+       PRIMARY KEY(institution_id, name),
+       FOREIGN KEY(institution_id) REFERENCES institutions(id)
+);
+
+CREATE TABLE admins(
+       institution_id INT NOT NULL,
+       chat_id INT NOT NULL,
+       PRIMARY KEY(institution_id, chat_id),
+       FOREIGN KEY(institution_id) REFERENCES institutions(id)
+);
+
+-- SQLite can't change primary and foreign keys in place, so the scoped tables are rebuilt
+ALTER TABLE assigned RENAME TO assigned_old;
+ALTER TABLE schedule RENAME TO schedule_old;
+ALTER TABLE meetings RENAME TO meetings_old;
+ALTER TABLE subjects RENAME TO subjects_old;
+ALTER TABLE calendar RENAME TO calendar_old;
+
+CREATE TABLE subjects(
+       institution_id INT NOT NULL,
+       id INT NOT NULL,
+       title TEXT NOT NULL,
+       gang TEXT NOT NULL,
+       optional INT NOT NULL,
+       PRIMARY KEY(institution_id, id),
+       FOREIGN KEY(institution_id) REFERENCES institutions(id)
+);
+
+CREATE TABLE meetings(
+       institution_id INT NOT NULL,
+       id INT NOT NULL,
+       name TEXT NOT NULL,
+       gang TEXT NOT NULL,
+       link TEXT NOT NULL,
+       PRIMARY KEY(institution_id, id),
+       FOREIGN KEY(institution_id) REFERENCES institutions(id)
+);
+
+CREATE TABLE schedule(
+       institution_id INT NOT NULL,
+       day INT NOT NULL,
+       repeat INT NOT NULL,
+       slot INT NOT NULL,
+       subject_id INT NOT NULL,
+       FOREIGN KEY(institution_id, subject_id) REFERENCES subjects(institution_id, id)
+);
+
+CREATE TABLE assigned(
+       institution_id INT NOT NULL,
+       meeting_id INT NOT NULL,
+       subject_id INT NOT NULL,
+       FOREIGN KEY(institution_id, meeting_id) REFERENCES meetings(institution_id, id),
+       FOREIGN KEY(institution_id, subject_id) REFERENCES subjects(institution_id, id)
+);
+
+CREATE TABLE calendar(
+       institution_id INT NOT NULL,
+       date TEXT NOT NULL,
+       title TEXT NOT NULL,
+       PRIMARY KEY(institution_id, date),
+       FOREIGN KEY(institution_id) REFERENCES institutions(id)
+);
+
+INSERT INTO subjects(institution_id, id, title, gang, optional)
+       SELECT 0, id, title, gang, optional FROM subjects_old;
+INSERT INTO meetings(institution_id, id, name, gang, link)
+       SELECT 0, id, name, gang, link FROM meetings_old;
+INSERT INTO schedule(institution_id, day, repeat, slot, subject_id)
+       SELECT 0, day, repeat, slot, subject_id FROM schedule_old;
+INSERT INTO assigned(institution_id, meeting_id, subject_id)
+       SELECT 0, meeting_id, subject_id FROM assigned_old;
+INSERT INTO calendar(institution_id, date, title)
+       SELECT 0, date, title FROM calendar_old;
+
+DROP TABLE assigned_old;
+DROP TABLE schedule_old;
+DROP TABLE meetings_old;
+DROP TABLE subjects_old;
+DROP TABLE calendar_old;
+
+-- SQLite only allows adding a reference without a default, so users are not constrained here
+ALTER TABLE users ADD COLUMN institution_id INT NOT NULL DEFAULT 0;
+
+INSERT INTO gangs(institution_id, name)
+       SELECT DISTINCT 0, gang FROM subjects
+       UNION SELECT DISTINCT 0, gang FROM users;
@@ -0,0 +1,4 @@
+CREATE TABLE dialogues(
+       chat_id INT NOT NULL UNIQUE PRIMARY KEY,
+       state TEXT NOT NULL
+);
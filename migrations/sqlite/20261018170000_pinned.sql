@@ -0,0 +1,5 @@
+CREATE TABLE pinned(
+       chat_id INT NOT NULL UNIQUE PRIMARY KEY,
+       message_id INT NOT NULL,
+       date TEXT NOT NULL
+);
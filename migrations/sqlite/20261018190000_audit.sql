@@ -0,0 +1,25 @@
+-- Append-only: entries are never updated nor deleted. Values are empty
+-- when there was none before or after the change.
+CREATE TABLE audit(
+       id INTEGER PRIMARY KEY AUTOINCREMENT,
+       made_at TEXT NOT NULL,
+       source TEXT NOT NULL,
+       actor TEXT NOT NULL,
+       institution_id INT,
+       action TEXT NOT NULL,
+       target TEXT NOT NULL,
+       before_value TEXT NOT NULL,
+       after_value TEXT NOT NULL
+);
+
+CREATE INDEX audit_by_institution ON audit(institution_id, id);
+
+CREATE TRIGGER audit_no_update BEFORE UPDATE ON audit
+BEGIN
+       SELECT RAISE(ABORT, 'audit is append-only');
+END;
+
+CREATE TRIGGER audit_no_delete BEFORE DELETE ON audit
+BEGIN
+       SELECT RAISE(ABORT, 'audit is append-only');
+END;
@@ -0,0 +1,5 @@
+CREATE TABLE uploads(
+       chat_id INT NOT NULL UNIQUE PRIMARY KEY,
+       name TEXT NOT NULL,
+       content TEXT NOT NULL
+);
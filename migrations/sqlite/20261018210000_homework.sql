@@ -0,0 +1,22 @@
+-- Shared homework is seen by the whole group of the subject, the rest only
+-- by the chat that added it
+CREATE TABLE homework(
+       id INTEGER PRIMARY KEY AUTOINCREMENT,
+       institution_id INT NOT NULL,
+       subject_id INT NOT NULL,
+       due TEXT NOT NULL,
+       content TEXT NOT NULL,
+       author INT NOT NULL,
+       shared INT NOT NULL,
+       reminded INT NOT NULL DEFAULT 0,
+       FOREIGN KEY(institution_id, subject_id) REFERENCES subjects(institution_id, id)
+);
+
+CREATE INDEX homework_by_due ON homework(institution_id, due);
+
+CREATE TABLE homework_done(
+       homework_id INT NOT NULL,
+       chat_id INT NOT NULL,
+       PRIMARY KEY(homework_id, chat_id),
+       FOREIGN KEY(homework_id) REFERENCES homework(id)
+);
@@ -0,0 +1,9 @@
+-- Private notes of a chat on the subjects of its group
+CREATE TABLE notes(
+       chat_id INT NOT NULL,
+       institution_id INT NOT NULL,
+       subject_id INT NOT NULL,
+       content TEXT NOT NULL,
+       PRIMARY KEY(chat_id, institution_id, subject_id),
+       FOREIGN KEY(institution_id, subject_id) REFERENCES subjects(institution_id, id)
+);
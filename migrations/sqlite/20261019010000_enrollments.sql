@@ -0,0 +1,8 @@
+-- Optional subjects a chat takes
+CREATE TABLE enrollments(
+       chat_id INT NOT NULL,
+       institution_id INT NOT NULL,
+       subject_id INT NOT NULL,
+       PRIMARY KEY(chat_id, institution_id, subject_id),
+       FOREIGN KEY(institution_id, subject_id) REFERENCES subjects(institution_id, id)
+);
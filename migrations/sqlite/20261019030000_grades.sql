@@ -0,0 +1,15 @@
+-- Points a chat logged for subjects, out of the points a subject is worth
+CREATE TABLE grades(
+       id INTEGER PRIMARY KEY AUTOINCREMENT,
+       chat_id INT NOT NULL,
+       institution_id INT NOT NULL,
+       subject_id INT NOT NULL,
+       date TEXT NOT NULL,
+       points INT NOT NULL,
+       comment TEXT NOT NULL,
+       FOREIGN KEY(institution_id, subject_id) REFERENCES subjects(institution_id, id)
+);
+
+CREATE INDEX grades_by_chat ON grades(chat_id, institution_id);
+
+ALTER TABLE subjects ADD COLUMN points INT NOT NULL DEFAULT 100;
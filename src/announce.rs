@@ -0,0 +1,334 @@
+use crate::bot::{failed, language, user, Error};
+use crate::data::Group;
+use crate::i18n::Msg;
+use crate::store::ScheduleStore;
+use std::future::Future;
+use std::time::Duration;
+use teloxide::{prelude::*, types::InlineKeyboardMarkup, ApiError, RequestError};
+use tokio::sync::{mpsc, oneshot};
+
+/// Time between messages of the [`Outbox`], well within Telegram's limit of
+/// about 30 messages a second.
+pub const PACE: Duration = Duration::from_millis(50);
+
+/// Longest announcement taken, leaving room for its heading in a message.
+const TEXT_LENGTH: usize = 3500;
+
+/// What came of sending a message to a chat.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Outcome {
+    Delivered,
+    /// Not delivered this time, e.g. because of the network.
+    Failed,
+    /// The chat can't be reached anymore: the bot was blocked or kicked, or
+    /// the account was deleted.
+    Gone,
+}
+
+/// A message for the [`Outbox`] to send, with buttons under it or none.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Letter {
+    pub chat_id: ChatId,
+    pub text: String,
+    pub keyboard: Option<InlineKeyboardMarkup>,
+}
+
+/// Plain `text` to `chat_id`.
+impl From<(ChatId, String)> for Letter {
+    fn from((chat_id, text): (ChatId, String)) -> Self {
+        Letter {
+            chat_id,
+            text,
+            keyboard: None,
+        }
+    }
+}
+
+struct Queued {
+    letter: Letter,
+    sent: oneshot::Sender<Outcome>,
+}
+
+/// Messages waiting to be sent one at a time, [`PACE`] apart, shared by all
+/// broadcasts so that they don't add up to a burst.
+#[derive(Clone)]
+pub struct Outbox {
+    queue: mpsc::UnboundedSender<Queued>,
+}
+
+impl Outbox {
+    /// Starts sending queued messages with `send`, `pace` apart.
+    pub fn spawn<F, Fut>(pace: Duration, send: F) -> Outbox
+    where
+        F: Fn(Letter) -> Fut + Send + 'static,
+        Fut: Future<Output = Outcome> + Send,
+    {
+        let (queue, mut letters) = mpsc::unbounded_channel::<Queued>();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(pace);
+            while let Some(queued) = letters.recv().await {
+                interval.tick().await;
+                let outcome = send(queued.letter).await;
+                let _ = queued.sent.send(outcome);
+            }
+        });
+        Outbox { queue }
+    }
+
+    /// Queues `letters` and waits until all of them are sent, with the
+    /// outcome for every chat.
+    pub async fn deliver(
+        &self,
+        letters: impl IntoIterator<Item = impl Into<Letter>>,
+    ) -> Vec<(ChatId, Outcome)> {
+        let mut pending = vec![];
+        for letter in letters {
+            let letter = letter.into();
+            let chat_id = letter.chat_id;
+            let (sent, outcome) = oneshot::channel();
+            // Only fails once the sending task is gone with the runtime
+            let _ = self.queue.send(Queued { letter, sent });
+            pending.push((chat_id, outcome));
+        }
+        let mut outcomes = vec![];
+        for (chat_id, outcome) in pending {
+            outcomes.push((chat_id, outcome.await.unwrap_or(Outcome::Failed)));
+        }
+        outcomes
+    }
+}
+
+/// Sends `letter` with `bot`, waiting out Telegram's flood control once.
+pub async fn send(bot: &Bot, letter: Letter) -> Outcome {
+    let Letter {
+        chat_id,
+        text,
+        keyboard,
+    } = letter;
+    let request = || {
+        let request = bot.send_message(chat_id, text.clone());
+        match keyboard.clone() {
+            Some(keyboard) => request.reply_markup(keyboard),
+            None => request,
+        }
+    };
+    let mut result = request().await;
+    if let Err(RequestError::RetryAfter(wait)) = result {
+        tokio::time::sleep(wait).await;
+        result = request().await;
+    }
+    match result {
+        Ok(_) => Outcome::Delivered,
+        Err(err) if gone(&err) => Outcome::Gone,
+        Err(err) => {
+            log::error!("Failed to deliver message to {}: {:?}", chat_id, err);
+            Outcome::Failed
+        }
+    }
+}
+
+/// Whether `err` means the chat won't ever get a message from the bot again:
+/// the bot was blocked or kicked, or the chat was deactivated. A chat that
+/// isn't found may only be a passing failure, so it isn't taken for gone.
+fn gone(err: &RequestError) -> bool {
+    use ApiError::*;
+    matches!(
+        err,
+        RequestError::Api(
+            BotBlocked | BotKicked | BotKickedFromSupergroup | UserDeactivated | GroupDeactivated
+        )
+    )
+}
+
+/// An announcement ready to go out: its group and the message to every chat
+/// of the group, each in the language of the chat.
+pub struct Announcement {
+    pub group: Group,
+    pub letters: Vec<(ChatId, String)>,
+}
+
+/// `/announce <group> <text>` sent to `chat_id`, as long as `sender` may edit
+/// the timetable of the group in the institution of the chat.
+pub async fn prepare<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+    sender: ChatId,
+    args: &str,
+) -> Result<Announcement, Error> {
+    let (group, text) = args
+        .trim()
+        .split_once(char::is_whitespace)
+        .ok_or(Error::Usage(chat_id, "announce"))?;
+    let text = text.trim();
+    if text.chars().count() > TEXT_LENGTH {
+        return Err(Error::TooLong(chat_id, TEXT_LENGTH));
+    }
+    let group = Group::try_from(group).map_err(|_| Error::InvalidGroup(chat_id, group.into()))?;
+
+    let (_, institution) = user(store, chat_id).await?;
+    let groups = store
+        .get_groups(institution.id)
+        .await
+        .map_err(failed(chat_id, "get groups"))?;
+    if !groups.contains(&group) {
+        return Err(Error::InvalidGroup(chat_id, group.to_string()));
+    }
+    let grants = store
+        .get_grants(&sender)
+        .await
+        .map_err(failed(chat_id, "get grants"))?;
+    if !grants.iter().any(|g| g.covers(institution.id, &group)) {
+        return Err(Error::NotEditor(chat_id));
+    }
+
+    let chats = store
+        .get_chats(institution.id, &group)
+        .await
+        .map_err(failed(chat_id, "get chats"))?;
+    let mut letters = vec![];
+    for chat in chats {
+        let language = language(store, chat, None).await;
+        let message = language.tr(Msg::Announcement {
+            group: group.as_str(),
+            text,
+        });
+        letters.push((chat, message));
+    }
+    Ok(Announcement { group, letters })
+}
+
+/// Forgets the chats found gone while delivering, returning how many there were.
+pub async fn clean_up<S: ScheduleStore>(
+    store: &S,
+    outcomes: &[(ChatId, Outcome)],
+) -> sqlx::Result<usize> {
+    let mut removed = 0;
+    for (chat_id, _) in outcomes.iter().filter(|(_, o)| *o == Outcome::Gone) {
+        log::trace!("Forgetting unreachable chat {}", chat_id);
+        store.remove_user(chat_id).await?;
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+/// How many of `outcomes` were delivered and how many failed.
+pub fn tally(outcomes: &[(ChatId, Outcome)]) -> (usize, usize) {
+    let delivered = outcomes
+        .iter()
+        .filter(|(_, o)| *o == Outcome::Delivered)
+        .count();
+    (delivered, outcomes.len() - delivered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Grant, Role, User};
+    use crate::i18n::Language;
+    use crate::store::MemoryStore;
+    use std::sync::{Arc, Mutex};
+
+    const HEADMAN: ChatId = ChatId(7);
+    const STUDENT: ChatId = ChatId(8);
+    const BLOCKED: ChatId = ChatId(9);
+
+    fn k25() -> Group {
+        Group::try_from("K-25").unwrap()
+    }
+
+    /// K-25 with a headman who may edit it and two students, one of whom
+    /// talks Ukrainian.
+    async fn store() -> MemoryStore {
+        let store = MemoryStore::new();
+        store.add_group(0, &k25()).await.unwrap();
+        let user = User {
+            institution: 0,
+            group: k25(),
+        };
+        for id in [HEADMAN, STUDENT, BLOCKED] {
+            store.add_user(&id, &user).await.unwrap();
+        }
+        store
+            .set_language(&BLOCKED, Some(Language::Uk))
+            .await
+            .unwrap();
+        let grant = Grant {
+            institution: 0,
+            group: Some(k25()),
+            role: Role::Editor,
+        };
+        store.add_grant(&HEADMAN, &grant).await.unwrap();
+        store
+    }
+
+    #[tokio::test]
+    async fn only_editors_announce() {
+        let store = store().await;
+        let announcement = prepare(&store, HEADMAN, HEADMAN, "K-25 No 3rd pair today")
+            .await
+            .unwrap();
+        assert_eq!(announcement.letters.len(), 3);
+        assert_eq!(
+            announcement.letters[1],
+            (
+                STUDENT,
+                "Announcement for K-25:\n\nNo 3rd pair today".into()
+            )
+        );
+        assert!(announcement.letters[2].1.starts_with("Оголошення"));
+
+        let refused = prepare(&store, STUDENT, STUDENT, "K-25 Party!").await;
+        assert!(matches!(refused, Err(Error::NotEditor(_))));
+        let unknown = prepare(&store, HEADMAN, HEADMAN, "K-26 Hi").await;
+        assert!(matches!(unknown, Err(Error::InvalidGroup(_, _))));
+        let empty = prepare(&store, HEADMAN, HEADMAN, "K-25").await;
+        assert!(matches!(empty, Err(Error::Usage(_, "announce"))));
+    }
+
+    #[tokio::test]
+    async fn outbox_delivers_in_order_and_gone_chats_are_forgotten() {
+        let store = store().await;
+        let sent = Arc::new(Mutex::new(vec![]));
+        let outbox = Outbox::spawn(Duration::from_millis(1), {
+            let sent = sent.clone();
+            move |letter: Letter| {
+                let chat_id = letter.chat_id;
+                sent.lock().unwrap().push(letter);
+                async move {
+                    match chat_id {
+                        BLOCKED => Outcome::Gone,
+                        _ => Outcome::Delivered,
+                    }
+                }
+            }
+        });
+
+        let announcement = prepare(&store, HEADMAN, HEADMAN, "K-25 Hi").await.unwrap();
+        let outcomes = outbox.deliver(announcement.letters).await;
+        assert_eq!(
+            outcomes,
+            vec![
+                (HEADMAN, Outcome::Delivered),
+                (STUDENT, Outcome::Delivered),
+                (BLOCKED, Outcome::Gone)
+            ]
+        );
+        assert_eq!(sent.lock().unwrap().len(), 3);
+        assert_eq!(tally(&outcomes), (2, 1));
+
+        assert_eq!(clean_up(&store, &outcomes).await.unwrap(), 1);
+        assert_eq!(
+            store.get_chats(0, &k25()).await.unwrap(),
+            vec![HEADMAN, STUDENT]
+        );
+    }
+
+    #[test]
+    fn blocked_bots_are_gone() {
+        assert!(gone(&RequestError::Api(ApiError::BotBlocked)));
+        assert!(gone(&RequestError::Api(ApiError::UserDeactivated)));
+        assert!(!gone(&RequestError::RetryAfter(Duration::from_secs(1))));
+        assert!(!gone(&RequestError::Api(ApiError::MessageIsTooLong)));
+        assert!(!gone(&RequestError::Api(ApiError::ChatNotFound)));
+    }
+}
@@ -0,0 +1,575 @@
+use crate::clock::Clock;
+use crate::data::{Day, Group, Institution, Repeat, Slot};
+use crate::expr::Expr;
+use crate::store::ScheduleStore;
+use crate::timetable;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use chrono::{Datelike, Duration, Months, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+/// What the handlers share: the data and the time to resolve dates by.
+pub struct Api<S> {
+    pub store: S,
+    pub clock: Arc<dyn Clock>,
+}
+
+/// Why a request can't be answered, sent back as `{"error": "..."}`.
+#[derive(Debug)]
+pub enum Failure {
+    NotFound(String),
+    BadRequest(String),
+    Internal,
+}
+
+impl From<sqlx::Error> for Failure {
+    fn from(err: sqlx::Error) -> Self {
+        match err {
+            sqlx::Error::RowNotFound => Failure::NotFound("Not found".into()),
+            err => {
+                log::error!("Failed to answer an API request: {:?}", err);
+                Failure::Internal
+            }
+        }
+    }
+}
+
+impl IntoResponse for Failure {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            Failure::NotFound(message) => (StatusCode::NOT_FOUND, message),
+            Failure::BadRequest(message) => (StatusCode::BAD_REQUEST, message),
+            Failure::Internal => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Something went wrong".into(),
+            ),
+        };
+        (status, Json(serde_json::json!({ "error": message }))).into_response()
+    }
+}
+
+#[derive(Serialize)]
+pub struct InstitutionInfo {
+    pub code: String,
+    pub name: String,
+    pub timezone: String,
+}
+
+#[derive(Serialize)]
+pub struct Subject {
+    pub id: i64,
+    pub title: String,
+    pub optional: bool,
+    pub room: Option<String>,
+}
+
+/// The subjects a group has in a slot, with the bell times of the slot.
+#[derive(Serialize)]
+pub struct Class {
+    pub slot: u8,
+    pub starts: String,
+    pub ends: String,
+    pub subjects: Vec<Subject>,
+}
+
+/// Classes of a group on a date, none on holidays and weekends.
+#[derive(Serialize)]
+pub struct Timetable {
+    pub date: String,
+    pub weekday: String,
+    /// `odd` or `even`, which classes of the date follow.
+    pub week: &'static str,
+    pub holiday: Option<String>,
+    pub classes: Vec<Class>,
+}
+
+/// Classes of a group from Monday to Friday.
+#[derive(Serialize)]
+pub struct Week {
+    pub monday: String,
+    pub week: &'static str,
+    pub days: Vec<Timetable>,
+}
+
+/// The slot going on where the institution is, if any.
+#[derive(Serialize)]
+pub struct Now {
+    pub date: String,
+    pub time: String,
+    pub week: &'static str,
+    pub slot: Option<u8>,
+}
+
+#[derive(Serialize)]
+pub struct Exam {
+    pub subject_id: i64,
+    pub title: String,
+    pub kind: String,
+    pub starts: String,
+    pub room: String,
+    pub lecturer: String,
+}
+
+/// `?date=`, an ISO date or anything `/today` understands, e.g. `tomorrow`.
+#[derive(Deserialize)]
+pub struct At {
+    pub date: Option<String>,
+}
+
+/// `odd` or `even`, the week of `date` where the institution is.
+pub fn parity(date: NaiveDate, institution: &Institution) -> &'static str {
+    match Repeat::from(&timetable::noon(date, &institution.timezone)) {
+        Repeat::Odd => "odd",
+        _ => "even",
+    }
+}
+
+/// Today where the institution is.
+pub fn today(clock: &dyn Clock, institution: &Institution) -> NaiveDate {
+    clock
+        .now()
+        .with_timezone(&institution.timezone)
+        .date_naive()
+}
+
+/// Monday of the week of `date`, or `date` itself at the start of the calendar.
+pub fn monday(date: NaiveDate) -> NaiveDate {
+    date.checked_sub_signed(Duration::days(date.weekday().num_days_from_monday().into()))
+        .unwrap_or(date)
+}
+
+/// Years either way from today that dates are looked up for.
+const REACH: u32 = 100;
+
+/// The date `value` stands for, from today on where the institution is,
+/// within [`REACH`] of today.
+pub fn resolve(
+    value: Option<&str>,
+    clock: &dyn Clock,
+    institution: &Institution,
+) -> Result<NaiveDate, Failure> {
+    let today = today(clock, institution);
+    let Some(value) = value else {
+        return Ok(today);
+    };
+    let invalid = || Failure::BadRequest(format!("Invalid date: {}", value));
+    let date = match value.parse() {
+        Ok(date) => date,
+        Err(_) => match Expr::try_from(value) {
+            Ok(Expr {
+                slot: None,
+                date: Some(when),
+                words,
+            }) if words.is_empty() => when.resolve(today).ok_or_else(invalid)?,
+            _ => return Err(invalid()),
+        },
+    };
+    if within_reach(date, today) {
+        Ok(date)
+    } else {
+        Err(Failure::BadRequest(format!("Date out of range: {}", value)))
+    }
+}
+
+/// Whether `date` is within [`REACH`] of `today`.
+pub fn within_reach(date: NaiveDate, today: NaiveDate) -> bool {
+    let reach = Months::new(12 * REACH);
+    let after = today
+        .checked_sub_months(reach)
+        .is_none_or(|from| from <= date);
+    let before = today.checked_add_months(reach).is_none_or(|to| date <= to);
+    after && before
+}
+
+/// The institution and one of its groups, by code and name.
+pub async fn group<S: ScheduleStore>(
+    store: &S,
+    code: &str,
+    name: &str,
+) -> Result<(Institution, Group), Failure> {
+    let institution = store
+        .find_institution(code)
+        .await
+        .map_err(|_| Failure::NotFound(format!("Unknown institution: {}", code)))?;
+    let groups = store.get_groups(institution.id).await?;
+    match Group::try_from(name) {
+        Ok(group) if groups.contains(&group) => Ok((institution, group)),
+        _ => Err(Failure::NotFound(format!("Unknown group: {}", name))),
+    }
+}
+
+/// Classes of `group` on `date`, changes made by editors and holidays included.
+pub async fn day<S: ScheduleStore>(
+    store: &S,
+    institution: &Institution,
+    group: &Group,
+    date: NaiveDate,
+) -> sqlx::Result<Timetable> {
+    let holiday = store.get_holiday(institution.id, date).await?;
+    let mut classes = vec![];
+    if holiday.is_none() {
+        for slot in Slot::ALL {
+            let found = timetable::classes(store, institution, group, date, slot).await?;
+            if found.is_empty() {
+                continue;
+            }
+            let mut subjects = vec![];
+            for s in found {
+                subjects.push(Subject {
+                    room: store.get_room(institution.id, s.id).await?,
+                    id: s.id,
+                    title: s.title,
+                    optional: s.optional,
+                });
+            }
+            let (starts, ends) = institution.bells.times(slot);
+            classes.push(Class {
+                slot: slot as u8,
+                starts: starts.format("%H:%M").to_string(),
+                ends: ends.format("%H:%M").to_string(),
+                subjects,
+            });
+        }
+    }
+    Ok(Timetable {
+        date: date.to_string(),
+        weekday: date.weekday().to_string(),
+        week: parity(date, institution),
+        holiday: holiday.map(|h| h.title),
+        classes,
+    })
+}
+
+/// Classes of `group` in the week of `date`, Monday to Friday.
+pub async fn week<S: ScheduleStore>(
+    store: &S,
+    institution: &Institution,
+    group: &Group,
+    date: NaiveDate,
+) -> sqlx::Result<Week> {
+    let monday = monday(date);
+    let mut days = vec![];
+    let dates = (0..5).map_while(|offset| monday.checked_add_signed(Duration::days(offset)));
+    for date in dates {
+        days.push(day(store, institution, group, date).await?);
+    }
+    Ok(Week {
+        monday: monday.to_string(),
+        week: parity(monday, institution),
+        days,
+    })
+}
+
+/// Routes of the API, all under `/api`.
+pub fn router<S: ScheduleStore>(api: Arc<Api<S>>) -> Router {
+    Router::new()
+        .route("/api/institutions", get(institutions::<S>))
+        .route("/api/:institution/groups", get(groups::<S>))
+        .route("/api/:institution/now", get(now::<S>))
+        .route(
+            "/api/:institution/groups/:group/subjects",
+            get(subjects::<S>),
+        )
+        .route("/api/:institution/groups/:group/day", get(get_day::<S>))
+        .route("/api/:institution/groups/:group/week", get(get_week::<S>))
+        .route("/api/:institution/groups/:group/exams", get(exams::<S>))
+        .with_state(api)
+}
+
+/// Serves `router` on `address` for as long as the program runs.
+pub async fn serve(address: SocketAddr, router: Router) {
+    log::trace!("Listening on {}", address);
+    if let Err(err) = axum::Server::bind(&address)
+        .serve(router.into_make_service())
+        .await
+    {
+        log::error!("HTTP server failed: {:?}", err);
+    }
+}
+
+async fn institutions<S: ScheduleStore>(
+    State(api): State<Arc<Api<S>>>,
+) -> Result<Json<Vec<InstitutionInfo>>, Failure> {
+    let institutions = api.store.get_institutions().await?;
+    Ok(Json(
+        institutions
+            .into_iter()
+            .map(|i| InstitutionInfo {
+                code: i.code,
+                name: i.name,
+                timezone: i.timezone.name().into(),
+            })
+            .collect(),
+    ))
+}
+
+async fn groups<S: ScheduleStore>(
+    State(api): State<Arc<Api<S>>>,
+    Path(code): Path<String>,
+) -> Result<Json<Vec<String>>, Failure> {
+    let institution = api
+        .store
+        .find_institution(&code)
+        .await
+        .map_err(|_| Failure::NotFound(format!("Unknown institution: {}", code)))?;
+    let groups = api.store.get_groups(institution.id).await?;
+    Ok(Json(groups.iter().map(String::from).collect()))
+}
+
+async fn now<S: ScheduleStore>(
+    State(api): State<Arc<Api<S>>>,
+    Path(code): Path<String>,
+) -> Result<Json<Now>, Failure> {
+    let institution = api
+        .store
+        .find_institution(&code)
+        .await
+        .map_err(|_| Failure::NotFound(format!("Unknown institution: {}", code)))?;
+    let now = api
+        .clock
+        .now()
+        .with_timezone(&institution.timezone)
+        .naive_local();
+    // No classes go on at weekends and on holidays, whatever the bells say
+    let weekday = Day::try_from(&timetable::noon(now.date(), &institution.timezone)).is_ok();
+    let holiday = api.store.get_holiday(institution.id, now.date()).await?;
+    let slot = Slot::ALL.into_iter().find(|slot| {
+        let (starts, ends) = institution.bells.times(*slot);
+        weekday && holiday.is_none() && starts <= now.time() && now.time() < ends
+    });
+    Ok(Json(Now {
+        date: now.date().to_string(),
+        time: now.time().format("%H:%M").to_string(),
+        week: parity(now.date(), &institution),
+        slot: slot.map(|s| s as u8),
+    }))
+}
+
+async fn subjects<S: ScheduleStore>(
+    State(api): State<Arc<Api<S>>>,
+    Path((code, name)): Path<(String, String)>,
+) -> Result<Json<Vec<Subject>>, Failure> {
+    let (institution, group) = group(&api.store, &code, &name).await?;
+    let mut subjects = vec![];
+    for s in api.store.get_all_subjects(institution.id).await? {
+        if s.group != group {
+            continue;
+        }
+        subjects.push(Subject {
+            room: api.store.get_room(institution.id, s.id).await?,
+            id: s.id,
+            title: s.title,
+            optional: s.optional,
+        });
+    }
+    Ok(Json(subjects))
+}
+
+async fn get_day<S: ScheduleStore>(
+    State(api): State<Arc<Api<S>>>,
+    Path((code, name)): Path<(String, String)>,
+    Query(at): Query<At>,
+) -> Result<Json<Timetable>, Failure> {
+    let (institution, group) = group(&api.store, &code, &name).await?;
+    let date = resolve(at.date.as_deref(), api.clock.as_ref(), &institution)?;
+    Ok(Json(day(&api.store, &institution, &group, date).await?))
+}
+
+async fn get_week<S: ScheduleStore>(
+    State(api): State<Arc<Api<S>>>,
+    Path((code, name)): Path<(String, String)>,
+    Query(at): Query<At>,
+) -> Result<Json<Week>, Failure> {
+    let (institution, group) = group(&api.store, &code, &name).await?;
+    let date = resolve(at.date.as_deref(), api.clock.as_ref(), &institution)?;
+    Ok(Json(week(&api.store, &institution, &group, date).await?))
+}
+
+/// Exams of the group still to come, by start.
+async fn exams<S: ScheduleStore>(
+    State(api): State<Arc<Api<S>>>,
+    Path((code, name)): Path<(String, String)>,
+) -> Result<Json<Vec<Exam>>, Failure> {
+    let (institution, group) = group(&api.store, &code, &name).await?;
+    let now = api
+        .clock
+        .now()
+        .with_timezone(&institution.timezone)
+        .naive_local();
+    let subjects = api.store.get_all_subjects(institution.id).await?;
+    let exams = api
+        .store
+        .get_exams(institution.id)
+        .await?
+        .into_iter()
+        .filter(|e| e.starts > now)
+        .filter_map(|e| {
+            let subject = subjects
+                .iter()
+                .find(|s| s.id == e.subject_id && s.group == group)?;
+            Some(Exam {
+                subject_id: e.subject_id,
+                title: subject.title.clone(),
+                kind: String::from(&e.kind),
+                starts: e.starts.format("%Y-%m-%dT%H:%M").to_string(),
+                room: e.room,
+                lecturer: e.lecturer,
+            })
+        })
+        .collect();
+    Ok(Json(exams))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::TestClock;
+    use crate::data::{Day, Holiday, Schedule, Subject};
+    use crate::store::MemoryStore;
+    use axum::body::Body;
+    use axum::http::Request;
+    use chrono::{TimeZone, Utc};
+    use tower::ServiceExt;
+
+    /// K-25 with Algebra first thing on Mondays of odd weeks and Logic in
+    /// room 204 in the third slot every Monday, on Monday, 9 Oct 2023, 9:00,
+    /// with the clock to move.
+    async fn setup() -> (Router, Arc<TestClock>) {
+        let store = MemoryStore::new();
+        let group = Group::try_from("K-25").unwrap();
+        store.add_group(0, &group).await.unwrap();
+        let classes = [
+            (0, "Algebra", Repeat::Odd, Slot::I),
+            (1, "Logic", Repeat::Both, Slot::III),
+        ];
+        for (id, title, repeat, slot) in classes {
+            let subject = Subject {
+                id,
+                title: title.into(),
+                group: group.clone(),
+                optional: false,
+            };
+            store.add_subject(0, &subject).await.unwrap();
+            let class = Schedule {
+                subject_id: id,
+                day: Day::Mon,
+                repeat,
+                slot,
+            };
+            store.add_schedule(0, &class).await.unwrap();
+        }
+        store.set_room(0, 1, "204").await.unwrap();
+        let holiday = Holiday {
+            date: NaiveDate::from_ymd_opt(2023, 10, 23).unwrap(),
+            title: "Day off".into(),
+        };
+        store.add_holiday(0, &holiday).await.unwrap();
+        let clock = Arc::new(TestClock::new(
+            Utc.with_ymd_and_hms(2023, 10, 9, 6, 0, 0).unwrap(),
+        ));
+        let api = Api {
+            store,
+            clock: clock.clone(),
+        };
+        (router(Arc::new(api)), clock)
+    }
+
+    async fn fetch(router: &Router, uri: &str) -> (StatusCode, serde_json::Value) {
+        let request = Request::get(uri).body(Body::empty()).unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn day_and_week_of_a_group() {
+        let router = setup().await.0;
+        let (status, body) = fetch(&router, "/api/default/groups/K-25/day").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "date": "2023-10-09",
+                "weekday": "Mon",
+                "week": "odd",
+                "holiday": null,
+                "classes": [
+                    {
+                        "slot": 1,
+                        "starts": "08:40",
+                        "ends": "10:15",
+                        "subjects": [
+                            {"id": 0, "title": "Algebra", "optional": false, "room": null}
+                        ]
+                    },
+                    {
+                        "slot": 3,
+                        "starts": "12:20",
+                        "ends": "13:55",
+                        "subjects": [
+                            {"id": 1, "title": "Logic", "optional": false, "room": "204"}
+                        ]
+                    }
+                ]
+            })
+        );
+
+        let (_, body) = fetch(&router, "/api/default/groups/K-25/week?date=next%20mon").await;
+        assert_eq!(body["monday"], "2023-10-16");
+        assert_eq!(body["week"], "even");
+        assert_eq!(body["days"][0]["classes"].as_array().unwrap().len(), 1);
+
+        let (_, body) = fetch(&router, "/api/default/groups/K-25/day?date=2023-10-23").await;
+        assert_eq!(body["holiday"], "Day off");
+        assert_eq!(body["classes"], serde_json::json!([]));
+
+        let (_, body) = fetch(&router, "/api/default/now").await;
+        assert_eq!(body["slot"], 1);
+        let (_, body) = fetch(&router, "/api/default/groups").await;
+        assert_eq!(body, serde_json::json!(["K-25"]));
+    }
+
+    #[tokio::test]
+    async fn no_slot_at_weekends_and_on_holidays() {
+        let (router, clock) = setup().await;
+        // 9:00 on Saturday, 14 Oct, and on Monday, 23 Oct, a day off
+        for day in [14, 23] {
+            clock.set(Utc.with_ymd_and_hms(2023, 10, day, 6, 0, 0).unwrap());
+            let (_, body) = fetch(&router, "/api/default/now").await;
+            assert_eq!(body["time"], "09:00");
+            assert_eq!(body["slot"], serde_json::Value::Null, "{}", day);
+        }
+    }
+
+    #[tokio::test]
+    async fn unknown_names_are_not_found() {
+        let router = setup().await.0;
+        for uri in ["/api/nowhere/groups", "/api/default/groups/K-99/day"] {
+            let (status, _) = fetch(&router, uri).await;
+            assert_eq!(status, StatusCode::NOT_FOUND, "{}", uri);
+        }
+        let (status, body) = fetch(&router, "/api/default/groups/K-25/day?date=someday").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "Invalid date: someday");
+
+        for date in [
+            "-262143-01-01",
+            "%2B200000000000000",
+            "2124-01-01",
+            "1923-10-08",
+        ] {
+            let uri = format!("/api/default/groups/K-25/week?date={}", date);
+            let (status, body) = fetch(&router, &uri).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", date);
+            assert!(body["error"].is_string(), "{}", date);
+        }
+        let (status, _) = fetch(&router, "/api/default/groups/K-25/day?date=1923-10-09").await;
+        assert_eq!(status, StatusCode::OK);
+    }
+}
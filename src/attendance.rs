@@ -0,0 +1,485 @@
+use crate::announce::{self, Letter, Outbox};
+use crate::bot::{failed, language, user, Error, Reply};
+use crate::clock::Clock;
+use crate::data::{Attendance, Slot, Subject};
+use crate::homework;
+use crate::i18n::{Language, Msg};
+use crate::store::ScheduleStore;
+use crate::timetable;
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use std::sync::Arc;
+use std::time::Duration;
+use teloxide::types::{ChatId, InlineKeyboardButton, InlineKeyboardMarkup};
+
+/// Callback data prefix of the attendance buttons under a class.
+pub const PREFIX: &str = "at:";
+
+/// How often classes are checked for having ended.
+const INTERVAL: Duration = Duration::from_secs(60);
+
+/// ✅ and ❌ buttons for every subject of a class on `date`, a row each.
+pub fn keyboard(language: Language, subjects: &[Subject], date: NaiveDate) -> InlineKeyboardMarkup {
+    let rows = subjects.iter().map(|s| {
+        let title = match subjects.len() {
+            1 => String::new(),
+            _ => format!("{}: ", s.title),
+        };
+        let data = |mark: &str| format!("{}{}:{}:{}", PREFIX, s.id, date, mark);
+        vec![
+            InlineKeyboardButton::callback(language.tr(Msg::Attended(&title)), data("y")),
+            InlineKeyboardButton::callback(language.tr(Msg::Missed(&title)), data("n")),
+        ]
+    });
+    InlineKeyboardMarkup::new(rows)
+}
+
+/// Warning once `missed` classes leave one absence or none out of `limit`.
+fn warning(language: Language, title: &str, missed: usize, limit: i64) -> Option<String> {
+    let missed = missed as i64;
+    (missed > 0 && missed + 1 >= limit).then(|| {
+        language.tr(Msg::AbsenceWarning {
+            title,
+            missed: &missed.to_string(),
+            limit: &limit.to_string(),
+        })
+    })
+}
+
+/// Subjects of the institution of `chat_id`, those of its group first, and
+/// the institution.
+async fn subjects<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+) -> Result<(Vec<Subject>, Vec<Subject>, i64), Error> {
+    let (user, institution) = user(store, chat_id).await?;
+    let (mine, others) = store
+        .get_all_subjects(institution.id)
+        .await
+        .map_err(failed(chat_id, "get subjects"))?
+        .into_iter()
+        .partition(|s| s.group == user.group);
+    Ok((mine, others, institution.id))
+}
+
+/// Handles a press on the buttons under a class in `chat_id`, marking it for
+/// `sender`, who pressed it. Comes back with a short confirmation, along with
+/// a warning when a miss brings them close to the limit of absences.
+pub async fn mark<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+    sender: ChatId,
+    language: Language,
+    data: &str,
+) -> Result<String, Error> {
+    let parsed = data.strip_prefix(PREFIX).and_then(|rest| {
+        let mut parts = rest.split(':');
+        let subject_id = parts.next()?.parse().ok()?;
+        let date = parts.next()?.parse().ok()?;
+        let attended = match parts.next()? {
+            "y" => true,
+            "n" => false,
+            _ => return None,
+        };
+        Some(Attendance {
+            subject_id,
+            date,
+            attended,
+        })
+    });
+    let (mine, _, institution) = subjects(store, chat_id).await?;
+    let found = parsed.and_then(|value| {
+        let subject = mine.iter().find(|s| s.id == value.subject_id)?;
+        Some((value, subject))
+    });
+    let Some((value, subject)) = found else {
+        return Ok(language.tr(Msg::AttendanceExpired));
+    };
+    store
+        .set_attendance(&sender, institution, &value)
+        .await
+        .map_err(failed(chat_id, "set attendance"))?;
+
+    let (title, date) = (&subject.title, &language.day(value.date, "%d.%m"));
+    if value.attended {
+        return Ok(language.tr(Msg::MarkedAttended { title, date }));
+    }
+    let mut text = language.tr(Msg::MarkedMissed { title, date });
+    let missed = store
+        .get_attendance(&sender, institution)
+        .await
+        .map_err(failed(chat_id, "get attendance"))?
+        .iter()
+        .filter(|a| a.subject_id == subject.id && !a.attended)
+        .count();
+    let limit = store
+        .get_absences(institution)
+        .await
+        .map_err(failed(chat_id, "get absences"))?;
+    if let Some(warning) = warning(language, title, missed, limit) {
+        text.push('\n');
+        text.push_str(&warning);
+    }
+    Ok(text)
+}
+
+/// Reminders of the classes that ended after `since` and by `now`, with the
+/// ✅ and ❌ buttons, for every private chat of their group that marked a
+/// class before, each in the language of the chat. Optional subjects are
+/// left to the chats enrolled in them.
+pub async fn reminders<S: ScheduleStore>(
+    store: &S,
+    since: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> sqlx::Result<Vec<Letter>> {
+    let mut letters = vec![];
+    for institution in store.get_institutions().await? {
+        let timezone = institution.timezone;
+        let date = now.with_timezone(&timezone).date_naive();
+        let ended: Vec<Slot> = Slot::ALL
+            .into_iter()
+            .filter(|slot| {
+                let end = date.and_time(institution.bells.times(*slot).1);
+                timezone
+                    .from_local_datetime(&end)
+                    .earliest()
+                    .is_some_and(|end| since < end && end <= now)
+            })
+            .collect();
+        if ended.is_empty() || store.get_holiday(institution.id, date).await?.is_some() {
+            continue;
+        }
+        let tracking = store.get_tracking(institution.id).await?;
+        if tracking.is_empty() {
+            continue;
+        }
+
+        for group in store.get_groups(institution.id).await? {
+            let mut classes = vec![];
+            for slot in &ended {
+                let subjects = timetable::classes(store, &institution, &group, date, *slot).await?;
+                if !subjects.is_empty() {
+                    classes.push(subjects);
+                }
+            }
+            if classes.is_empty() {
+                continue;
+            }
+            for chat in store.get_chats(institution.id, &group).await? {
+                let tracked = tracking.iter().find(|(c, _)| *c == chat && c.is_user());
+                let Some((_, enrolled)) = tracked else {
+                    continue;
+                };
+                let language = language(store, chat, None).await;
+                for subjects in &classes {
+                    let taken: Vec<Subject> = subjects
+                        .iter()
+                        .filter(|s| !s.optional || enrolled.contains(&s.id))
+                        .cloned()
+                        .collect();
+                    if taken.is_empty() {
+                        continue;
+                    }
+                    let titles: Vec<&str> = taken.iter().map(|s| s.title.as_str()).collect();
+                    letters.push(Letter {
+                        chat_id: chat,
+                        text: language.tr(Msg::ClassOver(&titles.join(", "))),
+                        keyboard: Some(keyboard(language, &taken, date)),
+                    });
+                }
+            }
+        }
+    }
+    Ok(letters)
+}
+
+/// Sends class reminders through `outbox` for as long as the bot runs.
+pub async fn watch<S: ScheduleStore>(store: Arc<S>, clock: Arc<dyn Clock>, outbox: Outbox) {
+    let mut interval = tokio::time::interval(INTERVAL);
+    let mut since = clock.now();
+    loop {
+        interval.tick().await;
+        let now = clock.now();
+        let letters = match reminders(store.as_ref(), since, now).await {
+            Ok(letters) => letters,
+            Err(err) => {
+                log::error!("Failed to gather class reminders: {:?}", err);
+                continue;
+            }
+        };
+        since = now;
+        if letters.is_empty() {
+            continue;
+        }
+        let outcomes = outbox.deliver(letters).await;
+        if let Err(err) = announce::clean_up(store.as_ref(), &outcomes).await {
+            log::error!("Failed to forget unreachable chats: {:?}", err);
+        }
+    }
+}
+
+/// `/attendance [subject]`: classes `sender` attended and missed, by subject,
+/// or the dates of those of one subject, with warnings of the subjects where
+/// they are close to the limit of absences. Answered in a private chat only,
+/// for no one else to see.
+pub async fn execute<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+    sender: ChatId,
+    language: Language,
+    args: &str,
+) -> Result<Reply, Error> {
+    if chat_id != sender {
+        return Err(Error::OnlyPrivate(chat_id));
+    }
+    let (mine, others, institution) = subjects(store, chat_id).await?;
+    let words: Vec<&str> = args.split_whitespace().collect();
+    let only = match homework::find(&mine, &words) {
+        _ if words.is_empty() => None,
+        Some((subject, [])) => Some(subject.id),
+        Some((_, [word, ..])) => return Err(Error::InvalidArgument(chat_id, word.to_string())),
+        None => return Err(Error::InvalidSubject(chat_id, words[0].to_string())),
+    };
+    let marks: Vec<Attendance> = store
+        .get_attendance(&sender, institution)
+        .await
+        .map_err(failed(chat_id, "get attendance"))?
+        .into_iter()
+        .filter(|a| only.is_none_or(|id| a.subject_id == id))
+        .collect();
+    if marks.is_empty() {
+        return Ok(Reply::Text(language.tr(Msg::NoAttendance)));
+    }
+    let limit = store
+        .get_absences(institution)
+        .await
+        .map_err(failed(chat_id, "get absences"))?;
+
+    let mut message = language.tr(Msg::AttendanceList(&limit.to_string()));
+    let mut warnings = vec![];
+    // Marks come by subject, so those of a subject are next to each other
+    for marks in marks.chunk_by(|a, b| a.subject_id == b.subject_id) {
+        let title = mine
+            .iter()
+            .chain(&others)
+            .find(|s| s.id == marks[0].subject_id)
+            .map_or("", |s| s.title.as_str());
+        let missed = marks.iter().filter(|a| !a.attended).count();
+        message.push('\n');
+        message.push_str(&language.tr(Msg::AttendanceLine {
+            title,
+            attended: &(marks.len() - missed).to_string(),
+            missed: &missed.to_string(),
+        }));
+        if only.is_some() {
+            for mark in marks {
+                let sign = if mark.attended { "✅" } else { "❌" };
+                message.push_str(&format!("\n{} {}", sign, language.day(mark.date, "%d.%m")));
+            }
+        }
+        warnings.extend(warning(language, title, missed, limit));
+    }
+    if !warnings.is_empty() {
+        message.push_str("\n\n");
+        message.push_str(&warnings.join("\n"));
+    }
+    Ok(Reply::Text(message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Day, Group, Repeat, Schedule, User};
+    use crate::store::MemoryStore;
+    use teloxide::types::InlineKeyboardButtonKind;
+
+    const CHAT: ChatId = ChatId(7);
+
+    /// K-25 with Algebra and Logic, the chat in it.
+    async fn store() -> MemoryStore {
+        let store = MemoryStore::new();
+        let group = Group::try_from("K-25").unwrap();
+        store.add_group(0, &group).await.unwrap();
+        for (id, title) in [(0, "Algebra"), (1, "Logic")] {
+            let subject = Subject {
+                id,
+                title: title.into(),
+                group: group.clone(),
+                optional: false,
+            };
+            store.add_subject(0, &subject).await.unwrap();
+        }
+        join(&store, CHAT).await;
+        store
+    }
+
+    /// Puts subject `subject_id` first thing on every Monday.
+    async fn monday(store: &MemoryStore, subject_id: i64) {
+        let class = Schedule {
+            subject_id,
+            day: Day::Mon,
+            repeat: Repeat::Both,
+            slot: Slot::I,
+        };
+        store.add_schedule(0, &class).await.unwrap();
+    }
+
+    async fn join(store: &MemoryStore, chat: ChatId) {
+        let user = User {
+            institution: 0,
+            group: Group::try_from("K-25").unwrap(),
+        };
+        store.add_user(&chat, &user).await.unwrap();
+    }
+
+    fn data(keyboard: &InlineKeyboardMarkup, column: usize) -> String {
+        match &keyboard.inline_keyboard[0][column].kind {
+            InlineKeyboardButtonKind::CallbackData(data) => data.clone(),
+            other => panic!("Not a callback button {:?}", other),
+        }
+    }
+
+    fn text(reply: Result<Reply, Error>) -> String {
+        match reply.unwrap() {
+            Reply::Text(text) => text,
+            other => panic!("Unexpected reply {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn misses_count_towards_the_limit() {
+        let store = store().await;
+        store.set_absences(0, 2).await.unwrap();
+        let algebra = [store.get_subject(0, 0).await.unwrap()];
+        let press = |data: String| {
+            let store = &store;
+            async move { mark(store, CHAT, CHAT, Language::En, &data).await.unwrap() }
+        };
+        let on = |day| {
+            let date = NaiveDate::from_ymd_opt(2023, 10, day).unwrap();
+            keyboard(Language::En, &algebra, date)
+        };
+        assert_eq!(on(9).inline_keyboard[0][1].text, "❌ missed");
+
+        assert_eq!(
+            press(data(&on(9), 0)).await,
+            "Algebra on Mon 09.10: attended."
+        );
+        assert_eq!(
+            press(data(&on(16), 1)).await,
+            "Algebra on Mon 16.10: missed.\n⚠️ 1 of 2 absences allowed used in Algebra."
+        );
+        // Pressed twice, a class still counts once
+        assert_eq!(
+            press(data(&on(9), 0)).await,
+            "Algebra on Mon 09.10: attended."
+        );
+        assert_eq!(
+            press(format!("{}5:2023-10-09:y", PREFIX)).await,
+            "This button is no longer valid."
+        );
+
+        assert_eq!(
+            text(execute(&store, CHAT, CHAT, Language::En, "").await),
+            "Attendance, 2 absences allowed per subject:\n\
+             Algebra: ✅ 1, ❌ 1\n\n\
+             ⚠️ 1 of 2 absences allowed used in Algebra."
+        );
+        assert_eq!(
+            text(execute(&store, CHAT, CHAT, Language::En, "alg").await),
+            "Attendance, 2 absences allowed per subject:\n\
+             Algebra: ✅ 1, ❌ 1\n\
+             ✅ Mon 09.10\n\
+             ❌ Mon 16.10\n\n\
+             ⚠️ 1 of 2 absences allowed used in Algebra."
+        );
+        assert_eq!(
+            text(execute(&store, CHAT, CHAT, Language::En, "logic").await),
+            "No classes marked yet, use the buttons under /subject."
+        );
+        let group = ChatId(-100);
+        join(&store, group).await;
+        assert!(matches!(
+            execute(&store, group, CHAT, Language::En, "").await,
+            Err(Error::OnlyPrivate(chat)) if chat == group
+        ));
+    }
+
+    #[tokio::test]
+    async fn reminders_go_out_once_a_class_ends() {
+        let store = store().await;
+        monday(&store, 0).await;
+        // Another student who doesn't keep track, and a group chat
+        join(&store, ChatId(8)).await;
+        join(&store, ChatId(-100)).await;
+        let monday = NaiveDate::from_ymd_opt(2023, 10, 9).unwrap();
+        let algebra = [store.get_subject(0, 0).await.unwrap()];
+        mark(
+            &store,
+            CHAT,
+            CHAT,
+            Language::En,
+            &data(&keyboard(Language::En, &algebra, monday), 0),
+        )
+        .await
+        .unwrap();
+
+        // The first class ends at 10:15 in Kyiv
+        let at = |hour, minute| Utc.with_ymd_and_hms(2023, 10, 9, hour, minute, 0).unwrap();
+        assert!(reminders(&store, at(7, 0), at(7, 14))
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            reminders(&store, at(7, 14), at(7, 15)).await.unwrap(),
+            vec![Letter {
+                chat_id: CHAT,
+                text: "Algebra is over. Did you attend?".into(),
+                keyboard: Some(keyboard(Language::En, &algebra, monday)),
+            }]
+        );
+        assert!(reminders(&store, at(7, 15), at(8, 0))
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn reminders_of_electives_go_to_those_enrolled() {
+        let store = store().await;
+        let drawing = Subject {
+            id: 2,
+            title: "Drawing".into(),
+            group: Group::try_from("K-25").unwrap(),
+            optional: true,
+        };
+        store.add_subject(0, &drawing).await.unwrap();
+        monday(&store, 0).await;
+        monday(&store, 2).await;
+        let artist = ChatId(8);
+        join(&store, artist).await;
+        store.set_enrolled(&artist, 0, 2, true).await.unwrap();
+        let monday = NaiveDate::from_ymd_opt(2023, 10, 2).unwrap();
+        for chat in [CHAT, artist] {
+            let mark = Attendance {
+                subject_id: 0,
+                date: monday,
+                attended: true,
+            };
+            store.set_attendance(&chat, 0, &mark).await.unwrap();
+        }
+
+        let at = |hour, minute| Utc.with_ymd_and_hms(2023, 10, 9, hour, minute, 0).unwrap();
+        let letters = reminders(&store, at(7, 0), at(7, 15)).await.unwrap();
+        let texts: Vec<(ChatId, &str)> = letters
+            .iter()
+            .map(|l| (l.chat_id, l.text.as_str()))
+            .collect();
+        assert_eq!(
+            texts,
+            vec![
+                (CHAT, "Algebra is over. Did you attend?"),
+                (artist, "Algebra, Drawing is over. Did you attend?"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,240 @@
+use crate::data::{Group, Slot};
+use anyhow::anyhow;
+use chrono::{DateTime, NaiveDate, Utc};
+use std::future::Future;
+use teloxide::types::ChatId;
+
+/// Where a change to the data is made from.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Source {
+    Bot,
+    Cli,
+    Import,
+}
+
+impl TryFrom<&str> for Source {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "bot" => Ok(Source::Bot),
+            "cli" => Ok(Source::Cli),
+            "import" => Ok(Source::Import),
+            other => Err(anyhow!("Not a source: {}", other)),
+        }
+    }
+}
+
+impl From<&Source> for String {
+    fn from(value: &Source) -> Self {
+        match value {
+            Source::Bot => "bot".into(),
+            Source::Cli => "cli".into(),
+            Source::Import => "import".into(),
+        }
+    }
+}
+
+/// Who changes the data, and from where.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Actor {
+    pub source: Source,
+    pub name: String,
+}
+
+impl Actor {
+    /// A user, or a chat writing on its own behalf, talking to the bot.
+    pub fn chat(id: ChatId) -> Actor {
+        Actor {
+            source: Source::Bot,
+            name: id.to_string(),
+        }
+    }
+
+    /// A user importing a timetable file they sent to the bot.
+    pub fn import(id: ChatId) -> Actor {
+        Actor {
+            source: Source::Import,
+            name: id.to_string(),
+        }
+    }
+
+    /// The bot on its own, e.g. moving pinned timetables on to a new day.
+    pub fn bot() -> Actor {
+        Actor {
+            source: Source::Bot,
+            name: "bot".into(),
+        }
+    }
+}
+
+tokio::task_local! {
+    static ACTOR: Actor;
+}
+
+/// Runs `f` with the changes it makes to the data attributed to `actor`.
+pub async fn acting<F: Future>(actor: Actor, f: F) -> F::Output {
+    ACTOR.scope(actor, f).await
+}
+
+/// Who the changes made right now are attributed to, the bot itself unless
+/// running within [`acting`].
+pub fn actor() -> Actor {
+    ACTOR
+        .try_with(Actor::clone)
+        .unwrap_or_else(|_| Actor::bot())
+}
+
+/// A change to the data, as kept in the append-only audit log.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Entry {
+    pub at: DateTime<Utc>,
+    pub source: Source,
+    pub actor: String,
+    /// The institution whose data changed, if the change concerns one.
+    pub institution: Option<i64>,
+    /// Name of the store method that made the change, e.g. `add_change`.
+    pub action: String,
+    /// What changed, e.g. `subject 3` or `chat 42`.
+    pub target: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+impl Entry {
+    /// An entry about a change made at `at` by the current [`actor`].
+    pub fn new(
+        at: DateTime<Utc>,
+        institution: Option<i64>,
+        action: &str,
+        target: String,
+        before: Option<String>,
+        after: Option<String>,
+    ) -> Entry {
+        let Actor { source, name } = actor();
+        Entry {
+            at,
+            source,
+            actor: name,
+            institution,
+            action: action.into(),
+            target,
+            before,
+            after,
+        }
+    }
+
+    /// An entry about a change to a user's own data, e.g. their notes, grades
+    /// or attendance. It names no institution, so that what the editors and
+    /// admins of the institution read of the log, like `/history` and
+    /// `setup audit <institution>`, leaves it out.
+    pub fn personal(
+        at: DateTime<Utc>,
+        action: &str,
+        target: String,
+        before: Option<String>,
+        after: Option<String>,
+    ) -> Entry {
+        Entry::new(at, None, action, target, before, after)
+    }
+}
+
+/// Actions that change the timetable of a group, as opposed to the settings
+/// and personal data of chats.
+pub const TIMETABLE: &[&str] = &[
+    "add_group",
+    "add_subject",
+    "add_schedule",
+    "import",
+    "add_meeting",
+    "assign",
+    "set_room",
+    "add_change",
+];
+
+/// `chat 42`, the way entries name a chat.
+pub fn chat(id: &ChatId) -> String {
+    format!("chat {}", id)
+}
+
+/// `chat 42 over K-25`, the way entries name the role of a chat.
+pub fn scope(id: &ChatId, group: Option<&Group>) -> String {
+    match group {
+        Some(group) => format!("{} over {}", chat(id), group),
+        None => format!("{} over all groups", chat(id)),
+    }
+}
+
+pub fn subject(id: i64) -> String {
+    format!("subject {}", id)
+}
+
+pub fn meeting(id: i64) -> String {
+    format!("meeting {}", id)
+}
+
+pub fn homework(id: i64) -> String {
+    format!("homework {}", id)
+}
+
+pub fn exam(id: i64) -> String {
+    format!("exam {}", id)
+}
+
+pub fn grade(id: i64) -> String {
+    format!("grade {}", id)
+}
+
+/// `set` or `cleared`, the way entries show a note without giving away its text.
+pub fn note(set: bool) -> String {
+    match set {
+        true => "set".into(),
+        false => "cleared".into(),
+    }
+}
+
+/// `K-25 2023-10-16 slot 1`, the way entries name a class.
+pub fn class(group: &Group, date: NaiveDate, slot: Slot) -> String {
+    format!("{} {} slot {}", group, date, slot as u8)
+}
+
+/// What a class is changed to: the subject taught instead, or `cancelled`.
+pub fn replaced(subject_id: Option<i64>) -> String {
+    subject_id.map_or("cancelled".into(), subject)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn entries_name_the_actor() {
+        let at = Utc::now();
+        assert_eq!(
+            Entry::new(at, None, "x", "y".into(), None, None).actor,
+            "bot"
+        );
+
+        let entry = acting(Actor::chat(ChatId(42)), async {
+            Entry::new(
+                at,
+                Some(0),
+                "set_room",
+                "subject 3".into(),
+                None,
+                Some("204".into()),
+            )
+        })
+        .await;
+        assert_eq!(entry.at, at);
+        assert_eq!(entry.actor, "42");
+        assert_eq!(entry.source, Source::Bot);
+        let entry = acting(Actor::import(ChatId(42)), async {
+            Entry::new(at, Some(0), "import", "K-25".into(), None, None)
+        })
+        .await;
+        assert_eq!((entry.source, entry.actor.as_str()), (Source::Import, "42"));
+        assert_eq!(Source::try_from("import").unwrap(), Source::Import);
+        assert_eq!(String::from(&Source::Cli), "cli");
+    }
+}
@@ -0,0 +1,36 @@
+use schedule_bot::api::{self, Api};
+use schedule_bot::clock::SystemClock;
+use schedule_bot::db::Database;
+use schedule_bot::web;
+use std::sync::Arc;
+
+const USAGE: &str = "Usage:
+    api <url> [address]";
+
+/// Serves the HTTP API and the timetable pages without the bot, on `127.0.0.1:8080` by default.
+#[tokio::main]
+async fn main() {
+    pretty_env_logger::init();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (url, address) = match &args[..] {
+        [url] => (url.as_str(), "127.0.0.1:8080"),
+        [url, address] => (url.as_str(), address.as_str()),
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
+    };
+    let address = address.parse().expect("Invalid address to listen on");
+    let db = Database::connect(url)
+        .await
+        .expect("Failed to connect to database");
+    db.migrate().await.expect("Failed to run migrations");
+
+    let api = Api {
+        store: db,
+        clock: Arc::new(SystemClock),
+    };
+    let api = Arc::new(api);
+    api::serve(address, api::router(api.clone()).merge(web::router(api))).await;
+}
@@ -1,19 +1,223 @@
-use schedule_bot::data::{unpack, Schedule, Subject};
-use sqlx::SqlitePool as Pool;
+use schedule_bot::audit::{self, Actor, Source};
+use schedule_bot::conflicts;
+use schedule_bot::data::{
+    unpack, Bells, Grant, Group, Institution, Role, Schedule, Semesters, Subject,
+};
+use schedule_bot::db::Database;
+use schedule_bot::store::ScheduleStore;
+use teloxide::types::ChatId;
+
+const USAGE: &str = "Usage:
+    setup <url> [institution]
+    setup institution <url> <code> <timezone> <name>
+    setup bells <url> <institution> <HH:MM-HH:MM> x4
+    setup absences <url> <institution> <count>
+    setup semesters <url> <institution> <autumn MM-DD> <spring MM-DD>
+    setup points <url> <institution> <subject id> <points>
+    setup admin <url> <institution> <chat id> [group]
+    setup audit <url> [institution]
+    setup lint <url> [institution]";
 
 #[tokio::main]
 async fn main() {
     pretty_env_logger::init();
 
-    // could've used Clap, but I literally need just 1 argument
-    let mut args = std::env::args();
-    args.next(); // skip the first element
-    let url = args.next().expect("Missing database url");
-    let pool = Pool::connect(&url)
+    // could've used Clap, but there are just a few positional arguments
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    audit::acting(actor(Source::Cli), run(&args)).await;
+}
+
+/// Changes made by the program are attributed to whoever runs it.
+fn actor(source: Source) -> Actor {
+    Actor {
+        source,
+        name: std::env::var("USER").unwrap_or_else(|_| "setup".into()),
+    }
+}
+
+async fn run(args: &[&str]) {
+    match args[..] {
+        ["institution", url, code, timezone, ref name @ ..] if !name.is_empty() => {
+            let db = connect(url).await;
+            let id = db
+                .get_institutions()
+                .await
+                .expect("Failed to get institutions")
+                .iter()
+                .map(|i| i.id + 1)
+                .max()
+                .unwrap_or(0);
+            let institution = Institution {
+                id,
+                code: code.into(),
+                name: name.join(" "),
+                timezone: timezone.parse().expect("Invalid timezone"),
+                bells: Bells::default(),
+            };
+            db.add_institution(&institution)
+                .await
+                .expect("Failed to add institution");
+            log::trace!("Added institution {} with id {}", code, id);
+        }
+        ["bells", url, code, ref ranges @ ..] => {
+            let bells = Bells::try_from(ranges.join(" ").as_str()).expect("Invalid bells");
+            let db = connect(url).await;
+            let institution = find(&db, code).await;
+            db.set_bells(institution.id, &bells)
+                .await
+                .expect("Failed to set bells");
+            log::trace!("Set bells of {}", code);
+        }
+        ["absences", url, code, count] => {
+            let count: i64 = count.parse().expect("Invalid count of absences");
+            let db = connect(url).await;
+            let institution = find(&db, code).await;
+            db.set_absences(institution.id, count)
+                .await
+                .expect("Failed to set absences");
+            log::trace!("Allowed {} absences per subject in {}", count, code);
+        }
+        ["semesters", url, code, autumn, spring] => {
+            let semesters = Semesters::try_from(format!("{} {}", autumn, spring).as_str())
+                .expect("Invalid semester starts");
+            let db = connect(url).await;
+            let institution = find(&db, code).await;
+            db.set_semesters(institution.id, &semesters)
+                .await
+                .expect("Failed to set semesters");
+            log::trace!("Set semesters of {}", code);
+        }
+        ["points", url, code, subject_id, points] => {
+            let subject_id: i64 = subject_id.parse().expect("Invalid subject id");
+            let points: i64 = points.parse().expect("Invalid points");
+            let db = connect(url).await;
+            let institution = find(&db, code).await;
+            db.set_max_points(institution.id, subject_id, points)
+                .await
+                .expect("Failed to set points");
+            log::trace!(
+                "Made subject {} of {} worth {} points",
+                subject_id,
+                code,
+                points
+            );
+        }
+        ["admin", url, code, chat_id, ref group @ ..] if group.len() <= 1 => {
+            let chat_id = ChatId(chat_id.parse().expect("Invalid chat id"));
+            let group = group
+                .first()
+                .map(|g| Group::try_from(*g).expect("Invalid group"));
+            let db = connect(url).await;
+            let institution = find(&db, code).await;
+            let grant = Grant {
+                institution: institution.id,
+                group,
+                role: Role::Owner,
+            };
+            db.add_grant(&chat_id, &grant)
+                .await
+                .expect("Failed to add admin");
+            log::trace!("Made {} an owner of {}", chat_id, code);
+        }
+        ["audit", url, ref code @ ..] if code.len() <= 1 => {
+            let db = connect(url).await;
+            let institution = match code.first() {
+                Some(code) => Some(find(&db, code).await.id),
+                None => None,
+            };
+            let entries = db
+                .get_audit(institution, &[], i64::MAX)
+                .await
+                .expect("Failed to get audit log");
+            // One JSON object per line, oldest first
+            for entry in entries {
+                let line = serde_json::json!({
+                    "at": entry.at.to_rfc3339(),
+                    "source": String::from(&entry.source),
+                    "actor": entry.actor,
+                    "institution": entry.institution,
+                    "action": entry.action,
+                    "target": entry.target,
+                    "before": entry.before,
+                    "after": entry.after,
+                });
+                println!("{}", line);
+            }
+        }
+        ["lint", url, ref code @ ..] if code.len() <= 1 => {
+            let db = connect(url).await;
+            let institutions = match code.first() {
+                Some(code) => vec![find(&db, code).await],
+                None => db
+                    .get_institutions()
+                    .await
+                    .expect("Failed to get institutions"),
+            };
+            let mut clashes = 0;
+            for institution in institutions {
+                let id = institution.id;
+                let subjects = db
+                    .get_all_subjects(id)
+                    .await
+                    .expect("Failed to get subjects");
+                let schedule = db.get_schedule(id).await.expect("Failed to get schedule");
+                let groups = db.get_groups(id).await.expect("Failed to get groups");
+                let title = |id: i64| {
+                    subjects
+                        .iter()
+                        .find(|s| s.id == id)
+                        .map_or(String::new(), |s| s.title.clone())
+                };
+                for group in groups {
+                    // Mandatory classes at the same time, which nobody in the group can attend
+                    for clash in conflicts::mandatory(&subjects, &schedule, &group) {
+                        clashes += 1;
+                        println!(
+                            "{} {}: {:?} slot {}, {:?}: {} {} clashes with {} {}",
+                            institution.code,
+                            group,
+                            clash.day,
+                            clash.slot as u8,
+                            clash.repeat,
+                            clash.first,
+                            title(clash.first),
+                            clash.second,
+                            title(clash.second)
+                        );
+                    }
+                }
+            }
+            if clashes > 0 {
+                std::process::exit(1);
+            }
+        }
+        [url] => audit::acting(actor(Source::Import), import(url, "default")).await,
+        [url, code] => audit::acting(actor(Source::Import), import(url, code)).await,
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
+    }
+}
+
+async fn connect(url: &str) -> Database {
+    let db = Database::connect(url)
         .await
         .expect("Failed to connect to database");
+    db.migrate().await.expect("Failed to run migrations");
+    db
+}
 
-    let db = schedule_bot::db::Database::new(pool);
+async fn find(db: &Database, code: &str) -> Institution {
+    db.find_institution(code)
+        .await
+        .expect("Failed to find institution")
+}
+
+async fn import(url: &str, code: &str) {
+    let db = connect(url).await;
+    let institution = find(&db, code).await.id;
 
     let subjects: Vec<Subject> = unpack("data/subjects.packed", 4).unwrap();
     log::trace!("Read subjects.packed");
@@ -21,14 +225,17 @@ async fn main() {
     log::trace!("Read schedule.packed");
 
     for subject in subjects {
-        if let Err(error) = db.add_subject(&subject).await {
+        if let Err(error) = db.add_group(institution, &subject.group).await {
+            log::error!("Failed to add {:?} to db: {:?}", subject.group, error);
+        }
+        if let Err(error) = db.add_subject(institution, &subject).await {
             log::error!("Failed to add {:?} to db: {:?}", subject, error);
         }
     }
     log::trace!("Written subjects to db");
 
     for record in schedule {
-        if let Err(error) = db.add_schedule(&record).await {
+        if let Err(error) = db.add_schedule(institution, &record).await {
             log::error!("Failed to add {:?} to db: {:?}", record, error);
         }
     }
@@ -1,42 +1,160 @@
-use crate::data::{Day, Group, Repeat, Slot};
+use crate::data::{Day, Group, Institution, User};
 use chrono::Datelike;
+use chrono_tz::Tz;
 use futures::future::BoxFuture;
 use teloxide::{
+    net::Download,
     prelude::*,
-    types::ParseMode,
-    utils::command::{BotCommands, ParseError},
+    types::{
+        BotCommand, Document, InlineKeyboardMarkup, InlineQueryResultArticle, InputFile,
+        InputMessageContent, InputMessageContentText, ParseMode,
+    },
+    utils::command::BotCommands,
 };
 
-use lazy_static::lazy_static;
-
-use crate::db::Database;
-use async_once::AsyncOnce;
-lazy_static! {
-    static ref DB: AsyncOnce<Database> = AsyncOnce::new(async {
-        let pool = sqlx::SqlitePool::connect("sqlite:///tmp/test.db")
-            .await
-            .unwrap();
-
-        Database::new(pool)
-    });
-}
+use crate::announce::{self, Outbox};
+use crate::attendance;
+use crate::audit::{self, Actor};
+use crate::calendar;
+use crate::clock::Clock;
+use crate::common;
+use crate::conflicts;
+use crate::editor;
+use crate::exams;
+use crate::expr::{Expr, Invalid};
+use crate::friends;
+use crate::grades;
+use crate::homework;
+use crate::i18n::{Language, Msg};
+use crate::import;
+use crate::inline;
+use crate::notes;
+use crate::now;
+use crate::onboarding::{self, OnboardingDialogue};
+use crate::pin;
+use crate::store::{Dialogues, ScheduleStore};
+use crate::timetable::{self, Span, View};
+use crate::upload;
+use std::sync::Arc;
 
-pub async fn run(token: String) {
+pub async fn run<S: ScheduleStore>(token: String, store: S, clock: Arc<dyn Clock>) {
     let bot = Bot::new(token);
 
-    bot.set_my_commands(Command::bot_commands())
-        .await
-        .expect("Failed to set bot commands");
+    for language in Language::ALL {
+        let request = bot.set_my_commands(commands(language));
+        let request = match language {
+            Language::En => request,
+            other => request.language_code(other.code()),
+        };
+        request.await.expect("Failed to set bot commands");
+    }
 
-    let handler = dptree::entry().branch(
-        Update::filter_message()
-            .filter_command::<Command>()
-            .endpoint(command_handler),
-    );
+    let handler = dptree::entry()
+        .branch(
+            Update::filter_message()
+                .filter_command::<Command>()
+                .inspect_async(remember::<S>)
+                .branch(
+                    dptree::filter_async(|msg: Message, cmd: Command, bot: Bot| async move {
+                        !allowed(&bot, &msg, &cmd).await
+                    })
+                    .endpoint(refuse_handler::<S>),
+                )
+                .branch(
+                    dptree::filter(|cmd: Command| matches!(cmd, Command::Pin | Command::Unpin))
+                        .endpoint(pin_handler::<S>),
+                )
+                .branch(dptree::case![Command::Announce(args)].endpoint(announce_handler::<S>))
+                .branch(dptree::case![Command::Hw(args)].endpoint(homework_handler::<S>))
+                .branch(dptree::case![Command::Common(args)].endpoint(common_handler::<S>))
+                .branch(dptree::case![Command::Friend(args)].endpoint(friend_handler::<S>))
+                .branch(dptree::case![Command::Attendance(args)].endpoint(attendance_handler::<S>))
+                .branch(dptree::case![Command::Grade(args)].endpoint(grade_handler::<S>))
+                .branch(
+                    dptree::filter(|cmd: Command| matches!(cmd, Command::Note(_) | Command::Notes))
+                        .endpoint(note_handler::<S>),
+                )
+                .branch(dptree::filter(|cmd: Command| cmd.edits()).endpoint(edit_handler::<S>))
+                .branch(
+                    dptree::case![Command::Start]
+                        .enter_dialogue::<Message, Dialogues<S>, onboarding::State>()
+                        .endpoint(start_handler::<S>),
+                )
+                .branch(dptree::endpoint(command_handler::<S>)),
+        )
+        .branch(
+            Update::filter_message()
+                .filter(|msg: Message| msg.chat.is_private())
+                .filter_map(|msg: Message| msg.document().cloned())
+                .endpoint(upload_handler::<S>),
+        )
+        .branch(Update::filter_inline_query().endpoint(inline_handler::<S>))
+        .branch(
+            Update::filter_callback_query()
+                .branch(
+                    dptree::filter(|query: CallbackQuery| {
+                        query
+                            .data
+                            .is_some_and(|data| data.starts_with(timetable::PREFIX))
+                    })
+                    .endpoint(navigation_handler::<S>),
+                )
+                .branch(
+                    dptree::filter(|query: CallbackQuery| {
+                        query
+                            .data
+                            .is_some_and(|data| data.starts_with(upload::PREFIX))
+                    })
+                    .endpoint(import_handler::<S>),
+                )
+                .branch(
+                    dptree::filter(|query: CallbackQuery| {
+                        query
+                            .data
+                            .is_some_and(|data| data.starts_with(friends::PREFIX))
+                    })
+                    .endpoint(friend_answer_handler::<S>),
+                )
+                .branch(
+                    dptree::filter(|query: CallbackQuery| {
+                        query
+                            .data
+                            .is_some_and(|data| data.starts_with(attendance::PREFIX))
+                    })
+                    .endpoint(attendance_mark_handler::<S>),
+                )
+                .branch(
+                    dptree::entry()
+                        .enter_dialogue::<CallbackQuery, Dialogues<S>, onboarding::State>()
+                        .endpoint(callback_handler::<S>),
+                ),
+        );
 
-    let error_handler = std::sync::Arc::new(ErrorHandler { bot: bot.clone() });
+    let error_handler = Arc::new(ErrorHandler { bot: bot.clone() });
+    let store = Arc::new(store);
+    let dialogues = Arc::new(Dialogues(store.clone()));
+    let outbox = Outbox::spawn(announce::PACE, {
+        let bot = bot.clone();
+        move |letter| {
+            let bot = bot.clone();
+            async move { announce::send(&bot, letter).await }
+        }
+    });
+    tokio::spawn(pin::watch(bot.clone(), store.clone(), clock.clone()));
+    tokio::spawn(homework::watch(
+        store.clone(),
+        clock.clone(),
+        outbox.clone(),
+    ));
+    tokio::spawn(exams::watch(store.clone(), clock.clone(), outbox.clone()));
+    tokio::spawn(attendance::watch(
+        store.clone(),
+        clock.clone(),
+        outbox.clone(),
+    ));
 
     Dispatcher::builder(bot, handler)
+        .dependencies(dptree::deps![store, dialogues, clock, outbox])
         .enable_ctrlc_handler()
         .error_handler(error_handler)
         .build()
@@ -47,45 +165,175 @@ pub async fn run(token: String) {
 #[derive(BotCommands, Clone)]
 #[command(description = "Commands:", rename_rule = "lowercase")]
 pub enum Command {
-    #[command(description = "<group>")]
+    #[command(description = "pick your institution and group")]
+    Start,
+    #[command(description = "[institution] [group]")]
     Config(String),
-    #[command(description = "<date> <slot> <group>", parse_with = parse_command_subject)]
-    Subject {
-        slot: Option<String>,
-        date: Option<String>,
-    },
+    #[command(description = "[slot] [date] in any order, e.g. 2 tomorrow")]
+    Subject(String),
+    #[command(description = "[date] classes of the day, e.g. завтра or next fri")]
+    Today(String),
+    #[command(description = "[date] classes of the week")]
+    Week(String),
+    #[command(description = "<zone> to show class times in, or - to reset")]
+    Timezone(String),
+    #[command(description = "<en|uk> language of the bot, or - to follow Telegram")]
+    Lang(String),
+    #[command(description = "pin today's timetable, kept up to date every day")]
+    Pin,
+    #[command(description = "unpin the timetable")]
+    Unpin,
+    #[command(description = "<date> <slot> cancel a class")]
+    Cancel(String),
+    #[command(description = "<date> <slot> <date> <slot> move a class")]
+    Move(String),
+    #[command(description = "<subject> <room> where the classes of a subject take place")]
+    Setroom(String),
+    #[command(description = "<date> <slot> <url> [name] add a meeting link to a class")]
+    Addlink(String),
+    #[command(description = "<user id> <owner|editor> [group] hand out a role")]
+    Grant(String),
+    #[command(description = "<user id> [group] take a role away")]
+    Revoke(String),
+    #[command(description = "your id and roles")]
+    Roles,
+    #[command(description = "latest changes to the timetables of your groups")]
+    History,
+    #[command(description = "<group> <text> send a message to everyone in a group")]
+    Announce(String),
+    #[command(
+        description = "homework due: add [private] <subject> <due date> <text>, done|undo|delete <id>"
+    )]
+    Hw(String),
+    #[command(description = "exams and credits of your group")]
+    Exams,
+    #[command(
+        rename = "next_exam",
+        description = "the next exam and how many days are left"
+    )]
+    NextExam,
+    #[command(description = "your timetable and exams as a calendar file")]
+    Calendar,
+    #[command(description = "<subject> <text> your own note on a subject, or - to remove it")]
+    Note(String),
+    #[command(description = "your notes on subjects")]
+    Notes,
+    #[command(description = "<group|@friend> ... [date] free slots everyone shares in a week")]
+    Common(String),
+    #[command(
+        description = "[@username] your friends, or ask to share free time; - @username to stop"
+    )]
+    Friend(String),
+    #[command(description = "[subject] take an elective, or - <subject> to drop it")]
+    Enroll(String),
+    #[command(description = "classes that clash in your timetable")]
+    Conflicts,
+    #[command(description = "the class going on, the next one and when the day ends")]
+    Now,
+    #[command(description = "[subject] classes you attended and missed")]
+    Attendance(String),
+    #[command(
+        description = "[subject] [points] [comment] your points this semester, or log more; - <id> to remove"
+    )]
+    Grade(String),
 }
 
-fn parse_command_subject(s: String) -> Result<(Option<String>, Option<String>), ParseError> {
-    let args: Vec<&str> = s.split(" ").collect();
-    match &args[..] {
-        [] => Ok((None, None)),
-        [slot] => Ok((Some(slot.to_string()), None)),
-        ["_", date] => Ok((None, Some(date.to_string()))),
-        [slot, date] => Ok((Some(slot.to_string()), Some(date.to_string()))),
-        _ => Err(ParseError::Custom("Only 2 arguments allowed!".into())),
+impl Command {
+    /// Whether the command changes settings shared by everyone in the chat,
+    /// which only admins may do in groups.
+    pub fn changes_settings(&self) -> bool {
+        use Command::*;
+        matches!(
+            self,
+            Start | Config(_) | Timezone(_) | Lang(_) | Pin | Unpin
+        )
+    }
+
+    /// Whether the command is about editing the timetable or the roles to do
+    /// so, which depends on who sent it rather than on the chat.
+    pub fn edits(&self) -> bool {
+        use Command::*;
+        matches!(
+            self,
+            Cancel(_) | Move(_) | Setroom(_) | Addlink(_) | Grant(_) | Revoke(_) | Roles | History
+        )
     }
 }
 
+/// The command menu in `language`, falling back to the English descriptions.
+fn commands(language: Language) -> Vec<BotCommand> {
+    Command::bot_commands()
+        .into_iter()
+        .map(|c| {
+            let description = describe(language, c.command.trim_start_matches('/'));
+            BotCommand::new(c.command, description)
+        })
+        .collect()
+}
+
+/// Description of `command` in `language`, or else in English.
+fn describe(language: Language, command: &str) -> String {
+    language.describe(command).map_or_else(
+        || {
+            Command::bot_commands()
+                .into_iter()
+                .find(|c| c.command.trim_start_matches('/') == command)
+                .map(|c| c.description)
+                .unwrap_or_default()
+        },
+        String::from,
+    )
+}
+
 pub struct ErrorHandler {
     bot: Bot,
 }
 
-impl teloxide::error_handlers::ErrorHandler<Error> for ErrorHandler {
-    fn handle_error(self: std::sync::Arc<Self>, error: Error) -> BoxFuture<'static, ()> {
+impl teloxide::error_handlers::ErrorHandler<Failure> for ErrorHandler {
+    fn handle_error(self: Arc<Self>, failure: Failure) -> BoxFuture<'static, ()> {
         use Error::*;
 
-        let (chat_id, message) = match error {
-            Some(x) => (x, "Something went wrong".into()),
-            NoGroupConfigured(x) => (
-                x,
-                "Please configure your group with `/config <group>`".into(),
-            ),
-            InvalidGroup(x, value) => (x, format!("Invalid group: {}.", &value)),
-            InvalidDate(x, value) => (x, format!("Invalid date: {}.", &value)),
-            InvalidSlot(x, value) => (x, format!("Invalid slot: {}.", &value)),
-            InvalidWeekday(x, value) => (x, format!("Invalid weekday: {}.", &value)),
+        let Failure(error, language) = failure;
+        let usage;
+        let (chat_id, msg) = match &error {
+            Some(x) => (*x, Msg::SomethingWentWrong),
+            NoGroupConfigured(x) => (*x, Msg::NoGroupConfigured),
+            InvalidInstitution(x, value) => (*x, Msg::InvalidInstitution(value)),
+            InvalidGroup(x, value) => (*x, Msg::InvalidGroup(value)),
+            InvalidDate(x, value) => (*x, Msg::InvalidDate(value)),
+            InvalidSlot(x, value) => (*x, Msg::InvalidSlot(value)),
+            InvalidWeekday(x, value) => (*x, Msg::InvalidWeekday(value)),
+            InvalidArgument(x, value) => (*x, Msg::InvalidArgument(value)),
+            InvalidTimezone(x, value) => (*x, Msg::InvalidTimezone(value)),
+            InvalidLanguage(x, value) => (*x, Msg::InvalidLanguage(value)),
+            NotChatAdmin(x) => (*x, Msg::NotChatAdmin),
+            NotEditor(x) => (*x, Msg::NotEditor),
+            NotOwner(x) => (*x, Msg::NotOwner),
+            InvalidRoom(x, value) => (*x, Msg::InvalidRoom(value)),
+            InvalidLink(x, value) => (*x, Msg::InvalidLink(value)),
+            InvalidSubject(x, value) => (*x, Msg::InvalidSubject(value)),
+            InvalidHomework(x, value) => (*x, Msg::InvalidHomework(value)),
+            NotAuthor(x) => (*x, Msg::NotAuthor),
+            OnlyPrivate(x) => (*x, Msg::OnlyPrivate),
+            UnknownUser(x, value) => (*x, Msg::UnknownUser(value)),
+            NotFriend(x, value) => (*x, Msg::NotFriend(value)),
+            InvalidFile(x, value) => (*x, Msg::InvalidFile(value)),
+            TooLong(x, limit) => {
+                usage = limit.to_string();
+                (*x, Msg::TooLong(&usage))
+            }
+            Usage(x, command) => {
+                usage = describe(language, command);
+                (
+                    *x,
+                    Msg::Usage {
+                        command,
+                        usage: &usage,
+                    },
+                )
+            }
         };
+        let message = language.tr(msg);
 
         let fut = async move {
             let _ = self.bot.send_message(chat_id, message).await;
@@ -97,118 +345,1784 @@ impl teloxide::error_handlers::ErrorHandler<Error> for ErrorHandler {
 
 #[derive(Debug)]
 pub enum Error {
+    InvalidInstitution(ChatId, String),
     InvalidGroup(ChatId, String),
     InvalidDate(ChatId, String),
     InvalidSlot(ChatId, String),
     InvalidWeekday(ChatId, String),
+    InvalidTimezone(ChatId, String),
+    InvalidArgument(ChatId, String),
+    InvalidLanguage(ChatId, String),
+    InvalidRoom(ChatId, String),
+    InvalidLink(ChatId, String),
+    InvalidSubject(ChatId, String),
+    InvalidHomework(ChatId, String),
+    /// A document that is too large or not a timetable file, by its name.
+    InvalidFile(ChatId, String),
+    /// Text longer than the limit given.
+    TooLong(ChatId, usize),
+    /// Arguments that don't fit the command, named without the slash.
+    Usage(ChatId, &'static str),
     NoGroupConfigured(ChatId),
+    NotChatAdmin(ChatId),
+    NotEditor(ChatId),
+    NotOwner(ChatId),
+    /// Not the chat that added the homework, nor an editor of its group.
+    NotAuthor(ChatId),
+    /// Personal records asked for in a chat other than the sender's own.
+    OnlyPrivate(ChatId),
+    /// A username the bot hasn't seen in a private chat with a group picked.
+    UnknownUser(ChatId, String),
+    /// A user who doesn't share free time with the sender both ways, or is
+    /// in another institution.
+    NotFriend(ChatId, String),
     Some(ChatId),
 }
 
-async fn command_handler(msg: Message, bot: Bot, cmd: Command) -> Result<(), Error> {
+/// An [`Error`] along with the language to tell the user about it in.
+#[derive(Debug)]
+pub struct Failure(pub Error, pub Language);
+
+/// Text the bot answers a command with.
+#[derive(PartialEq, Debug)]
+pub enum Reply {
+    Text(String),
+    Markdown(String),
+    Menu(String, InlineKeyboardMarkup),
+    /// Markdown with buttons to move around the timetable.
+    Page(String, InlineKeyboardMarkup),
+    /// A file to download, by its name.
+    File(String, Vec<u8>),
+}
+
+async fn command_handler<S: ScheduleStore>(
+    msg: Message,
+    bot: Bot,
+    cmd: Command,
+    store: Arc<S>,
+    clock: Arc<dyn Clock>,
+) -> Result<(), Failure> {
+    let chat_id = msg.chat.id;
+    let language = language(store.as_ref(), chat_id, msg.from()).await;
+    let sender = sender(&msg);
+    let reply = execute(
+        store.as_ref(),
+        clock.as_ref(),
+        chat_id,
+        sender,
+        language,
+        cmd,
+    );
+    let reply = audit::acting(Actor::chat(sender), reply)
+        .await
+        .map_err(|err| Failure(err, language))?;
+    if let Some(reply) = reply {
+        send(&bot, chat_id, reply).await;
+    }
+    Ok(())
+}
+
+/// Whether the sender of `msg` may run `cmd`: anyone in private chats, only
+/// admins when it changes the settings of a group.
+async fn allowed(bot: &Bot, msg: &Message, cmd: &Command) -> bool {
+    if msg.chat.is_private() || !cmd.changes_settings() {
+        return true;
+    }
+    // Anonymous admins write on behalf of the chat itself
+    if msg.sender_chat().is_some_and(|c| c.id == msg.chat.id) {
+        return true;
+    }
+    match msg.from() {
+        Some(user) => admin(bot, msg.chat.id, user.id).await,
+        None => false,
+    }
+}
+
+async fn admin(bot: &Bot, chat_id: ChatId, user_id: UserId) -> bool {
+    match bot.get_chat_member(chat_id, user_id).await {
+        Ok(member) => member.is_privileged(),
+        Err(err) => {
+            log::error!("Failed to get chat member: {:?}", err);
+            false
+        }
+    }
+}
+
+async fn refuse_handler<S: ScheduleStore>(msg: Message, store: Arc<S>) -> Result<(), Failure> {
+    let language = language(store.as_ref(), msg.chat.id, msg.from()).await;
+    Err(Failure(Error::NotChatAdmin(msg.chat.id), language))
+}
+
+async fn pin_handler<S: ScheduleStore>(
+    msg: Message,
+    bot: Bot,
+    cmd: Command,
+    store: Arc<S>,
+    clock: Arc<dyn Clock>,
+) -> Result<(), Failure> {
+    let chat_id = msg.chat.id;
+    let language = language(store.as_ref(), chat_id, msg.from()).await;
+    let reply = async {
+        match cmd {
+            Command::Pin => {
+                log::trace!("/pin");
+                pin::pin(&bot, store.as_ref(), clock.as_ref(), chat_id, language).await
+            }
+            _ => {
+                log::trace!("/unpin");
+                pin::unpin(&bot, store.as_ref(), chat_id)
+                    .await
+                    .map(|_| Some(Reply::Text(language.tr(Msg::Unpinned))))
+            }
+        }
+    };
+    let reply = audit::acting(Actor::chat(sender(&msg)), reply).await;
+    if let Some(reply) = reply.map_err(|err| Failure(err, language))? {
+        send(&bot, chat_id, reply).await;
+    }
+    Ok(())
+}
+
+/// Sends an announcement out to a group in the background, reporting back
+/// to the chat once it is delivered.
+async fn announce_handler<S: ScheduleStore>(
+    msg: Message,
+    bot: Bot,
+    args: String,
+    store: Arc<S>,
+    outbox: Outbox,
+) -> Result<(), Failure> {
+    log::trace!("/announce {}", &args);
+    let chat_id = msg.chat.id;
+    let language = language(store.as_ref(), chat_id, msg.from()).await;
+    let announcement = announce::prepare(store.as_ref(), chat_id, sender(&msg), &args)
+        .await
+        .map_err(|err| Failure(err, language))?;
+    let group = announcement.group.to_string();
+    if announcement.letters.is_empty() {
+        send(
+            &bot,
+            chat_id,
+            Reply::Text(language.tr(Msg::NoChats(&group))),
+        )
+        .await;
+        return Ok(());
+    }
+    let count = announcement.letters.len().to_string();
+    let reply = language.tr(Msg::Announcing {
+        group: &group,
+        count: &count,
+    });
+    send(&bot, chat_id, Reply::Text(reply)).await;
+
+    tokio::spawn(async move {
+        let outcomes = outbox.deliver(announcement.letters).await;
+        let removed = match announce::clean_up(store.as_ref(), &outcomes).await {
+            Ok(removed) => removed,
+            Err(err) => {
+                log::error!("Failed to forget unreachable chats: {:?}", err);
+                0
+            }
+        };
+        let (delivered, failed) = announce::tally(&outcomes);
+        let report = language.tr(Msg::Announced {
+            group: &group,
+            delivered: &delivered.to_string(),
+            failed: &failed.to_string(),
+            removed: &removed.to_string(),
+        });
+        send(&bot, chat_id, Reply::Text(report)).await;
+    });
+    Ok(())
+}
+
+/// Handles `/hw` in the chat, on behalf of whoever sent it.
+async fn homework_handler<S: ScheduleStore>(
+    msg: Message,
+    bot: Bot,
+    args: String,
+    store: Arc<S>,
+    clock: Arc<dyn Clock>,
+) -> Result<(), Failure> {
+    log::trace!("/hw {}", &args);
+    let chat_id = msg.chat.id;
+    let sender = sender(&msg);
+    let language = language(store.as_ref(), chat_id, msg.from()).await;
+    let reply = homework::execute(
+        store.as_ref(),
+        clock.as_ref(),
+        chat_id,
+        sender,
+        language,
+        &args,
+    );
+    let reply = audit::acting(Actor::chat(sender), reply)
+        .await
+        .map_err(|err| Failure(err, language))?;
+    send(&bot, chat_id, reply).await;
+    Ok(())
+}
+
+/// Keeps the username of the user behind a private chat up to date.
+async fn remember<S: ScheduleStore>(msg: Message, store: Arc<S>) {
+    if !msg.chat.is_private() {
+        return;
+    }
+    let username = msg.from().and_then(|user| user.username.as_deref());
+    let remembered = friends::remember(store.as_ref(), msg.chat.id, username);
+    if let Err(err) = audit::acting(Actor::chat(msg.chat.id), remembered).await {
+        log::error!("Failed to remember username: {:?}", err);
+    }
+}
+
+/// Handles `/common` in the chat, with the friends of whoever sent it.
+async fn common_handler<S: ScheduleStore>(
+    msg: Message,
+    bot: Bot,
+    args: String,
+    store: Arc<S>,
+    clock: Arc<dyn Clock>,
+) -> Result<(), Failure> {
+    log::trace!("/common {}", &args);
+    let chat_id = msg.chat.id;
+    let language = language(store.as_ref(), chat_id, msg.from()).await;
+    let reply = common::execute(
+        store.as_ref(),
+        clock.as_ref(),
+        chat_id,
+        sender(&msg),
+        language,
+        &args,
+    )
+    .await
+    .map_err(|err| Failure(err, language))?;
+    send(&bot, chat_id, reply).await;
+    Ok(())
+}
+
+/// Handles `/friend` on behalf of whoever sent it, passing requests on.
+async fn friend_handler<S: ScheduleStore>(
+    msg: Message,
+    bot: Bot,
+    args: String,
+    store: Arc<S>,
+) -> Result<(), Failure> {
+    log::trace!("/friend {}", &args);
+    let chat_id = msg.chat.id;
+    let sender = sender(&msg);
+    let name = match msg.from() {
+        Some(user) => match &user.username {
+            Some(username) => format!("@{}", username),
+            None => user.full_name(),
+        },
+        None => sender.to_string(),
+    };
+    let language = language(store.as_ref(), chat_id, msg.from()).await;
+    let reply = friends::execute(store.as_ref(), chat_id, sender, &name, language, &args);
+    let (reply, request) = audit::acting(Actor::chat(sender), reply)
+        .await
+        .map_err(|err| Failure(err, language))?;
+    send(&bot, chat_id, reply).await;
+    if let Some((friend, request)) = request {
+        send(&bot, friend, request).await;
+    }
+    Ok(())
+}
+
+/// Handles `/attendance` on behalf of whoever sent it.
+async fn attendance_handler<S: ScheduleStore>(
+    msg: Message,
+    bot: Bot,
+    args: String,
+    store: Arc<S>,
+) -> Result<(), Failure> {
+    log::trace!("/attendance {}", &args);
+    let chat_id = msg.chat.id;
+    let language = language(store.as_ref(), chat_id, msg.from()).await;
+    let reply = attendance::execute(store.as_ref(), chat_id, sender(&msg), language, &args)
+        .await
+        .map_err(|err| Failure(err, language))?;
+    send(&bot, chat_id, reply).await;
+    Ok(())
+}
+
+/// Handles `/grade` on behalf of whoever sent it.
+async fn grade_handler<S: ScheduleStore>(
+    msg: Message,
+    bot: Bot,
+    args: String,
+    store: Arc<S>,
+    clock: Arc<dyn Clock>,
+) -> Result<(), Failure> {
+    log::trace!("/grade {}", &args);
+    let chat_id = msg.chat.id;
+    let sender = sender(&msg);
+    let language = language(store.as_ref(), chat_id, msg.from()).await;
+    let reply = grades::execute(
+        store.as_ref(),
+        clock.as_ref(),
+        chat_id,
+        sender,
+        language,
+        &args,
+    );
+    let reply = audit::acting(Actor::chat(sender), reply)
+        .await
+        .map_err(|err| Failure(err, language))?;
+    send(&bot, chat_id, reply).await;
+    Ok(())
+}
+
+/// Handles `/note` and `/notes` on behalf of whoever sent them.
+async fn note_handler<S: ScheduleStore>(
+    msg: Message,
+    bot: Bot,
+    cmd: Command,
+    store: Arc<S>,
+) -> Result<(), Failure> {
+    let chat_id = msg.chat.id;
+    let sender = sender(&msg);
+    let language = language(store.as_ref(), chat_id, msg.from()).await;
+    let reply = match cmd {
+        Command::Note(args) => {
+            log::trace!("/note {}", &args);
+            let reply = notes::execute(store.as_ref(), chat_id, sender, language, &args);
+            audit::acting(Actor::chat(sender), reply).await
+        }
+        _ => {
+            log::trace!("/notes");
+            notes::list(store.as_ref(), chat_id, sender, language).await
+        }
+    };
+    let reply = reply.map_err(|err| Failure(err, language))?;
+    send(&bot, chat_id, reply).await;
+    Ok(())
+}
+
+/// Handles a command that edits the timetable, on behalf of whoever sent it.
+async fn edit_handler<S: ScheduleStore>(
+    msg: Message,
+    bot: Bot,
+    cmd: Command,
+    store: Arc<S>,
+    clock: Arc<dyn Clock>,
+) -> Result<(), Failure> {
+    let chat_id = msg.chat.id;
+    let sender = sender(&msg);
+    let language = language(store.as_ref(), chat_id, msg.from()).await;
+    let reply = editor::execute(
+        store.as_ref(),
+        clock.as_ref(),
+        chat_id,
+        sender,
+        language,
+        cmd,
+    );
+    let reply = audit::acting(Actor::chat(sender), reply)
+        .await
+        .map_err(|err| Failure(err, language))?;
+    if let Some(reply) = reply {
+        send(&bot, chat_id, reply).await;
+    }
+    Ok(())
+}
+
+async fn start_handler<S: ScheduleStore>(
+    msg: Message,
+    bot: Bot,
+    dialogue: OnboardingDialogue<S>,
+    store: Arc<S>,
+) -> Result<(), Failure> {
+    log::trace!("/start");
+    let chat_id = msg.chat.id;
+    let language = language(store.as_ref(), chat_id, msg.from()).await;
+    let fail = |err| Failure(err, language);
+    let (state, reply) = onboarding::start(store.as_ref(), chat_id, language)
+        .await
+        .map_err(fail)?;
+    audit::acting(Actor::chat(sender(&msg)), keep(&dialogue, state))
+        .await
+        .map_err(fail)?;
+    send(&bot, chat_id, reply).await;
+    Ok(())
+}
+
+/// Handles a press on an onboarding menu, editing it in place.
+async fn callback_handler<S: ScheduleStore>(
+    query: CallbackQuery,
+    bot: Bot,
+    dialogue: OnboardingDialogue<S>,
+    store: Arc<S>,
+) -> Result<(), Failure> {
+    let (Some(message), Some(data)) = (query.message, query.data) else {
+        let _ = bot.answer_callback_query(query.id).await;
+        return Ok(());
+    };
+    let chat_id = message.chat.id;
+    log::trace!("callback {}", &data);
+    let language = language(store.as_ref(), chat_id, Some(&query.from)).await;
+    if !message.chat.is_private() && !admin(&bot, chat_id, query.from.id).await {
+        let _ = bot
+            .answer_callback_query(query.id)
+            .text(language.tr(Msg::NotChatAdmin))
+            .await;
+        return Ok(());
+    }
+    let _ = bot.answer_callback_query(query.id).await;
+    let fail = |err| Failure(err, language);
+
+    let state = dialogue
+        .get_or_default()
+        .await
+        .map_err(failed(chat_id, "get dialogue"))
+        .map_err(fail)?;
+    let picked = async {
+        let (state, reply) =
+            onboarding::pick(store.as_ref(), chat_id, language, state, &data).await?;
+        keep(&dialogue, state).await?;
+        Ok::<_, Error>(reply)
+    };
+    let reply = audit::acting(Actor::chat(ChatId::from(query.from.id)), picked)
+        .await
+        .map_err(fail)?;
+    edit(&bot, &message, reply).await;
+    Ok(())
+}
+
+/// Handles a press on the buttons under a timetable, moving it in place.
+async fn navigation_handler<S: ScheduleStore>(
+    query: CallbackQuery,
+    bot: Bot,
+    store: Arc<S>,
+    clock: Arc<dyn Clock>,
+) -> Result<(), Failure> {
+    let (Some(message), Some(data)) = (&query.message, &query.data) else {
+        let _ = bot.answer_callback_query(query.id).await;
+        return Ok(());
+    };
+    log::trace!("callback {}", data);
+    let chat_id = message.chat.id;
+    let language = language(store.as_ref(), chat_id, Some(&query.from)).await;
+
+    let reply = match View::try_from(data.as_str()) {
+        Ok(view) => {
+            let sender = ChatId::from(query.from.id);
+            timetable::render(
+                store.as_ref(),
+                clock.as_ref(),
+                chat_id,
+                sender,
+                language,
+                view,
+            )
+            .await
+        }
+        Err(err) => {
+            log::trace!("Rejected callback {}: {:?}", data, err);
+            Ok(None)
+        }
+    };
+    match reply {
+        Ok(Some(reply)) => {
+            let _ = bot.answer_callback_query(query.id).await;
+            edit(&bot, message, reply).await;
+            Ok(())
+        }
+        Ok(None) => {
+            let _ = bot
+                .answer_callback_query(query.id)
+                .text(language.tr(Msg::ButtonExpired))
+                .await;
+            Ok(())
+        }
+        Err(err) => {
+            let _ = bot.answer_callback_query(query.id).await;
+            Err(Failure(err, language))
+        }
+    }
+}
+
+/// Previews a timetable file sent to the bot, to be imported once confirmed.
+async fn upload_handler<S: ScheduleStore>(
+    msg: Message,
+    bot: Bot,
+    document: Document,
+    store: Arc<S>,
+) -> Result<(), Failure> {
+    let chat_id = msg.chat.id;
+    let name = document.file_name.clone().unwrap_or_default();
+    log::trace!("upload {}", &name);
+    let language = language(store.as_ref(), chat_id, msg.from()).await;
+    let fail = |err| Failure(err, language);
+    let text = download(&bot, chat_id, &document).await.map_err(fail)?;
+    let reply = upload::preview(
+        store.as_ref(),
+        chat_id,
+        sender(&msg),
+        language,
+        &name,
+        &text,
+    );
+    let reply = audit::acting(Actor::chat(sender(&msg)), reply)
+        .await
+        .map_err(fail)?;
+    send(&bot, chat_id, reply).await;
+    Ok(())
+}
+
+/// Text of a timetable file sent to `chat_id`.
+async fn download(bot: &Bot, chat_id: ChatId, document: &Document) -> Result<String, Error> {
+    let name = document.file_name.clone().unwrap_or_default();
+    if document.file.size > upload::MAX_SIZE || import::Format::try_from(name.as_str()).is_err() {
+        return Err(Error::InvalidFile(chat_id, name));
+    }
+    let mut content = vec![];
+    let downloaded = match bot.get_file(&document.file.id).await {
+        Ok(file) => bot
+            .download_file(&file.path, &mut content)
+            .await
+            .map_err(|err| format!("{:?}", err)),
+        Err(err) => Err(format!("{:?}", err)),
+    };
+    if let Err(err) = downloaded {
+        log::error!("Failed to download {}: {}", name, err);
+        return Err(Error::Some(chat_id));
+    }
+    String::from_utf8(content).map_err(|_| Error::InvalidFile(chat_id, name))
+}
+
+/// Handles a press on the buttons under an upload preview, answering in place.
+async fn import_handler<S: ScheduleStore>(
+    query: CallbackQuery,
+    bot: Bot,
+    store: Arc<S>,
+) -> Result<(), Failure> {
+    let _ = bot.answer_callback_query(query.id.clone()).await;
+    let (Some(message), Some(data)) = (&query.message, &query.data) else {
+        return Ok(());
+    };
+    log::trace!("callback {}", data);
+    let chat_id = message.chat.id;
+    let language = language(store.as_ref(), chat_id, Some(&query.from)).await;
+    let sender = ChatId::from(query.from.id);
+    let reply = upload::apply(store.as_ref(), chat_id, sender, language, data);
+    let reply = audit::acting(Actor::import(sender), reply)
+        .await
+        .map_err(|err| Failure(err, language))?;
+    edit(&bot, message, reply).await;
+    Ok(())
+}
+
+/// Handles a press on the buttons under a friend request, answering in place.
+async fn friend_answer_handler<S: ScheduleStore>(
+    query: CallbackQuery,
+    bot: Bot,
+    store: Arc<S>,
+) -> Result<(), Failure> {
+    let _ = bot.answer_callback_query(query.id.clone()).await;
+    let (Some(message), Some(data)) = (&query.message, &query.data) else {
+        return Ok(());
+    };
+    log::trace!("callback {}", data);
+    let chat_id = message.chat.id;
+    // Requests only go to private chats, answered by the user behind them
+    let sender = ChatId::from(query.from.id);
+    if sender != chat_id {
+        return Ok(());
+    }
+    let language = language(store.as_ref(), chat_id, Some(&query.from)).await;
+    let reply = friends::answer(store.as_ref(), chat_id, language, data);
+    let (reply, letter) = audit::acting(Actor::chat(sender), reply)
+        .await
+        .map_err(|err| Failure(err, language))?;
+    edit(&bot, message, reply).await;
+    if let Some((friend, letter)) = letter {
+        send(&bot, friend, letter).await;
+    }
+    Ok(())
+}
+
+/// Marks a class as attended or missed by whoever pressed the button under
+/// it, telling them in a notification so that the message stays as it is.
+async fn attendance_mark_handler<S: ScheduleStore>(
+    query: CallbackQuery,
+    bot: Bot,
+    store: Arc<S>,
+) -> Result<(), Failure> {
+    let (Some(message), Some(data)) = (&query.message, &query.data) else {
+        let _ = bot.answer_callback_query(query.id).await;
+        return Ok(());
+    };
+    log::trace!("callback {}", data);
+    let chat_id = message.chat.id;
+    let sender = ChatId::from(query.from.id);
+    let language = language(store.as_ref(), chat_id, Some(&query.from)).await;
+    let marked = attendance::mark(store.as_ref(), chat_id, sender, language, data);
+    let marked = audit::acting(Actor::chat(sender), marked).await;
+    let answer = bot.answer_callback_query(query.id.clone());
+    let _ = match &marked {
+        Ok(text) => answer.text(text).await,
+        Err(_) => answer.await,
+    };
+    marked.map_err(|err| Failure(err, language))?;
+    Ok(())
+}
+
+/// Offers the slot, day and week asked for by an inline query.
+///
+/// Queries that can't be answered get no results, or a button leading to
+/// `/start` when the user still has to pick a group.
+async fn inline_handler<S: ScheduleStore>(
+    query: InlineQuery,
+    bot: Bot,
+    store: Arc<S>,
+    clock: Arc<dyn Clock>,
+) -> Result<(), Failure> {
+    log::trace!("inline {}", &query.query);
+    let chat_id = ChatId::from(query.from.id);
+    let language = language(store.as_ref(), chat_id, Some(&query.from)).await;
+    let answer = inline::answer(
+        store.as_ref(),
+        clock.as_ref(),
+        chat_id,
+        language,
+        &query.query,
+    );
+    let answer = match answer.await {
+        Ok(articles) => bot.answer_inline_query(
+            query.id,
+            articles.into_iter().map(|a| {
+                let content =
+                    InputMessageContentText::new(a.text).parse_mode(ParseMode::MarkdownV2);
+                InlineQueryResultArticle::new(a.id, a.title, InputMessageContent::Text(content))
+                    .into()
+            }),
+        ),
+        Err(Error::NoGroupConfigured(_)) => bot
+            .answer_inline_query(query.id, [])
+            .switch_pm_text(language.tr(Msg::PickGroupFirst))
+            .switch_pm_parameter("inline"),
+        Err(err) => {
+            log::trace!(
+                "Failed to answer inline query {:?}: {:?}",
+                &query.query,
+                err
+            );
+            bot.answer_inline_query(query.id, [])
+        }
+    };
+    let _ = answer.is_personal(true).cache_time(60).await;
+    Ok(())
+}
+
+async fn send(bot: &Bot, chat_id: ChatId, reply: Reply) {
+    let _ = match reply {
+        Reply::Text(text) => bot.send_message(chat_id, text).await,
+        Reply::Markdown(text) => {
+            bot.send_message(chat_id, text)
+                .parse_mode(ParseMode::MarkdownV2)
+                .await
+        }
+        Reply::Menu(text, keyboard) => bot.send_message(chat_id, text).reply_markup(keyboard).await,
+        Reply::Page(text, keyboard) => {
+            bot.send_message(chat_id, text)
+                .parse_mode(ParseMode::MarkdownV2)
+                .reply_markup(keyboard)
+                .await
+        }
+        Reply::File(name, content) => {
+            bot.send_document(chat_id, InputFile::memory(content).file_name(name))
+                .await
+        }
+    };
+}
+
+/// Replaces `message` with `reply`, dropping its buttons unless `reply` has some.
+async fn edit(bot: &Bot, message: &Message, reply: Reply) {
+    let (chat_id, id) = (message.chat.id, message.id);
+    let _ = match reply {
+        Reply::Text(text) => bot.edit_message_text(chat_id, id, text).await,
+        Reply::Markdown(text) => {
+            bot.edit_message_text(chat_id, id, text)
+                .parse_mode(ParseMode::MarkdownV2)
+                .await
+        }
+        Reply::Menu(text, keyboard) => {
+            bot.edit_message_text(chat_id, id, text)
+                .reply_markup(keyboard)
+                .await
+        }
+        Reply::Page(text, keyboard) => {
+            bot.edit_message_text(chat_id, id, text)
+                .parse_mode(ParseMode::MarkdownV2)
+                .reply_markup(keyboard)
+                .await
+        }
+        // Messages can't turn into files, so it comes after them instead
+        Reply::File(name, content) => {
+            bot.send_document(chat_id, InputFile::memory(content).file_name(name))
+                .await
+        }
+    };
+}
+
+/// Who sent `msg`: the user, or else the chat writing on its own behalf.
+fn sender(msg: &Message) -> ChatId {
+    msg.from().map_or(msg.chat.id, |user| ChatId::from(user.id))
+}
+
+/// Stores the onboarding `state`, forgetting the dialogue once it's over.
+async fn keep<S: ScheduleStore>(
+    dialogue: &OnboardingDialogue<S>,
+    state: onboarding::State,
+) -> Result<(), Error> {
+    let result = match state {
+        onboarding::State::Idle => dialogue.exit().await,
+        state => dialogue.update(state).await,
+    };
+    result.map_err(failed(dialogue.chat_id(), "store dialogue"))
+}
+
+/// The language `chat_id` picked with `/lang`, or else the one of their Telegram app.
+pub(crate) async fn language<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+    from: Option<&teloxide::types::User>,
+) -> Language {
+    let fallback = || Language::from_telegram(from.and_then(|u| u.language_code.as_deref()));
+    match store.get_language(&chat_id).await {
+        Ok(language) => language.unwrap_or_else(fallback),
+        Err(sqlx::Error::RowNotFound) => fallback(),
+        Err(err) => {
+            log::error!("Failed to get user language: {:?}", err);
+            fallback()
+        }
+    }
+}
+
+/// Runs `cmd` on behalf of `chat_id`, resolving omitted slot and date with `clock`.
+pub async fn execute<S: ScheduleStore>(
+    store: &S,
+    clock: &dyn Clock,
+    chat_id: ChatId,
+    sender: ChatId,
+    language: Language,
+    cmd: Command,
+) -> Result<Option<Reply>, Error> {
     use Command::*;
     match cmd {
-        Config(gang) => {
-            log::trace!("/config {}", &gang);
-            let group = Group::try_from(gang.as_str())
-                .map_err(|_| Error::InvalidGroup(msg.chat.id, gang))?;
-
-            match DB.get().await.get_group(&msg.chat.id).await {
-                Ok(_) => DB
-                    .get()
-                    .await
-                    .update_user(&msg.chat.id, &group)
-                    .await
-                    .map_err(|err| {
-                        log::error!("Failed to update user group: {:?}", err);
-                        Error::Some(msg.chat.id)
-                    })?,
-                Err(sqlx::Error::RowNotFound) => DB
-                    .get()
-                    .await
-                    .add_user(&msg.chat.id, &group)
+        // Answered by `start_handler`, which also keeps the dialogue state
+        Start => Ok(None),
+        // Answered by `pin_handler`, which needs the bot to pin messages
+        Pin | Unpin => Ok(None),
+        // Answered by `announce_handler`, which sends it out with the outbox
+        Announce(_) => Ok(None),
+        // Answered by `homework_handler`, which knows who sent it
+        Hw(_) => Ok(None),
+        // Answered by `common_handler` and `friend_handler`, which know who sent them
+        Common(_) | Friend(_) => Ok(None),
+        // Answered by `attendance_handler` and `grade_handler`, which know who sent them
+        Attendance(_) | Grade(_) => Ok(None),
+        // Answered by `note_handler`, which knows who sent them
+        Note(_) | Notes => Ok(None),
+        // Answered by `edit_handler`, which knows who sent them
+        Cancel(_) | Move(_) | Setroom(_) | Addlink(_) | Grant(_) | Revoke(_) | Roles | History => {
+            Ok(None)
+        }
+        Config(args) => {
+            log::trace!("/config {}", &args);
+            config(store, chat_id, language, args).await
+        }
+        Subject(args) => {
+            log::debug!("/subject {}", &args);
+            subject(store, clock, chat_id, sender, language, args).await
+        }
+        Today(args) => {
+            log::trace!("/today {}", &args);
+            view(store, clock, chat_id, sender, language, Span::Day, args).await
+        }
+        Week(args) => {
+            log::trace!("/week {}", &args);
+            view(store, clock, chat_id, sender, language, Span::Week, args).await
+        }
+        Timezone(name) => {
+            log::trace!("/timezone {}", &name);
+            timezone(store, chat_id, language, name).await
+        }
+        Lang(code) => {
+            log::trace!("/lang {}", &code);
+            lang(store, chat_id, language, code).await
+        }
+        Exams => {
+            log::trace!("/exams");
+            exams::list(store, clock, chat_id, language).await.map(Some)
+        }
+        NextExam => {
+            log::trace!("/next_exam");
+            exams::next(store, clock, chat_id, language).await.map(Some)
+        }
+        Calendar => {
+            log::trace!("/calendar");
+            calendar::export(store, clock, chat_id).await.map(Some)
+        }
+        Enroll(args) => {
+            log::trace!("/enroll {}", &args);
+            conflicts::enroll(store, chat_id, sender, language, &args)
+                .await
+                .map(Some)
+        }
+        Conflicts => {
+            log::trace!("/conflicts");
+            conflicts::list(store, chat_id, sender, language)
+                .await
+                .map(Some)
+        }
+        Now => {
+            log::trace!("/now");
+            now::execute(store, clock, chat_id, language)
+                .await
+                .map(Some)
+        }
+    }
+}
+
+/// Logs a failed store operation and reports it to the user as a generic error.
+pub(crate) fn failed(chat_id: ChatId, what: &'static str) -> impl FnOnce(sqlx::Error) -> Error {
+    move |err| {
+        log::error!("Failed to {}: {:?}", what, err);
+        Error::Some(chat_id)
+    }
+}
+
+/// Slot and date words of a command, in any order.
+pub(crate) fn expr(chat_id: ChatId, args: &str) -> Result<Expr, Error> {
+    Expr::try_from(args).map_err(|invalid| match invalid {
+        Invalid::Slot(word) => Error::InvalidSlot(chat_id, word),
+        Invalid::Date(word) => Error::InvalidDate(chat_id, word),
+    })
+}
+
+/// `/today` or `/week`, of another day when `args` name one.
+async fn view<S: ScheduleStore>(
+    store: &S,
+    clock: &dyn Clock,
+    chat_id: ChatId,
+    sender: ChatId,
+    language: Language,
+    span: Span,
+    args: String,
+) -> Result<Option<Reply>, Error> {
+    let expr = expr(chat_id, &args)?;
+    if let Some(word) = expr.words.into_iter().next() {
+        return Err(Error::InvalidArgument(chat_id, word));
+    }
+    if expr.slot.is_some() {
+        return Err(Error::InvalidArgument(chat_id, args));
+    }
+
+    let mut view = View::today(span);
+    if let Some(when) = expr.date {
+        let (_, institution) = user(store, chat_id).await?;
+        let today = clock
+            .now()
+            .with_timezone(&institution.timezone)
+            .date_naive();
+        view.date = Some(
+            when.resolve(today)
+                .ok_or(Error::InvalidDate(chat_id, args))?,
+        );
+    }
+    timetable::render(store, clock, chat_id, sender, language, view).await
+}
+
+/// The user behind `chat_id` along with their institution.
+pub(crate) async fn user<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+) -> Result<(User, Institution), Error> {
+    let user = match store.get_user(&chat_id).await {
+        Ok(ok) => ok,
+        Err(sqlx::Error::RowNotFound) => return Err(Error::NoGroupConfigured(chat_id)),
+        Err(err) => return Err(failed(chat_id, "get user")(err)),
+    };
+    let institution = store
+        .get_institution(user.institution)
+        .await
+        .map_err(failed(chat_id, "get user institution"))?;
+    Ok((user, institution))
+}
+
+async fn config<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+    language: Language,
+    args: String,
+) -> Result<Option<Reply>, Error> {
+    let institutions = store
+        .get_institutions()
+        .await
+        .map_err(failed(chat_id, "get institutions"))?;
+
+    let (institution, gang) = match args.split_whitespace().collect::<Vec<_>>()[..] {
+        [] => {
+            let mut message = language.tr(Msg::Institutions);
+            for i in institutions {
+                message.push_str(format!("\n{} — {}", i.code, i.name).as_str());
+            }
+            return Ok(Some(Reply::Text(message)));
+        }
+        [code] if institutions.iter().any(|i| i.code == code) => {
+            let institution = institutions.into_iter().find(|i| i.code == code).unwrap();
+            let groups = store
+                .get_groups(institution.id)
+                .await
+                .map_err(failed(chat_id, "get groups"))?;
+            let groups: Vec<&str> = groups.iter().map(Group::as_str).collect();
+            return Ok(Some(Reply::Text(language.tr(Msg::GroupsOf {
+                institution: &institution.name,
+                groups: &groups.join(", "),
+                code: &institution.code,
+            }))));
+        }
+        // A bare group is fine as long as only one institution has it
+        [gang] => {
+            let mut found = vec![];
+            for i in institutions {
+                let groups = store
+                    .get_groups(i.id)
                     .await
-                    .map_err(|err| {
-                        log::error!("Failed to add new user: {:?}", err);
-                        Error::Some(msg.chat.id)
-                    })?,
-                Err(err) => {
-                    log::error!("Failed to add new user: {:?}", err);
-                    return Err(Error::Some(msg.chat.id));
+                    .map_err(failed(chat_id, "get groups"))?;
+                if groups.iter().any(|g| g.as_str() == gang) {
+                    found.push(i);
                 }
             }
-        }
-        Subject { slot, date } => {
-            log::debug!("/subject {:?} {:?}", &slot, &date);
-
-            let slot = if let Some(value) = slot {
-                Slot::try_from(value.as_str())
-                    .map_err(|_| Error::InvalidSlot(msg.chat.id, value))?
-            } else {
-                Slot::from(&msg.date)
-            };
-
-            let dt = if let Some(value) = date {
-                let format = "%d.%m.%Y";
-                chrono::NaiveDate::parse_from_str(value.as_str(), format)
-                    .map_err(|err| {
-                        log::trace!("Failed to parse {} as '{}': {:?}", &value, format, &err);
-                        Error::InvalidDate(msg.chat.id, value)
-                    })?
-                    .and_hms_opt(12, 0, 0)
-                    .unwrap()
-                    .and_utc()
-            } else {
-                msg.date.clone()
-            };
-
-            let day = Day::try_from(&dt).map_err(|err| {
-                log::trace!(
-                    "Failed to convert weekday '{}' to `Day`: {:?}",
-                    dt.weekday(),
-                    &err
-                );
-                Error::InvalidWeekday(msg.chat.id, format!("{}", dt.weekday()))
-            })?;
-
-            let repeat = Repeat::from(&dt);
-
-            let group = match DB.get().await.get_group(&msg.chat.id).await {
-                Ok(ok) => ok,
-                Err(sqlx::Error::RowNotFound) => return Err(Error::NoGroupConfigured(msg.chat.id)),
-                Err(err) => {
-                    log::error!("Failed to get user group: {:?}", &err);
-                    return Err(Error::Some(msg.chat.id));
+            match found.len() {
+                0 => return Err(Error::InvalidGroup(chat_id, gang.into())),
+                1 => (found.remove(0), gang),
+                _ => {
+                    let codes: Vec<String> = found.into_iter().map(|i| i.code).collect();
+                    return Ok(Some(Reply::Text(language.tr(Msg::Ambiguous {
+                        group: gang,
+                        codes: &codes.join("|"),
+                    }))));
                 }
-            };
+            }
+        }
+        [code, gang] => {
+            let institution = institutions
+                .into_iter()
+                .find(|i| i.code == code)
+                .ok_or(Error::InvalidInstitution(chat_id, code.into()))?;
+            (institution, gang)
+        }
+        _ => return Err(Error::InvalidGroup(chat_id, args)),
+    };
 
-            let subjects = DB
-                .get()
-                .await
-                .get_subjects(day, repeat, slot, group)
+    let group = store
+        .get_groups(institution.id)
+        .await
+        .map_err(failed(chat_id, "get groups"))?
+        .into_iter()
+        .find(|g| g.as_str() == gang)
+        .ok_or(Error::InvalidGroup(chat_id, gang.into()))?;
+    let user = User {
+        institution: institution.id,
+        group,
+    };
+    save(store, chat_id, language, user, &institution)
+        .await
+        .map(Some)
+}
+
+/// Saves the group of a new or returning user, as picked by `/config` or `/start`.
+pub(crate) async fn save<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+    language: Language,
+    user: User,
+    institution: &Institution,
+) -> Result<Reply, Error> {
+    match store.get_user(&chat_id).await {
+        Ok(_) => store
+            .update_user(&chat_id, &user)
+            .await
+            .map_err(failed(chat_id, "update user group"))?,
+        Err(sqlx::Error::RowNotFound) => store
+            .add_user(&chat_id, &user)
+            .await
+            .map_err(failed(chat_id, "add new user"))?,
+        Err(err) => return Err(failed(chat_id, "get user")(err)),
+    }
+
+    Ok(Reply::Text(language.tr(Msg::Saved {
+        group: user.group.as_str(),
+        institution: &institution.name,
+    })))
+}
+
+async fn subject<S: ScheduleStore>(
+    store: &S,
+    clock: &dyn Clock,
+    chat_id: ChatId,
+    sender: ChatId,
+    language: Language,
+    args: String,
+) -> Result<Option<Reply>, Error> {
+    let expr = expr(chat_id, &args)?;
+    if let Some(word) = expr.words.into_iter().next() {
+        return Err(Error::InvalidArgument(chat_id, word));
+    }
+    let (user, institution) = user(store, chat_id).await?;
+    let timezone = institution.timezone;
+    let now = clock.now().with_timezone(&timezone);
+
+    let slot = expr.slot.unwrap_or_else(|| institution.bells.slot_at(&now));
+    let dt = match expr.date {
+        Some(when) => {
+            let date = when
+                .resolve(now.date_naive())
+                .ok_or(Error::InvalidDate(chat_id, args))?;
+            timetable::noon(date, &timezone)
+        }
+        None => now,
+    };
+
+    Day::try_from(&dt).map_err(|err| {
+        log::trace!(
+            "Failed to convert weekday '{}' to `Day`: {:?}",
+            dt.weekday(),
+            &err
+        );
+        Error::InvalidWeekday(chat_id, format!("{}", dt.weekday()))
+    })?;
+
+    let date = dt.date_naive();
+    let holiday = store
+        .get_holiday(institution.id, date)
+        .await
+        .map_err(failed(chat_id, "get holiday"))?;
+    if let Some(holiday) = holiday {
+        return Ok(Some(Reply::Text(language.tr(Msg::NoClassesOn {
+            date: &date.format("%d.%m.%Y").to_string(),
+            title: &holiday.title,
+        }))));
+    }
+
+    let reader = store
+        .get_timezone(&chat_id)
+        .await
+        .map_err(failed(chat_id, "get user time zone"))?
+        .unwrap_or(timezone);
+    let lessons = timetable::lessons(
+        store,
+        chat_id,
+        sender,
+        &institution,
+        &user.group,
+        reader,
+        date,
+        slot,
+    )
+    .await?;
+
+    if lessons.is_empty() {
+        return Ok(Some(Reply::Text(language.tr(Msg::NoSubject))));
+    }
+
+    let mut message = String::new();
+    for lesson in lessons {
+        message.push_str(format!("{}\n", &lesson).as_str());
+    }
+    let subjects =
+        timetable::subjects(store, chat_id, &institution, &user.group, date, slot).await?;
+    let keyboard = attendance::keyboard(language, &subjects, date);
+    Ok(Some(Reply::Page(message, keyboard)))
+}
+
+async fn timezone<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+    language: Language,
+    name: String,
+) -> Result<Option<Reply>, Error> {
+    let (_, institution) = user(store, chat_id).await?;
+    let reader = match name.trim() {
+        "-" => None,
+        value => Some(
+            value
+                .parse::<Tz>()
+                .map_err(|_| Error::InvalidTimezone(chat_id, name.clone()))?,
+        ),
+    };
+
+    store
+        .set_timezone(&chat_id, reader)
+        .await
+        .map_err(failed(chat_id, "set user time zone"))?;
+
+    Ok(Some(Reply::Text(language.tr(Msg::TimesShownIn(
+        reader.unwrap_or(institution.timezone).name(),
+    )))))
+}
+
+/// `/lang`, answered in the newly picked language when there is one.
+async fn lang<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+    language: Language,
+    code: String,
+) -> Result<Option<Reply>, Error> {
+    let picked = match code.trim() {
+        "" => return Ok(Some(Reply::Text(language.tr(Msg::Language)))),
+        "-" => None,
+        value => Some(
+            Language::try_from(value).map_err(|_| Error::InvalidLanguage(chat_id, code.clone()))?,
+        ),
+    };
+    user(store, chat_id).await?;
+
+    store
+        .set_language(&chat_id, picked)
+        .await
+        .map_err(failed(chat_id, "set user language"))?;
+
+    let language = picked.unwrap_or(language);
+    Ok(Some(Reply::Text(language.tr(Msg::Language))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::TestClock;
+    use crate::data::{Holiday, Meeting, Repeat, Schedule, Slot, Subject};
+    use crate::store::MemoryStore;
+    use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+    use chrono_tz::Europe::{Lisbon, Warsaw};
+
+    const CHAT: ChatId = ChatId(42);
+
+    /// Monday, 9 Oct 2023, 09:00 in Kyiv.
+    fn monday_morning() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2023, 10, 9, 6, 0, 0).unwrap()
+    }
+
+    fn k25() -> Group {
+        Group::try_from("K-25").unwrap()
+    }
+
+    fn user(institution: i64) -> User {
+        User {
+            institution,
+            group: k25(),
+        }
+    }
+
+    async fn store() -> MemoryStore {
+        let store = MemoryStore::new();
+        store.add_group(0, &k25()).await.unwrap();
+        store
+            .add_subject(
+                0,
+                &Subject {
+                    id: 0,
+                    title: "Test title".into(),
+                    group: k25(),
+                    optional: false,
+                },
+            )
+            .await
+            .unwrap();
+        store
+            .add_schedule(
+                0,
+                &Schedule {
+                    subject_id: 0,
+                    day: Day::Mon,
+                    repeat: Repeat::Both,
+                    slot: Slot::I,
+                },
+            )
+            .await
+            .unwrap();
+        store
+    }
+
+    fn config(group: &str) -> Command {
+        Command::Config(group.into())
+    }
+
+    fn subject(slot: Option<&str>, date: Option<&str>) -> Command {
+        let args: Vec<&str> = [slot, date].into_iter().flatten().collect();
+        Command::Subject(args.join(" "))
+    }
+
+    async fn add_lisbon(store: &MemoryStore) {
+        store
+            .add_institution(&Institution {
+                id: 1,
+                code: "ulisboa".into(),
+                name: "Lisboa".into(),
+                timezone: Lisbon,
+                bells: Default::default(),
+            })
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn config_rejects_unknown_group() {
+        let store = store().await;
+        let clock = TestClock::new(monday_morning());
+        let result = execute(&store, &clock, CHAT, CHAT, Language::En, config("K-99")).await;
+        assert!(matches!(result, Err(Error::InvalidGroup(CHAT, value)) if value == "K-99"));
+
+        let result = execute(
+            &store,
+            &clock,
+            CHAT,
+            CHAT,
+            Language::En,
+            config("default K-99"),
+        )
+        .await;
+        assert!(matches!(result, Err(Error::InvalidGroup(CHAT, value)) if value == "K-99"));
+
+        let result = execute(
+            &store,
+            &clock,
+            CHAT,
+            CHAT,
+            Language::En,
+            config("nowhere K-25"),
+        )
+        .await;
+        assert!(
+            matches!(result, Err(Error::InvalidInstitution(CHAT, value)) if value == "nowhere")
+        );
+    }
+
+    #[tokio::test]
+    async fn config_lists_choices() {
+        let store = store().await;
+        let clock = TestClock::new(monday_morning());
+        add_lisbon(&store).await;
+
+        let reply = execute(&store, &clock, CHAT, CHAT, Language::En, config(""))
+            .await
+            .unwrap();
+        assert_eq!(
+            reply,
+            Some(Reply::Text(
+                "Pick your institution with /config <institution>:\ndefault — Default\nulisboa — Lisboa"
+                    .into()
+            ))
+        );
+
+        let reply = execute(&store, &clock, CHAT, CHAT, Language::En, config("default"))
+            .await
+            .unwrap();
+        assert_eq!(
+            reply,
+            Some(Reply::Text(
+                "Groups of Default: K-25.\nPick yours with /config default <group>".into()
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn config_saves_group() {
+        let store = store().await;
+        let clock = TestClock::new(monday_morning());
+        for _ in 0..2 {
+            let reply = execute(&store, &clock, CHAT, CHAT, Language::En, config("K-25"))
                 .await
-                .map_err(|err| {
-                    log::error!("Failed to get subjects: {:?}", &err);
-                    Error::Some(msg.chat.id)
-                })?;
-
-            if subjects.is_empty() {
-                let _ = bot
-                    .send_message(msg.chat.id, "No such subject is found.")
-                    .await;
-            } else {
-                let mut message = String::new();
-                for s in subjects {
-                    let d = crate::display::Subject::new(slot, s.title, vec![]);
-                    message.push_str(format!("{}\n", &d).as_str());
-                }
+                .unwrap();
+            assert_eq!(reply, Some(Reply::Text("Saved: K-25, Default.".into())));
+        }
+        assert_eq!(store.get_user(&CHAT).await.unwrap(), user(0));
+    }
 
-                let _ = bot
-                    .send_message(msg.chat.id, message)
-                    .parse_mode(ParseMode::MarkdownV2)
-                    .await;
-            }
+    #[tokio::test]
+    async fn config_asks_for_institution_when_ambiguous() {
+        let store = store().await;
+        let clock = TestClock::new(monday_morning());
+        add_lisbon(&store).await;
+        store.add_group(1, &k25()).await.unwrap();
+
+        let reply = execute(&store, &clock, CHAT, CHAT, Language::En, config("K-25"))
+            .await
+            .unwrap();
+        assert_eq!(
+            reply,
+            Some(Reply::Text(
+                "K-25 exists in several institutions, please pick one: /config <default|ulisboa> K-25"
+                    .into()
+            ))
+        );
+        assert!(store.get_user(&CHAT).await.is_err());
+
+        execute(
+            &store,
+            &clock,
+            CHAT,
+            CHAT,
+            Language::En,
+            config("ulisboa K-25"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(store.get_user(&CHAT).await.unwrap(), user(1));
+    }
+
+    #[tokio::test]
+    async fn subject_requires_group() {
+        let store = store().await;
+        let clock = TestClock::new(monday_morning());
+        let result = execute(
+            &store,
+            &clock,
+            CHAT,
+            CHAT,
+            Language::En,
+            subject(None, None),
+        )
+        .await;
+        assert!(matches!(result, Err(Error::NoGroupConfigured(CHAT))));
+    }
+
+    #[tokio::test]
+    async fn subject_uses_current_slot() {
+        let store = store().await;
+        let clock = TestClock::new(monday_morning());
+        store.add_user(&CHAT, &user(0)).await.unwrap();
+
+        let reply = execute(
+            &store,
+            &clock,
+            CHAT,
+            CHAT,
+            Language::En,
+            subject(None, None),
+        )
+        .await
+        .unwrap();
+        assert!(matches!(reply, Some(Reply::Page(text, _)) if text.contains("Test title")));
+    }
+
+    #[tokio::test]
+    async fn subject_lists_meetings() {
+        let store = store().await;
+        let clock = TestClock::new(monday_morning());
+        store.add_user(&CHAT, &user(0)).await.unwrap();
+        store
+            .add_meeting(
+                0,
+                &Meeting {
+                    id: 1,
+                    name: "Zoom".into(),
+                    group: k25(),
+                    link: r"https://fake-link.lol/(k\25)".into(),
+                },
+            )
+            .await
+            .unwrap();
+        store
+            .assign(
+                0,
+                &crate::data::Assigned {
+                    meeting_id: 1,
+                    subject_id: 0,
+                },
+            )
+            .await
+            .unwrap();
+
+        let reply = execute(
+            &store,
+            &clock,
+            CHAT,
+            CHAT,
+            Language::En,
+            subject(Some("1"), None),
+        )
+        .await
+        .unwrap();
+        assert!(
+            matches!(reply, Some(Reply::Page(text, _)) if text.contains(r"[Zoom](https://fake-link.lol/(k\\25\))"))
+        );
+    }
+
+    #[tokio::test]
+    async fn subject_on_other_slot_is_empty() {
+        let store = store().await;
+        let clock = TestClock::new(monday_morning());
+        store.add_user(&CHAT, &user(0)).await.unwrap();
+
+        let reply = execute(
+            &store,
+            &clock,
+            CHAT,
+            CHAT,
+            Language::En,
+            subject(Some("2"), None),
+        )
+        .await
+        .unwrap();
+        assert_eq!(reply, Some(Reply::Text("No such subject is found.".into())));
+    }
+
+    #[tokio::test]
+    async fn subject_validates_arguments() {
+        let store = store().await;
+        let clock = TestClock::new(monday_morning());
+        store.add_user(&CHAT, &user(0)).await.unwrap();
+
+        let result = execute(
+            &store,
+            &clock,
+            CHAT,
+            CHAT,
+            Language::En,
+            subject(Some("5"), None),
+        )
+        .await;
+        assert!(matches!(result, Err(Error::InvalidSlot(CHAT, _))));
+
+        let result = execute(
+            &store,
+            &clock,
+            CHAT,
+            CHAT,
+            Language::En,
+            subject(None, Some("32.10.2023")),
+        )
+        .await;
+        assert!(matches!(result, Err(Error::InvalidDate(CHAT, _))));
+
+        let result = execute(
+            &store,
+            &clock,
+            CHAT,
+            CHAT,
+            Language::En,
+            subject(None, Some("14.10.2023")),
+        )
+        .await;
+        assert!(matches!(result, Err(Error::InvalidWeekday(CHAT, day)) if day == "Sat"));
+    }
+
+    #[tokio::test]
+    async fn subject_accepts_arguments_in_any_order() {
+        let store = store().await;
+        let clock = TestClock::new(monday_morning());
+        store.add_user(&CHAT, &user(0)).await.unwrap();
+
+        for args in ["1 +7", "next mon 1", "_ 16.10", "наступного понеділка I"] {
+            let reply = execute(
+                &store,
+                &clock,
+                CHAT,
+                CHAT,
+                Language::En,
+                Command::Subject(args.into()),
+            )
+            .await
+            .unwrap();
+            assert!(
+                matches!(&reply, Some(Reply::Page(text, _)) if text.contains("Test title")),
+                "{}",
+                args
+            );
         }
+
+        let result = execute(
+            &store,
+            &clock,
+            CHAT,
+            CHAT,
+            Language::En,
+            Command::Subject("K-25".into()),
+        )
+        .await;
+        assert!(matches!(result, Err(Error::InvalidArgument(CHAT, word)) if word == "K-25"));
+    }
+
+    #[tokio::test]
+    async fn subject_skips_holidays() {
+        let store = store().await;
+        let clock = TestClock::new(monday_morning());
+        store.add_user(&CHAT, &user(0)).await.unwrap();
+        store
+            .add_holiday(
+                0,
+                &Holiday {
+                    date: NaiveDate::from_ymd_opt(2023, 10, 16).unwrap(),
+                    title: "Defenders Day".into(),
+                },
+            )
+            .await
+            .unwrap();
+
+        let reply = execute(
+            &store,
+            &clock,
+            CHAT,
+            CHAT,
+            Language::En,
+            subject(Some("1"), Some("16.10.2023")),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            reply,
+            Some(Reply::Text(
+                "No classes on 16.10.2023: Defenders Day.".into()
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn subject_follows_clock_across_break() {
+        let store = store().await;
+        store.add_user(&CHAT, &user(0)).await.unwrap();
+        store
+            .add_subject(
+                0,
+                &Subject {
+                    id: 1,
+                    title: "Second title".into(),
+                    group: k25(),
+                    optional: false,
+                },
+            )
+            .await
+            .unwrap();
+        store
+            .add_schedule(
+                0,
+                &Schedule {
+                    subject_id: 1,
+                    day: Day::Mon,
+                    repeat: Repeat::Both,
+                    slot: Slot::II,
+                },
+            )
+            .await
+            .unwrap();
+
+        // 10:14 in Kyiv, one minute before the first slot ends
+        let clock = TestClock::new(Utc.with_ymd_and_hms(2023, 10, 9, 7, 14, 0).unwrap());
+        let reply = execute(
+            &store,
+            &clock,
+            CHAT,
+            CHAT,
+            Language::En,
+            subject(None, None),
+        )
+        .await
+        .unwrap();
+        assert!(matches!(reply, Some(Reply::Page(text, _)) if text.contains("Test title")));
+
+        clock.advance(Duration::minutes(1));
+        let reply = execute(
+            &store,
+            &clock,
+            CHAT,
+            CHAT,
+            Language::En,
+            subject(None, None),
+        )
+        .await
+        .unwrap();
+        assert!(matches!(reply, Some(Reply::Page(text, _)) if text.contains("Second title")));
+    }
+
+    #[tokio::test]
+    async fn subject_shows_times_in_user_timezone() {
+        let store = store().await;
+        let clock = TestClock::new(monday_morning());
+        store.add_user(&CHAT, &user(0)).await.unwrap();
+
+        let reply = execute(
+            &store,
+            &clock,
+            CHAT,
+            CHAT,
+            Language::En,
+            subject(Some("1"), None),
+        )
+        .await
+        .unwrap();
+        assert!(matches!(reply, Some(Reply::Page(text, _)) if text.contains("08:40–10:15")));
+
+        let reply = execute(
+            &store,
+            &clock,
+            CHAT,
+            CHAT,
+            Language::En,
+            Command::Timezone("Europe/Warsaw".into()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            reply,
+            Some(Reply::Text(
+                "Class times are now shown in Europe/Warsaw.".into()
+            ))
+        );
+        assert_eq!(store.get_timezone(&CHAT).await.unwrap(), Some(Warsaw));
+
+        let reply = execute(
+            &store,
+            &clock,
+            CHAT,
+            CHAT,
+            Language::En,
+            subject(Some("1"), None),
+        )
+        .await
+        .unwrap();
+        assert!(matches!(reply, Some(Reply::Page(text, _)) if text.contains("07:40–09:15")));
+
+        let result = execute(
+            &store,
+            &clock,
+            CHAT,
+            CHAT,
+            Language::En,
+            Command::Timezone("Mars/Olympus".into()),
+        )
+        .await;
+        assert!(matches!(result, Err(Error::InvalidTimezone(CHAT, _))));
+
+        execute(
+            &store,
+            &clock,
+            CHAT,
+            CHAT,
+            Language::En,
+            Command::Timezone("-".into()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(store.get_timezone(&CHAT).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn subject_in_institution_timezone() {
+        let store = store().await;
+        add_lisbon(&store).await;
+        store.add_group(1, &k25()).await.unwrap();
+        store
+            .add_subject(
+                1,
+                &Subject {
+                    id: 0,
+                    title: "Lisbon title".into(),
+                    group: k25(),
+                    optional: false,
+                },
+            )
+            .await
+            .unwrap();
+        store
+            .add_schedule(
+                1,
+                &Schedule {
+                    subject_id: 0,
+                    day: Day::Mon,
+                    repeat: Repeat::Both,
+                    slot: Slot::I,
+                },
+            )
+            .await
+            .unwrap();
+
+        // 08:00 UTC is still the first slot in Lisbon but already the second in Kyiv
+        let clock = TestClock::new(Utc.with_ymd_and_hms(2023, 10, 9, 8, 0, 0).unwrap());
+        store.add_user(&CHAT, &user(1)).await.unwrap();
+        let reply = execute(
+            &store,
+            &clock,
+            CHAT,
+            CHAT,
+            Language::En,
+            subject(None, None),
+        )
+        .await
+        .unwrap();
+        assert!(matches!(reply, Some(Reply::Page(text, _)) if text.contains("Lisbon title")));
+
+        store.update_user(&CHAT, &user(0)).await.unwrap();
+        let reply = execute(
+            &store,
+            &clock,
+            CHAT,
+            CHAT,
+            Language::En,
+            subject(None, None),
+        )
+        .await
+        .unwrap();
+        assert_eq!(reply, Some(Reply::Text("No such subject is found.".into())));
+    }
+    #[tokio::test]
+    async fn lang_switches_replies() {
+        let store = store().await;
+        let clock = TestClock::new(monday_morning());
+        let lang = |code: &str| Command::Lang(code.into());
+
+        let result = execute(&store, &clock, CHAT, CHAT, Language::En, lang("uk")).await;
+        assert!(matches!(result, Err(Error::NoGroupConfigured(CHAT))));
+
+        store.add_user(&CHAT, &user(0)).await.unwrap();
+        let reply = execute(&store, &clock, CHAT, CHAT, Language::En, lang("uk"))
+            .await
+            .unwrap();
+        assert_eq!(reply, Some(Reply::Text(Language::Uk.tr(Msg::Language))));
+        assert_eq!(store.get_language(&CHAT).await.unwrap(), Some(Language::Uk));
+
+        let reply = execute(
+            &store,
+            &clock,
+            CHAT,
+            CHAT,
+            Language::Uk,
+            subject(Some("2"), None),
+        )
+        .await
+        .unwrap();
+        assert_eq!(reply, Some(Reply::Text("Такої пари не знайдено.".into())));
+
+        let result = execute(&store, &clock, CHAT, CHAT, Language::Uk, lang("fr")).await;
+        assert!(matches!(result, Err(Error::InvalidLanguage(CHAT, code)) if code == "fr"));
+
+        execute(&store, &clock, CHAT, CHAT, Language::Uk, lang("-"))
+            .await
+            .unwrap();
+        assert_eq!(store.get_language(&CHAT).await.unwrap(), None);
+    }
+
+    #[test]
+    fn commands_are_described_in_every_language() {
+        let english = commands(Language::En);
+        let ukrainian = commands(Language::Uk);
+        for (en, uk) in english.iter().zip(&ukrainian) {
+            assert_eq!(en.command, uk.command);
+            assert_ne!(en.description, uk.description, "{}", en.command);
+        }
+    }
+
+    #[test]
+    fn commands_accept_bot_name() {
+        let cmd = Command::parse("/today@schedule_bot завтра", "schedule_bot").unwrap();
+        assert!(matches!(cmd, Command::Today(args) if args == "завтра"));
+        assert!(Command::parse("/today@other_bot", "schedule_bot").is_err());
+        assert!(Command::parse("/pin@schedule_bot", "schedule_bot")
+            .unwrap()
+            .changes_settings());
+        assert!(!Command::Week("".into()).changes_settings());
     }
-    Ok(())
 }
@@ -0,0 +1,420 @@
+use crate::bot::{failed, user, Error, Reply};
+use crate::clock::Clock;
+use crate::data::{Day, Exam, Group, Institution, Repeat, Schedule, Semesters, Subject};
+use crate::store::ScheduleStore;
+use crate::timetable;
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Offset,
+    TimeZone, Utc,
+};
+use chrono_tz::{OffsetComponents, OffsetName, Tz};
+use teloxide::types::ChatId;
+
+/// Longest line of a calendar file, in bytes, before it is folded.
+const LINE_LENGTH: usize = 75;
+
+/// `/calendar`: the weekly classes of the group of `chat_id` for the rest of
+/// the semester, along with its exams, as a calendar file named after the group.
+pub async fn export<S: ScheduleStore>(
+    store: &S,
+    clock: &dyn Clock,
+    chat_id: ChatId,
+) -> Result<Reply, Error> {
+    let (user, institution) = user(store, chat_id).await?;
+    let text = of(store, &institution, &user.group, clock.now())
+        .await
+        .map_err(failed(chat_id, "get calendar"))?;
+    let name = format!("{}.ics", user.group.as_str().replace('/', "_"));
+    Ok(Reply::File(name, text.into_bytes()))
+}
+
+/// The calendar of `group`: its weekly classes from the week of `now` to the
+/// end of the semester, holidays left out, and its exams.
+pub async fn of<S: ScheduleStore>(
+    store: &S,
+    institution: &Institution,
+    group: &Group,
+    now: DateTime<Utc>,
+) -> sqlx::Result<String> {
+    let subjects: Vec<Subject> = store
+        .get_all_subjects(institution.id)
+        .await?
+        .into_iter()
+        .filter(|s| &s.group == group)
+        .collect();
+    let ours = |id: i64| subjects.iter().any(|s| s.id == id);
+    let schedule: Vec<Schedule> = store
+        .get_schedule(institution.id)
+        .await?
+        .into_iter()
+        .filter(|c| ours(c.subject_id))
+        .collect();
+    let exams: Vec<Exam> = store
+        .get_exams(institution.id)
+        .await?
+        .into_iter()
+        .filter(|e| ours(e.subject_id))
+        .collect();
+
+    let semesters = store.get_semesters(institution.id).await?;
+    let (monday, end) = span(institution, &semesters, now);
+    let holidays: Vec<NaiveDate> = store
+        .get_holidays(institution.id, monday, end)
+        .await?
+        .into_iter()
+        .map(|h| h.date)
+        .collect();
+    Ok(ics(
+        institution,
+        &semesters,
+        &subjects,
+        &schedule,
+        &exams,
+        &holidays,
+        now,
+    ))
+}
+
+/// Monday of the week of `now` and the last day of its semester, where the
+/// institution is.
+fn span(
+    institution: &Institution,
+    semesters: &Semesters,
+    now: DateTime<Utc>,
+) -> (NaiveDate, NaiveDate) {
+    let today = now.with_timezone(&institution.timezone).date_naive();
+    let monday = today - Duration::days(today.weekday().num_days_from_monday().into());
+    (monday, semesters.around(today).1)
+}
+
+/// A calendar of `schedule` and `exams`, in the shape [`crate::import`]
+/// reads back: classes repeat weekly from their first date in the week of
+/// `now` to the end of its semester, with the weeks of the other parity and
+/// `holidays` excluded, and exams are one-off events with their kind in the
+/// categories.
+pub fn ics(
+    institution: &Institution,
+    semesters: &Semesters,
+    subjects: &[Subject],
+    schedule: &[Schedule],
+    exams: &[Exam],
+    holidays: &[NaiveDate],
+    now: DateTime<Utc>,
+) -> String {
+    let timezone = &institution.timezone;
+    let stamp = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let (monday, last) = span(institution, semesters, now);
+    let local =
+        |dt: NaiveDateTime| format!(";TZID={}:{}", timezone.name(), dt.format("%Y%m%dT%H%M%S"));
+
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".into(),
+        "PRODID:-//schedule-bot//EN".into(),
+        "CALSCALE:GREGORIAN".into(),
+    ];
+    let dates = exams.iter().map(|e| e.starts.date());
+    let from = dates.clone().fold(monday, NaiveDate::min);
+    let to = dates.fold(last, NaiveDate::max);
+    lines.extend(vtimezone(timezone, from, to));
+
+    for class in schedule {
+        let Some(subject) = subjects.iter().find(|s| s.id == class.subject_id) else {
+            continue;
+        };
+        let Some(date) = first(institution, monday, class).filter(|date| *date <= last) else {
+            continue;
+        };
+        let (start, end) = institution.bells.times(class.slot);
+        let weeks: Vec<NaiveDate> = (0..)
+            .map(|week| date + Duration::weeks(week))
+            .take_while(|date| *date <= last)
+            .collect();
+        let excluded: Vec<String> = weeks
+            .iter()
+            .filter(|date| {
+                let parity = Repeat::from(&timetable::noon(**date, timezone));
+                (class.repeat != Repeat::Both && parity != class.repeat) || holidays.contains(date)
+            })
+            .map(|date| date.and_time(start).format("%Y%m%dT%H%M%S").to_string())
+            .collect();
+        // `UNTIL` is in UTC when `DTSTART` has a time zone
+        let until = weeks.last().and_then(|date| {
+            timezone
+                .from_local_datetime(&date.and_time(start))
+                .earliest()
+                .map(|dt| dt.with_timezone(&Utc).format(";UNTIL=%Y%m%dT%H%M%SZ"))
+        });
+        lines.extend([
+            "BEGIN:VEVENT".to_string(),
+            format!(
+                "UID:class-{}-{:?}-{}-{:?}@{}",
+                subject.id, class.day, class.slot as u8, class.repeat, institution.code
+            ),
+            format!("DTSTAMP:{}", stamp),
+            format!("DTSTART{}", local(date.and_time(start))),
+            format!("DTEND{}", local(date.and_time(end))),
+            format!(
+                "RRULE:FREQ=WEEKLY{}",
+                until.map_or(String::new(), |until| until.to_string())
+            ),
+        ]);
+        if !excluded.is_empty() {
+            lines.push(format!(
+                "EXDATE;TZID={}:{}",
+                timezone.name(),
+                excluded.join(",")
+            ));
+        }
+        lines.extend([
+            format!("SUMMARY:{}", escape(&subject.title)),
+            format!("CATEGORIES:{}", escape(subject.group.as_str())),
+            "END:VEVENT".into(),
+        ]);
+    }
+    for exam in exams {
+        let Some(subject) = subjects.iter().find(|s| s.id == exam.subject_id) else {
+            continue;
+        };
+        lines.extend([
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:exam-{}@{}", exam.id, institution.code),
+            format!("DTSTAMP:{}", stamp),
+            format!("DTSTART{}", local(exam.starts)),
+            format!("SUMMARY:{}", escape(&subject.title)),
+            format!(
+                "CATEGORIES:{},{}",
+                String::from(&exam.kind).to_uppercase(),
+                escape(subject.group.as_str())
+            ),
+        ]);
+        if !exam.room.is_empty() {
+            lines.push(format!("LOCATION:{}", escape(&exam.room)));
+        }
+        if !exam.lecturer.is_empty() {
+            lines.push(format!("DESCRIPTION:{}", escape(&exam.lecturer)));
+        }
+        lines.push("END:VEVENT".into());
+    }
+    lines.push("END:VCALENDAR".into());
+
+    let mut text = String::new();
+    for line in lines {
+        text.push_str(&fold(&line));
+    }
+    text
+}
+
+/// A `VTIMEZONE` of `timezone` with the offset in effect on `from` and every
+/// change of it up to `to`.
+fn vtimezone(timezone: &Tz, from: NaiveDate, to: NaiveDate) -> Vec<String> {
+    let midnight = |date: NaiveDate| Utc.from_utc_datetime(&date.and_time(NaiveTime::MIN));
+    let offset = |at: DateTime<Utc>| timezone.offset_from_utc_datetime(&at.naive_utc());
+    let observance = |at: DateTime<Utc>, before: FixedOffset, after: &<Tz as TimeZone>::Offset| {
+        let component = if after.dst_offset().is_zero() {
+            "STANDARD"
+        } else {
+            "DAYLIGHT"
+        };
+        [
+            format!("BEGIN:{}", component),
+            format!(
+                "DTSTART:{}",
+                (at.naive_utc() + before).format("%Y%m%dT%H%M%S")
+            ),
+            format!("TZOFFSETFROM:{}", utc_offset(before)),
+            format!("TZOFFSETTO:{}", utc_offset(after.fix())),
+            format!("TZNAME:{}", after.abbreviation()),
+            format!("END:{}", component),
+        ]
+    };
+
+    // A day early and late, so that local midnights are covered in any zone
+    let (Some(from), Some(to)) = (from.pred_opt(), to.succ_opt().and_then(|d| d.succ_opt())) else {
+        return vec![];
+    };
+    let mut lines = vec![
+        "BEGIN:VTIMEZONE".to_string(),
+        format!("TZID:{}", timezone.name()),
+    ];
+    let start = offset(midnight(from));
+    lines.extend(observance(midnight(from), start.fix(), &start));
+    let mut day = from;
+    while day < to {
+        let next = day + Duration::days(1);
+        let before = offset(midnight(day)).fix();
+        if offset(midnight(next)).fix() != before {
+            // Changes happen on the minute, so the first minute of the new
+            // offset is when it starts
+            let (mut earlier, mut later) = (0, 24 * 60);
+            while later - earlier > 1 {
+                let minute = (earlier + later) / 2;
+                if offset(midnight(day) + Duration::minutes(minute)).fix() == before {
+                    earlier = minute;
+                } else {
+                    later = minute;
+                }
+            }
+            let at = midnight(day) + Duration::minutes(later);
+            lines.extend(observance(at, before, &offset(at)));
+        }
+        day = next;
+    }
+    lines.push("END:VTIMEZONE".into());
+    lines
+}
+
+/// `offset` the way `TZOFFSETFROM` and `TZOFFSETTO` take it, e.g. `+0300`.
+fn utc_offset(offset: FixedOffset) -> String {
+    let seconds = offset.local_minus_utc();
+    let sign = if seconds < 0 { '-' } else { '+' };
+    let minutes = seconds.abs() / 60;
+    format!("{}{:02}{:02}", sign, minutes / 60, minutes % 60)
+}
+
+/// The first date of `class` in the two weeks from `monday`, on its day and
+/// in a week of its parity.
+fn first(institution: &Institution, monday: NaiveDate, class: &Schedule) -> Option<NaiveDate> {
+    (0..14)
+        .map(|offset| monday + Duration::days(offset))
+        .find(|date| {
+            let dt = timetable::noon(*date, &institution.timezone);
+            Day::try_from(&dt).is_ok_and(|day| day == class.day)
+                && (class.repeat == Repeat::Both || Repeat::from(&dt) == class.repeat)
+        })
+}
+
+/// Text of a property value, with the characters calendars treat specially
+/// escaped.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(',', "\\,")
+        .replace(';', "\\;")
+        .replace('\n', "\\n")
+}
+
+/// `line` ending with CRLF, folded into lines of at most [`LINE_LENGTH`]
+/// bytes that continue after a space.
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > LINE_LENGTH {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Bells, ExamKind, Group, Slot};
+    use crate::import;
+    use chrono::TimeZone;
+
+    fn institution() -> Institution {
+        Institution {
+            id: 0,
+            code: "default".into(),
+            name: "Default".into(),
+            timezone: chrono_tz::Europe::Kiev,
+            bells: Bells::default(),
+        }
+    }
+
+    fn k25() -> Group {
+        Group::try_from("K-25").unwrap()
+    }
+
+    #[test]
+    fn calendar_reads_back() {
+        let subjects = [
+            Subject {
+                id: 0,
+                title: "Algebra, linear".into(),
+                group: k25(),
+                optional: false,
+            },
+            Subject {
+                id: 1,
+                title: "Logic".into(),
+                group: k25(),
+                optional: false,
+            },
+        ];
+        let schedule = [
+            Schedule {
+                subject_id: 0,
+                day: Day::Mon,
+                repeat: Repeat::Both,
+                slot: Slot::I,
+            },
+            Schedule {
+                subject_id: 1,
+                day: Day::Wed,
+                repeat: Repeat::Odd,
+                slot: Slot::III,
+            },
+        ];
+        let exam = Exam {
+            id: 4,
+            subject_id: 1,
+            kind: ExamKind::Credit,
+            starts: NaiveDate::from_ymd_opt(2024, 1, 12)
+                .unwrap()
+                .and_hms_opt(13, 0, 0)
+                .unwrap(),
+            room: "204".into(),
+            lecturer: "O. Petrenko".into(),
+        };
+        let now = Utc.with_ymd_and_hms(2023, 10, 11, 8, 0, 0).unwrap();
+        let holiday = NaiveDate::from_ymd_opt(2023, 10, 23).unwrap();
+        let text = ics(
+            &institution(),
+            &Semesters::default(),
+            &subjects,
+            &schedule,
+            std::slice::from_ref(&exam),
+            &[holiday],
+            now,
+        );
+        assert!(text.contains("DTSTART;TZID=Europe/Kiev:20231009T084000\r\n"));
+        assert!(text.contains("RRULE:FREQ=WEEKLY;UNTIL=20240129T064000Z\r\n"));
+        let unfolded = text.replace("\r\n ", "");
+        assert!(unfolded.contains("EXDATE;TZID=Europe/Kiev:20231023T084000\r\n"));
+        // Odd weeks are the second and fourth of a month, so 25.10 is one
+        // and 18.10 and 01.11 aren't
+        assert!(unfolded
+            .contains("EXDATE;TZID=Europe/Kiev:20231018T122000,20231101T122000,20231115T122000,"));
+        assert!(text.contains(
+            "BEGIN:VTIMEZONE\r\nTZID:Europe/Kiev\r\nBEGIN:DAYLIGHT\r\nDTSTART:20231008T030000\r\n"
+        ));
+        assert!(text.contains(
+            "BEGIN:STANDARD\r\nDTSTART:20231029T040000\r\n\
+             TZOFFSETFROM:+0300\r\nTZOFFSETTO:+0200\r\nTZNAME:EET\r\nEND:STANDARD\r\n"
+        ));
+        assert!(text.contains("SUMMARY:Algebra\\, linear\r\n"));
+        assert!(text.contains("CATEGORIES:CREDIT,K-25\r\n"));
+        assert!(text.lines().all(|line| line.len() <= LINE_LENGTH));
+
+        let import = import::read("k25.ics", &text, &institution(), &k25(), &subjects).unwrap();
+        assert_eq!(import.subjects.len(), 2);
+        let classes: Vec<String> = import.schedule.iter().map(import::describe).collect();
+        assert_eq!(classes, vec!["Mon slot 1, Both", "Wed slot 3, Odd"]);
+        assert_eq!(import.exams.len(), 1);
+        assert!(import.exams[0].same(&exam));
+    }
+
+    #[test]
+    fn long_lines_fold() {
+        let line = format!("SUMMARY:{}", "й".repeat(50));
+        let folded = fold(&line);
+        assert!(folded.split("\r\n").all(|line| line.len() <= LINE_LENGTH));
+        assert_eq!(folded.replace("\r\n ", ""), format!("{}\r\n", line));
+    }
+}
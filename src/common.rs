@@ -0,0 +1,246 @@
+use crate::bot::{expr, failed, user, Error, Reply};
+use crate::clock::Clock;
+use crate::data::{Group, Slot};
+use crate::friends;
+use crate::i18n::{Language, Msg};
+use crate::store::ScheduleStore;
+use crate::timetable;
+use chrono::{Datelike, Duration};
+use teloxide::types::ChatId;
+
+/// `/common <group|@friend> ... [date]`: the slots in the week of the date,
+/// this week by default, when none of the groups has a class.
+///
+/// Friends are the ones `sender` agreed to share free time with both ways,
+/// standing for their groups, which are taken along with the group of the
+/// chat.
+pub async fn execute<S: ScheduleStore>(
+    store: &S,
+    clock: &dyn Clock,
+    chat_id: ChatId,
+    sender: ChatId,
+    language: Language,
+    args: &str,
+) -> Result<Reply, Error> {
+    let expr = expr(chat_id, args)?;
+    if expr.slot.is_some() || expr.words.is_empty() {
+        return Err(Error::Usage(chat_id, "common"));
+    }
+    let (user, institution) = user(store, chat_id).await?;
+    let known = store
+        .get_groups(institution.id)
+        .await
+        .map_err(failed(chat_id, "get groups"))?;
+
+    let mut groups: Vec<Group> = vec![];
+    for word in &expr.words {
+        let group = match friends::username(word) {
+            Some(username) => {
+                let friend = store
+                    .find_username(&username)
+                    .await
+                    .map_err(failed(chat_id, "find username"))?
+                    .ok_or_else(|| Error::UnknownUser(chat_id, word.clone()))?;
+                let agreed = friends::mutual(store, sender, friend)
+                    .await
+                    .map_err(failed(chat_id, "get friends"))?;
+                let theirs = match store.get_user(&friend).await {
+                    Ok(theirs) if agreed && theirs.institution == institution.id => theirs,
+                    Ok(_) | Err(sqlx::Error::RowNotFound) => {
+                        return Err(Error::NotFriend(chat_id, word.clone()))
+                    }
+                    Err(err) => return Err(failed(chat_id, "get user")(err)),
+                };
+                if !groups.contains(&user.group) {
+                    groups.push(user.group.clone());
+                }
+                theirs.group
+            }
+            None => match Group::try_from(word.as_str()) {
+                Ok(group) if known.contains(&group) => group,
+                _ => return Err(Error::InvalidGroup(chat_id, word.clone())),
+            },
+        };
+        if !groups.contains(&group) {
+            groups.push(group);
+        }
+    }
+
+    let today = clock
+        .now()
+        .with_timezone(&institution.timezone)
+        .date_naive();
+    let date = match expr.date {
+        Some(when) => when
+            .resolve(today)
+            .ok_or_else(|| Error::InvalidDate(chat_id, args.into()))?,
+        None => today,
+    };
+    let monday = date - Duration::days(date.weekday().num_days_from_monday().into());
+    let reader = timetable::reader(store, chat_id, &institution).await?;
+
+    let names: Vec<&str> = groups.iter().map(Group::as_str).collect();
+    let mut message = language.tr(Msg::CommonFree {
+        groups: &names.join(", "),
+        week: &monday.format("%d.%m.%Y").to_string(),
+    });
+    for offset in 0..5 {
+        let date = monday + Duration::days(offset);
+        let holiday = store
+            .get_holiday(institution.id, date)
+            .await
+            .map_err(failed(chat_id, "get holiday"))?;
+        let mut free = vec![];
+        for slot in Slot::ALL {
+            let mut busy = false;
+            if holiday.is_none() {
+                for group in &groups {
+                    let subjects =
+                        timetable::subjects(store, chat_id, &institution, group, date, slot)
+                            .await?;
+                    if !subjects.is_empty() {
+                        busy = true;
+                        break;
+                    }
+                }
+            }
+            if !busy {
+                let (start, end) =
+                    institution
+                        .bells
+                        .times_in(slot, date, &institution.timezone, &reader);
+                free.push(format!(
+                    "{} {}–{}",
+                    slot as u8,
+                    start.format("%H:%M"),
+                    end.format("%H:%M")
+                ));
+            }
+        }
+        let free = if free.is_empty() {
+            "—".to_string()
+        } else {
+            free.join(", ")
+        };
+        message.push_str(&format!("\n{}: {}", language.day(date, "%d.%m"), free));
+    }
+    Ok(Reply::Text(message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::TestClock;
+    use crate::data::{Day, Holiday, Repeat, Schedule, Subject, User};
+    use crate::store::MemoryStore;
+    use chrono::{NaiveDate, TimeZone, Utc};
+
+    const ALICE: ChatId = ChatId(7);
+    const BOB: ChatId = ChatId(8);
+
+    fn group(name: &str) -> Group {
+        Group::try_from(name).unwrap()
+    }
+
+    /// K-25 busy first thing on Mondays and K-26 in the second slot of odd
+    /// Mondays, Alice in K-25 and Bob in K-26, on Tuesday, 10 Oct 2023.
+    async fn setup() -> (MemoryStore, TestClock) {
+        let store = MemoryStore::new();
+        let classes = [
+            (0, "K-25", Repeat::Both, Slot::I),
+            (1, "K-26", Repeat::Odd, Slot::II),
+        ];
+        for (id, name, repeat, slot) in classes {
+            store.add_group(0, &group(name)).await.unwrap();
+            let subject = Subject {
+                id,
+                title: format!("Subject {}", id),
+                group: group(name),
+                optional: false,
+            };
+            store.add_subject(0, &subject).await.unwrap();
+            let class = Schedule {
+                subject_id: id,
+                day: Day::Mon,
+                repeat,
+                slot,
+            };
+            store.add_schedule(0, &class).await.unwrap();
+        }
+        for (chat, name) in [(ALICE, "K-25"), (BOB, "K-26")] {
+            let user = User {
+                institution: 0,
+                group: group(name),
+            };
+            store.add_user(&chat, &user).await.unwrap();
+        }
+        let clock = TestClock::new(Utc.with_ymd_and_hms(2023, 10, 10, 6, 0, 0).unwrap());
+        (store, clock)
+    }
+
+    async fn common(store: &MemoryStore, clock: &TestClock, args: &str) -> Result<String, Error> {
+        match execute(store, clock, ALICE, ALICE, Language::En, args).await? {
+            Reply::Text(text) => Ok(text),
+            other => panic!("Unexpected reply {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn free_slots_of_every_group() {
+        let (store, clock) = setup().await;
+        let text = common(&store, &clock, "K-25 K-26").await.unwrap();
+        let mut lines = text.lines();
+        assert_eq!(
+            lines.next(),
+            Some("Free for K-25, K-26 in the week of 09.10.2023:")
+        );
+        // 9 Oct is in an odd week
+        assert_eq!(
+            lines.next(),
+            Some("Mon 09.10: 3 12:20–13:55, 4 14:05–15:40")
+        );
+        assert_eq!(
+            lines.next(),
+            Some("Tue 10.10: 1 08:40–10:15, 2 10:35–12:10, 3 12:20–13:55, 4 14:05–15:40")
+        );
+
+        let text = common(&store, &clock, "K-25 K-26 +7").await.unwrap();
+        assert!(text.contains("Mon 16.10: 2 10:35–12:10, 3"));
+
+        let holiday = Holiday {
+            date: NaiveDate::from_ymd_opt(2023, 10, 16).unwrap(),
+            title: "Day off".into(),
+        };
+        store.add_holiday(0, &holiday).await.unwrap();
+        let text = common(&store, &clock, "K-25 next mon").await.unwrap();
+        assert!(text.contains("Mon 16.10: 1 08:40–10:15, 2"));
+    }
+
+    #[tokio::test]
+    async fn friends_stand_for_their_groups() {
+        let (store, clock) = setup().await;
+        for (chat, name) in [(ALICE, "alice"), (BOB, "bob")] {
+            store.set_username(&chat, Some(name)).await.unwrap();
+        }
+        store.add_friend(&ALICE, &BOB).await.unwrap();
+        assert!(matches!(
+            common(&store, &clock, "@bob").await,
+            Err(Error::NotFriend(_, _))
+        ));
+
+        store.add_friend(&BOB, &ALICE).await.unwrap();
+        let text = common(&store, &clock, "@bob").await.unwrap();
+        assert!(text.starts_with("Free for K-25, K-26 in"));
+        assert!(text.contains("Mon 09.10: 3 12:20–13:55, 4"));
+
+        for (args, expected) in [("@carol", "unknown"), ("K-99", "group"), ("", "usage")] {
+            let found = match common(&store, &clock, args).await {
+                Err(Error::UnknownUser(..)) => "unknown",
+                Err(Error::InvalidGroup(..)) => "group",
+                Err(Error::Usage(_, "common")) => "usage",
+                other => panic!("Unexpected result {:?}", other),
+            };
+            assert_eq!(found, expected, "{}", args);
+        }
+    }
+}
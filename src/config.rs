@@ -1,12 +1,21 @@
-#[derive(serde::Deserialize)]
+use serde::Deserialize;
+
+#[derive(Deserialize)]
 pub struct Config {
     pub token: String,
+    /// `sqlite://...` or `postgres://...`, the backend is picked from the scheme.
+    pub database: String,
+    /// Address to serve the HTTP API on alongside the bot, e.g.
+    /// `127.0.0.1:8080`. There is none unless set.
+    pub listen: Option<String>,
 }
 
 pub fn get() -> Config {
     use config::File;
 
     let config = config::Config::builder()
+        .set_default("database", "sqlite:///tmp/test.db")
+        .expect("Failed to set default database url")
         .add_source(File::with_name("config.toml"))
         .build()
         .expect("Failed to build config::Config");
@@ -0,0 +1,346 @@
+use crate::bot::{failed, user, Error, Reply};
+use crate::data::{Day, Group, Repeat, Schedule, Slot, Subject};
+use crate::homework;
+use crate::i18n::{Language, Msg};
+use crate::store::ScheduleStore;
+use teloxide::types::ChatId;
+
+/// Two subjects with classes at the same time.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Clash {
+    pub first: i64,
+    pub second: i64,
+    pub day: Day,
+    pub slot: Slot,
+    /// Weeks both classes take place in.
+    pub repeat: Repeat,
+}
+
+/// Weeks both `a` and `b` take place in, if they share a day and a slot.
+/// Classes of every week meet those of odd and even weeks alike.
+pub fn overlap(a: &Schedule, b: &Schedule) -> Option<Repeat> {
+    if a.day != b.day || a.slot != b.slot {
+        return None;
+    }
+    match a.repeat as u8 & b.repeat as u8 {
+        0b01 => Some(Repeat::Odd),
+        0b10 => Some(Repeat::Even),
+        0b11 => Some(Repeat::Both),
+        _ => None,
+    }
+}
+
+/// Clashes between classes in `schedule` of different subjects among
+/// `subjects`, by day and slot.
+pub fn find(subjects: &[i64], schedule: &[Schedule]) -> Vec<Clash> {
+    let classes: Vec<&Schedule> = schedule
+        .iter()
+        .filter(|c| subjects.contains(&c.subject_id))
+        .collect();
+    let mut clashes = vec![];
+    for (index, a) in classes.iter().enumerate() {
+        for b in &classes[index + 1..] {
+            if a.subject_id == b.subject_id {
+                continue;
+            }
+            if let Some(repeat) = overlap(a, b) {
+                clashes.push(Clash {
+                    first: a.subject_id.min(b.subject_id),
+                    second: a.subject_id.max(b.subject_id),
+                    day: a.day,
+                    slot: a.slot,
+                    repeat,
+                });
+            }
+        }
+    }
+    clashes.sort_by_key(|c| (c.day as u8, c.slot as u8, c.first, c.second));
+    clashes
+}
+
+/// Clashes between the mandatory classes of `group`, which no student of it
+/// can attend both of.
+pub fn mandatory(subjects: &[Subject], schedule: &[Schedule], group: &Group) -> Vec<Clash> {
+    let ids: Vec<i64> = subjects
+        .iter()
+        .filter(|s| s.group == *group && !s.optional)
+        .map(|s| s.id)
+        .collect();
+    find(&ids, schedule)
+}
+
+/// `Mon slot 2, odd weeks: Algebra and Drawing`.
+fn line(language: Language, clash: &Clash, subjects: &[Subject]) -> String {
+    let title = |id: i64| {
+        subjects
+            .iter()
+            .find(|s| s.id == id)
+            .map_or(String::new(), |s| s.title.clone())
+    };
+    language.tr(Msg::Clash {
+        day: language.weekday(clash.day.into()),
+        slot: &(clash.slot as u8).to_string(),
+        weeks: language.weeks(clash.repeat),
+        first: &title(clash.first),
+        second: &title(clash.second),
+    })
+}
+
+/// Subjects of the group of `chat_id`, the institution, its weekly classes
+/// and the ids of the subjects `sender` takes: mandatory and enrolled ones.
+async fn taken<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+    sender: ChatId,
+) -> Result<(Vec<Subject>, i64, Vec<Schedule>, Vec<i64>), Error> {
+    let (user, institution) = user(store, chat_id).await?;
+    let subjects: Vec<Subject> = store
+        .get_all_subjects(institution.id)
+        .await
+        .map_err(failed(chat_id, "get subjects"))?
+        .into_iter()
+        .filter(|s| s.group == user.group)
+        .collect();
+    let schedule = store
+        .get_schedule(institution.id)
+        .await
+        .map_err(failed(chat_id, "get schedule"))?;
+    let enrolled = store
+        .get_enrolled(&sender, institution.id)
+        .await
+        .map_err(failed(chat_id, "get enrolled"))?;
+    let taken = subjects
+        .iter()
+        .filter(|s| !s.optional || enrolled.contains(&s.id))
+        .map(|s| s.id)
+        .collect();
+    Ok((subjects, institution.id, schedule, taken))
+}
+
+/// `/conflicts`: classes of the subjects the sender takes that clash.
+pub async fn list<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+    sender: ChatId,
+    language: Language,
+) -> Result<Reply, Error> {
+    let (subjects, _, schedule, taken) = taken(store, chat_id, sender).await?;
+    let clashes = find(&taken, &schedule);
+    if clashes.is_empty() {
+        return Ok(Reply::Text(language.tr(Msg::NoClashes)));
+    }
+    let mut message = language.tr(Msg::ClashList);
+    for clash in &clashes {
+        message.push('\n');
+        message.push_str(&line(language, clash, &subjects));
+    }
+    Ok(Reply::Text(message))
+}
+
+/// `/enroll`: the optional subjects of the group; `/enroll <subject>` takes
+/// one for the sender, warning of the classes it clashes with, and
+/// `/enroll - <subject>` drops it.
+pub async fn enroll<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+    sender: ChatId,
+    language: Language,
+    args: &str,
+) -> Result<Reply, Error> {
+    let (subjects, institution, schedule, mut taken) = taken(store, chat_id, sender).await?;
+    let electives: Vec<Subject> = subjects.iter().filter(|s| s.optional).cloned().collect();
+    let words: Vec<&str> = args.split_whitespace().collect();
+    let (drop, words) = match words.split_first() {
+        None => return Ok(electives_list(language, &electives, &taken)),
+        Some((&"-", rest)) => (true, rest),
+        Some(_) => (false, &words[..]),
+    };
+    let subject = match homework::find(&electives, words) {
+        Some((subject, [])) => subject,
+        Some((_, [word, ..])) => return Err(Error::InvalidArgument(chat_id, word.to_string())),
+        None => {
+            let word = words.first().ok_or(Error::Usage(chat_id, "enroll"))?;
+            return Err(Error::InvalidSubject(chat_id, word.to_string()));
+        }
+    };
+    store
+        .set_enrolled(&sender, institution, subject.id, !drop)
+        .await
+        .map_err(failed(chat_id, "set enrolled"))?;
+    if drop {
+        return Ok(Reply::Text(language.tr(Msg::Unenrolled(&subject.title))));
+    }
+
+    let mut message = language.tr(Msg::Enrolled(&subject.title));
+    if !taken.contains(&subject.id) {
+        taken.push(subject.id);
+    }
+    let clashes: Vec<Clash> = find(&taken, &schedule)
+        .into_iter()
+        .filter(|c| c.first == subject.id || c.second == subject.id)
+        .collect();
+    if !clashes.is_empty() {
+        message.push_str("\n\n");
+        message.push_str(&language.tr(Msg::ClashWarning));
+        for clash in &clashes {
+            message.push('\n');
+            message.push_str(&line(language, clash, &subjects));
+        }
+    }
+    Ok(Reply::Text(message))
+}
+
+/// Optional subjects of the group, those among `taken` marked.
+fn electives_list(language: Language, electives: &[Subject], taken: &[i64]) -> Reply {
+    if electives.is_empty() {
+        return Reply::Text(language.tr(Msg::NoElectives));
+    }
+    let mut message = language.tr(Msg::ElectiveList);
+    for subject in electives {
+        message.push('\n');
+        message.push_str(&subject.title);
+        if taken.contains(&subject.id) {
+            message.push_str(&format!(" ({})", language.tr(Msg::EnrolledMark)));
+        }
+    }
+    Reply::Text(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::User;
+    use crate::store::MemoryStore;
+
+    const CHAT: ChatId = ChatId(7);
+
+    fn class(subject_id: i64, day: Day, repeat: Repeat, slot: Slot) -> Schedule {
+        Schedule {
+            subject_id,
+            day,
+            repeat,
+            slot,
+        }
+    }
+
+    #[test]
+    fn every_week_meets_odd_and_even() {
+        use Repeat::*;
+        let at = |id, repeat| class(id, Day::Mon, repeat, Slot::II);
+        assert_eq!(overlap(&at(0, Both), &at(1, Odd)), Some(Odd));
+        assert_eq!(overlap(&at(0, Even), &at(1, Both)), Some(Even));
+        assert_eq!(overlap(&at(0, Both), &at(1, Both)), Some(Both));
+        assert_eq!(overlap(&at(0, Odd), &at(1, Even)), None);
+        assert_eq!(
+            overlap(&at(0, Both), &class(1, Day::Mon, Both, Slot::III)),
+            None
+        );
+
+        let schedule = [at(2, Odd), at(1, Both), at(2, Even), at(3, Odd)];
+        let clashes = find(&[1, 2], &schedule);
+        let weeks: Vec<(i64, i64, Repeat)> = clashes
+            .iter()
+            .map(|c| (c.first, c.second, c.repeat))
+            .collect();
+        assert_eq!(weeks, vec![(1, 2, Odd), (1, 2, Even)]);
+    }
+
+    /// K-25 with Algebra every Monday first thing, and electives Drawing
+    /// then on odd weeks and Music on Tuesdays, the chat in K-25.
+    async fn store() -> MemoryStore {
+        let store = MemoryStore::new();
+        let group = Group::try_from("K-25").unwrap();
+        let subjects = [
+            (0, "Algebra", false, Day::Mon, Repeat::Both),
+            (1, "Drawing", true, Day::Mon, Repeat::Odd),
+            (2, "Music", true, Day::Tue, Repeat::Both),
+        ];
+        for (id, title, optional, day, repeat) in subjects {
+            let subject = Subject {
+                id,
+                title: title.into(),
+                group: group.clone(),
+                optional,
+            };
+            store.add_subject(0, &subject).await.unwrap();
+            store
+                .add_schedule(0, &class(id, day, repeat, Slot::I))
+                .await
+                .unwrap();
+        }
+        let user = User {
+            institution: 0,
+            group,
+        };
+        store.add_user(&CHAT, &user).await.unwrap();
+        store
+    }
+
+    fn text(reply: Result<Reply, Error>) -> String {
+        match reply.unwrap() {
+            Reply::Text(text) => text,
+            other => panic!("Unexpected reply {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn enrolling_warns_of_clashes() {
+        let store = store().await;
+        let enroll = |args: &'static str| enroll(&store, CHAT, CHAT, Language::En, args);
+        assert_eq!(
+            text(list(&store, CHAT, CHAT, Language::En).await),
+            "None of your classes clash."
+        );
+        assert_eq!(text(enroll("music").await), "Enrolled in Music.");
+        assert_eq!(
+            text(enroll("drawing").await),
+            "Enrolled in Drawing.\n\n\
+             Mind that it clashes with:\n\
+             Mon slot 1, odd weeks: Algebra and Drawing"
+        );
+        assert_eq!(
+            text(enroll("").await),
+            "Electives of your group:\nDrawing (enrolled)\nMusic (enrolled)"
+        );
+        assert_eq!(
+            text(list(&store, CHAT, CHAT, Language::En).await),
+            "These classes clash:\nMon slot 1, odd weeks: Algebra and Drawing"
+        );
+
+        assert_eq!(text(enroll("- drawing").await), "Dropped Drawing.");
+        assert_eq!(
+            text(list(&store, CHAT, CHAT, Language::En).await),
+            "None of your classes clash."
+        );
+        assert!(matches!(
+            enroll("algebra").await,
+            Err(Error::InvalidSubject(_, _))
+        ));
+    }
+
+    #[tokio::test]
+    async fn members_of_a_group_chat_enroll_for_themselves() {
+        let store = store().await;
+        let group = ChatId(-100);
+        let artist = ChatId(8);
+        let user = User {
+            institution: 0,
+            group: Group::try_from("K-25").unwrap(),
+        };
+        store.add_user(&group, &user).await.unwrap();
+        enroll(&store, group, artist, Language::En, "drawing")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            text(list(&store, group, artist, Language::En).await),
+            "These classes clash:\nMon slot 1, odd weeks: Algebra and Drawing"
+        );
+        assert_eq!(
+            text(list(&store, group, CHAT, Language::En).await),
+            "None of your classes clash."
+        );
+        assert_eq!(store.get_enrolled(&artist, 0).await.unwrap(), vec![1]);
+        assert!(store.get_enrolled(&group, 0).await.unwrap().is_empty());
+    }
+}
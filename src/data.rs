@@ -1,8 +1,9 @@
 use anyhow::anyhow;
-use chrono::{offset::TimeZone, DateTime, Datelike, NaiveTime};
-use chrono_tz::Europe::Kiev;
+use chrono::{offset::TimeZone, DateTime, Datelike, NaiveDate, NaiveDateTime, NaiveTime};
+use chrono_tz::Tz;
+use teloxide::types::ChatId;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Subject {
     pub id: i64,
     pub title: String,
@@ -10,7 +11,7 @@ pub struct Subject {
     pub optional: bool,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Meeting {
     pub id: i64,
     pub name: String,
@@ -18,7 +19,7 @@ pub struct Meeting {
     pub link: String,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Schedule {
     pub subject_id: i64,
     pub day: Day,
@@ -26,12 +27,19 @@ pub struct Schedule {
     pub slot: Slot,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Assigned {
     pub meeting_id: i64,
     pub subject_id: i64,
 }
 
+/// A date without classes, e.g. a public holiday or a break between terms.
+#[derive(Debug, Clone)]
+pub struct Holiday {
+    pub date: NaiveDate,
+    pub title: String,
+}
+
 #[derive(PartialEq, Debug, Clone, Copy)]
 #[repr(u8)]
 pub enum Repeat {
@@ -55,10 +63,10 @@ impl TryFrom<&str> for Repeat {
     }
 }
 
+/// Week parity of the local date of `value`, in whatever time zone it is in.
 impl<Tz: TimeZone> From<&DateTime<Tz>> for Repeat {
     fn from(value: &DateTime<Tz>) -> Self {
-        let time = value.with_timezone(&Kiev);
-        let is_odd = ((Datelike::day(&time) + 6) / 7 % 2) == 0;
+        let is_odd = Datelike::day(value).div_ceil(7).is_multiple_of(2);
         if is_odd {
             Repeat::Odd
         } else {
@@ -67,30 +75,264 @@ impl<Tz: TimeZone> From<&DateTime<Tz>> for Repeat {
     }
 }
 
-#[derive(PartialEq, Debug)]
-pub enum Group {
-    K25,
+/// Name of an academic group, e.g. `K-25`, unique within its institution.
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+pub struct Group(String);
+
+impl Group {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Faculty prefix before the dash, e.g. `K` of `K-25/1`.
+    pub fn faculty(&self) -> &str {
+        self.0
+            .split_once('-')
+            .map_or(&self.0, |(faculty, _)| faculty)
+    }
+
+    /// Year of study, the first digit after the dash, e.g. `2` of `K-25/1`.
+    ///
+    /// Empty for names that don't follow the convention.
+    pub fn year(&self) -> &str {
+        match self.0.split_once('-') {
+            Some((_, rest)) if rest.starts_with(|c: char| c.is_ascii_digit()) => &rest[..1],
+            _ => "",
+        }
+    }
+
+    /// Name without the subgroup, e.g. `K-25` of `K-25/1`.
+    pub fn base(&self) -> &str {
+        self.0.split_once('/').map_or(&self.0, |(base, _)| base)
+    }
+
+    /// Subgroup after the slash, e.g. `1` of `K-25/1`, or empty for a whole group.
+    pub fn subgroup(&self) -> &str {
+        self.0.split_once('/').map_or("", |(_, subgroup)| subgroup)
+    }
 }
 
 impl TryFrom<&str> for Group {
     type Error = anyhow::Error;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        use Group::*;
-        match value {
-            "K-25" => Ok(K25),
-            other => Err(anyhow!("Not a group: {}", other)),
+        let valid = !value.is_empty()
+            && value.chars().count() <= 32
+            && !value.chars().any(char::is_whitespace);
+        if valid {
+            Ok(Group(value.into()))
+        } else {
+            Err(anyhow!("Not a group: {}", value))
         }
     }
 }
 
 impl From<&Group> for String {
     fn from(value: &Group) -> Self {
-        use Group::*;
+        value.0.clone()
+    }
+}
+
+impl std::fmt::Display for Group {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", &self.0)
+    }
+}
+
+/// A faculty, or any other body with its own groups and timetable.
+#[derive(Debug, Clone)]
+pub struct Institution {
+    pub id: i64,
+    /// Short name users pick the institution by, e.g. `cyb`.
+    pub code: String,
+    pub name: String,
+    pub timezone: Tz,
+    pub bells: Bells,
+}
+
+/// A user's choice of institution and group.
+#[derive(PartialEq, Debug, Clone)]
+pub struct User {
+    pub institution: i64,
+    pub group: Group,
+}
+
+/// What a user may do with a timetable.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Role {
+    /// Edits the timetable and hands out roles.
+    Owner,
+    /// Edits the timetable.
+    Editor,
+}
+
+impl TryFrom<&str> for Role {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "owner" => Ok(Role::Owner),
+            "editor" => Ok(Role::Editor),
+            other => Err(anyhow!("Not a role: {}", other)),
+        }
+    }
+}
+
+impl From<&Role> for String {
+    fn from(value: &Role) -> Self {
+        match value {
+            Role::Owner => "owner".into(),
+            Role::Editor => "editor".into(),
+        }
+    }
+}
+
+/// A role over the timetable of an institution, or of one of its groups only.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Grant {
+    pub institution: i64,
+    pub group: Option<Group>,
+    pub role: Role,
+}
+
+impl Grant {
+    /// Whether the grant extends to `group` of `institution`.
+    pub fn covers(&self, institution: i64, group: &Group) -> bool {
+        self.institution == institution && self.group.as_ref().is_none_or(|g| g == group)
+    }
+}
+
+/// A one-off change to the timetable of a group: the classes in `slot` on
+/// `date` replaced by `subject_id`, or cancelled when there is none.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Change {
+    pub group: Group,
+    pub date: NaiveDate,
+    pub slot: Slot,
+    pub subject_id: Option<i64>,
+}
+
+/// Homework for a subject, due at its class on `due`. Shared homework is
+/// seen by the whole group of the subject, the rest only by its author.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Homework {
+    pub id: i64,
+    pub subject_id: i64,
+    pub due: NaiveDate,
+    pub text: String,
+    /// The chat that added it.
+    pub author: ChatId,
+    pub shared: bool,
+}
+
+impl Homework {
+    /// `subject 3 due 2023-10-16, shared: Exercises 1-5`, the way audit
+    /// entries show it. The text of private homework is left out.
+    pub fn describe(&self) -> String {
+        let text = format!("subject {} due {}", self.subject_id, self.due);
+        match self.shared {
+            true => format!("{}, shared: {}", text, self.text),
+            false => format!("{}, private", text),
+        }
+    }
+}
+
+/// A private note of a chat on a subject, e.g. `bring a calculator`.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Note {
+    pub subject_id: i64,
+    pub text: String,
+}
+
+/// Whether a user attended a class of a subject on a date.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Attendance {
+    pub subject_id: i64,
+    pub date: NaiveDate,
+    pub attended: bool,
+}
+
+/// Points a user got for a subject, e.g. for a test. `comment` is empty
+/// when there is none.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Grade {
+    pub id: i64,
+    pub subject_id: i64,
+    pub date: NaiveDate,
+    pub points: i64,
+    pub comment: String,
+}
+
+/// What an event of the exam session is.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum ExamKind {
+    Exam,
+    Credit,
+    Consultation,
+}
+
+impl TryFrom<&str> for ExamKind {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "exam" => Ok(ExamKind::Exam),
+            "credit" => Ok(ExamKind::Credit),
+            "consultation" => Ok(ExamKind::Consultation),
+            other => Err(anyhow!("Not a kind of exam: {}", other)),
+        }
+    }
+}
+
+impl From<&ExamKind> for String {
+    fn from(value: &ExamKind) -> Self {
         match value {
-            K25 => "K-25",
+            ExamKind::Exam => "exam".into(),
+            ExamKind::Credit => "credit".into(),
+            ExamKind::Consultation => "consultation".into(),
+        }
+    }
+}
+
+/// A one-off event of the exam session for a subject, starting at a local
+/// time where the institution is. The room and lecturer may be empty.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Exam {
+    pub id: i64,
+    pub subject_id: i64,
+    pub kind: ExamKind,
+    pub starts: NaiveDateTime,
+    pub room: String,
+    pub lecturer: String,
+}
+
+impl Exam {
+    /// `exam 2024-01-15 09:00, room 204, O. Petrenko`, the way the import
+    /// diff and audit entries show it.
+    pub fn describe(&self) -> String {
+        let mut text = format!(
+            "{} {}",
+            String::from(&self.kind),
+            self.starts.format("%Y-%m-%d %H:%M")
+        );
+        if !self.room.is_empty() {
+            text.push_str(&format!(", room {}", self.room));
+        }
+        if !self.lecturer.is_empty() {
+            text.push_str(&format!(", {}", self.lecturer));
+        }
+        text
+    }
+
+    /// Whether `other` is the same event, whatever their ids.
+    pub fn same(&self, other: &Exam) -> bool {
+        Exam {
+            id: 0,
+            ..self.clone()
+        } == Exam {
+            id: 0,
+            ..other.clone()
         }
-        .into()
     }
 }
 
@@ -121,12 +363,29 @@ impl TryFrom<&str> for Day {
     }
 }
 
+impl Day {
+    pub const ALL: [Day; 5] = [Day::Mon, Day::Tue, Day::Wed, Day::Thu, Day::Fri];
+}
+
+impl From<Day> for chrono::Weekday {
+    fn from(value: Day) -> Self {
+        use chrono::Weekday::*;
+        match value {
+            Day::Mon => Mon,
+            Day::Tue => Tue,
+            Day::Wed => Wed,
+            Day::Thu => Thu,
+            Day::Fri => Fri,
+        }
+    }
+}
+
+/// Weekday of the local date of `value`, in whatever time zone it is in.
 impl<Tz: TimeZone> TryFrom<&DateTime<Tz>> for Day {
     type Error = anyhow::Error;
     fn try_from(value: &DateTime<Tz>) -> Result<Self, Self::Error> {
-        let time = value.with_timezone(&Kiev);
         use chrono::Weekday::*;
-        match time.weekday() {
+        match value.weekday() {
             Mon => Ok(Day::Mon),
             Tue => Ok(Day::Tue),
             Wed => Ok(Day::Wed),
@@ -162,22 +421,197 @@ impl TryFrom<&str> for Slot {
     }
 }
 
-impl<Tz: TimeZone> From<&DateTime<Tz>> for Slot {
-    fn from(value: &DateTime<Tz>) -> Self {
-        let time = value.with_timezone(&Kiev).time();
-        let ends = [
-            (NaiveTime::from_hms_opt(10, 15, 0).unwrap(), Slot::I),
-            (NaiveTime::from_hms_opt(12, 10, 0).unwrap(), Slot::II),
-            (NaiveTime::from_hms_opt(13, 55, 0).unwrap(), Slot::III),
-            (NaiveTime::from_hms_opt(15, 40, 0).unwrap(), Slot::IV),
-        ];
-        for (end, slot) in ends {
-            if time < end {
+impl Slot {
+    pub const ALL: [Slot; 4] = [Slot::I, Slot::II, Slot::III, Slot::IV];
+}
+
+/// Local start and end times of every slot, as rung by an institution.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Bells([(NaiveTime, NaiveTime); 4]);
+
+impl Default for Bells {
+    fn default() -> Self {
+        let hm = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+        Bells([
+            (hm(8, 40), hm(10, 15)),
+            (hm(10, 35), hm(12, 10)),
+            (hm(12, 20), hm(13, 55)),
+            (hm(14, 5), hm(15, 40)),
+        ])
+    }
+}
+
+impl Bells {
+    pub fn new(times: [(NaiveTime, NaiveTime); 4]) -> anyhow::Result<Bells> {
+        let mut last = NaiveTime::MIN;
+        for (start, end) in times {
+            if start < last || end <= start {
+                return Err(anyhow!("Slots must follow each other without overlapping"));
+            }
+            last = end;
+        }
+        Ok(Bells(times))
+    }
+
+    pub fn times(&self, slot: Slot) -> (NaiveTime, NaiveTime) {
+        self.0[slot as usize - 1]
+    }
+
+    /// Start and end of `slot` on `date`, with the timetable kept in `institution`'s
+    /// time zone, as a clock in `reader`'s time zone shows them.
+    pub fn times_in<A: TimeZone, B: TimeZone>(
+        &self,
+        slot: Slot,
+        date: NaiveDate,
+        institution: &A,
+        reader: &B,
+    ) -> (NaiveTime, NaiveTime) {
+        let convert = |time: NaiveTime| {
+            institution
+                .from_local_datetime(&date.and_time(time))
+                .earliest()
+                .map(|dt| dt.with_timezone(reader).time())
+                .unwrap_or(time)
+        };
+        let (start, end) = self.times(slot);
+        (convert(start), convert(end))
+    }
+
+    /// The slot that is ongoing or next at the local time of `value`.
+    pub fn slot_at<Tz: TimeZone>(&self, value: &DateTime<Tz>) -> Slot {
+        let time = value.time();
+        for slot in Slot::ALL {
+            if time < self.times(slot).1 {
                 return slot;
             }
         }
 
-        return Slot::I;
+        Slot::I
+    }
+}
+
+impl TryFrom<&str> for Bells {
+    type Error = anyhow::Error;
+
+    /// Parses four `HH:MM-HH:MM` ranges separated by whitespace.
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let mut times = [(NaiveTime::MIN, NaiveTime::MIN); 4];
+        let mut ranges = value.split_whitespace();
+        for time in times.iter_mut() {
+            let range = ranges.next().ok_or(anyhow!("Expected 4 slots"))?;
+            let (start, end) = range
+                .split_once('-')
+                .ok_or(anyhow!("Not a time range: {}", range))?;
+            *time = (
+                NaiveTime::parse_from_str(start, "%H:%M")?,
+                NaiveTime::parse_from_str(end, "%H:%M")?,
+            );
+        }
+        if ranges.next().is_some() {
+            return Err(anyhow!("Expected 4 slots"));
+        }
+        Bells::new(times)
+    }
+}
+
+/// Four `HH:MM-HH:MM` ranges, the way [`Bells`] are parsed.
+impl From<&Bells> for String {
+    fn from(value: &Bells) -> Self {
+        let ranges: Vec<String> = value
+            .0
+            .iter()
+            .map(|(start, end)| format!("{}-{}", start.format("%H:%M"), end.format("%H:%M")))
+            .collect();
+        ranges.join(" ")
+    }
+}
+
+/// Month and day the autumn and spring semesters of an institution start on,
+/// each running up to the day before the other one starts.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Semesters {
+    autumn: (u32, u32),
+    spring: (u32, u32),
+}
+
+impl Default for Semesters {
+    /// Autumn from September to January, spring from February to August.
+    fn default() -> Self {
+        Semesters {
+            autumn: (9, 1),
+            spring: (2, 1),
+        }
+    }
+}
+
+impl Semesters {
+    pub fn new(autumn: (u32, u32), spring: (u32, u32)) -> anyhow::Result<Semesters> {
+        // A year without 29 Feb, for a semester to start every year
+        for (month, day) in [autumn, spring] {
+            NaiveDate::from_ymd_opt(2023, month, day).ok_or(anyhow!(
+                "Not a day of every year: {:02}-{:02}",
+                month,
+                day
+            ))?;
+        }
+        if spring >= autumn {
+            return Err(anyhow!(
+                "The spring semester must start before the autumn one"
+            ));
+        }
+        Ok(Semesters { autumn, spring })
+    }
+
+    /// First and last day of the semester `date` is in.
+    pub fn around(&self, date: NaiveDate) -> (NaiveDate, NaiveDate) {
+        let start = |year, (month, day)| NaiveDate::from_ymd_opt(year, month, day).unwrap();
+        let eve = |year, begins| start(year, begins).pred_opt().unwrap();
+        let year = date.year();
+        if date >= start(year, self.autumn) {
+            (start(year, self.autumn), eve(year + 1, self.spring))
+        } else if date >= start(year, self.spring) {
+            (start(year, self.spring), eve(year, self.autumn))
+        } else {
+            (start(year - 1, self.autumn), eve(year, self.spring))
+        }
+    }
+}
+
+impl TryFrom<&str> for Semesters {
+    type Error = anyhow::Error;
+
+    /// Parses the `MM-DD` starts of the autumn and spring semesters,
+    /// separated by whitespace.
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let parse = |start: &str| -> anyhow::Result<(u32, u32)> {
+            let (month, day) = start
+                .split_once('-')
+                .ok_or(anyhow!("Not a month and day: {}", start))?;
+            Ok((month.parse()?, day.parse()?))
+        };
+        match value.split_whitespace().collect::<Vec<_>>()[..] {
+            [autumn, spring] => Semesters::new(parse(autumn)?, parse(spring)?),
+            _ => Err(anyhow!("Expected the starts of 2 semesters")),
+        }
+    }
+}
+
+/// The `MM-DD` starts of the autumn and spring semesters, the way
+/// [`Semesters`] are parsed.
+impl From<&Semesters> for String {
+    fn from(value: &Semesters) -> Self {
+        let (autumn, spring) = (value.autumn, value.spring);
+        format!(
+            "{:02}-{:02} {:02}-{:02}",
+            autumn.0, autumn.1, spring.0, spring.1
+        )
+    }
+}
+
+/// The slot that is ongoing or next at the local time of `value`, with the default bells.
+impl<Tz: TimeZone> From<&DateTime<Tz>> for Slot {
+    fn from(value: &DateTime<Tz>) -> Self {
+        Bells::default().slot_at(value)
     }
 }
 
@@ -197,14 +631,11 @@ impl Unpackable for Subject {
 
         let id = {
             let value = next("id")?;
-            i64::from_str_radix(value.as_ref(), 10)?
+            value.parse::<i64>()?
         };
         let title = next("title")?;
         let group = Group::try_from(next("group")?.as_str())?;
-        let optional = match next("optional")?.as_ref() {
-            "true" => true,
-            _ => false,
-        };
+        let optional = matches!(next("optional")?.as_ref(), "true");
 
         if let Some(extra) = iter.next() {
             if !extra.is_empty() {
@@ -231,7 +662,7 @@ impl Unpackable for Meeting {
 
         let id = {
             let value = next("id")?;
-            i64::from_str_radix(value.as_ref(), 10)?
+            value.parse::<i64>()?
         };
         let name = next("name")?;
         let group = Group::try_from(next("group")?.as_str())?;
@@ -263,7 +694,7 @@ impl Unpackable for Schedule {
         let day = Day::try_from(next("day")?.as_str())?;
         let subject_id = {
             let value = next("subject_id")?;
-            i64::from_str_radix(value.as_ref(), 10)?
+            value.parse::<i64>()?
         };
         let repeat = Repeat::try_from(next("repeat")?.as_str())?;
         let slot = Slot::try_from(next("slot")?.as_str())?;
@@ -293,11 +724,11 @@ impl Unpackable for Assigned {
 
         let meeting_id = {
             let value = next("meeting_id")?;
-            i64::from_str_radix(value.as_ref(), 10)?
+            value.parse::<i64>()?
         };
         let subject_id = {
             let value = next("subject_id")?;
-            i64::from_str_radix(value.as_ref(), 10)?
+            value.parse::<i64>()?
         };
 
         if let Some(extra) = iter.next() {
@@ -317,23 +748,34 @@ pub fn unpack<P: AsRef<std::path::Path>, U: Unpackable>(
     path: P,
     fields: usize,
 ) -> anyhow::Result<Vec<U>> {
-    let lines: Vec<String> = std::fs::read_to_string(path)?
-        .lines()
-        .map(String::from)
-        .collect();
+    let (unpacked, problems) = unpack_str(&std::fs::read_to_string(path)?, fields);
+    for problem in problems {
+        log::error!("{}", problem);
+    }
+    Ok(unpacked)
+}
+
+/// Records of `text`, `fields` lines each followed by an empty one, along with
+/// what is wrong with the records that don't unpack.
+pub fn unpack_str<U: Unpackable>(text: &str, fields: usize) -> (Vec<U>, Vec<String>) {
+    let lines: Vec<String> = text.lines().map(String::from).collect();
     let mut unpacked = vec![];
-    for chunk in lines.chunks(fields + 1) {
-        match U::unpack(chunk.into_iter().cloned()) {
+    let mut problems = vec![];
+    for (index, chunk) in lines.chunks(fields + 1).enumerate() {
+        if chunk.iter().all(|line| line.trim().is_empty()) {
+            continue;
+        }
+        match U::unpack(chunk.iter().cloned()) {
             Ok(value) => {
                 unpacked.push(value);
             }
             Err(error) => {
-                log::error!("{}", error.to_string());
+                problems.push(format!("Record {}: {}", index + 1, error));
             }
         }
     }
 
-    Ok(unpacked)
+    (unpacked, problems)
 }
 
 #[cfg(test)]
@@ -353,8 +795,8 @@ mod tests {
 
         assert_eq!(unpacked.id, 0);
         assert_eq!(unpacked.title, "Test title");
-        assert_eq!(unpacked.group, Group::K25);
-        assert_eq!(unpacked.optional, false);
+        assert_eq!(unpacked.group.as_str(), "K-25");
+        assert!(!unpacked.optional);
     }
 
     #[test]
@@ -370,7 +812,7 @@ mod tests {
 
         assert_eq!(unpacked.id, 0);
         assert_eq!(unpacked.name, "Test name");
-        assert_eq!(unpacked.group, Group::K25);
+        assert_eq!(unpacked.group.as_str(), "K-25");
         assert_eq!(unpacked.link, "https://fake-link.lol");
     }
 
@@ -395,4 +837,203 @@ mod tests {
         assert_eq!(unpacked.meeting_id, 1);
         assert_eq!(unpacked.subject_id, 2);
     }
+
+    /// Kyiv local time at the given UTC moment.
+    fn utc(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<chrono_tz::Tz> {
+        kyiv(chrono::Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap())
+    }
+
+    fn kyiv(value: DateTime<chrono::Utc>) -> DateTime<chrono_tz::Tz> {
+        value.with_timezone(&chrono_tz::Europe::Kiev)
+    }
+
+    #[test]
+    fn slot_boundaries() {
+        // Kyiv is UTC+3 in October 2023
+        let cases = [
+            ((0, 0), Slot::I),
+            ((7, 14), Slot::I),
+            ((7, 15), Slot::II),
+            ((9, 9), Slot::II),
+            ((9, 10), Slot::III),
+            ((10, 54), Slot::III),
+            ((10, 55), Slot::IV),
+            ((12, 39), Slot::IV),
+            ((12, 40), Slot::I),
+            ((20, 59), Slot::I),
+        ];
+        for ((h, min), slot) in cases {
+            assert_eq!(
+                Slot::from(&utc(2023, 10, 9, h, min)),
+                slot,
+                "{:02}:{:02} UTC",
+                h,
+                min
+            );
+        }
+    }
+
+    #[test]
+    fn slot_follows_dst() {
+        use crate::clock::{Clock, TestClock};
+        let at = |y, m, d, h, min| chrono::Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap();
+
+        // Spring forward on 26 Mar 2023, 03:00 EET -> 04:00 EEST
+        let clock = TestClock::new(at(2023, 3, 24, 8, 14));
+        assert_eq!(Slot::from(&kyiv(clock.now())), Slot::I);
+        clock.set(at(2023, 3, 27, 8, 14));
+        assert_eq!(Slot::from(&kyiv(clock.now())), Slot::II);
+        clock.set(at(2023, 3, 27, 7, 14));
+        assert_eq!(Slot::from(&kyiv(clock.now())), Slot::I);
+
+        // Fall back on 29 Oct 2023, 04:00 EEST -> 03:00 EET
+        clock.set(at(2023, 10, 27, 7, 15));
+        assert_eq!(Slot::from(&kyiv(clock.now())), Slot::II);
+        clock.set(at(2023, 10, 30, 7, 15));
+        assert_eq!(Slot::from(&kyiv(clock.now())), Slot::I);
+        clock.set(at(2023, 10, 30, 8, 15));
+        assert_eq!(Slot::from(&kyiv(clock.now())), Slot::II);
+    }
+
+    #[test]
+    fn slot_times_convert_between_zones() {
+        use chrono_tz::{America::New_York, Europe::Kiev, Europe::Warsaw};
+        let hm = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+
+        let date = NaiveDate::from_ymd_opt(2023, 10, 9).unwrap();
+        let bells = Bells::default();
+        assert_eq!(
+            bells.times_in(Slot::I, date, &Kiev, &Kiev),
+            bells.times(Slot::I)
+        );
+        assert_eq!(
+            bells.times_in(Slot::I, date, &Kiev, &Warsaw),
+            (hm(7, 40), hm(9, 15))
+        );
+
+        // The US leaves summer time a week after Europe
+        let date = NaiveDate::from_ymd_opt(2023, 10, 30).unwrap();
+        assert_eq!(
+            bells.times_in(Slot::II, date, &Kiev, &New_York),
+            (hm(4, 35), hm(6, 10))
+        );
+        let date = NaiveDate::from_ymd_opt(2023, 11, 6).unwrap();
+        assert_eq!(
+            bells.times_in(Slot::II, date, &Kiev, &New_York),
+            (hm(3, 35), hm(5, 10))
+        );
+    }
+
+    #[test]
+    fn day_changes_at_kyiv_midnight() {
+        // Sunday 23:59 and Monday 00:00 in Kyiv, summer time
+        assert!(Day::try_from(&utc(2023, 10, 8, 20, 59)).is_err());
+        assert_eq!(Day::try_from(&utc(2023, 10, 8, 21, 0)).unwrap(), Day::Mon);
+
+        // Same on the first Monday of winter time
+        assert!(Day::try_from(&utc(2023, 10, 29, 21, 59)).is_err());
+        assert_eq!(Day::try_from(&utc(2023, 10, 29, 22, 0)).unwrap(), Day::Mon);
+
+        // Friday night turns into Saturday
+        assert_eq!(Day::try_from(&utc(2023, 10, 13, 20, 59)).unwrap(), Day::Fri);
+        assert!(Day::try_from(&utc(2023, 10, 13, 21, 0)).is_err());
+    }
+
+    #[test]
+    fn repeat_changes_at_kyiv_midnight() {
+        // 7 Oct is still in the first week of the month, 8 Oct is in the second
+        assert_eq!(Repeat::from(&utc(2023, 10, 7, 20, 59)), Repeat::Even);
+        assert_eq!(Repeat::from(&utc(2023, 10, 7, 21, 0)), Repeat::Odd);
+
+        // Across the spring DST switch, Kyiv is UTC+2 before and UTC+3 after
+        assert_eq!(Repeat::from(&utc(2023, 3, 21, 21, 59)), Repeat::Even);
+        assert_eq!(Repeat::from(&utc(2023, 3, 21, 22, 0)), Repeat::Odd);
+        assert_eq!(Repeat::from(&utc(2023, 3, 28, 20, 59)), Repeat::Odd);
+        assert_eq!(Repeat::from(&utc(2023, 3, 28, 21, 0)), Repeat::Even);
+    }
+
+    #[test]
+    fn group_names() {
+        assert_eq!(Group::try_from("K-25").unwrap().as_str(), "K-25");
+        assert_eq!(Group::try_from("МП-31").unwrap().as_str(), "МП-31");
+        assert!(Group::try_from("").is_err());
+        assert!(Group::try_from("K 25").is_err());
+    }
+
+    #[test]
+    fn grant_covers_its_scope() {
+        let k25 = Group::try_from("K-25").unwrap();
+        let k26 = Group::try_from("K-26").unwrap();
+        let mut grant = Grant {
+            institution: 0,
+            group: None,
+            role: Role::Editor,
+        };
+        assert!(grant.covers(0, &k25) && grant.covers(0, &k26));
+        assert!(!grant.covers(1, &k25));
+
+        grant.group = Some(k25.clone());
+        assert!(grant.covers(0, &k25));
+        assert!(!grant.covers(0, &k26));
+
+        assert_eq!(Role::try_from("Owner").unwrap(), Role::Owner);
+        assert_eq!(String::from(&Role::Editor), "editor");
+        assert!(Role::try_from("admin").is_err());
+    }
+
+    #[test]
+    fn group_parts() {
+        let group = Group::try_from("МП-31/2").unwrap();
+        assert_eq!(group.faculty(), "МП");
+        assert_eq!(group.year(), "3");
+        assert_eq!(group.base(), "МП-31");
+        assert_eq!(group.subgroup(), "2");
+
+        let group = Group::try_from("Seminar").unwrap();
+        assert_eq!(group.faculty(), "Seminar");
+        assert_eq!(group.year(), "");
+        assert_eq!(group.base(), "Seminar");
+        assert_eq!(group.subgroup(), "");
+    }
+
+    #[test]
+    fn bells_parse() {
+        let hm = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+
+        let bells = Bells::try_from("8:30-9:50 10:00-11:20 11:40-13:00 13:30-14:50").unwrap();
+        assert_eq!(bells.times(Slot::I), (hm(8, 30), hm(9, 50)));
+        assert_eq!(bells.times(Slot::IV), (hm(13, 30), hm(14, 50)));
+        assert_eq!(bells.slot_at(&utc(2023, 10, 9, 6, 50)), Slot::II);
+
+        assert!(Bells::try_from("8:30-9:50 10:00-11:20 11:40-13:00").is_err());
+        assert!(Bells::try_from("8:30-9:50 9:00-11:20 11:40-13:00 13:30-14:50").is_err());
+        assert!(Bells::try_from("8:30-9:50 10:00-11:20 11:40-13:00 14:50-13:30").is_err());
+    }
+
+    #[test]
+    fn semesters() {
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        let semesters = Semesters::default();
+        let autumn = (date(2023, 9, 1), date(2024, 1, 31));
+        assert_eq!(semesters.around(date(2023, 10, 9)), autumn);
+        assert_eq!(semesters.around(date(2024, 1, 20)), autumn);
+        assert_eq!(
+            semesters.around(date(2024, 2, 1)),
+            (date(2024, 2, 1), date(2024, 8, 31))
+        );
+
+        let semesters = Semesters::try_from("09-15 02-10").unwrap();
+        assert_eq!(String::from(&semesters), "09-15 02-10");
+        assert_eq!(
+            semesters.around(date(2024, 2, 9)),
+            (date(2023, 9, 15), date(2024, 2, 9))
+        );
+        assert_eq!(
+            semesters.around(date(2024, 9, 14)),
+            (date(2024, 2, 10), date(2024, 9, 14))
+        );
+        assert!(Semesters::try_from("02-29 09-01").is_err());
+        assert!(Semesters::try_from("02-01 09-01").is_err());
+        assert!(Semesters::try_from("09-01").is_err());
+    }
 }
@@ -1,80 +1,898 @@
-use crate::data::{Day, Group, Repeat, Schedule, Slot, Subject};
-use sqlx::SqlitePool as Pool;
-use teloxide::types::ChatId;
+use crate::audit::{self, Entry, Source};
+use crate::clock::{Clock, SystemClock};
+use crate::data::{
+    Assigned, Attendance, Bells, Change, Day, Exam, ExamKind, Grade, Grant, Group, Holiday,
+    Homework, Institution, Meeting, Note, Repeat, Role, Schedule, Semesters, Slot, Subject, User,
+};
+use crate::i18n::Language;
+use crate::import::Import;
+use crate::store::{self, Pinned, ScheduleStore, Upload};
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use chrono_tz::Tz;
+use sqlx::{
+    any::{AnyPoolOptions, AnyRow},
+    migrate::Migrator,
+    AnyConnection, AnyPool as Pool, Row,
+};
+use std::sync::Arc;
+use teloxide::types::{ChatId, MessageId};
 
+static SQLITE: Migrator = sqlx::migrate!("migrations/sqlite");
+static POSTGRES: Migrator = sqlx::migrate!("migrations/postgres");
+
+/// Database engine behind a [`Database`], picked from the connection url.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Backend {
+    Sqlite,
+    Postgres,
+}
+
+impl TryFrom<&str> for Backend {
+    type Error = anyhow::Error;
+
+    fn try_from(url: &str) -> Result<Self, Self::Error> {
+        use Backend::*;
+
+        match url.split_once(':').map(|(scheme, _)| scheme) {
+            Some("sqlite") => Ok(Sqlite),
+            Some("postgres" | "postgresql") => Ok(Postgres),
+            _ => Err(anyhow::anyhow!("Unsupported database url: {}", url)),
+        }
+    }
+}
+
+/// Clones share the connection pool.
+#[derive(Clone)]
 pub struct Database {
     pool: Pool,
+    backend: Backend,
+    /// Stamps the entries of the audit log.
+    clock: Arc<dyn Clock>,
+}
+
+impl Database {
+    pub fn new(pool: Pool, backend: Backend) -> Database {
+        Database {
+            pool,
+            backend,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// The same database, with changes logged at the times `clock` tells.
+    pub fn with_clock(self, clock: Arc<dyn Clock>) -> Database {
+        Database { clock, ..self }
+    }
+
+    /// Connects to either SQLite or Postgres, depending on the scheme of `url`.
+    pub async fn connect(url: &str) -> anyhow::Result<Database> {
+        Database::connect_with(AnyPoolOptions::new(), url).await
+    }
+
+    pub async fn connect_with(options: AnyPoolOptions, url: &str) -> anyhow::Result<Database> {
+        sqlx::any::install_default_drivers();
+        let backend = Backend::try_from(url)?;
+        let pool = options.connect(url).await?;
+        Ok(Database::new(pool, backend))
+    }
+
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    /// Applies pending migrations for the current backend.
+    pub async fn migrate(&self) -> anyhow::Result<()> {
+        let migrator = match self.backend {
+            Backend::Sqlite => &SQLITE,
+            Backend::Postgres => &POSTGRES,
+        };
+        migrator.run(&self.pool).await?;
+        Ok(())
+    }
 }
 
 impl Database {
-    pub fn new(pool: Pool) -> Database {
-        Database { pool }
+    async fn get_bells(&self, institution: i64) -> sqlx::Result<Bells> {
+        let records = sqlx::query(
+            "SELECT slot, starts, ends FROM bells WHERE institution_id = $1 ORDER BY slot;",
+        )
+        .bind(institution)
+        .fetch_all(&self.pool)
+        .await?;
+        if records.len() != Slot::ALL.len() {
+            return Ok(Bells::default());
+        }
+
+        let mut times = [(NaiveTime::MIN, NaiveTime::MIN); 4];
+        for (time, record) in times.iter_mut().zip(records) {
+            let parse = |column: &str| -> sqlx::Result<NaiveTime> {
+                let value: String = record.try_get(column)?;
+                NaiveTime::parse_from_str(&value, "%H:%M").map_err(|err| {
+                    sqlx::Error::ColumnDecode {
+                        index: column.into(),
+                        source: err.into(),
+                    }
+                })
+            };
+            *time = (parse("starts")?, parse("ends")?);
+        }
+        Bells::new(times).map_err(|err| sqlx::Error::Decode(err.into()))
+    }
+
+    async fn institution_from(&self, record: AnyRow) -> sqlx::Result<Institution> {
+        let id = record.try_get("id")?;
+        let timezone: String = record.try_get("timezone")?;
+        Ok(Institution {
+            id,
+            code: record.try_get("code")?,
+            name: record.try_get("name")?,
+            timezone: timezone.parse().map_err(|err: chrono_tz::ParseError| {
+                sqlx::Error::ColumnDecode {
+                    index: "timezone".into(),
+                    source: err.to_string().into(),
+                }
+            })?,
+            bells: self.get_bells(id).await?,
+        })
+    }
+}
+
+/// Chat id selected as `CAST(<column> AS TEXT)`: the `Any` driver reads
+/// every SQLite integer as 32-bit, which cuts off the ids of supergroups.
+fn chat_from(record: &AnyRow, column: &str) -> sqlx::Result<ChatId> {
+    let id: String = record.try_get(column)?;
+    id.parse()
+        .map(ChatId)
+        .map_err(|err| sqlx::Error::ColumnDecode {
+            index: column.into(),
+            source: Box::new(err),
+        })
+}
+
+fn pinned_from(record: &AnyRow) -> sqlx::Result<Pinned> {
+    let message_id: i64 = record.try_get("message_id")?;
+    let date: String = record.try_get("date")?;
+    Ok(Pinned {
+        chat_id: chat_from(record, "chat_id")?,
+        message_id: MessageId(
+            message_id
+                .try_into()
+                .map_err(|err| sqlx::Error::ColumnDecode {
+                    index: "message_id".into(),
+                    source: Box::new(err),
+                })?,
+        ),
+        date: date.parse().map_err(|err| sqlx::Error::ColumnDecode {
+            index: "date".into(),
+            source: Box::new(err),
+        })?,
+    })
+}
+
+/// Appends `entry` to the audit log, within the transaction making the change.
+async fn audit(conn: &mut AnyConnection, entry: &Entry) -> sqlx::Result<()> {
+    // Postgres keeps the parameter types a statement is first prepared with,
+    // so a NULL would break later inserts on the same connection
+    sqlx::query("INSERT INTO audit(made_at, source, actor, institution_id, action, target, before_value, after_value) VALUES($1, $2, $3, NULLIF($4, -1), $5, $6, $7, $8);")
+        .bind(entry.at.to_rfc3339())
+        .bind(String::from(&entry.source))
+        .bind(&entry.actor)
+        .bind(entry.institution.unwrap_or(-1))
+        .bind(&entry.action)
+        .bind(&entry.target)
+        .bind(entry.before.as_deref().unwrap_or_default())
+        .bind(entry.after.as_deref().unwrap_or_default())
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// Adds a row per slot of `bells`, within the transaction making the change.
+async fn insert_bells(
+    conn: &mut AnyConnection,
+    institution: i64,
+    bells: &Bells,
+) -> sqlx::Result<()> {
+    for slot in Slot::ALL {
+        let (starts, ends) = bells.times(slot);
+        sqlx::query(
+            "INSERT INTO bells(institution_id, slot, starts, ends) VALUES($1, $2, $3, $4);",
+        )
+        .bind(institution)
+        .bind(slot as i64)
+        .bind(starts.format("%H:%M").to_string())
+        .bind(ends.format("%H:%M").to_string())
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+fn entry_from(record: &AnyRow) -> sqlx::Result<Entry> {
+    let decode = |column: &str, err: anyhow::Error| sqlx::Error::ColumnDecode {
+        index: column.into(),
+        source: err.into(),
+    };
+    let at: String = record.try_get("made_at")?;
+    let source: String = record.try_get("source")?;
+    let institution: i64 = record.try_get("institution_id")?;
+    let value = |column: &str| -> sqlx::Result<Option<String>> {
+        let value: String = record.try_get(column)?;
+        Ok(Some(value).filter(|v| !v.is_empty()))
+    };
+    Ok(Entry {
+        at: DateTime::parse_from_rfc3339(&at)
+            .map_err(|err| decode("made_at", err.into()))?
+            .with_timezone(&Utc),
+        source: Source::try_from(source.as_str()).map_err(|err| decode("source", err))?,
+        actor: record.try_get("actor")?,
+        institution: (institution >= 0).then_some(institution),
+        action: record.try_get("action")?,
+        target: record.try_get("target")?,
+        before: value("before_value")?,
+        after: value("after_value")?,
+    })
+}
+
+fn subject_from(record: &AnyRow) -> sqlx::Result<Subject> {
+    Ok(Subject {
+        id: record.try_get("id")?,
+        title: record.try_get("title")?,
+        group: group_from(record)?,
+        optional: record.try_get::<i64, _>("optional")? == 1,
+    })
+}
+
+fn schedule_from(record: &AnyRow) -> sqlx::Result<Schedule> {
+    // Enums are kept as their discriminants
+    fn pick<T: Copy>(
+        record: &AnyRow,
+        column: &str,
+        values: &[T],
+        key: impl Fn(T) -> u8,
+    ) -> sqlx::Result<T> {
+        let value: i64 = record.try_get(column)?;
+        values
+            .iter()
+            .copied()
+            .find(|v| i64::from(key(*v)) == value)
+            .ok_or_else(|| sqlx::Error::ColumnDecode {
+                index: column.into(),
+                source: anyhow::anyhow!("Not a {}: {}", column, value).into(),
+            })
+    }
+    let repeats = [Repeat::Odd, Repeat::Even, Repeat::Both];
+    Ok(Schedule {
+        subject_id: record.try_get("subject_id")?,
+        day: pick(record, "day", &Day::ALL, |d| d as u8)?,
+        repeat: pick(record, "repeat", &repeats, |r| r as u8)?,
+        slot: pick(record, "slot", &Slot::ALL, |s| s as u8)?,
+    })
+}
+
+fn upload_from(record: &AnyRow) -> sqlx::Result<Upload> {
+    Ok(Upload {
+        chat_id: chat_from(record, "chat_id")?,
+        name: record.try_get("name")?,
+        text: record.try_get("content")?,
+    })
+}
+
+fn homework_from(record: &AnyRow) -> sqlx::Result<Homework> {
+    let due: String = record.try_get("due")?;
+    Ok(Homework {
+        id: record.try_get("id")?,
+        subject_id: record.try_get("subject_id")?,
+        due: due.parse().map_err(|err| sqlx::Error::ColumnDecode {
+            index: "due".into(),
+            source: Box::new(err),
+        })?,
+        text: record.try_get("content")?,
+        author: chat_from(record, "author")?,
+        shared: record.try_get::<i64, _>("shared")? == 1,
+    })
+}
+
+/// Columns [`homework_from`] reads, of a table named `h`.
+const HOMEWORK: &str =
+    "h.id, h.subject_id, h.due, h.content, CAST(h.author AS TEXT) AS author, h.shared";
+
+fn exam_from(record: &AnyRow) -> sqlx::Result<Exam> {
+    let decode = |column: &str, err: anyhow::Error| sqlx::Error::ColumnDecode {
+        index: column.into(),
+        source: err.into(),
+    };
+    let kind: String = record.try_get("kind")?;
+    let starts: String = record.try_get("starts")?;
+    Ok(Exam {
+        id: record.try_get("id")?,
+        subject_id: record.try_get("subject_id")?,
+        kind: ExamKind::try_from(kind.as_str()).map_err(|err| decode("kind", err))?,
+        starts: starts
+            .parse()
+            .map_err(|err: chrono::ParseError| decode("starts", err.into()))?,
+        room: record.try_get("room")?,
+        lecturer: record.try_get("lecturer")?,
+    })
+}
+
+/// Columns [`exam_from`] reads.
+const EXAM: &str = "id, subject_id, kind, starts, room, lecturer";
+
+fn group_from(record: &AnyRow) -> sqlx::Result<Group> {
+    let gang: String = record.try_get("gang")?;
+    Group::try_from(gang.as_str()).map_err(|err| sqlx::Error::ColumnDecode {
+        index: "gang".into(),
+        source: err.into(),
+    })
+}
+
+impl ScheduleStore for Database {
+    async fn add_institution(&self, value: &Institution) -> sqlx::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("INSERT INTO institutions(id, code, name, timezone) VALUES($1, $2, $3, $4);")
+            .bind(value.id)
+            .bind(&value.code)
+            .bind(&value.name)
+            .bind(value.timezone.name())
+            .execute(&mut *tx)
+            .await?;
+        let after = format!("{}, {}", value.name, value.timezone.name());
+        let entry = Entry::new(
+            self.clock.now(),
+            Some(value.id),
+            "add_institution",
+            value.code.clone(),
+            None,
+            Some(after),
+        );
+        insert_bells(&mut tx, value.id, &value.bells).await?;
+        audit(&mut tx, &entry).await?;
+        tx.commit().await
+    }
+
+    async fn set_bells(&self, institution: i64, bells: &Bells) -> sqlx::Result<()> {
+        let before = self.get_bells(institution).await?;
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM bells WHERE institution_id = $1;")
+            .bind(institution)
+            .execute(&mut *tx)
+            .await?;
+        insert_bells(&mut tx, institution, bells).await?;
+        let entry = Entry::new(
+            self.clock.now(),
+            Some(institution),
+            "set_bells",
+            format!("institution {}", institution),
+            Some(String::from(&before)),
+            Some(String::from(bells)),
+        );
+        audit(&mut tx, &entry).await?;
+        tx.commit().await
+    }
+
+    async fn set_absences(&self, institution: i64, absences: i64) -> sqlx::Result<()> {
+        let before = self.get_absences(institution).await?;
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("UPDATE institutions SET absences = $1 WHERE id = $2;")
+            .bind(absences)
+            .bind(institution)
+            .execute(&mut *tx)
+            .await?;
+        let entry = Entry::new(
+            self.clock.now(),
+            Some(institution),
+            "set_absences",
+            format!("institution {}", institution),
+            Some(before.to_string()),
+            Some(absences.to_string()),
+        );
+        audit(&mut tx, &entry).await?;
+        tx.commit().await
+    }
+
+    async fn get_absences(&self, institution: i64) -> sqlx::Result<i64> {
+        sqlx::query("SELECT absences FROM institutions WHERE id = $1;")
+            .bind(institution)
+            .fetch_one(&self.pool)
+            .await?
+            .try_get("absences")
+    }
+
+    async fn set_semesters(&self, institution: i64, semesters: &Semesters) -> sqlx::Result<()> {
+        let before = self.get_semesters(institution).await?;
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("UPDATE institutions SET semesters = $1 WHERE id = $2;")
+            .bind(String::from(semesters))
+            .bind(institution)
+            .execute(&mut *tx)
+            .await?;
+        let entry = Entry::new(
+            self.clock.now(),
+            Some(institution),
+            "set_semesters",
+            format!("institution {}", institution),
+            Some(String::from(&before)),
+            Some(String::from(semesters)),
+        );
+        audit(&mut tx, &entry).await?;
+        tx.commit().await
+    }
+
+    async fn get_semesters(&self, institution: i64) -> sqlx::Result<Semesters> {
+        let semesters: String = sqlx::query("SELECT semesters FROM institutions WHERE id = $1;")
+            .bind(institution)
+            .fetch_one(&self.pool)
+            .await?
+            .try_get("semesters")?;
+        Semesters::try_from(semesters.as_str()).map_err(|err| sqlx::Error::Decode(err.into()))
+    }
+
+    async fn get_institution(&self, id: i64) -> sqlx::Result<Institution> {
+        let record =
+            sqlx::query("SELECT id, code, name, timezone FROM institutions WHERE id = $1;")
+                .bind(id)
+                .fetch_one(&self.pool)
+                .await?;
+        self.institution_from(record).await
+    }
+
+    async fn find_institution(&self, code: &str) -> sqlx::Result<Institution> {
+        let record =
+            sqlx::query("SELECT id, code, name, timezone FROM institutions WHERE code = $1;")
+                .bind(code)
+                .fetch_one(&self.pool)
+                .await?;
+        self.institution_from(record).await
+    }
+
+    async fn get_institutions(&self) -> sqlx::Result<Vec<Institution>> {
+        let records = sqlx::query("SELECT id, code, name, timezone FROM institutions ORDER BY id;")
+            .fetch_all(&self.pool)
+            .await?;
+        let mut institutions = Vec::with_capacity(records.len());
+        for record in records {
+            institutions.push(self.institution_from(record).await?);
+        }
+        Ok(institutions)
+    }
+
+    async fn add_group(&self, institution: i64, group: &Group) -> sqlx::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        let result = sqlx::query(
+            "INSERT INTO gangs(institution_id, name) VALUES($1, $2) ON CONFLICT DO NOTHING;",
+        )
+        .bind(institution)
+        .bind(group.as_str())
+        .execute(&mut *tx)
+        .await?;
+        if result.rows_affected() > 0 {
+            let entry = Entry::new(
+                self.clock.now(),
+                Some(institution),
+                "add_group",
+                group.to_string(),
+                None,
+                Some(group.to_string()),
+            );
+            audit(&mut tx, &entry).await?;
+        }
+        tx.commit().await
+    }
+
+    async fn get_groups(&self, institution: i64) -> sqlx::Result<Vec<Group>> {
+        let records =
+            sqlx::query("SELECT name AS gang FROM gangs WHERE institution_id = $1 ORDER BY name;")
+                .bind(institution)
+                .fetch_all(&self.pool)
+                .await?;
+        records.iter().map(group_from).collect()
+    }
+
+    async fn add_grant(&self, id: &ChatId, value: &Grant) -> sqlx::Result<()> {
+        let before = self
+            .get_grants(id)
+            .await?
+            .into_iter()
+            .find(|g| g.institution == value.institution && g.group == value.group);
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("INSERT INTO roles(institution_id, gang, chat_id, role) VALUES($1, $2, $3, $4) ON CONFLICT(institution_id, gang, chat_id) DO UPDATE SET role = excluded.role;")
+            .bind(value.institution)
+            .bind(value.group.as_ref().map_or("", Group::as_str))
+            .bind(id.0)
+            .bind(String::from(&value.role))
+            .execute(&mut *tx)
+            .await?;
+        let entry = Entry::new(
+            self.clock.now(),
+            Some(value.institution),
+            "add_grant",
+            audit::scope(id, value.group.as_ref()),
+            before.map(|g| String::from(&g.role)),
+            Some(String::from(&value.role)),
+        );
+        audit(&mut tx, &entry).await?;
+        tx.commit().await
+    }
+
+    async fn get_grants(&self, id: &ChatId) -> sqlx::Result<Vec<Grant>> {
+        let records = sqlx::query(
+            "SELECT institution_id, gang, role FROM roles WHERE chat_id = $1 ORDER BY institution_id, gang;",
+        )
+        .bind(id.0)
+        .fetch_all(&self.pool)
+        .await?;
+        let mut grants = Vec::with_capacity(records.len());
+        for record in records {
+            let gang: String = record.try_get("gang")?;
+            let role: String = record.try_get("role")?;
+            grants.push(Grant {
+                institution: record.try_get("institution_id")?,
+                group: match gang.as_str() {
+                    "" => None,
+                    _ => Some(group_from(&record)?),
+                },
+                role: Role::try_from(role.as_str()).map_err(|err| sqlx::Error::ColumnDecode {
+                    index: "role".into(),
+                    source: err.into(),
+                })?,
+            });
+        }
+        Ok(grants)
+    }
+
+    async fn remove_grant(
+        &self,
+        id: &ChatId,
+        institution: i64,
+        group: Option<&Group>,
+    ) -> sqlx::Result<()> {
+        let before = self
+            .get_grants(id)
+            .await?
+            .into_iter()
+            .find(|g| g.institution == institution && g.group.as_ref() == group);
+        let Some(before) = before else {
+            return Ok(());
+        };
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM roles WHERE institution_id = $1 AND gang = $2 AND chat_id = $3;")
+            .bind(institution)
+            .bind(group.map_or("", Group::as_str))
+            .bind(id.0)
+            .execute(&mut *tx)
+            .await?;
+        let entry = Entry::new(
+            self.clock.now(),
+            Some(institution),
+            "remove_grant",
+            audit::scope(id, group),
+            Some(String::from(&before.role)),
+            None,
+        );
+        audit(&mut tx, &entry).await?;
+        tx.commit().await
+    }
+
+    async fn add_user(&self, id: &ChatId, user: &User) -> sqlx::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("INSERT INTO users(chat_id, institution_id, gang) VALUES($1, $2, $3);")
+            .bind(id.0)
+            .bind(user.institution)
+            .bind(user.group.as_str())
+            .execute(&mut *tx)
+            .await?;
+        let entry = Entry::new(
+            self.clock.now(),
+            Some(user.institution),
+            "add_user",
+            audit::chat(id),
+            None,
+            Some(user.group.to_string()),
+        );
+        audit(&mut tx, &entry).await?;
+        tx.commit().await
+    }
+
+    async fn update_user(&self, id: &ChatId, user: &User) -> sqlx::Result<()> {
+        let before = match self.get_user(id).await {
+            Ok(before) => before,
+            Err(sqlx::Error::RowNotFound) => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("UPDATE users SET institution_id = $1, gang = $2 WHERE chat_id = $3;")
+            .bind(user.institution)
+            .bind(user.group.as_str())
+            .bind(id.0)
+            .execute(&mut *tx)
+            .await?;
+        let entry = Entry::new(
+            self.clock.now(),
+            Some(user.institution),
+            "update_user",
+            audit::chat(id),
+            Some(before.group.to_string()),
+            Some(user.group.to_string()),
+        );
+        audit(&mut tx, &entry).await?;
+        tx.commit().await
+    }
+
+    async fn get_user(&self, id: &ChatId) -> sqlx::Result<User> {
+        let rec = sqlx::query("SELECT institution_id, gang FROM users WHERE chat_id = $1;")
+            .bind(id.0)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(User {
+            institution: rec.try_get("institution_id")?,
+            group: group_from(&rec)?,
+        })
+    }
+
+    async fn remove_user(&self, id: &ChatId) -> sqlx::Result<()> {
+        let before = match self.get_user(id).await {
+            Ok(before) => before,
+            Err(sqlx::Error::RowNotFound) => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM users WHERE chat_id = $1;")
+            .bind(id.0)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM friends WHERE chat_id = $1 OR friend_id = $1;")
+            .bind(id.0)
+            .execute(&mut *tx)
+            .await?;
+        let entry = Entry::new(
+            self.clock.now(),
+            Some(before.institution),
+            "remove_user",
+            audit::chat(id),
+            Some(before.group.to_string()),
+            None,
+        );
+        audit(&mut tx, &entry).await?;
+        tx.commit().await
+    }
+
+    async fn get_chats(&self, institution: i64, group: &Group) -> sqlx::Result<Vec<ChatId>> {
+        let records = sqlx::query(
+            "SELECT CAST(chat_id AS TEXT) AS chat_id FROM users WHERE institution_id = $1 AND gang = $2 ORDER BY chat_id;",
+        )
+        .bind(institution)
+        .bind(group.as_str())
+        .fetch_all(&self.pool)
+        .await?;
+        records.iter().map(|r| chat_from(r, "chat_id")).collect()
+    }
+
+    async fn set_timezone(&self, id: &ChatId, timezone: Option<Tz>) -> sqlx::Result<()> {
+        let before = self.get_timezone(id).await?;
+        let mut tx = self.pool.begin().await?;
+        // Zone names are never empty, see `audit` for why NULL isn't bound directly
+        sqlx::query("UPDATE users SET timezone = NULLIF($1, '') WHERE chat_id = $2;")
+            .bind(timezone.map_or("", |tz| tz.name()))
+            .bind(id.0)
+            .execute(&mut *tx)
+            .await?;
+        let entry = Entry::new(
+            self.clock.now(),
+            None,
+            "set_timezone",
+            audit::chat(id),
+            before.map(|tz| tz.name().to_string()),
+            timezone.map(|tz| tz.name().to_string()),
+        );
+        audit(&mut tx, &entry).await?;
+        tx.commit().await
+    }
+
+    async fn get_timezone(&self, id: &ChatId) -> sqlx::Result<Option<Tz>> {
+        // NULLs don't decode reliably through the `Any` driver, so map them to ''
+        let rec =
+            sqlx::query("SELECT COALESCE(timezone, '') AS timezone FROM users WHERE chat_id = $1;")
+                .bind(id.0)
+                .fetch_one(&self.pool)
+                .await?;
+        let timezone: String = rec.try_get("timezone")?;
+        Ok(timezone.parse().ok())
+    }
+
+    async fn set_language(&self, id: &ChatId, language: Option<Language>) -> sqlx::Result<()> {
+        let before = self.get_language(id).await?;
+        let mut tx = self.pool.begin().await?;
+        // Language codes are never empty, see `audit` for why NULL isn't bound directly
+        sqlx::query("UPDATE users SET language = NULLIF($1, '') WHERE chat_id = $2;")
+            .bind(language.map_or("", |l| l.code()))
+            .bind(id.0)
+            .execute(&mut *tx)
+            .await?;
+        let entry = Entry::new(
+            self.clock.now(),
+            None,
+            "set_language",
+            audit::chat(id),
+            before.map(|l| l.code().to_string()),
+            language.map(|l| l.code().to_string()),
+        );
+        audit(&mut tx, &entry).await?;
+        tx.commit().await
+    }
+
+    async fn get_language(&self, id: &ChatId) -> sqlx::Result<Option<Language>> {
+        let rec =
+            sqlx::query("SELECT COALESCE(language, '') AS language FROM users WHERE chat_id = $1;")
+                .bind(id.0)
+                .fetch_one(&self.pool)
+                .await?;
+        let language: String = rec.try_get("language")?;
+        Ok(Language::try_from(language.as_str()).ok())
+    }
+
+    async fn set_username(&self, id: &ChatId, username: Option<&str>) -> sqlx::Result<()> {
+        let before = self.get_username(id).await?;
+        let mut tx = self.pool.begin().await?;
+        if let Some(username) = username {
+            sqlx::query("UPDATE users SET username = NULL WHERE username = $1;")
+                .bind(username)
+                .execute(&mut *tx)
+                .await?;
+        }
+        // Usernames are never empty, see `audit` for why NULL isn't bound directly
+        sqlx::query("UPDATE users SET username = NULLIF($1, '') WHERE chat_id = $2;")
+            .bind(username.unwrap_or_default())
+            .bind(id.0)
+            .execute(&mut *tx)
+            .await?;
+        let entry = Entry::new(
+            self.clock.now(),
+            None,
+            "set_username",
+            audit::chat(id),
+            before,
+            username.map(String::from),
+        );
+        audit(&mut tx, &entry).await?;
+        tx.commit().await
     }
 
-    pub async fn add_subject(&self, value: &Subject) -> sqlx::Result<()> {
+    async fn get_username(&self, id: &ChatId) -> sqlx::Result<Option<String>> {
+        let rec =
+            sqlx::query("SELECT COALESCE(username, '') AS username FROM users WHERE chat_id = $1;")
+                .bind(id.0)
+                .fetch_one(&self.pool)
+                .await?;
+        let username: String = rec.try_get("username")?;
+        Ok(Some(username).filter(|u| !u.is_empty()))
+    }
+
+    async fn find_username(&self, username: &str) -> sqlx::Result<Option<ChatId>> {
+        let rec =
+            sqlx::query("SELECT CAST(chat_id AS TEXT) AS chat_id FROM users WHERE username = $1;")
+                .bind(username)
+                .fetch_optional(&self.pool)
+                .await?;
+        rec.map(|r| chat_from(&r, "chat_id")).transpose()
+    }
+
+    async fn add_friend(&self, id: &ChatId, friend: &ChatId) -> sqlx::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            "INSERT INTO friends(chat_id, friend_id) VALUES($1, $2) ON CONFLICT DO NOTHING;",
+        )
+        .bind(id.0)
+        .bind(friend.0)
+        .execute(&mut *tx)
+        .await?;
+        let entry = Entry::new(
+            self.clock.now(),
+            None,
+            "add_friend",
+            audit::chat(id),
+            None,
+            Some(audit::chat(friend)),
+        );
+        audit(&mut tx, &entry).await?;
+        tx.commit().await
+    }
+
+    async fn remove_friend(&self, id: &ChatId, friend: &ChatId) -> sqlx::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM friends WHERE (chat_id = $1 AND friend_id = $2) OR (chat_id = $2 AND friend_id = $1);")
+            .bind(id.0)
+            .bind(friend.0)
+            .execute(&mut *tx)
+            .await?;
+        let entry = Entry::new(
+            self.clock.now(),
+            None,
+            "remove_friend",
+            audit::chat(id),
+            Some(audit::chat(friend)),
+            None,
+        );
+        audit(&mut tx, &entry).await?;
+        tx.commit().await
+    }
+
+    async fn get_friends(&self, id: &ChatId) -> sqlx::Result<Vec<ChatId>> {
+        let records = sqlx::query("SELECT CAST(friend_id AS TEXT) AS friend_id FROM friends WHERE chat_id = $1 ORDER BY friends.friend_id;")
+            .bind(id.0)
+            .fetch_all(&self.pool)
+            .await?;
+        records.iter().map(|r| chat_from(r, "friend_id")).collect()
+    }
+
+    async fn add_subject(&self, institution: i64, value: &Subject) -> sqlx::Result<()> {
         let Subject {
             id,
             title,
             group,
             optional,
         } = value;
-        let gang: String = group.into();
-        sqlx::query!(
-            "INSERT INTO subjects(id, title, gang, optional) VALUES(?, ?, ?, ?);",
-            id,
-            title,
-            gang,
-            optional
-        )
-        .execute(&self.pool)
-        .await?;
-        Ok(())
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("INSERT INTO subjects(institution_id, id, title, gang, optional) VALUES($1, $2, $3, $4, $5);")
+            .bind(institution)
+            .bind(id)
+            .bind(title)
+            .bind(group.as_str())
+            .bind(*optional as i64)
+            .execute(&mut *tx)
+            .await?;
+        let entry = Entry::new(
+            self.clock.now(),
+            Some(institution),
+            "add_subject",
+            audit::subject(*id),
+            None,
+            Some(format!("{}, {}", title, group)),
+        );
+        audit(&mut tx, &entry).await?;
+        tx.commit().await
     }
 
-    pub async fn add_schedule(&self, value: &Schedule) -> sqlx::Result<()> {
-        let Schedule {
-            subject_id,
-            day,
-            repeat,
-            slot,
-        } = value;
-        let day = *day as u8;
-        let repeat = *repeat as u8;
-        let slot = *slot as u8;
-
-        sqlx::query!(
-            "INSERT INTO schedule(day, repeat, slot, subject_id) VALUES(?, ?, ?, ?);",
-            day,
-            repeat,
-            slot,
-            subject_id
+    async fn get_subject(&self, institution: i64, id: i64) -> sqlx::Result<Subject> {
+        let record = sqlx::query(
+            "SELECT id, title, gang, optional FROM subjects WHERE institution_id = $1 AND id = $2;",
         )
-        .execute(&self.pool)
+        .bind(institution)
+        .bind(id)
+        .fetch_one(&self.pool)
         .await?;
-
-        Ok(())
+        Ok(Subject {
+            id: record.try_get("id")?,
+            title: record.try_get("title")?,
+            group: group_from(&record)?,
+            optional: record.try_get::<i64, _>("optional")? == 1,
+        })
     }
 
-    pub async fn get_subjects(
+    async fn get_subjects(
         &self,
+        institution: i64,
         day: Day,
         repeat: Repeat,
         slot: Slot,
         group: Group,
     ) -> sqlx::Result<Vec<Subject>> {
-        let gang = String::from(&group);
-        let repeat = repeat as u8;
-        let slot = slot as u8;
-        let day = day as u8;
-
-        let records = sqlx::query!("SELECT * FROM subjects WHERE id = (SELECT subject_id FROM schedule WHERE day = ? AND (repeat = ? OR repeat = ?) AND slot = ?) AND gang = ?;", day, repeat, Repeat::Both as u8, slot, gang).fetch_all(&self.pool).await?;
+        let records = sqlx::query("SELECT id, title, gang, optional FROM subjects WHERE institution_id = $1 AND id IN (SELECT subject_id FROM schedule WHERE institution_id = $1 AND day = $2 AND (repeat = $3 OR repeat = $4) AND slot = $5) AND gang = $6;")
+            .bind(institution)
+            .bind(day as i64)
+            .bind(repeat as i64)
+            .bind(Repeat::Both as i64)
+            .bind(slot as i64)
+            .bind(group.as_str())
+            .fetch_all(&self.pool)
+            .await?;
         let mut subjects = Vec::with_capacity(records.len());
         for record in records {
             let (id, title, group, optional) = (
-                record.id,
-                record.title,
-                Group::try_from(record.gang.as_str()).unwrap(),
-                record.optional == 1,
+                record.try_get("id")?,
+                record.try_get("title")?,
+                group_from(&record)?,
+                record.try_get::<i64, _>("optional")? == 1,
             );
             let subject = Subject {
                 id,
@@ -87,26 +905,1965 @@ impl Database {
         Ok(subjects)
     }
 
-    pub async fn add_user(&self, id: &ChatId, group: &Group) -> sqlx::Result<()> {
-        let gang = String::from(group);
-        sqlx::query!("INSERT INTO users(chat_id, gang) VALUES(?, ?);", id.0, gang)
-            .execute(&self.pool)
+    async fn get_all_subjects(&self, institution: i64) -> sqlx::Result<Vec<Subject>> {
+        let records = sqlx::query(
+            "SELECT id, title, gang, optional FROM subjects WHERE institution_id = $1 ORDER BY id;",
+        )
+        .bind(institution)
+        .fetch_all(&self.pool)
+        .await?;
+        records.iter().map(subject_from).collect()
+    }
+
+    async fn add_schedule(&self, institution: i64, value: &Schedule) -> sqlx::Result<()> {
+        let Schedule {
+            subject_id,
+            day,
+            repeat,
+            slot,
+        } = value;
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("INSERT INTO schedule(institution_id, day, repeat, slot, subject_id) VALUES($1, $2, $3, $4, $5);")
+            .bind(institution)
+            .bind(*day as i64)
+            .bind(*repeat as i64)
+            .bind(*slot as i64)
+            .bind(subject_id)
+            .execute(&mut *tx)
             .await?;
-        Ok(())
+        let entry = Entry::new(
+            self.clock.now(),
+            Some(institution),
+            "add_schedule",
+            audit::subject(*subject_id),
+            None,
+            Some(format!("{:?} slot {}, {:?}", day, *slot as u8, repeat)),
+        );
+        audit(&mut tx, &entry).await?;
+        tx.commit().await
     }
 
-    pub async fn update_user(&self, id: &ChatId, group: &Group) -> sqlx::Result<()> {
-        let gang = String::from(group);
-        sqlx::query!("UPDATE users SET gang = ? WHERE chat_id = ?;", gang, id.0)
-            .execute(&self.pool)
+    async fn get_schedule(&self, institution: i64) -> sqlx::Result<Vec<Schedule>> {
+        let records = sqlx::query("SELECT day, repeat, slot, subject_id FROM schedule WHERE institution_id = $1 ORDER BY subject_id, day, slot, repeat;")
+            .bind(institution)
+            .fetch_all(&self.pool)
             .await?;
-        Ok(())
+        records.iter().map(schedule_from).collect()
+    }
+
+    async fn import(&self, institution: i64, value: &Import) -> sqlx::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        for subject in &value.subjects {
+            let result = sqlx::query(
+                "INSERT INTO gangs(institution_id, name) VALUES($1, $2) ON CONFLICT DO NOTHING;",
+            )
+            .bind(institution)
+            .bind(subject.group.as_str())
+            .execute(&mut *tx)
+            .await?;
+            if result.rows_affected() > 0 {
+                let entry = Entry::new(
+                    self.clock.now(),
+                    Some(institution),
+                    "add_group",
+                    subject.group.to_string(),
+                    None,
+                    Some(subject.group.to_string()),
+                );
+                audit(&mut tx, &entry).await?;
+            }
+
+            let before = sqlx::query(
+                "SELECT id, title, gang, optional FROM subjects WHERE institution_id = $1 AND id = $2;",
+            )
+            .bind(institution)
+            .bind(subject.id)
+            .fetch_optional(&mut *tx)
+            .await?;
+            let before = before.as_ref().map(subject_from).transpose()?;
+            sqlx::query("INSERT INTO subjects(institution_id, id, title, gang, optional) VALUES($1, $2, $3, $4, $5) ON CONFLICT(institution_id, id) DO UPDATE SET title = excluded.title, gang = excluded.gang, optional = excluded.optional;")
+                .bind(institution)
+                .bind(subject.id)
+                .bind(&subject.title)
+                .bind(subject.group.as_str())
+                .bind(subject.optional as i64)
+                .execute(&mut *tx)
+                .await?;
+            let before = before.map(|s| format!("{}, {}", s.title, s.group));
+            let after = format!("{}, {}", subject.title, subject.group);
+            if before.as_ref() != Some(&after) {
+                let target = audit::subject(subject.id);
+                let entry = Entry::new(
+                    self.clock.now(),
+                    Some(institution),
+                    "import",
+                    target,
+                    before,
+                    Some(after),
+                );
+                audit(&mut tx, &entry).await?;
+            }
+        }
+
+        for id in value.scheduled() {
+            let records = sqlx::query("SELECT day, repeat, slot, subject_id FROM schedule WHERE institution_id = $1 AND subject_id = $2;")
+                .bind(institution)
+                .bind(id)
+                .fetch_all(&mut *tx)
+                .await?;
+            let old = records
+                .iter()
+                .map(schedule_from)
+                .collect::<sqlx::Result<Vec<_>>>()?;
+            sqlx::query("DELETE FROM schedule WHERE institution_id = $1 AND subject_id = $2;")
+                .bind(institution)
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+            let new: Vec<&Schedule> = value
+                .schedule
+                .iter()
+                .filter(|s| s.subject_id == id)
+                .collect();
+            for class in &new {
+                sqlx::query("INSERT INTO schedule(institution_id, day, repeat, slot, subject_id) VALUES($1, $2, $3, $4, $5);")
+                    .bind(institution)
+                    .bind(class.day as i64)
+                    .bind(class.repeat as i64)
+                    .bind(class.slot as i64)
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            let (before, after) = (store::classes(old.iter()), store::classes(new.into_iter()));
+            if before != after {
+                let entry = Entry::new(
+                    self.clock.now(),
+                    Some(institution),
+                    "import",
+                    audit::subject(id),
+                    before,
+                    after,
+                );
+                audit(&mut tx, &entry).await?;
+            }
+        }
+
+        for id in value.examined() {
+            let query = format!(
+                "SELECT {} FROM exams WHERE institution_id = $1 AND subject_id = $2;",
+                EXAM
+            );
+            let records = sqlx::query(&query)
+                .bind(institution)
+                .bind(id)
+                .fetch_all(&mut *tx)
+                .await?;
+            let old = records
+                .iter()
+                .map(exam_from)
+                .collect::<sqlx::Result<Vec<_>>>()?;
+            let new: Vec<&Exam> = value.exams.iter().filter(|e| e.subject_id == id).collect();
+            for exam in old.iter().filter(|o| !new.iter().any(|e| e.same(o))) {
+                sqlx::query("DELETE FROM exams WHERE id = $1;")
+                    .bind(exam.id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            for exam in new.iter().filter(|e| !old.iter().any(|o| o.same(e))) {
+                sqlx::query("INSERT INTO exams(institution_id, subject_id, kind, starts, room, lecturer) VALUES($1, $2, $3, $4, $5, $6);")
+                    .bind(institution)
+                    .bind(id)
+                    .bind(String::from(&exam.kind))
+                    .bind(exam.starts.format("%Y-%m-%dT%H:%M:%S").to_string())
+                    .bind(&exam.room)
+                    .bind(&exam.lecturer)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            let (before, after) = (store::exams(old.iter()), store::exams(new.into_iter()));
+            if before != after {
+                let entry = Entry::new(
+                    self.clock.now(),
+                    Some(institution),
+                    "import",
+                    audit::subject(id),
+                    before,
+                    after,
+                );
+                audit(&mut tx, &entry).await?;
+            }
+        }
+        tx.commit().await
+    }
+
+    async fn add_meeting(&self, institution: i64, value: &Meeting) -> sqlx::Result<()> {
+        let Meeting {
+            id,
+            name,
+            group,
+            link,
+        } = value;
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("INSERT INTO meetings(institution_id, id, name, gang, link) VALUES($1, $2, $3, $4, $5);")
+            .bind(institution)
+            .bind(id)
+            .bind(name)
+            .bind(group.as_str())
+            .bind(link)
+            .execute(&mut *tx)
+            .await?;
+        let entry = Entry::new(
+            self.clock.now(),
+            Some(institution),
+            "add_meeting",
+            audit::meeting(*id),
+            None,
+            Some(format!("{}, {}", name, link)),
+        );
+        audit(&mut tx, &entry).await?;
+        tx.commit().await
+    }
+
+    async fn assign(&self, institution: i64, value: &Assigned) -> sqlx::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            "INSERT INTO assigned(institution_id, meeting_id, subject_id) VALUES($1, $2, $3);",
+        )
+        .bind(institution)
+        .bind(value.meeting_id)
+        .bind(value.subject_id)
+        .execute(&mut *tx)
+        .await?;
+        let entry = Entry::new(
+            self.clock.now(),
+            Some(institution),
+            "assign",
+            audit::meeting(value.meeting_id),
+            None,
+            Some(audit::subject(value.subject_id)),
+        );
+        audit(&mut tx, &entry).await?;
+        tx.commit().await
+    }
+
+    async fn get_meetings(&self, institution: i64, subject_id: i64) -> sqlx::Result<Vec<Meeting>> {
+        let records = sqlx::query("SELECT id, name, gang, link FROM meetings WHERE institution_id = $1 AND id IN (SELECT meeting_id FROM assigned WHERE institution_id = $1 AND subject_id = $2);")
+            .bind(institution)
+            .bind(subject_id)
+            .fetch_all(&self.pool)
+            .await?;
+        let mut meetings = Vec::with_capacity(records.len());
+        for record in records {
+            meetings.push(Meeting {
+                id: record.try_get("id")?,
+                name: record.try_get("name")?,
+                group: group_from(&record)?,
+                link: record.try_get("link")?,
+            });
+        }
+        Ok(meetings)
+    }
+
+    async fn next_meeting_id(&self, institution: i64) -> sqlx::Result<i64> {
+        let record = sqlx::query(
+            "SELECT COALESCE(MAX(id) + 1, 0) AS id FROM meetings WHERE institution_id = $1;",
+        )
+        .bind(institution)
+        .fetch_one(&self.pool)
+        .await?;
+        record.try_get("id")
+    }
+
+    async fn set_room(&self, institution: i64, subject_id: i64, room: &str) -> sqlx::Result<()> {
+        let before = self.get_room(institution, subject_id).await?;
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("INSERT INTO rooms(institution_id, subject_id, room) VALUES($1, $2, $3) ON CONFLICT(institution_id, subject_id) DO UPDATE SET room = excluded.room;")
+            .bind(institution)
+            .bind(subject_id)
+            .bind(room)
+            .execute(&mut *tx)
+            .await?;
+        let entry = Entry::new(
+            self.clock.now(),
+            Some(institution),
+            "set_room",
+            audit::subject(subject_id),
+            before,
+            Some(room.into()),
+        );
+        audit(&mut tx, &entry).await?;
+        tx.commit().await
+    }
+
+    async fn get_room(&self, institution: i64, subject_id: i64) -> sqlx::Result<Option<String>> {
+        let record =
+            sqlx::query("SELECT room FROM rooms WHERE institution_id = $1 AND subject_id = $2;")
+                .bind(institution)
+                .bind(subject_id)
+                .fetch_optional(&self.pool)
+                .await?;
+        record.map(|r| r.try_get("room")).transpose()
+    }
+
+    async fn add_change(&self, institution: i64, value: &Change) -> sqlx::Result<()> {
+        let before = self
+            .get_change(institution, &value.group, value.date, value.slot)
+            .await?;
+        let mut tx = self.pool.begin().await?;
+        // Ids are never negative, see `audit` for why NULL isn't bound directly
+        sqlx::query("INSERT INTO changes(institution_id, gang, date, slot, subject_id) VALUES($1, $2, $3, $4, NULLIF($5, -1)) ON CONFLICT(institution_id, gang, date, slot) DO UPDATE SET subject_id = excluded.subject_id;")
+            .bind(institution)
+            .bind(value.group.as_str())
+            .bind(value.date.to_string())
+            .bind(value.slot as i64)
+            .bind(value.subject_id.unwrap_or(-1))
+            .execute(&mut *tx)
+            .await?;
+        let entry = Entry::new(
+            self.clock.now(),
+            Some(institution),
+            "add_change",
+            audit::class(&value.group, value.date, value.slot),
+            before.map(|c| audit::replaced(c.subject_id)),
+            Some(audit::replaced(value.subject_id)),
+        );
+        audit(&mut tx, &entry).await?;
+        tx.commit().await
+    }
+
+    async fn get_change(
+        &self,
+        institution: i64,
+        group: &Group,
+        date: NaiveDate,
+        slot: Slot,
+    ) -> sqlx::Result<Option<Change>> {
+        // NULLs don't decode reliably through the `Any` driver, and ids are never negative
+        let record = sqlx::query("SELECT COALESCE(subject_id, -1) AS subject_id FROM changes WHERE institution_id = $1 AND gang = $2 AND date = $3 AND slot = $4;")
+            .bind(institution)
+            .bind(group.as_str())
+            .bind(date.to_string())
+            .bind(slot as i64)
+            .fetch_optional(&self.pool)
+            .await?;
+        let Some(record) = record else {
+            return Ok(None);
+        };
+        let subject_id: i64 = record.try_get("subject_id")?;
+        Ok(Some(Change {
+            group: group.clone(),
+            date,
+            slot,
+            subject_id: (subject_id >= 0).then_some(subject_id),
+        }))
+    }
+
+    async fn add_holiday(&self, institution: i64, value: &Holiday) -> sqlx::Result<()> {
+        let before = self.get_holiday(institution, value.date).await?;
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("INSERT INTO calendar(institution_id, date, title) VALUES($1, $2, $3) ON CONFLICT(institution_id, date) DO UPDATE SET title = excluded.title;")
+            .bind(institution)
+            .bind(value.date.to_string())
+            .bind(&value.title)
+            .execute(&mut *tx)
+            .await?;
+        let entry = Entry::new(
+            self.clock.now(),
+            Some(institution),
+            "add_holiday",
+            value.date.to_string(),
+            before.map(|h| h.title),
+            Some(value.title.clone()),
+        );
+        audit(&mut tx, &entry).await?;
+        tx.commit().await
+    }
+
+    async fn get_holiday(
+        &self,
+        institution: i64,
+        date: NaiveDate,
+    ) -> sqlx::Result<Option<Holiday>> {
+        let record =
+            sqlx::query("SELECT title FROM calendar WHERE institution_id = $1 AND date = $2;")
+                .bind(institution)
+                .bind(date.to_string())
+                .fetch_optional(&self.pool)
+                .await?;
+        match record {
+            Some(record) => Ok(Some(Holiday {
+                date,
+                title: record.try_get("title")?,
+            })),
+            None => Ok(None),
+        }
+    }
+
+    async fn get_holidays(
+        &self,
+        institution: i64,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> sqlx::Result<Vec<Holiday>> {
+        // Dates are kept as `2023-12-25`, which sort the way the days do
+        let records = sqlx::query("SELECT date, title FROM calendar WHERE institution_id = $1 AND date >= $2 AND date <= $3 ORDER BY date;")
+            .bind(institution)
+            .bind(from.to_string())
+            .bind(to.to_string())
+            .fetch_all(&self.pool)
+            .await?;
+        let mut holidays = Vec::with_capacity(records.len());
+        for record in records {
+            let date: String = record.try_get("date")?;
+            holidays.push(Holiday {
+                date: date.parse().map_err(|err| sqlx::Error::ColumnDecode {
+                    index: "date".into(),
+                    source: Box::new(err),
+                })?,
+                title: record.try_get("title")?,
+            });
+        }
+        Ok(holidays)
+    }
+
+    async fn add_homework(&self, institution: i64, value: &Homework) -> sqlx::Result<i64> {
+        let mut tx = self.pool.begin().await?;
+        let record = sqlx::query("INSERT INTO homework(institution_id, subject_id, due, content, author, shared) VALUES($1, $2, $3, $4, $5, $6) RETURNING id;")
+            .bind(institution)
+            .bind(value.subject_id)
+            .bind(value.due.to_string())
+            .bind(&value.text)
+            .bind(value.author.0)
+            .bind(value.shared as i64)
+            .fetch_one(&mut *tx)
+            .await?;
+        let id: i64 = record.try_get("id")?;
+        let value = Homework {
+            id,
+            ..value.clone()
+        };
+        let entry = Entry::new(
+            self.clock.now(),
+            Some(institution),
+            "add_homework",
+            audit::homework(id),
+            None,
+            Some(value.describe()),
+        );
+        audit(&mut tx, &entry).await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    async fn get_homework(&self, institution: i64, id: i64) -> sqlx::Result<Homework> {
+        let query = format!(
+            "SELECT {} FROM homework h WHERE h.institution_id = $1 AND h.id = $2;",
+            HOMEWORK
+        );
+        let record = sqlx::query(&query)
+            .bind(institution)
+            .bind(id)
+            .fetch_one(&self.pool)
+            .await?;
+        homework_from(&record)
+    }
+
+    async fn get_due_homework(
+        &self,
+        institution: i64,
+        group: &Group,
+        chat: &ChatId,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> sqlx::Result<Vec<Homework>> {
+        let query = format!(
+            "SELECT {} FROM homework h JOIN subjects s ON s.institution_id = h.institution_id AND s.id = h.subject_id WHERE h.institution_id = $1 AND h.due >= $2 AND h.due <= $3 AND ((h.shared = 1 AND s.gang = $4) OR h.author = $5) ORDER BY h.due, h.id;",
+            HOMEWORK
+        );
+        let records = sqlx::query(&query)
+            .bind(institution)
+            .bind(from.to_string())
+            .bind(to.to_string())
+            .bind(group.as_str())
+            .bind(chat.0)
+            .fetch_all(&self.pool)
+            .await?;
+        records.iter().map(homework_from).collect()
+    }
+
+    async fn remove_homework(&self, institution: i64, id: i64) -> sqlx::Result<()> {
+        let before = match self.get_homework(institution, id).await {
+            Ok(before) => before,
+            Err(sqlx::Error::RowNotFound) => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM homework_done WHERE homework_id = $1;")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM homework WHERE id = $1;")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        let entry = Entry::new(
+            self.clock.now(),
+            Some(institution),
+            "remove_homework",
+            audit::homework(id),
+            Some(before.describe()),
+            None,
+        );
+        audit(&mut tx, &entry).await?;
+        tx.commit().await
+    }
+
+    async fn set_done(&self, id: &ChatId, homework: i64, done: bool) -> sqlx::Result<()> {
+        // Only homework that exists can be done
+        sqlx::query("SELECT id FROM homework WHERE id = $1;")
+            .bind(homework)
+            .fetch_one(&self.pool)
+            .await?;
+        let before = self.get_done(homework).await?.contains(id);
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM homework_done WHERE homework_id = $1 AND chat_id = $2;")
+            .bind(homework)
+            .bind(id.0)
+            .execute(&mut *tx)
+            .await?;
+        if done {
+            sqlx::query("INSERT INTO homework_done(homework_id, chat_id) VALUES($1, $2);")
+                .bind(homework)
+                .bind(id.0)
+                .execute(&mut *tx)
+                .await?;
+        }
+        let entry = Entry::personal(
+            self.clock.now(),
+            "set_done",
+            format!("{} by {}", audit::homework(homework), audit::chat(id)),
+            before.then(|| "done".into()),
+            done.then(|| "done".into()),
+        );
+        audit(&mut tx, &entry).await?;
+        tx.commit().await
+    }
+
+    async fn get_done(&self, homework: i64) -> sqlx::Result<Vec<ChatId>> {
+        let records = sqlx::query("SELECT CAST(chat_id AS TEXT) AS chat_id FROM homework_done WHERE homework_id = $1 ORDER BY chat_id;")
+            .bind(homework)
+            .fetch_all(&self.pool)
+            .await?;
+        records.iter().map(|r| chat_from(r, "chat_id")).collect()
+    }
+
+    async fn get_unreminded(&self) -> sqlx::Result<Vec<(i64, Homework)>> {
+        let query = format!(
+            "SELECT h.institution_id, {} FROM homework h WHERE h.reminded = 0 ORDER BY h.id;",
+            HOMEWORK
+        );
+        let records = sqlx::query(&query).fetch_all(&self.pool).await?;
+        records
+            .iter()
+            .map(|r| Ok((r.try_get("institution_id")?, homework_from(r)?)))
+            .collect()
+    }
+
+    async fn set_reminded(&self, homework: i64) -> sqlx::Result<()> {
+        let record = sqlx::query("SELECT institution_id, reminded FROM homework WHERE id = $1;")
+            .bind(homework)
+            .fetch_optional(&self.pool)
+            .await?;
+        let Some(record) = record else {
+            return Ok(());
+        };
+        if record.try_get::<i64, _>("reminded")? == 1 {
+            return Ok(());
+        }
+        let institution: i64 = record.try_get("institution_id")?;
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("UPDATE homework SET reminded = 1 WHERE id = $1;")
+            .bind(homework)
+            .execute(&mut *tx)
+            .await?;
+        let entry = Entry::new(
+            self.clock.now(),
+            Some(institution),
+            "set_reminded",
+            audit::homework(homework),
+            None,
+            Some("reminded".into()),
+        );
+        audit(&mut tx, &entry).await?;
+        tx.commit().await
+    }
+
+    async fn set_note(
+        &self,
+        id: &ChatId,
+        institution: i64,
+        subject_id: i64,
+        text: Option<&str>,
+    ) -> sqlx::Result<()> {
+        let before = self
+            .get_notes(id, institution)
+            .await?
+            .into_iter()
+            .find(|n| n.subject_id == subject_id)
+            .map(|n| n.text);
+        let mut tx = self.pool.begin().await?;
+        match text {
+            Some(text) => {
+                sqlx::query("INSERT INTO notes(chat_id, institution_id, subject_id, content) VALUES($1, $2, $3, $4) ON CONFLICT(chat_id, institution_id, subject_id) DO UPDATE SET content = excluded.content;")
+                    .bind(id.0)
+                    .bind(institution)
+                    .bind(subject_id)
+                    .bind(text)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            None => {
+                sqlx::query("DELETE FROM notes WHERE chat_id = $1 AND institution_id = $2 AND subject_id = $3;")
+                    .bind(id.0)
+                    .bind(institution)
+                    .bind(subject_id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+        let entry = Entry::personal(
+            self.clock.now(),
+            "set_note",
+            format!("{} by {}", audit::subject(subject_id), audit::chat(id)),
+            before.map(|_| audit::note(true)),
+            Some(audit::note(text.is_some())),
+        );
+        audit(&mut tx, &entry).await?;
+        tx.commit().await
+    }
+
+    async fn get_notes(&self, id: &ChatId, institution: i64) -> sqlx::Result<Vec<Note>> {
+        let records = sqlx::query("SELECT subject_id, content FROM notes WHERE chat_id = $1 AND institution_id = $2 ORDER BY subject_id;")
+            .bind(id.0)
+            .bind(institution)
+            .fetch_all(&self.pool)
+            .await?;
+        records
+            .iter()
+            .map(|r| {
+                Ok(Note {
+                    subject_id: r.try_get("subject_id")?,
+                    text: r.try_get("content")?,
+                })
+            })
+            .collect()
     }
 
-    pub async fn get_group(&self, id: &ChatId) -> sqlx::Result<Group> {
-        let rec = sqlx::query!("SELECT gang FROM users WHERE chat_id = ?;", id.0)
+    async fn set_enrolled(
+        &self,
+        id: &ChatId,
+        institution: i64,
+        subject_id: i64,
+        enrolled: bool,
+    ) -> sqlx::Result<()> {
+        let before = self
+            .get_enrolled(id, institution)
+            .await?
+            .contains(&subject_id);
+        let mut tx = self.pool.begin().await?;
+        let query = if enrolled {
+            "INSERT INTO enrollments(chat_id, institution_id, subject_id) VALUES($1, $2, $3) ON CONFLICT DO NOTHING;"
+        } else {
+            "DELETE FROM enrollments WHERE chat_id = $1 AND institution_id = $2 AND subject_id = $3;"
+        };
+        sqlx::query(query)
+            .bind(id.0)
+            .bind(institution)
+            .bind(subject_id)
+            .execute(&mut *tx)
+            .await?;
+        let entry = Entry::personal(
+            self.clock.now(),
+            "set_enrolled",
+            format!("{} by {}", audit::subject(subject_id), audit::chat(id)),
+            Some(before.to_string()),
+            Some(enrolled.to_string()),
+        );
+        audit(&mut tx, &entry).await?;
+        tx.commit().await
+    }
+
+    async fn get_enrolled(&self, id: &ChatId, institution: i64) -> sqlx::Result<Vec<i64>> {
+        let records = sqlx::query("SELECT subject_id FROM enrollments WHERE chat_id = $1 AND institution_id = $2 ORDER BY subject_id;")
+            .bind(id.0)
+            .bind(institution)
+            .fetch_all(&self.pool)
+            .await?;
+        records.iter().map(|r| r.try_get("subject_id")).collect()
+    }
+
+    async fn set_attendance(
+        &self,
+        id: &ChatId,
+        institution: i64,
+        value: &Attendance,
+    ) -> sqlx::Result<()> {
+        let before = self
+            .get_attendance(id, institution)
+            .await?
+            .into_iter()
+            .find(|a| a.subject_id == value.subject_id && a.date == value.date)
+            .map(|a| a.attended.to_string());
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("INSERT INTO attendance(chat_id, institution_id, subject_id, date, attended) VALUES($1, $2, $3, $4, $5) ON CONFLICT(chat_id, institution_id, subject_id, date) DO UPDATE SET attended = excluded.attended;")
+            .bind(id.0)
+            .bind(institution)
+            .bind(value.subject_id)
+            .bind(value.date.to_string())
+            .bind(i64::from(value.attended))
+            .execute(&mut *tx)
+            .await?;
+        let entry = Entry::personal(
+            self.clock.now(),
+            "set_attendance",
+            format!(
+                "{} on {} by {}",
+                audit::subject(value.subject_id),
+                value.date,
+                audit::chat(id)
+            ),
+            before,
+            Some(value.attended.to_string()),
+        );
+        audit(&mut tx, &entry).await?;
+        tx.commit().await
+    }
+
+    async fn get_attendance(&self, id: &ChatId, institution: i64) -> sqlx::Result<Vec<Attendance>> {
+        let records = sqlx::query("SELECT subject_id, date, attended FROM attendance WHERE chat_id = $1 AND institution_id = $2 ORDER BY subject_id, date;")
+            .bind(id.0)
+            .bind(institution)
+            .fetch_all(&self.pool)
+            .await?;
+        records
+            .iter()
+            .map(|r| {
+                let date: String = r.try_get("date")?;
+                Ok(Attendance {
+                    subject_id: r.try_get("subject_id")?,
+                    date: date.parse().map_err(|err| sqlx::Error::ColumnDecode {
+                        index: "date".into(),
+                        source: Box::new(err),
+                    })?,
+                    attended: r.try_get::<i64, _>("attended")? == 1,
+                })
+            })
+            .collect()
+    }
+
+    async fn get_tracking(&self, institution: i64) -> sqlx::Result<Vec<(ChatId, Vec<i64>)>> {
+        let records = sqlx::query("SELECT DISTINCT CAST(chat_id AS TEXT) AS chat_id FROM attendance WHERE institution_id = $1;")
+            .bind(institution)
+            .fetch_all(&self.pool)
+            .await?;
+        let mut chats = records
+            .iter()
+            .map(|r| Ok((chat_from(r, "chat_id")?, vec![])))
+            .collect::<sqlx::Result<Vec<(ChatId, Vec<i64>)>>>()?;
+        chats.sort();
+        let records = sqlx::query("SELECT CAST(chat_id AS TEXT) AS chat_id, subject_id FROM enrollments WHERE institution_id = $1 AND chat_id IN (SELECT chat_id FROM attendance WHERE institution_id = $1) ORDER BY subject_id;")
+            .bind(institution)
+            .fetch_all(&self.pool)
+            .await?;
+        for record in records {
+            let chat = chat_from(&record, "chat_id")?;
+            if let Some((_, ids)) = chats.iter_mut().find(|(c, _)| *c == chat) {
+                ids.push(record.try_get("subject_id")?);
+            }
+        }
+        Ok(chats)
+    }
+
+    async fn set_max_points(
+        &self,
+        institution: i64,
+        subject_id: i64,
+        points: i64,
+    ) -> sqlx::Result<()> {
+        let before = self.get_max_points(institution, subject_id).await?;
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("UPDATE subjects SET points = $1 WHERE institution_id = $2 AND id = $3;")
+            .bind(points)
+            .bind(institution)
+            .bind(subject_id)
+            .execute(&mut *tx)
+            .await?;
+        let entry = Entry::new(
+            self.clock.now(),
+            Some(institution),
+            "set_max_points",
+            audit::subject(subject_id),
+            Some(before.to_string()),
+            Some(points.to_string()),
+        );
+        audit(&mut tx, &entry).await?;
+        tx.commit().await
+    }
+
+    async fn get_max_points(&self, institution: i64, subject_id: i64) -> sqlx::Result<i64> {
+        sqlx::query("SELECT points FROM subjects WHERE institution_id = $1 AND id = $2;")
+            .bind(institution)
+            .bind(subject_id)
             .fetch_one(&self.pool)
+            .await?
+            .try_get("points")
+    }
+
+    async fn add_grade(&self, id: &ChatId, institution: i64, value: &Grade) -> sqlx::Result<i64> {
+        let mut tx = self.pool.begin().await?;
+        let record = sqlx::query("INSERT INTO grades(chat_id, institution_id, subject_id, date, points, comment) VALUES($1, $2, $3, $4, $5, $6) RETURNING id;")
+            .bind(id.0)
+            .bind(institution)
+            .bind(value.subject_id)
+            .bind(value.date.to_string())
+            .bind(value.points)
+            .bind(&value.comment)
+            .fetch_one(&mut *tx)
+            .await?;
+        let grade: i64 = record.try_get("id")?;
+        let entry = Entry::personal(
+            self.clock.now(),
+            "add_grade",
+            format!("{} by {}", audit::grade(grade), audit::chat(id)),
+            None,
+            Some(audit::subject(value.subject_id)),
+        );
+        audit(&mut tx, &entry).await?;
+        tx.commit().await?;
+        Ok(grade)
+    }
+
+    async fn remove_grade(&self, id: &ChatId, institution: i64, grade: i64) -> sqlx::Result<()> {
+        let Some(before) = self
+            .get_grades(id, institution)
+            .await?
+            .into_iter()
+            .find(|g| g.id == grade)
+        else {
+            return Ok(());
+        };
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM grades WHERE id = $1;")
+            .bind(grade)
+            .execute(&mut *tx)
+            .await?;
+        let entry = Entry::personal(
+            self.clock.now(),
+            "remove_grade",
+            format!("{} by {}", audit::grade(grade), audit::chat(id)),
+            Some(audit::subject(before.subject_id)),
+            None,
+        );
+        audit(&mut tx, &entry).await?;
+        tx.commit().await
+    }
+
+    async fn get_grades(&self, id: &ChatId, institution: i64) -> sqlx::Result<Vec<Grade>> {
+        let records = sqlx::query("SELECT id, subject_id, date, points, comment FROM grades WHERE chat_id = $1 AND institution_id = $2 ORDER BY subject_id, date, id;")
+            .bind(id.0)
+            .bind(institution)
+            .fetch_all(&self.pool)
             .await?;
-        Ok(Group::try_from(rec.gang.as_str()).unwrap())
+        records
+            .iter()
+            .map(|r| {
+                let date: String = r.try_get("date")?;
+                Ok(Grade {
+                    id: r.try_get("id")?,
+                    subject_id: r.try_get("subject_id")?,
+                    date: date.parse().map_err(|err| sqlx::Error::ColumnDecode {
+                        index: "date".into(),
+                        source: Box::new(err),
+                    })?,
+                    points: r.try_get("points")?,
+                    comment: r.try_get("comment")?,
+                })
+            })
+            .collect()
+    }
+
+    async fn get_exams(&self, institution: i64) -> sqlx::Result<Vec<Exam>> {
+        let query = format!(
+            "SELECT {} FROM exams WHERE institution_id = $1 ORDER BY starts, id;",
+            EXAM
+        );
+        let records = sqlx::query(&query)
+            .bind(institution)
+            .fetch_all(&self.pool)
+            .await?;
+        records.iter().map(exam_from).collect()
+    }
+
+    async fn get_countdowns(&self) -> sqlx::Result<Vec<(i64, Exam, Option<i64>)>> {
+        let query = format!(
+            "SELECT institution_id, countdown, {} FROM exams WHERE countdown <> 0 ORDER BY starts, id;",
+            EXAM
+        );
+        let records = sqlx::query(&query).fetch_all(&self.pool).await?;
+        records
+            .iter()
+            .map(|r| {
+                let days: i64 = r.try_get("countdown")?;
+                let days = (days > 0).then_some(days);
+                Ok((r.try_get("institution_id")?, exam_from(r)?, days))
+            })
+            .collect()
+    }
+
+    async fn set_countdown(&self, exam: i64, days: i64) -> sqlx::Result<()> {
+        let record = sqlx::query("SELECT institution_id, countdown FROM exams WHERE id = $1;")
+            .bind(exam)
+            .fetch_optional(&self.pool)
+            .await?;
+        let Some(record) = record else {
+            return Ok(());
+        };
+        let institution: i64 = record.try_get("institution_id")?;
+        let before: i64 = record.try_get("countdown")?;
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("UPDATE exams SET countdown = $1 WHERE id = $2;")
+            .bind(days)
+            .bind(exam)
+            .execute(&mut *tx)
+            .await?;
+        let entry = Entry::new(
+            self.clock.now(),
+            Some(institution),
+            "set_countdown",
+            audit::exam(exam),
+            (before >= 0).then(|| before.to_string()),
+            Some(days.to_string()),
+        );
+        audit(&mut tx, &entry).await?;
+        tx.commit().await
+    }
+
+    async fn get_dialogue(&self, id: &ChatId) -> sqlx::Result<Option<String>> {
+        let record = sqlx::query("SELECT state FROM dialogues WHERE chat_id = $1;")
+            .bind(id.0)
+            .fetch_optional(&self.pool)
+            .await?;
+        record.map(|r| r.try_get("state")).transpose()
+    }
+
+    async fn set_dialogue(&self, id: &ChatId, state: &str) -> sqlx::Result<()> {
+        let before = self.get_dialogue(id).await?;
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("INSERT INTO dialogues(chat_id, state) VALUES($1, $2) ON CONFLICT(chat_id) DO UPDATE SET state = excluded.state;")
+            .bind(id.0)
+            .bind(state)
+            .execute(&mut *tx)
+            .await?;
+        let entry = Entry::new(
+            self.clock.now(),
+            None,
+            "set_dialogue",
+            audit::chat(id),
+            before,
+            Some(state.into()),
+        );
+        audit(&mut tx, &entry).await?;
+        tx.commit().await
+    }
+
+    async fn remove_dialogue(&self, id: &ChatId) -> sqlx::Result<()> {
+        let Some(before) = self.get_dialogue(id).await? else {
+            return Ok(());
+        };
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM dialogues WHERE chat_id = $1;")
+            .bind(id.0)
+            .execute(&mut *tx)
+            .await?;
+        let entry = Entry::new(
+            self.clock.now(),
+            None,
+            "remove_dialogue",
+            audit::chat(id),
+            Some(before),
+            None,
+        );
+        audit(&mut tx, &entry).await?;
+        tx.commit().await
+    }
+
+    async fn set_pinned(&self, value: &Pinned) -> sqlx::Result<()> {
+        let before = self.get_pinned(&value.chat_id).await?;
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("INSERT INTO pinned(chat_id, message_id, date) VALUES($1, $2, $3) ON CONFLICT(chat_id) DO UPDATE SET message_id = excluded.message_id, date = excluded.date;")
+            .bind(value.chat_id.0)
+            .bind(i64::from(value.message_id.0))
+            .bind(value.date.to_string())
+            .execute(&mut *tx)
+            .await?;
+        let entry = Entry::new(
+            self.clock.now(),
+            None,
+            "set_pinned",
+            audit::chat(&value.chat_id),
+            before.as_ref().map(Pinned::describe),
+            Some(value.describe()),
+        );
+        audit(&mut tx, &entry).await?;
+        tx.commit().await
+    }
+
+    async fn set_upload(&self, value: &Upload) -> sqlx::Result<()> {
+        let before = self.get_upload(&value.chat_id).await?;
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("INSERT INTO uploads(chat_id, name, content) VALUES($1, $2, $3) ON CONFLICT(chat_id) DO UPDATE SET name = excluded.name, content = excluded.content;")
+            .bind(value.chat_id.0)
+            .bind(&value.name)
+            .bind(&value.text)
+            .execute(&mut *tx)
+            .await?;
+        let entry = Entry::new(
+            self.clock.now(),
+            None,
+            "set_upload",
+            audit::chat(&value.chat_id),
+            before.map(|u| u.name),
+            Some(value.name.clone()),
+        );
+        audit(&mut tx, &entry).await?;
+        tx.commit().await
+    }
+
+    async fn get_upload(&self, id: &ChatId) -> sqlx::Result<Option<Upload>> {
+        let record = sqlx::query("SELECT CAST(chat_id AS TEXT) AS chat_id, name, content FROM uploads WHERE chat_id = $1;")
+            .bind(id.0)
+            .fetch_optional(&self.pool)
+            .await?;
+        record.as_ref().map(upload_from).transpose()
+    }
+
+    async fn remove_upload(&self, id: &ChatId) -> sqlx::Result<()> {
+        let Some(before) = self.get_upload(id).await? else {
+            return Ok(());
+        };
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM uploads WHERE chat_id = $1;")
+            .bind(id.0)
+            .execute(&mut *tx)
+            .await?;
+        let entry = Entry::new(
+            self.clock.now(),
+            None,
+            "remove_upload",
+            audit::chat(id),
+            Some(before.name),
+            None,
+        );
+        audit(&mut tx, &entry).await?;
+        tx.commit().await
+    }
+
+    async fn get_audit(
+        &self,
+        institution: Option<i64>,
+        actions: &[&str],
+        limit: i64,
+    ) -> sqlx::Result<Vec<Entry>> {
+        let filter = match actions.len() {
+            0 => String::new(),
+            n => {
+                let params: Vec<String> = (3..n + 3).map(|i| format!("${}", i)).collect();
+                format!(" AND action IN ({})", params.join(", "))
+            }
+        };
+        // NULLs don't decode reliably through the `Any` driver, and ids are never negative
+        let query = format!("SELECT made_at, source, actor, COALESCE(institution_id, -1) AS institution_id, action, target, before_value, after_value FROM audit WHERE ($1 < 0 OR institution_id = $1){} ORDER BY id DESC LIMIT $2;", filter);
+        let mut query = sqlx::query(&query)
+            .bind(institution.unwrap_or(-1))
+            .bind(limit);
+        for action in actions {
+            query = query.bind(*action);
+        }
+        let records = query.fetch_all(&self.pool).await?;
+        let mut entries = records
+            .iter()
+            .map(entry_from)
+            .collect::<sqlx::Result<Vec<_>>>()?;
+        entries.reverse();
+        Ok(entries)
+    }
+
+    async fn get_pinned(&self, id: &ChatId) -> sqlx::Result<Option<Pinned>> {
+        let record =
+            sqlx::query("SELECT CAST(chat_id AS TEXT) AS chat_id, message_id, date FROM pinned WHERE chat_id = $1;")
+                .bind(id.0)
+                .fetch_optional(&self.pool)
+                .await?;
+        record.as_ref().map(pinned_from).transpose()
+    }
+
+    async fn get_all_pinned(&self) -> sqlx::Result<Vec<Pinned>> {
+        let records = sqlx::query("SELECT CAST(chat_id AS TEXT) AS chat_id, message_id, date FROM pinned ORDER BY chat_id;")
+            .fetch_all(&self.pool)
+            .await?;
+        records.iter().map(pinned_from).collect()
+    }
+
+    async fn remove_pinned(&self, id: &ChatId) -> sqlx::Result<()> {
+        let Some(before) = self.get_pinned(id).await? else {
+            return Ok(());
+        };
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM pinned WHERE chat_id = $1;")
+            .bind(id.0)
+            .execute(&mut *tx)
+            .await?;
+        let entry = Entry::new(
+            self.clock.now(),
+            None,
+            "remove_pinned",
+            audit::chat(id),
+            Some(before.describe()),
+            None,
+        );
+        audit(&mut tx, &entry).await?;
+        tx.commit().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::TestClock;
+    use chrono::TimeZone;
+
+    /// Fresh, migrated databases for every backend available to the test run.
+    ///
+    /// SQLite always runs in memory. Postgres is only exercised when
+    /// `TEST_POSTGRES_URL` is set, each call getting its own schema.
+    async fn databases() -> Vec<Database> {
+        let sqlite =
+            Database::connect_with(AnyPoolOptions::new().max_connections(1), "sqlite::memory:")
+                .await
+                .expect("Failed to open in-memory SQLite");
+        let mut databases = vec![sqlite];
+
+        if let Ok(url) = std::env::var("TEST_POSTGRES_URL") {
+            databases.push(postgres(&url).await);
+        }
+
+        for db in &databases {
+            db.migrate().await.expect("Failed to run migrations");
+        }
+        databases
+    }
+
+    async fn postgres(url: &str) -> Database {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let schema = format!("test_{}_{}", nanos, COUNTER.fetch_add(1, Ordering::Relaxed));
+
+        let setup = Database::connect(url)
+            .await
+            .expect("Failed to connect to Postgres");
+        sqlx::query(&format!("CREATE SCHEMA {};", schema))
+            .execute(&setup.pool)
+            .await
+            .expect("Failed to create test schema");
+
+        // A single connection, so later statements run on what earlier ones
+        // prepared, as they do on pooled connections in production
+        let options = AnyPoolOptions::new()
+            .max_connections(1)
+            .after_connect(move |conn, _| {
+                let statement = format!("SET search_path TO {};", schema);
+                Box::pin(async move {
+                    sqlx::Executor::execute(conn, statement.as_str()).await?;
+                    Ok(())
+                })
+            });
+        Database::connect_with(options, url)
+            .await
+            .expect("Failed to connect to Postgres")
+    }
+
+    #[test]
+    fn backend_from_url() {
+        assert_eq!(
+            Backend::try_from("sqlite:///tmp/test.db").unwrap(),
+            Backend::Sqlite
+        );
+        assert_eq!(
+            Backend::try_from("postgres://localhost/schedule").unwrap(),
+            Backend::Postgres
+        );
+        assert_eq!(
+            Backend::try_from("postgresql://localhost/schedule").unwrap(),
+            Backend::Postgres
+        );
+        assert!(Backend::try_from("mysql://localhost/schedule").is_err());
+    }
+
+    fn k25() -> Group {
+        Group::try_from("K-25").unwrap()
+    }
+
+    #[tokio::test]
+    async fn institutions_roundtrip() {
+        for db in databases().await {
+            let default = db.find_institution("default").await.unwrap();
+            assert_eq!(default.id, 0);
+            assert_eq!(default.timezone, chrono_tz::Europe::Kiev);
+            assert_eq!(default.bells, Bells::default());
+
+            let lisbon = Institution {
+                id: 1,
+                code: "ulisboa".into(),
+                name: "Universidade de Lisboa".into(),
+                timezone: chrono_tz::Europe::Lisbon,
+                bells: Bells::try_from("9:00-10:30 10:45-12:15 12:30-14:00 15:00-16:30").unwrap(),
+            };
+            db.add_institution(&lisbon).await.unwrap();
+            assert!(db.add_institution(&lisbon).await.is_err());
+            assert_eq!(db.get_institution(1).await.unwrap().bells, lisbon.bells);
+            let bells = Bells::try_from("8:00-9:30 9:45-11:15 11:30-13:00 14:00-15:30").unwrap();
+            db.set_bells(1, &bells).await.unwrap();
+
+            let found = db.get_institution(1).await.unwrap();
+            assert_eq!(found.code, "ulisboa");
+            assert_eq!(found.timezone, chrono_tz::Europe::Lisbon);
+            assert_eq!(found.bells, bells);
+            assert_eq!(db.get_institutions().await.unwrap().len(), 2);
+            assert!(matches!(
+                db.find_institution("nowhere").await,
+                Err(sqlx::Error::RowNotFound)
+            ));
+
+            db.add_group(1, &k25()).await.unwrap();
+            db.add_group(1, &k25()).await.unwrap();
+            assert_eq!(db.get_groups(1).await.unwrap(), vec![k25()]);
+            assert!(db.get_groups(0).await.unwrap().is_empty());
+
+            let chat = ChatId(42);
+            assert!(db.get_grants(&chat).await.unwrap().is_empty());
+            let mut grant = Grant {
+                institution: 1,
+                group: None,
+                role: Role::Editor,
+            };
+            db.add_grant(&chat, &grant).await.unwrap();
+            grant.role = Role::Owner;
+            db.add_grant(&chat, &grant).await.unwrap();
+            let editor = Grant {
+                institution: 1,
+                group: Some(k25()),
+                role: Role::Editor,
+            };
+            db.add_grant(&chat, &editor).await.unwrap();
+            assert_eq!(
+                db.get_grants(&chat).await.unwrap(),
+                vec![grant, editor.clone()]
+            );
+            db.remove_grant(&chat, 1, None).await.unwrap();
+            assert_eq!(db.get_grants(&chat).await.unwrap(), vec![editor]);
+        }
+    }
+
+    #[tokio::test]
+    async fn users_roundtrip() {
+        for db in databases().await {
+            let chat = ChatId(-1001234567890);
+            let user = User {
+                institution: 0,
+                group: k25(),
+            };
+
+            assert!(matches!(
+                db.get_user(&chat).await,
+                Err(sqlx::Error::RowNotFound)
+            ));
+            db.add_user(&chat, &user).await.unwrap();
+            db.update_user(&chat, &user).await.unwrap();
+            assert_eq!(db.get_user(&chat).await.unwrap(), user);
+
+            assert_eq!(db.get_timezone(&chat).await.unwrap(), None);
+            db.set_timezone(&chat, None).await.unwrap();
+            assert_eq!(db.get_timezone(&chat).await.unwrap(), None);
+            db.set_timezone(&chat, Some(chrono_tz::Europe::Lisbon))
+                .await
+                .unwrap();
+            assert_eq!(
+                db.get_timezone(&chat).await.unwrap(),
+                Some(chrono_tz::Europe::Lisbon)
+            );
+            db.set_timezone(&chat, None).await.unwrap();
+            assert_eq!(db.get_timezone(&chat).await.unwrap(), None);
+
+            assert_eq!(db.get_language(&chat).await.unwrap(), None);
+            db.set_language(&chat, None).await.unwrap();
+            db.set_language(&chat, Some(Language::Uk)).await.unwrap();
+            assert_eq!(db.get_language(&chat).await.unwrap(), Some(Language::Uk));
+            db.set_language(&chat, None).await.unwrap();
+            assert_eq!(db.get_language(&chat).await.unwrap(), None);
+            db.set_language(&chat, Some(Language::En)).await.unwrap();
+            assert_eq!(db.get_language(&chat).await.unwrap(), Some(Language::En));
+            assert!(matches!(
+                db.set_timezone(&ChatId(1), None).await,
+                Err(sqlx::Error::RowNotFound)
+            ));
+
+            assert_eq!(db.get_chats(0, &k25()).await.unwrap(), vec![chat]);
+            db.remove_user(&chat).await.unwrap();
+            db.remove_user(&chat).await.unwrap();
+            assert!(db.get_chats(0, &k25()).await.unwrap().is_empty());
+            assert!(matches!(
+                db.get_user(&chat).await,
+                Err(sqlx::Error::RowNotFound)
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn subjects_by_slot() {
+        for db in databases().await {
+            let subject = Subject {
+                id: 7,
+                title: "Test title".into(),
+                group: k25(),
+                optional: true,
+            };
+            db.add_subject(0, &subject).await.unwrap();
+            db.add_schedule(
+                0,
+                &Schedule {
+                    subject_id: 7,
+                    day: Day::Wed,
+                    repeat: Repeat::Odd,
+                    slot: Slot::III,
+                },
+            )
+            .await
+            .unwrap();
+
+            let found = db
+                .get_subjects(0, Day::Wed, Repeat::Odd, Slot::III, k25())
+                .await
+                .unwrap();
+            assert_eq!(found.len(), 1);
+            assert_eq!(found[0].id, 7);
+            assert_eq!(found[0].title, "Test title");
+            assert!(found[0].optional);
+
+            let missing = db
+                .get_subjects(0, Day::Wed, Repeat::Even, Slot::III, k25())
+                .await
+                .unwrap();
+            assert!(missing.is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn meetings_by_subject() {
+        for db in databases().await {
+            let subject = Subject {
+                id: 1,
+                title: "Test title".into(),
+                group: k25(),
+                optional: false,
+            };
+            db.add_subject(0, &subject).await.unwrap();
+            db.add_meeting(
+                0,
+                &Meeting {
+                    id: 2,
+                    name: "Test name".into(),
+                    group: k25(),
+                    link: "https://fake-link.lol".into(),
+                },
+            )
+            .await
+            .unwrap();
+            db.assign(
+                0,
+                &Assigned {
+                    meeting_id: 2,
+                    subject_id: 1,
+                },
+            )
+            .await
+            .unwrap();
+
+            let meetings = db.get_meetings(0, 1).await.unwrap();
+            assert_eq!(meetings.len(), 1);
+            assert_eq!(meetings[0].name, "Test name");
+            assert_eq!(meetings[0].link, "https://fake-link.lol");
+            assert!(db.get_meetings(0, 2).await.unwrap().is_empty());
+            assert_eq!(db.next_meeting_id(0).await.unwrap(), 3);
+            assert_eq!(db.next_meeting_id(1).await.unwrap(), 0);
+
+            assert_eq!(db.get_subject(0, 1).await.unwrap().title, "Test title");
+            assert!(matches!(
+                db.get_subject(0, 2).await,
+                Err(sqlx::Error::RowNotFound)
+            ));
+            assert_eq!(db.get_room(0, 1).await.unwrap(), None);
+            db.set_room(0, 1, "101").await.unwrap();
+            db.set_room(0, 1, "Aula").await.unwrap();
+            assert_eq!(db.get_room(0, 1).await.unwrap().as_deref(), Some("Aula"));
+        }
+    }
+
+    #[tokio::test]
+    async fn import_replaces_classes() {
+        for db in databases().await {
+            let subject = |id, title: &str| Subject {
+                id,
+                title: title.into(),
+                group: k25(),
+                optional: false,
+            };
+            let class = |subject_id, day, slot| Schedule {
+                subject_id,
+                day,
+                repeat: Repeat::Both,
+                slot,
+            };
+            db.add_subject(0, &subject(1, "Algebra")).await.unwrap();
+            db.add_subject(0, &subject(2, "Logic")).await.unwrap();
+            db.add_schedule(0, &class(1, Day::Mon, Slot::I))
+                .await
+                .unwrap();
+            db.add_schedule(0, &class(2, Day::Tue, Slot::II))
+                .await
+                .unwrap();
+
+            let import = Import {
+                subjects: vec![subject(1, "Linear algebra"), {
+                    let mut geometry = subject(3, "Geometry");
+                    geometry.group = Group::try_from("K-26").unwrap();
+                    geometry
+                }],
+                schedule: vec![class(1, Day::Fri, Slot::IV), class(3, Day::Mon, Slot::I)],
+                exams: vec![],
+            };
+            db.import(0, &import).await.unwrap();
+
+            let titles: Vec<String> = db
+                .get_all_subjects(0)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|s| s.title)
+                .collect();
+            assert_eq!(titles, vec!["Linear algebra", "Logic", "Geometry"]);
+            assert_eq!(db.get_groups(0).await.unwrap().len(), 2);
+            let classes: Vec<(i64, Day, Slot)> = db
+                .get_schedule(0)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|c| (c.subject_id, c.day, c.slot))
+                .collect();
+            assert_eq!(
+                classes,
+                vec![
+                    (1, Day::Fri, Slot::IV),
+                    (2, Day::Tue, Slot::II),
+                    (3, Day::Mon, Slot::I)
+                ]
+            );
+            let audit = db.get_audit(Some(0), &[], 1).await.unwrap();
+            assert_eq!(audit[0].action, "import");
+            assert_eq!(audit[0].after.as_deref(), Some("Mon slot 1, Both"));
+
+            let upload = Upload {
+                chat_id: ChatId(7),
+                name: "k25.csv".into(),
+                text: "id,title,group".into(),
+            };
+            db.set_upload(&upload).await.unwrap();
+            assert_eq!(
+                db.get_upload(&upload.chat_id).await.unwrap(),
+                Some(upload.clone())
+            );
+            db.remove_upload(&upload.chat_id).await.unwrap();
+            assert_eq!(db.get_upload(&upload.chat_id).await.unwrap(), None);
+        }
+    }
+
+    #[tokio::test]
+    async fn changes_by_slot() {
+        for db in databases().await {
+            let date = NaiveDate::from_ymd_opt(2023, 10, 9).unwrap();
+            let mut change = Change {
+                group: k25(),
+                date,
+                slot: Slot::II,
+                subject_id: Some(3),
+            };
+            db.add_change(0, &change).await.unwrap();
+            change.subject_id = None;
+            db.add_change(0, &change).await.unwrap();
+
+            let found = db.get_change(0, &k25(), date, Slot::II).await.unwrap();
+            assert_eq!(found, Some(change));
+            assert_eq!(db.get_change(0, &k25(), date, Slot::I).await.unwrap(), None);
+        }
+    }
+
+    #[tokio::test]
+    async fn homework_roundtrip() {
+        for db in databases().await {
+            let subject = Subject {
+                id: 3,
+                title: "Algebra".into(),
+                group: k25(),
+                optional: false,
+            };
+            db.add_subject(0, &subject).await.unwrap();
+            let date = NaiveDate::from_ymd_opt(2023, 10, 16).unwrap();
+            let author = ChatId(-1001234567890);
+            let mut homework = Homework {
+                id: 0,
+                subject_id: 3,
+                due: date,
+                text: "Exercises 1-5".into(),
+                author,
+                shared: true,
+            };
+            homework.id = db.add_homework(0, &homework).await.unwrap();
+            let private = Homework {
+                shared: false,
+                ..homework.clone()
+            };
+            let private = Homework {
+                id: db.add_homework(0, &private).await.unwrap(),
+                ..private
+            };
+            assert_ne!(homework.id, private.id);
+            assert_eq!(db.get_homework(0, homework.id).await.unwrap(), homework);
+            let audit = db.get_audit(Some(0), &[], 1).await.unwrap();
+            assert_eq!(
+                audit[0].after.as_deref(),
+                Some("subject 3 due 2023-10-16, private")
+            );
+
+            let other = ChatId(8);
+            let group = k25();
+            let due = |chat| db.get_due_homework(0, &group, chat, date, date);
+            assert_eq!(due(&other).await.unwrap(), vec![homework.clone()]);
+            assert_eq!(
+                due(&author).await.unwrap(),
+                vec![homework.clone(), private.clone()]
+            );
+            let next = date.succ_opt().unwrap();
+            let later = db.get_due_homework(0, &group, &author, next, next).await;
+            assert!(later.unwrap().is_empty());
+
+            db.set_done(&author, homework.id, true).await.unwrap();
+            db.set_done(&other, homework.id, true).await.unwrap();
+            db.set_done(&other, homework.id, false).await.unwrap();
+            assert_eq!(db.get_done(homework.id).await.unwrap(), vec![author]);
+
+            assert_eq!(db.get_unreminded().await.unwrap().len(), 2);
+            db.set_reminded(homework.id).await.unwrap();
+            db.set_reminded(homework.id).await.unwrap();
+            assert_eq!(db.get_unreminded().await.unwrap(), vec![(0, private)]);
+
+            db.remove_homework(0, homework.id).await.unwrap();
+            assert!(matches!(
+                db.get_homework(0, homework.id).await,
+                Err(sqlx::Error::RowNotFound)
+            ));
+            assert!(db.get_done(homework.id).await.unwrap().is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn exams_roundtrip() {
+        for db in databases().await {
+            let subject = Subject {
+                id: 3,
+                title: "Algebra".into(),
+                group: k25(),
+                optional: false,
+            };
+            let exam = |kind, day, room: &str| Exam {
+                id: 0,
+                subject_id: 3,
+                kind,
+                starts: NaiveDate::from_ymd_opt(2024, 1, day)
+                    .unwrap()
+                    .and_hms_opt(9, 0, 0)
+                    .unwrap(),
+                room: room.into(),
+                lecturer: "O. Petrenko".into(),
+            };
+            let mut import = Import {
+                subjects: vec![subject],
+                schedule: vec![],
+                exams: vec![
+                    exam(ExamKind::Exam, 15, "204"),
+                    exam(ExamKind::Consultation, 12, ""),
+                ],
+            };
+            db.import(0, &import).await.unwrap();
+            let exams = db.get_exams(0).await.unwrap();
+            assert_eq!(exams.len(), 2);
+            assert!(exams[0].same(&import.exams[1]));
+            let (consultation, first) = (exams[0].id, exams[1].id);
+
+            db.set_countdown(first, 7).await.unwrap();
+            db.set_countdown(consultation, 0).await.unwrap();
+            let countdowns = db.get_countdowns().await.unwrap();
+            assert_eq!(countdowns, vec![(0, exams[1].clone(), Some(7))]);
+
+            // Moving the exam replaces it, the consultation stays as it was
+            import.exams[0].room = "301".into();
+            db.import(0, &import).await.unwrap();
+            let exams = db.get_exams(0).await.unwrap();
+            assert_eq!(exams[0].id, consultation);
+            assert_eq!(exams[1].room, "301");
+            let countdowns = db.get_countdowns().await.unwrap();
+            assert_eq!(countdowns, vec![(0, exams[1].clone(), None)]);
+            let audit = db.get_audit(Some(0), &[], 1).await.unwrap();
+            assert_eq!(
+                audit[0].after.as_deref(),
+                Some("consultation 2024-01-12 09:00, O. Petrenko; exam 2024-01-15 09:00, room 301, O. Petrenko")
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn notes_roundtrip() {
+        for db in databases().await {
+            let subject = Subject {
+                id: 3,
+                title: "Algebra".into(),
+                group: k25(),
+                optional: false,
+            };
+            db.add_subject(0, &subject).await.unwrap();
+            let (alice, bob) = (ChatId(7), ChatId(8));
+            db.set_note(&alice, 0, 3, Some("bring a calculator"))
+                .await
+                .unwrap();
+            db.set_note(&alice, 0, 3, Some("answers email"))
+                .await
+                .unwrap();
+            let note = Note {
+                subject_id: 3,
+                text: "answers email".into(),
+            };
+            assert_eq!(db.get_notes(&alice, 0).await.unwrap(), vec![note]);
+            assert!(db.get_notes(&bob, 0).await.unwrap().is_empty());
+            let audit = db.get_audit(None, &[], 1).await.unwrap();
+            assert_eq!(audit[0].before.as_deref(), Some("set"));
+            assert_eq!(audit[0].after.as_deref(), Some("set"));
+
+            db.set_note(&alice, 0, 3, None).await.unwrap();
+            assert!(db.get_notes(&alice, 0).await.unwrap().is_empty());
+            let audit = db.get_audit(None, &[], 1).await.unwrap();
+            assert_eq!(audit[0].after.as_deref(), Some("cleared"));
+            let audit = db.get_audit(Some(0), &[], 1).await.unwrap();
+            assert_eq!(audit[0].action, "add_subject");
+        }
+    }
+
+    #[tokio::test]
+    async fn attendance_roundtrip() {
+        for db in databases().await {
+            let subject = Subject {
+                id: 3,
+                title: "Algebra".into(),
+                group: k25(),
+                optional: false,
+            };
+            db.add_subject(0, &subject).await.unwrap();
+            let alice = ChatId(-1001234567890);
+            let mark = |day, attended| Attendance {
+                subject_id: 3,
+                date: NaiveDate::from_ymd_opt(2023, 10, day).unwrap(),
+                attended,
+            };
+            db.set_attendance(&alice, 0, &mark(16, false))
+                .await
+                .unwrap();
+            db.set_attendance(&alice, 0, &mark(9, false)).await.unwrap();
+            db.set_attendance(&alice, 0, &mark(9, true)).await.unwrap();
+            assert_eq!(
+                db.get_attendance(&alice, 0).await.unwrap(),
+                vec![mark(9, true), mark(16, false)]
+            );
+            let audit = db.get_audit(None, &[], 1).await.unwrap();
+            assert_eq!(audit[0].before.as_deref(), Some("false"));
+            let audit = db.get_audit(Some(0), &[], 1).await.unwrap();
+            assert_eq!(audit[0].action, "add_subject");
+            db.set_attendance(&ChatId(8), 0, &mark(9, true))
+                .await
+                .unwrap();
+            db.set_enrolled(&alice, 0, 3, true).await.unwrap();
+            assert_eq!(
+                db.get_tracking(0).await.unwrap(),
+                vec![(alice, vec![3]), (ChatId(8), vec![])]
+            );
+
+            assert_eq!(db.get_absences(0).await.unwrap(), 3);
+            db.set_absences(0, 5).await.unwrap();
+            assert_eq!(db.get_absences(0).await.unwrap(), 5);
+        }
+    }
+
+    #[tokio::test]
+    async fn grades_roundtrip() {
+        for db in databases().await {
+            let subject = Subject {
+                id: 3,
+                title: "Algebra".into(),
+                group: k25(),
+                optional: false,
+            };
+            assert_eq!(db.get_semesters(0).await.unwrap(), Semesters::default());
+            let semesters = Semesters::new((9, 15), (2, 15)).unwrap();
+            db.set_semesters(0, &semesters).await.unwrap();
+            assert_eq!(db.get_semesters(0).await.unwrap(), semesters);
+            let audit = db.get_audit(Some(0), &[], 1).await.unwrap();
+            assert_eq!(audit[0].after.as_deref(), Some("09-15 02-15"));
+            db.add_subject(0, &subject).await.unwrap();
+            assert_eq!(db.get_max_points(0, 3).await.unwrap(), 100);
+            db.set_max_points(0, 3, 60).await.unwrap();
+            assert_eq!(db.get_max_points(0, 3).await.unwrap(), 60);
+
+            let alice = ChatId(-1001234567890);
+            let grade = Grade {
+                id: 0,
+                subject_id: 3,
+                date: NaiveDate::from_ymd_opt(2023, 10, 9).unwrap(),
+                points: 8,
+                comment: "test 1".into(),
+            };
+            let id = db.add_grade(&alice, 0, &grade).await.unwrap();
+            let other = db.add_grade(&alice, 0, &grade).await.unwrap();
+            assert_ne!(id, other);
+            assert_eq!(
+                db.get_grades(&alice, 0).await.unwrap(),
+                vec![
+                    Grade {
+                        id,
+                        ..grade.clone()
+                    },
+                    Grade { id: other, ..grade }
+                ]
+            );
+            assert!(db.get_grades(&ChatId(8), 0).await.unwrap().is_empty());
+
+            db.remove_grade(&alice, 0, id).await.unwrap();
+            assert_eq!(db.get_grades(&alice, 0).await.unwrap().len(), 1);
+            let audit = db.get_audit(None, &[], 1).await.unwrap();
+            assert_eq!(audit[0].action, "remove_grade");
+            assert_eq!(audit[0].before.as_deref(), Some("subject 3"));
+            let audit = db.get_audit(Some(0), &[], 1).await.unwrap();
+            assert_eq!(audit[0].action, "set_max_points");
+        }
+    }
+
+    #[tokio::test]
+    async fn friends_roundtrip() {
+        for db in databases().await {
+            let (alice, bob) = (ChatId(7), ChatId(-1001234567890));
+            let user = User {
+                institution: 0,
+                group: k25(),
+            };
+            for id in [alice, bob] {
+                db.add_user(&id, &user).await.unwrap();
+            }
+            db.set_username(&alice, Some("alice")).await.unwrap();
+            db.set_username(&bob, Some("alice")).await.unwrap();
+            assert_eq!(db.find_username("alice").await.unwrap(), Some(bob));
+            assert_eq!(db.get_username(&alice).await.unwrap(), None);
+            db.set_username(&bob, None).await.unwrap();
+            assert_eq!(db.find_username("alice").await.unwrap(), None);
+
+            db.add_friend(&alice, &bob).await.unwrap();
+            db.add_friend(&alice, &bob).await.unwrap();
+            db.add_friend(&bob, &alice).await.unwrap();
+            assert_eq!(db.get_friends(&alice).await.unwrap(), vec![bob]);
+            db.remove_friend(&bob, &alice).await.unwrap();
+            assert!(db.get_friends(&alice).await.unwrap().is_empty());
+
+            db.add_friend(&alice, &bob).await.unwrap();
+            db.remove_user(&bob).await.unwrap();
+            assert!(db.get_friends(&alice).await.unwrap().is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn audit_log_is_append_only() {
+        let at = Utc.with_ymd_and_hms(2023, 10, 9, 6, 0, 0).unwrap();
+        for db in databases().await {
+            let db = db.with_clock(Arc::new(TestClock::new(at)));
+            let date = NaiveDate::from_ymd_opt(2023, 10, 9).unwrap();
+            let cancel = Change {
+                group: k25(),
+                date,
+                slot: Slot::II,
+                subject_id: None,
+            };
+            let user = User {
+                institution: 0,
+                group: k25(),
+            };
+            let subject = Subject {
+                id: 3,
+                title: "Algebra".into(),
+                group: k25(),
+                optional: false,
+            };
+            db.add_group(0, &k25()).await.unwrap();
+            db.add_subject(0, &subject).await.unwrap();
+            db.add_user(&ChatId(9), &user).await.unwrap();
+            audit::acting(audit::Actor::chat(ChatId(7)), async {
+                db.set_room(0, 3, "204").await.unwrap();
+                db.set_room(0, 3, "301").await.unwrap();
+                db.add_change(0, &cancel).await.unwrap();
+            })
+            .await;
+            db.set_language(&ChatId(9), Some(Language::Uk))
+                .await
+                .unwrap();
+
+            let entries = db.get_audit(Some(0), &[], 2).await.unwrap();
+            assert_eq!(entries.len(), 2);
+            assert_eq!(entries[0].action, "set_room");
+            assert_eq!(entries[0].target, "subject 3");
+            assert_eq!(entries[0].before.as_deref(), Some("204"));
+            assert_eq!(entries[0].after.as_deref(), Some("301"));
+            assert_eq!(entries[1].at, at);
+            assert_eq!(entries[1].actor, "7");
+            assert_eq!(entries[1].source, Source::Bot);
+            assert_eq!(entries[1].target, "K-25 2023-10-09 slot 2");
+            assert_eq!(entries[1].before, None);
+            assert_eq!(entries[1].after.as_deref(), Some("cancelled"));
+
+            let timetable = db.get_audit(Some(0), audit::TIMETABLE, 10).await.unwrap();
+            let actions: Vec<&str> = timetable.iter().map(|e| e.action.as_str()).collect();
+            assert_eq!(
+                actions,
+                [
+                    "add_group",
+                    "add_subject",
+                    "set_room",
+                    "set_room",
+                    "add_change"
+                ]
+            );
+
+            let latest = db.get_audit(None, &[], 1).await.unwrap();
+            assert_eq!(latest[0].actor, "bot");
+            assert_eq!(latest[0].institution, None);
+            assert_eq!(latest[0].after.as_deref(), Some("uk"));
+
+            assert!(sqlx::query("DELETE FROM audit;")
+                .execute(&db.pool)
+                .await
+                .is_err());
+            assert!(sqlx::query("UPDATE audit SET actor = 'nobody';")
+                .execute(&db.pool)
+                .await
+                .is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn holidays_by_date() {
+        for db in databases().await {
+            let date = NaiveDate::from_ymd_opt(2023, 12, 25).unwrap();
+            for title in ["Christmas", "Різдво"] {
+                db.add_holiday(
+                    0,
+                    &Holiday {
+                        date,
+                        title: title.into(),
+                    },
+                )
+                .await
+                .unwrap();
+            }
+
+            let holiday = db.get_holiday(0, date).await.unwrap().unwrap();
+            assert_eq!(holiday.title, "Різдво");
+            assert!(db
+                .get_holiday(0, date.succ_opt().unwrap())
+                .await
+                .unwrap()
+                .is_none());
+
+            let new_year = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+            db.add_holiday(
+                0,
+                &Holiday {
+                    date: new_year,
+                    title: "New Year".into(),
+                },
+            )
+            .await
+            .unwrap();
+            let holidays = db.get_holidays(0, date, new_year).await.unwrap();
+            let dates: Vec<NaiveDate> = holidays.iter().map(|h| h.date).collect();
+            assert_eq!(dates, vec![date, new_year]);
+            let later = db.get_holidays(0, date.succ_opt().unwrap(), new_year).await;
+            assert_eq!(later.unwrap().len(), 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn dialogues_roundtrip() {
+        for db in databases().await {
+            let chat = ChatId(7);
+            assert_eq!(db.get_dialogue(&chat).await.unwrap(), None);
+            db.set_dialogue(&chat, "\"Institution\"").await.unwrap();
+            db.set_dialogue(&chat, "{\"Group\":{}}").await.unwrap();
+            assert_eq!(
+                db.get_dialogue(&chat).await.unwrap().as_deref(),
+                Some("{\"Group\":{}}")
+            );
+            db.remove_dialogue(&chat).await.unwrap();
+            assert_eq!(db.get_dialogue(&chat).await.unwrap(), None);
+        }
+    }
+
+    #[tokio::test]
+    async fn pinned_roundtrip() {
+        for db in databases().await {
+            let mut pinned = Pinned {
+                chat_id: ChatId(-1001234567890),
+                message_id: MessageId(5),
+                date: NaiveDate::from_ymd_opt(2023, 10, 9).unwrap(),
+            };
+            assert_eq!(db.get_pinned(&pinned.chat_id).await.unwrap(), None);
+            db.set_pinned(&pinned).await.unwrap();
+            pinned.date = pinned.date.succ_opt().unwrap();
+            db.set_pinned(&pinned).await.unwrap();
+            assert_eq!(
+                db.get_pinned(&pinned.chat_id).await.unwrap(),
+                Some(pinned.clone())
+            );
+            assert_eq!(db.get_all_pinned().await.unwrap(), vec![pinned.clone()]);
+            db.remove_pinned(&pinned.chat_id).await.unwrap();
+            assert!(db.get_all_pinned().await.unwrap().is_empty());
+        }
     }
 }
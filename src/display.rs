@@ -1,18 +1,37 @@
 use crate::data::Slot;
+use chrono::NaiveTime;
 use teloxide::utils::markdown as md;
 
 pub struct Subject {
     slot: Slot,
+    time: (NaiveTime, NaiveTime),
     title: String,
+    room: Option<String>,
     meetings: Vec<Meeting>,
+    homework: Vec<Homework>,
+    note: Option<String>,
 }
 
 impl Subject {
-    pub fn new(slot: Slot, title: String, meetings: Vec<Meeting>) -> Subject {
+    /// `time` is the start and end of the class, as the reader's clock shows
+    /// it, and `note` the reader's own note on the subject, shown last.
+    pub fn new(
+        slot: Slot,
+        time: (NaiveTime, NaiveTime),
+        title: String,
+        room: Option<String>,
+        meetings: Vec<Meeting>,
+        homework: Vec<Homework>,
+        note: Option<String>,
+    ) -> Subject {
         Subject {
             slot,
+            time,
             title,
+            room,
             meetings,
+            homework,
+            note,
         }
     }
 }
@@ -27,10 +46,28 @@ impl std::fmt::Display for Subject {
             IV => "four",
         })
         .unwrap();
-        write!(f, "{} {}", slot, &self.title)?;
+        let (start, end) = self.time;
+        write!(
+            f,
+            "{} {}–{} {}",
+            slot,
+            start.format("%H:%M"),
+            end.format("%H:%M"),
+            md::escape(&self.title)
+        )?;
+        if let Some(room) = &self.room {
+            write!(f, " · {}", md::escape(room))?;
+        }
         for m in &self.meetings {
             write!(f, "\n:teacher: {}", m)?;
         }
+        for h in &self.homework {
+            write!(f, "\n{}", h)?;
+        }
+        if let Some(note) = &self.note {
+            let pin = emojis::get_by_shortcode("pushpin").unwrap();
+            write!(f, "\n{} {}", pin, md::italic(&md::escape(note)))?;
+        }
         Ok(())
     }
 }
@@ -47,11 +84,48 @@ impl Meeting {
 }
 
 impl std::fmt::Display for Meeting {
+    // `md::link` escapes `)` in the URL but not `\`, which MarkdownV2 wants
+    // escaped there as well
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if let Some(value) = &self.url {
-            write!(f, "{}", md::link(value, &self.name))
+            write!(
+                f,
+                "{}",
+                md::link(&value.replace('\\', r"\\"), &md::escape(&self.name))
+            )
         } else {
-            write!(f, "{}", &self.name)
+            write!(f, "{}", md::escape(&self.name))
         }
     }
 }
+
+/// Homework due at a class, as the reader sees it.
+pub struct Homework {
+    id: i64,
+    text: String,
+    done: bool,
+}
+
+impl Homework {
+    pub fn new(id: i64, text: String, done: bool) -> Homework {
+        Homework { id, text, done }
+    }
+}
+
+impl std::fmt::Display for Homework {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mark = emojis::get_by_shortcode(if self.done {
+            "white_check_mark"
+        } else {
+            "memo"
+        })
+        .unwrap();
+        write!(
+            f,
+            "{} {} {}",
+            mark,
+            md::escape(&format!("#{}", self.id)),
+            md::escape(&self.text)
+        )
+    }
+}
@@ -0,0 +1,801 @@
+use crate::audit::{self, Entry};
+use crate::bot::{expr, failed, user, Command, Error, Reply};
+use crate::clock::Clock;
+use crate::data::{
+    Assigned, Change, Day, Grant, Group, Institution, Meeting, Role, Slot, Subject, User,
+};
+use crate::homework;
+use crate::i18n::{Language, Msg};
+use crate::store::ScheduleStore;
+use crate::timetable;
+use chrono::{Datelike, NaiveDate};
+use teloxide::types::ChatId;
+
+/// Longest room name `/setroom` takes.
+const ROOM_LENGTH: usize = 32;
+
+/// How many of the latest changes `/history` shows.
+const HISTORY_LENGTH: usize = 20;
+
+/// How many of the latest timetable changes of the institution `/history`
+/// picks those of the sender's groups from.
+const HISTORY_WINDOW: i64 = 500;
+
+/// Runs a command that changes the timetable of the group of `chat_id`,
+/// which `sender` needs a role for.
+pub async fn execute<S: ScheduleStore>(
+    store: &S,
+    clock: &dyn Clock,
+    chat_id: ChatId,
+    sender: ChatId,
+    language: Language,
+    cmd: Command,
+) -> Result<Option<Reply>, Error> {
+    use Command::*;
+    let reply = match cmd {
+        Cancel(args) => {
+            log::trace!("/cancel {}", &args);
+            cancel(store, clock, chat_id, sender, language, args).await?
+        }
+        Move(args) => {
+            log::trace!("/move {}", &args);
+            move_class(store, clock, chat_id, sender, language, args).await?
+        }
+        Setroom(args) => {
+            log::trace!("/setroom {}", &args);
+            set_room(store, chat_id, sender, language, args).await?
+        }
+        Addlink(args) => {
+            log::trace!("/addlink {}", &args);
+            add_link(store, clock, chat_id, sender, language, args).await?
+        }
+        Grant(args) => {
+            log::trace!("/grant {}", &args);
+            grant(store, chat_id, sender, language, args).await?
+        }
+        Revoke(args) => {
+            log::trace!("/revoke {}", &args);
+            revoke(store, chat_id, sender, language, args).await?
+        }
+        Roles => {
+            log::trace!("/roles");
+            roles(store, chat_id, sender, language).await?
+        }
+        History => {
+            log::trace!("/history");
+            history(store, chat_id, sender, language).await?
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(Reply::Text(reply)))
+}
+
+/// The user behind `chat_id` and their institution, as long as `sender` may
+/// edit the timetable of their group.
+pub(crate) async fn editable<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+    sender: ChatId,
+) -> Result<(User, Institution), Error> {
+    let (user, institution) = user(store, chat_id).await?;
+    let grants = store
+        .get_grants(&sender)
+        .await
+        .map_err(failed(chat_id, "get grants"))?;
+    if !grants.iter().any(|g| g.covers(institution.id, &user.group)) {
+        return Err(Error::NotEditor(chat_id));
+    }
+    Ok((user, institution))
+}
+
+/// Whether `grants` let their holder hand out roles over `group` of
+/// `institution`, or over all of it when there is no group.
+fn owns(grants: &[Grant], institution: i64, group: Option<&Group>) -> bool {
+    grants.iter().any(|g| {
+        g.role == Role::Owner
+            && match group {
+                Some(group) => g.covers(institution, group),
+                None => g.institution == institution && g.group.is_none(),
+            }
+    })
+}
+
+/// The class a command starts with, given by its date and slot in any order,
+/// along with the words after it.
+///
+/// Dates are read as of today in the institution, weekends are rejected.
+fn class<'a>(
+    clock: &dyn Clock,
+    chat_id: ChatId,
+    institution: &Institution,
+    words: &'a [&'a str],
+    command: &'static str,
+) -> Result<(NaiveDate, Slot, &'a [&'a str]), Error> {
+    // The shortest run of words that names both, so that a room such as
+    // `204` that follows isn't taken for a slot
+    let found = (1..=words.len()).find_map(|end| {
+        let text = words[..end].join(" ");
+        match expr(chat_id, &text) {
+            Ok(e) if e.words.is_empty() => Some((e.slot?, e.date?, text, end)),
+            _ => None,
+        }
+    });
+    let Some((slot, when, text, end)) = found else {
+        expr(chat_id, &words[..words.len().min(2)].join(" "))?;
+        return Err(Error::Usage(chat_id, command));
+    };
+
+    let today = clock
+        .now()
+        .with_timezone(&institution.timezone)
+        .date_naive();
+    let date = when
+        .resolve(today)
+        .ok_or(Error::InvalidDate(chat_id, text))?;
+    let dt = timetable::noon(date, &institution.timezone);
+    Day::try_from(&dt).map_err(|_| Error::InvalidWeekday(chat_id, format!("{}", dt.weekday())))?;
+    Ok((date, slot, &words[end..]))
+}
+
+/// Titles of the classes in a reply.
+fn titles(subjects: &[Subject]) -> String {
+    let titles: Vec<&str> = subjects.iter().map(|s| s.title.as_str()).collect();
+    titles.join(", ")
+}
+
+/// `/cancel`: no classes in the slot on that date.
+async fn cancel<S: ScheduleStore>(
+    store: &S,
+    clock: &dyn Clock,
+    chat_id: ChatId,
+    sender: ChatId,
+    language: Language,
+    args: String,
+) -> Result<String, Error> {
+    let (user, institution) = editable(store, chat_id, sender).await?;
+    let words: Vec<&str> = args.split_whitespace().collect();
+    let (date, slot, rest) = class(clock, chat_id, &institution, &words, "cancel")?;
+    if !rest.is_empty() {
+        return Err(Error::Usage(chat_id, "cancel"));
+    }
+
+    let subjects =
+        timetable::subjects(store, chat_id, &institution, &user.group, date, slot).await?;
+    if subjects.is_empty() {
+        return Ok(language.tr(Msg::NoSubject));
+    }
+    store
+        .add_change(
+            institution.id,
+            &Change {
+                group: user.group,
+                date,
+                slot,
+                subject_id: None,
+            },
+        )
+        .await
+        .map_err(failed(chat_id, "add change"))?;
+    Ok(language.tr(Msg::Cancelled {
+        title: &titles(&subjects),
+        date: &language.day(date, "%d.%m"),
+        slot: slot as u8,
+    }))
+}
+
+/// `/move`: a class taken to a free slot, possibly on another date.
+async fn move_class<S: ScheduleStore>(
+    store: &S,
+    clock: &dyn Clock,
+    chat_id: ChatId,
+    sender: ChatId,
+    language: Language,
+    args: String,
+) -> Result<String, Error> {
+    let (user, institution) = editable(store, chat_id, sender).await?;
+    let words: Vec<&str> = args.split_whitespace().collect();
+    let (from, from_slot, rest) = class(clock, chat_id, &institution, &words, "move")?;
+    let (to, to_slot, rest) = class(clock, chat_id, &institution, rest, "move")?;
+    if !rest.is_empty() {
+        return Err(Error::Usage(chat_id, "move"));
+    }
+
+    let group = &user.group;
+    let subject = match &timetable::subjects(store, chat_id, &institution, group, from, from_slot)
+        .await?[..]
+    {
+        [] => return Ok(language.tr(Msg::NoSubject)),
+        [subject] => subject.clone(),
+        _ => return Ok(language.tr(Msg::SeveralClasses)),
+    };
+    let taken = timetable::subjects(store, chat_id, &institution, group, to, to_slot).await?;
+    if !taken.is_empty() {
+        return Ok(language.tr(Msg::SlotTaken(&titles(&taken))));
+    }
+
+    for (date, slot, subject_id) in [(to, to_slot, Some(subject.id)), (from, from_slot, None)] {
+        let change = Change {
+            group: group.clone(),
+            date,
+            slot,
+            subject_id,
+        };
+        store
+            .add_change(institution.id, &change)
+            .await
+            .map_err(failed(chat_id, "add change"))?;
+    }
+    Ok(language.tr(Msg::Moved {
+        title: &subject.title,
+        date: &language.day(to, "%d.%m"),
+        slot: to_slot as u8,
+    }))
+}
+
+/// `/setroom`: where the classes of a subject of the group take place from
+/// now on.
+async fn set_room<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+    sender: ChatId,
+    language: Language,
+    args: String,
+) -> Result<String, Error> {
+    let (user, institution) = editable(store, chat_id, sender).await?;
+    let words: Vec<&str> = args.split_whitespace().collect();
+    let subjects: Vec<_> = store
+        .get_all_subjects(institution.id)
+        .await
+        .map_err(failed(chat_id, "get subjects"))?
+        .into_iter()
+        .filter(|s| s.group == user.group)
+        .collect();
+    let Some((subject, rest)) = homework::find(&subjects, &words) else {
+        let word = words.first().ok_or(Error::Usage(chat_id, "setroom"))?;
+        return Err(Error::InvalidSubject(chat_id, word.to_string()));
+    };
+    let room = rest.join(" ");
+    if room.is_empty() {
+        return Err(Error::Usage(chat_id, "setroom"));
+    }
+    if room.chars().count() > ROOM_LENGTH {
+        return Err(Error::InvalidRoom(chat_id, room));
+    }
+
+    store
+        .set_room(institution.id, subject.id, &room)
+        .await
+        .map_err(failed(chat_id, "set room"))?;
+    Ok(language.tr(Msg::RoomSet {
+        title: &subject.title,
+        room: &room,
+    }))
+}
+
+/// `/addlink`: a meeting link for the class, named after it unless a name is given.
+async fn add_link<S: ScheduleStore>(
+    store: &S,
+    clock: &dyn Clock,
+    chat_id: ChatId,
+    sender: ChatId,
+    language: Language,
+    args: String,
+) -> Result<String, Error> {
+    let (user, institution) = editable(store, chat_id, sender).await?;
+    let words: Vec<&str> = args.split_whitespace().collect();
+    let (date, slot, rest) = class(clock, chat_id, &institution, &words, "addlink")?;
+    let [link, name @ ..] = rest else {
+        return Err(Error::Usage(chat_id, "addlink"));
+    };
+    let link = url::Url::parse(link)
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .ok_or(Error::InvalidLink(chat_id, link.to_string()))?;
+
+    let subject = match &timetable::subjects(store, chat_id, &institution, &user.group, date, slot)
+        .await?[..]
+    {
+        [] => return Ok(language.tr(Msg::NoSubject)),
+        [subject] => subject.clone(),
+        _ => return Ok(language.tr(Msg::SeveralClasses)),
+    };
+    let id = store
+        .next_meeting_id(institution.id)
+        .await
+        .map_err(failed(chat_id, "get next meeting id"))?;
+    let meeting = Meeting {
+        id,
+        name: match name {
+            [] => subject.title.clone(),
+            name => name.join(" "),
+        },
+        group: subject.group.clone(),
+        link: link.into(),
+    };
+    store
+        .add_meeting(institution.id, &meeting)
+        .await
+        .map_err(failed(chat_id, "add meeting"))?;
+    store
+        .assign(
+            institution.id,
+            &Assigned {
+                meeting_id: id,
+                subject_id: subject.id,
+            },
+        )
+        .await
+        .map_err(failed(chat_id, "assign meeting"))?;
+    Ok(language.tr(Msg::LinkAdded(&subject.title)))
+}
+
+/// Who a role is handed to or taken from and over what, as long as `sender`
+/// owns that part of the institution of `chat_id`.
+async fn scope<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+    sender: ChatId,
+    id: &str,
+    group: Option<&str>,
+    command: &'static str,
+) -> Result<(ChatId, Institution, Option<Group>), Error> {
+    let id = id
+        .parse()
+        .map(ChatId)
+        .map_err(|_| Error::Usage(chat_id, command))?;
+    let (_, institution) = user(store, chat_id).await?;
+    let group = match group {
+        Some(name) => Some(
+            store
+                .get_groups(institution.id)
+                .await
+                .map_err(failed(chat_id, "get groups"))?
+                .into_iter()
+                .find(|g| g.as_str() == name)
+                .ok_or(Error::InvalidGroup(chat_id, name.into()))?,
+        ),
+        None => None,
+    };
+
+    let grants = store
+        .get_grants(&sender)
+        .await
+        .map_err(failed(chat_id, "get grants"))?;
+    if !owns(&grants, institution.id, group.as_ref()) {
+        return Err(Error::NotOwner(chat_id));
+    }
+    Ok((id, institution, group))
+}
+
+/// `/grant`: a role over the institution, or over one of its groups.
+async fn grant<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+    sender: ChatId,
+    language: Language,
+    args: String,
+) -> Result<String, Error> {
+    let (id, role, group) = match args.split_whitespace().collect::<Vec<_>>()[..] {
+        [id, role] => (id, role, None),
+        [id, role, group] => (id, role, Some(group)),
+        _ => return Err(Error::Usage(chat_id, "grant")),
+    };
+    let role = Role::try_from(role).map_err(|_| Error::Usage(chat_id, "grant"))?;
+    let (id, institution, group) = scope(store, chat_id, sender, id, group, "grant").await?;
+
+    let scope = group
+        .as_ref()
+        .map_or(institution.name.clone(), |g| g.as_str().to_string());
+    store
+        .add_grant(
+            &id,
+            &Grant {
+                institution: institution.id,
+                group,
+                role,
+            },
+        )
+        .await
+        .map_err(failed(chat_id, "add grant"))?;
+    Ok(language.tr(Msg::Granted {
+        user: &id.to_string(),
+        role: &String::from(&role),
+        scope: &scope,
+    }))
+}
+
+/// `/revoke`: takes away the role over the institution, or over one of its groups.
+async fn revoke<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+    sender: ChatId,
+    language: Language,
+    args: String,
+) -> Result<String, Error> {
+    let (id, group) = match args.split_whitespace().collect::<Vec<_>>()[..] {
+        [id] => (id, None),
+        [id, group] => (id, Some(group)),
+        _ => return Err(Error::Usage(chat_id, "revoke")),
+    };
+    let (id, institution, group) = scope(store, chat_id, sender, id, group, "revoke").await?;
+
+    store
+        .remove_grant(&id, institution.id, group.as_ref())
+        .await
+        .map_err(failed(chat_id, "remove grant"))?;
+    Ok(language.tr(Msg::Revoked {
+        user: &id.to_string(),
+        scope: group.as_ref().map_or(&institution.name, |g| g.as_str()),
+    }))
+}
+
+/// `/roles`: the id of `sender`, which owners grant roles to, and the roles they have.
+async fn roles<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+    sender: ChatId,
+    language: Language,
+) -> Result<String, Error> {
+    let grants = store
+        .get_grants(&sender)
+        .await
+        .map_err(failed(chat_id, "get grants"))?;
+    let mut message = language.tr(Msg::YourId(&sender.to_string()));
+    if grants.is_empty() {
+        message.push('\n');
+        message.push_str(&language.tr(Msg::NoRoles));
+    }
+    for grant in grants {
+        let institution = store
+            .get_institution(grant.institution)
+            .await
+            .map_err(failed(chat_id, "get grant institution"))?;
+        let role = String::from(&grant.role);
+        match grant.group {
+            Some(group) => message.push_str(&format!(
+                "\n{} — {}, {}",
+                role,
+                group.as_str(),
+                institution.name
+            )),
+            None => message.push_str(&format!("\n{} — {}", role, institution.name)),
+        }
+    }
+    Ok(message)
+}
+
+/// The group whose timetable an entry of the audit log changes, if it is one
+/// of `subjects` or `meetings` or is named by the entry.
+fn changed(entry: &Entry, subjects: &[Subject], meetings: &[Meeting]) -> Option<Group> {
+    let id = |prefix: &str| entry.target.strip_prefix(prefix)?.parse::<i64>().ok();
+    match entry.action.as_str() {
+        "add_group" => Group::try_from(entry.target.as_str()).ok(),
+        "add_change" => Group::try_from(entry.target.split(' ').next()?).ok(),
+        _ => match (id("subject "), id("meeting ")) {
+            (Some(id), _) => subjects
+                .iter()
+                .find(|s| s.id == id)
+                .map(|s| s.group.clone()),
+            (_, Some(id)) => meetings
+                .iter()
+                .find(|m| m.id == id)
+                .map(|m| m.group.clone()),
+            _ => None,
+        },
+    }
+}
+
+/// `/history`: the latest changes to the timetable of the groups `sender`
+/// may edit, with times in the time zone of the institution.
+async fn history<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+    sender: ChatId,
+    language: Language,
+) -> Result<String, Error> {
+    let (_, institution) = editable(store, chat_id, sender).await?;
+    let grants = store
+        .get_grants(&sender)
+        .await
+        .map_err(failed(chat_id, "get grants"))?;
+    let covered = |group: &Group| grants.iter().any(|g| g.covers(institution.id, group));
+    let subjects = store
+        .get_all_subjects(institution.id)
+        .await
+        .map_err(failed(chat_id, "get subjects"))?;
+    let mut meetings = vec![];
+    for subject in subjects.iter().filter(|s| covered(&s.group)) {
+        meetings.extend(
+            store
+                .get_meetings(institution.id, subject.id)
+                .await
+                .map_err(failed(chat_id, "get meetings"))?,
+        );
+    }
+    let entries = store
+        .get_audit(Some(institution.id), audit::TIMETABLE, HISTORY_WINDOW)
+        .await
+        .map_err(failed(chat_id, "get audit log"))?;
+    let entries: Vec<&Entry> = entries
+        .iter()
+        .filter(|e| changed(e, &subjects, &meetings).is_some_and(|g| covered(&g)))
+        .collect();
+    if entries.is_empty() {
+        return Ok(language.tr(Msg::NoHistory));
+    }
+
+    let skip = entries.len().saturating_sub(HISTORY_LENGTH);
+    let lines: Vec<String> = entries[skip..]
+        .iter()
+        .map(|e| {
+            format!(
+                "{} {} {} {}: {} → {}",
+                e.at.with_timezone(&institution.timezone)
+                    .format("%d.%m %H:%M"),
+                e.actor,
+                e.action,
+                e.target,
+                e.before.as_deref().unwrap_or("—"),
+                e.after.as_deref().unwrap_or("—"),
+            )
+        })
+        .collect();
+    Ok(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::Actor;
+    use crate::clock::TestClock;
+    use crate::data::{Grade, Repeat, Schedule};
+    use crate::store::MemoryStore;
+    use chrono::{TimeZone, Utc};
+    use std::sync::Arc;
+
+    const CHAT: ChatId = ChatId(-100);
+    const HEADMAN: ChatId = ChatId(7);
+    const STUDENT: ChatId = ChatId(8);
+
+    fn k25() -> Group {
+        Group::try_from("K-25").unwrap()
+    }
+
+    /// Monday, 9 Oct 2023, 09:00 in Kyiv.
+    fn clock() -> TestClock {
+        TestClock::new(Utc.with_ymd_and_hms(2023, 10, 9, 6, 0, 0).unwrap())
+    }
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2023, 10, day).unwrap()
+    }
+
+    /// K-25 with one class on Mondays in the first slot, and a headman
+    /// who may edit it.
+    async fn store() -> MemoryStore {
+        let store = MemoryStore::new();
+        store.add_group(0, &k25()).await.unwrap();
+        store
+            .add_subject(
+                0,
+                &Subject {
+                    id: 0,
+                    title: "Algebra".into(),
+                    group: k25(),
+                    optional: false,
+                },
+            )
+            .await
+            .unwrap();
+        store
+            .add_schedule(
+                0,
+                &Schedule {
+                    subject_id: 0,
+                    day: Day::Mon,
+                    repeat: Repeat::Both,
+                    slot: Slot::I,
+                },
+            )
+            .await
+            .unwrap();
+        let user = User {
+            institution: 0,
+            group: k25(),
+        };
+        store.add_user(&CHAT, &user).await.unwrap();
+        store
+            .add_grant(
+                &HEADMAN,
+                &Grant {
+                    institution: 0,
+                    group: Some(k25()),
+                    role: Role::Editor,
+                },
+            )
+            .await
+            .unwrap();
+        store
+    }
+
+    async fn run(store: &MemoryStore, sender: ChatId, cmd: Command) -> Result<String, Error> {
+        let reply = execute(store, &clock(), CHAT, sender, Language::En, cmd).await?;
+        match reply {
+            Some(Reply::Text(text)) => Ok(text),
+            other => panic!("Unexpected reply {:?}", other),
+        }
+    }
+
+    async fn subjects(store: &MemoryStore, day: u32, slot: Slot) -> Vec<Subject> {
+        let institution = store.get_institution(0).await.unwrap();
+        timetable::subjects(store, CHAT, &institution, &k25(), date(day), slot)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn cancel_needs_a_role() {
+        let store = store().await;
+        let cmd = || Command::Cancel("16.10 1".into());
+
+        let result = run(&store, STUDENT, cmd()).await;
+        assert!(matches!(result, Err(Error::NotEditor(CHAT))));
+
+        let reply = run(&store, HEADMAN, cmd()).await.unwrap();
+        assert_eq!(reply, "Cancelled Algebra on Mon 16.10, slot 1.");
+        assert!(subjects(&store, 16, Slot::I).await.is_empty());
+        assert_eq!(subjects(&store, 23, Slot::I).await.len(), 1);
+
+        let reply = run(&store, HEADMAN, cmd()).await.unwrap();
+        assert_eq!(reply, "No such subject is found.");
+    }
+
+    #[tokio::test]
+    async fn class_is_validated() {
+        let store = store().await;
+        let run = |args: &str| run(&store, HEADMAN, Command::Cancel(args.into()));
+
+        assert!(matches!(
+            run("32.10 1").await,
+            Err(Error::InvalidDate(CHAT, _))
+        ));
+        assert!(matches!(
+            run("16.10 9").await,
+            Err(Error::InvalidSlot(CHAT, _))
+        ));
+        assert!(matches!(
+            run("sat 1").await,
+            Err(Error::InvalidWeekday(CHAT, _))
+        ));
+        assert!(matches!(
+            run("16.10").await,
+            Err(Error::Usage(CHAT, "cancel"))
+        ));
+        assert!(run("next mon 1").await.unwrap().starts_with("Cancelled"));
+    }
+
+    #[tokio::test]
+    async fn move_to_a_free_slot() {
+        let store = store().await;
+        let run = |args: &str| run(&store, HEADMAN, Command::Move(args.into()));
+
+        let reply = run("16.10 1 tue 2").await.unwrap();
+        assert_eq!(reply, "Moved Algebra to Tue 10.10, slot 2.");
+        assert!(subjects(&store, 16, Slot::I).await.is_empty());
+        assert_eq!(subjects(&store, 10, Slot::II).await[0].title, "Algebra");
+
+        let reply = run("23.10 1 10.10 2").await.unwrap();
+        assert_eq!(reply, "Slot is taken by Algebra.");
+    }
+
+    #[tokio::test]
+    async fn room_and_link() {
+        let store = store().await;
+        let run = |cmd| run(&store, HEADMAN, cmd);
+
+        let reply = run(Command::Setroom("alg 204 main".into())).await;
+        assert_eq!(reply.unwrap(), "Algebra is now in 204 main.");
+        assert_eq!(
+            store.get_room(0, 0).await.unwrap(),
+            Some("204 main".to_string())
+        );
+        let result = run(Command::Setroom(format!("Algebra {}", "x".repeat(33)))).await;
+        assert!(matches!(result, Err(Error::InvalidRoom(CHAT, _))));
+        let result = run(Command::Setroom("Algebra".into())).await;
+        assert!(matches!(result, Err(Error::Usage(CHAT, "setroom"))));
+        let result = run(Command::Setroom("Logic 204".into())).await;
+        assert!(matches!(result, Err(Error::InvalidSubject(CHAT, _))));
+
+        let result = run(Command::Addlink("16.10 1 ftp://fake-link.lol".into())).await;
+        assert!(matches!(result, Err(Error::InvalidLink(CHAT, _))));
+        let reply = run(Command::Addlink(
+            "16.10 1 https://fake-link.lol Zoom".into(),
+        ))
+        .await;
+        assert_eq!(reply.unwrap(), "Added the link to Algebra.");
+        let meetings = store.get_meetings(0, 0).await.unwrap();
+        assert_eq!(meetings[0].name, "Zoom");
+        assert_eq!(meetings[0].link, "https://fake-link.lol/");
+    }
+
+    #[tokio::test]
+    async fn history_of_changes() {
+        let store = store().await.with_clock(Arc::new(clock()));
+        let result = run(&store, STUDENT, Command::History).await;
+        assert!(matches!(result, Err(Error::NotEditor(CHAT))));
+
+        let cancel = run(&store, HEADMAN, Command::Cancel("16.10 1".into()));
+        audit::acting(Actor::chat(HEADMAN), cancel).await.unwrap();
+        let reply = run(&store, HEADMAN, Command::History).await.unwrap();
+        assert_eq!(
+            reply.lines().last().unwrap(),
+            "09.10 09:00 7 add_change K-25 2023-10-16 slot 1: — → cancelled"
+        );
+    }
+
+    #[tokio::test]
+    async fn history_leaves_out_personal_data_and_other_groups() {
+        let store = store().await;
+        store
+            .set_note(&STUDENT, 0, 0, Some("ask about the retake"))
+            .await
+            .unwrap();
+        let grade = Grade {
+            id: 0,
+            subject_id: 0,
+            date: date(9),
+            points: 5,
+            comment: "quiz on matrices".into(),
+        };
+        store.add_grade(&STUDENT, 0, &grade).await.unwrap();
+        let k26 = Group::try_from("K-26").unwrap();
+        store.add_group(0, &k26).await.unwrap();
+        let change = Change {
+            group: k26,
+            date: date(16),
+            slot: Slot::I,
+            subject_id: None,
+        };
+        store.add_change(0, &change).await.unwrap();
+        run(&store, HEADMAN, Command::Setroom("Algebra 204".into()))
+            .await
+            .unwrap();
+
+        let reply = run(&store, HEADMAN, Command::History).await.unwrap();
+        assert!(!reply.contains("retake"));
+        assert!(!reply.contains("matrices"));
+        assert!(!reply.contains("K-26"));
+        let actions: Vec<&str> = reply
+            .lines()
+            .map(|l| l.split(' ').nth(3).unwrap())
+            .collect();
+        assert_eq!(
+            actions,
+            ["add_group", "add_subject", "add_schedule", "set_room"]
+        );
+    }
+
+    #[tokio::test]
+    async fn owners_hand_out_roles() {
+        let store = store().await;
+        let result = run(&store, HEADMAN, Command::Grant("8 editor K-25".into())).await;
+        assert!(matches!(result, Err(Error::NotOwner(CHAT))));
+
+        let owner = Grant {
+            institution: 0,
+            group: None,
+            role: Role::Owner,
+        };
+        store.add_grant(&HEADMAN, &owner).await.unwrap();
+        let reply = run(&store, HEADMAN, Command::Grant("8 editor K-25".into())).await;
+        assert_eq!(reply.unwrap(), "8 is now an editor of K-25.");
+        let reply = run(&store, STUDENT, Command::Roles).await.unwrap();
+        assert_eq!(reply, "Your id: 8\neditor — K-25, Default");
+
+        let result = run(&store, HEADMAN, Command::Grant("8 editor K-99".into())).await;
+        assert!(matches!(result, Err(Error::InvalidGroup(CHAT, _))));
+        let reply = run(&store, HEADMAN, Command::Revoke("8 K-25".into())).await;
+        assert_eq!(reply.unwrap(), "8 no longer has a role over K-25.");
+        assert!(store.get_grants(&STUDENT).await.unwrap().is_empty());
+    }
+}
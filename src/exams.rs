@@ -0,0 +1,305 @@
+use crate::announce::{self, Outbox};
+use crate::bot::{failed, language, user, Error, Reply};
+use crate::clock::Clock;
+use crate::data::{Exam, Institution, Subject};
+use crate::i18n::{Language, Msg};
+use crate::store::ScheduleStore;
+use chrono::{Duration, NaiveTime};
+use std::sync::Arc;
+use teloxide::types::ChatId;
+
+/// Days before an exam when countdown reminders go out, the last one last.
+const COUNTDOWN: [i64; 3] = [7, 3, 1];
+
+/// Time of the day when countdown reminders go out, where the institution is.
+const REMIND_AT: NaiveTime = match NaiveTime::from_hms_opt(9, 0, 0) {
+    Some(time) => time,
+    None => panic!("Invalid reminder time"),
+};
+
+/// How often exams are checked for countdown reminders to send.
+const INTERVAL: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
+/// Exams of the group of `chat_id` that haven't started yet, by start, along
+/// with their subjects and the institution.
+async fn upcoming<S: ScheduleStore>(
+    store: &S,
+    clock: &dyn Clock,
+    chat_id: ChatId,
+) -> Result<(Vec<(Exam, Subject)>, Institution), Error> {
+    let (user, institution) = user(store, chat_id).await?;
+    let now = clock
+        .now()
+        .with_timezone(&institution.timezone)
+        .naive_local();
+    let subjects = store
+        .get_all_subjects(institution.id)
+        .await
+        .map_err(failed(chat_id, "get subjects"))?;
+    let exams = store
+        .get_exams(institution.id)
+        .await
+        .map_err(failed(chat_id, "get exams"))?
+        .into_iter()
+        .filter(|e| e.starts > now)
+        .filter_map(|e| {
+            let subject = subjects
+                .iter()
+                .find(|s| s.id == e.subject_id && s.group == user.group)?;
+            Some((e, subject.clone()))
+        })
+        .collect();
+    Ok((exams, institution))
+}
+
+/// `Exam Algebra — Mon 15.01 09:00, 204, O. Petrenko`.
+pub fn line(language: Language, exam: &Exam, subject: &Subject) -> String {
+    let mut line = format!(
+        "{} {} — {} {}",
+        language.kind(exam.kind),
+        subject.title,
+        language.day(exam.starts.date(), "%d.%m"),
+        exam.starts.format("%H:%M")
+    );
+    for detail in [&exam.room, &exam.lecturer] {
+        if !detail.is_empty() {
+            line.push_str(&format!(", {}", detail));
+        }
+    }
+    line
+}
+
+/// `/exams`: every exam of the group still to come.
+pub async fn list<S: ScheduleStore>(
+    store: &S,
+    clock: &dyn Clock,
+    chat_id: ChatId,
+    language: Language,
+) -> Result<Reply, Error> {
+    let (exams, _) = upcoming(store, clock, chat_id).await?;
+    if exams.is_empty() {
+        return Ok(Reply::Text(language.tr(Msg::NoExams)));
+    }
+    let mut message = language.tr(Msg::ExamList);
+    for (exam, subject) in &exams {
+        message.push('\n');
+        message.push_str(&line(language, exam, subject));
+    }
+    Ok(Reply::Text(message))
+}
+
+/// `/next_exam`: the first exam of the group to come, and how many days
+/// are left until it.
+pub async fn next<S: ScheduleStore>(
+    store: &S,
+    clock: &dyn Clock,
+    chat_id: ChatId,
+    language: Language,
+) -> Result<Reply, Error> {
+    let (exams, institution) = upcoming(store, clock, chat_id).await?;
+    let Some((exam, subject)) = exams.first() else {
+        return Ok(Reply::Text(language.tr(Msg::NoExams)));
+    };
+    let today = clock
+        .now()
+        .with_timezone(&institution.timezone)
+        .date_naive();
+    let days = (exam.starts.date() - today).num_days();
+    Ok(Reply::Text(language.tr(Msg::NextExam {
+        exam: &line(language, exam, subject),
+        days: &days.to_string(),
+    })))
+}
+
+/// Countdown reminders of exams to come, for every chat of the group of the
+/// subject, each in the language of the chat. They go out from
+/// [`REMIND_AT`] on the days in [`COUNTDOWN`] before the exam, only the
+/// latest one when several are due at once.
+pub async fn reminders<S: ScheduleStore>(
+    store: &S,
+    clock: &dyn Clock,
+) -> sqlx::Result<Vec<(ChatId, String)>> {
+    let mut letters = vec![];
+    for (institution, exam, sent) in store.get_countdowns().await? {
+        let institution = store.get_institution(institution).await?;
+        let now = clock
+            .now()
+            .with_timezone(&institution.timezone)
+            .naive_local();
+        if now >= exam.starts {
+            // Too late to count down to
+            store.set_countdown(exam.id, 0).await?;
+            continue;
+        }
+        let due = COUNTDOWN
+            .into_iter()
+            .filter(|days| sent.is_none_or(|sent| *days < sent))
+            .filter(|days| (exam.starts.date() - Duration::days(*days)).and_time(REMIND_AT) <= now)
+            .min();
+        let Some(due) = due else {
+            continue;
+        };
+
+        let subject = store.get_subject(institution.id, exam.subject_id).await?;
+        let days = (exam.starts.date() - now.date()).num_days().to_string();
+        for chat in store.get_chats(institution.id, &subject.group).await? {
+            let language = language(store, chat, None).await;
+            let text = language.tr(Msg::ExamCountdown {
+                exam: &line(language, &exam, &subject),
+                days: &days,
+            });
+            letters.push((chat, text));
+        }
+        let last = COUNTDOWN[COUNTDOWN.len() - 1];
+        store
+            .set_countdown(exam.id, if due == last { 0 } else { due })
+            .await?;
+    }
+    Ok(letters)
+}
+
+/// Sends exam countdown reminders through `outbox` for as long as the bot runs.
+pub async fn watch<S: ScheduleStore>(store: Arc<S>, clock: Arc<dyn Clock>, outbox: Outbox) {
+    let mut interval = tokio::time::interval(INTERVAL);
+    loop {
+        interval.tick().await;
+        let letters = match reminders(store.as_ref(), clock.as_ref()).await {
+            Ok(letters) => letters,
+            Err(err) => {
+                log::error!("Failed to gather exam reminders: {:?}", err);
+                continue;
+            }
+        };
+        if letters.is_empty() {
+            continue;
+        }
+        let outcomes = outbox.deliver(letters).await;
+        if let Err(err) = announce::clean_up(store.as_ref(), &outcomes).await {
+            log::error!("Failed to forget unreachable chats: {:?}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::TestClock;
+    use crate::data::{ExamKind, Group, User};
+    use crate::import::Import;
+    use crate::store::MemoryStore;
+    use chrono::{NaiveDate, TimeZone, Utc};
+
+    const ALICE: ChatId = ChatId(7);
+    const BOB: ChatId = ChatId(8);
+
+    fn group(name: &str) -> Group {
+        Group::try_from(name).unwrap()
+    }
+
+    fn exam(subject_id: i64, kind: ExamKind, day: u32, hour: u32, room: &str) -> Exam {
+        Exam {
+            id: 0,
+            subject_id,
+            kind,
+            starts: NaiveDate::from_ymd_opt(2024, 1, day)
+                .unwrap()
+                .and_hms_opt(hour, 0, 0)
+                .unwrap(),
+            room: room.into(),
+            lecturer: String::new(),
+        }
+    }
+
+    /// Algebra and Logic of K-25 and Drawing of K-26 with exams in January
+    /// 2024, Alice in K-25 and Bob in K-26, on Wednesday, 10 Jan 2024, 10:00.
+    async fn setup() -> (MemoryStore, TestClock) {
+        let store = MemoryStore::new();
+        let subjects = [
+            (0, "Algebra", "K-25"),
+            (1, "Logic", "K-25"),
+            (2, "Drawing", "K-26"),
+        ];
+        let import = Import {
+            subjects: subjects
+                .into_iter()
+                .map(|(id, title, name)| Subject {
+                    id,
+                    title: title.into(),
+                    group: group(name),
+                    optional: false,
+                })
+                .collect(),
+            schedule: vec![],
+            exams: vec![
+                exam(0, ExamKind::Consultation, 9, 12, ""),
+                exam(0, ExamKind::Exam, 15, 9, "204"),
+                exam(1, ExamKind::Credit, 12, 13, ""),
+                exam(2, ExamKind::Exam, 11, 9, "101"),
+            ],
+        };
+        store.import(0, &import).await.unwrap();
+        for (chat, name) in [(ALICE, "K-25"), (BOB, "K-26")] {
+            let user = User {
+                institution: 0,
+                group: group(name),
+            };
+            store.add_user(&chat, &user).await.unwrap();
+        }
+        let clock = TestClock::new(Utc.with_ymd_and_hms(2024, 1, 10, 8, 0, 0).unwrap());
+        (store, clock)
+    }
+
+    fn text(reply: Reply) -> String {
+        match reply {
+            Reply::Text(text) => text,
+            other => panic!("Unexpected reply {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn exams_of_the_group_to_come() {
+        let (store, clock) = setup().await;
+        let reply = list(&store, &clock, ALICE, Language::En).await.unwrap();
+        assert_eq!(
+            text(reply),
+            "Exam session:\n\
+             Credit Logic — Fri 12.01 13:00\n\
+             Exam Algebra — Mon 15.01 09:00, 204"
+        );
+        let reply = next(&store, &clock, BOB, Language::Uk).await.unwrap();
+        assert_eq!(
+            text(reply),
+            "Найближче, днів залишилося: 1\nІспит Drawing — Чт 11.01 09:00, 101"
+        );
+
+        clock.advance(Duration::days(7));
+        let reply = next(&store, &clock, BOB, Language::En).await.unwrap();
+        assert_eq!(text(reply), "No exams are scheduled for your group.");
+    }
+
+    #[tokio::test]
+    async fn countdown_reminders() {
+        let (store, clock) = setup().await;
+        // A week before Algebra, 3 days before Logic and a day before Drawing
+        // all at once
+        let letters = reminders(&store, &clock).await.unwrap();
+        let chats: Vec<ChatId> = letters.iter().map(|(chat, _)| *chat).collect();
+        assert_eq!(chats, vec![BOB, ALICE, ALICE]);
+        assert_eq!(
+            letters[0].1,
+            "Reminder, days left: 1\nExam Drawing — Thu 11.01 09:00, 101"
+        );
+        assert!(reminders(&store, &clock).await.unwrap().is_empty());
+
+        // The morning a day before Logic
+        clock.advance(Duration::hours(23));
+        let letters = reminders(&store, &clock).await.unwrap();
+        assert_eq!(letters.len(), 1);
+        assert!(letters[0].1.contains("Logic"));
+
+        // Well after the session, with the last reminder for Algebra missed
+        clock.advance(Duration::days(10));
+        assert!(reminders(&store, &clock).await.unwrap().is_empty());
+        assert!(store.get_countdowns().await.unwrap().is_empty());
+    }
+}
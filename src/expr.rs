@@ -0,0 +1,291 @@
+use crate::data::Slot;
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+
+/// A date the way students type it, relative to the day it is read on.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum When {
+    /// Days from today, e.g. `tomorrow`, `завтра` or `+2`.
+    Offset(i64),
+    /// The nearest such weekday from today on, e.g. `fri` or `пт`, or the one
+    /// of the following week with `next`.
+    Weekday { day: Weekday, next: bool },
+    /// Day and month of the current year, e.g. `17.10`.
+    DayMonth(u32, u32),
+    /// A full `dd.mm.yyyy` date.
+    Date(NaiveDate),
+}
+
+impl When {
+    /// The date this stands for as of `today`, if there is such a date.
+    pub fn resolve(self, today: NaiveDate) -> Option<NaiveDate> {
+        match self {
+            When::Offset(days) => {
+                Duration::try_days(days).and_then(|days| today.checked_add_signed(days))
+            }
+            When::Weekday { day, next: false } => {
+                let ahead =
+                    (day.num_days_from_monday() + 7 - today.weekday().num_days_from_monday()) % 7;
+                Some(today + Duration::days(ahead.into()))
+            }
+            When::Weekday { day, next: true } => {
+                let monday = today - Duration::days(today.weekday().num_days_from_monday().into());
+                Some(monday + Duration::days((7 + day.num_days_from_monday()).into()))
+            }
+            When::DayMonth(day, month) => NaiveDate::from_ymd_opt(today.year(), month, day),
+            When::Date(date) => Some(date),
+        }
+    }
+}
+
+/// Slot, date and the rest of the words of a command or an inline query,
+/// which may come in any order.
+#[derive(PartialEq, Debug, Default, Clone)]
+pub struct Expr {
+    pub slot: Option<Slot>,
+    pub date: Option<When>,
+    /// Words that are neither, e.g. a group name.
+    pub words: Vec<String>,
+}
+
+/// A word that looks like a slot or a date, but isn't a valid one.
+#[derive(PartialEq, Debug)]
+pub enum Invalid {
+    Slot(String),
+    Date(String),
+}
+
+/// Largest `+N`/`-N` offset in days, about ten years either way.
+const MAX_OFFSET: i64 = 3660;
+
+const OFFSETS: &[(&str, i64)] = &[
+    ("today", 0),
+    ("tomorrow", 1),
+    ("yesterday", -1),
+    ("сьогодні", 0),
+    ("завтра", 1),
+    ("післязавтра", 2),
+    ("вчора", -1),
+    ("учора", -1),
+    ("позавчора", -2),
+];
+
+const WEEKDAYS: &[(&str, Weekday)] = &[
+    ("mon", Weekday::Mon),
+    ("monday", Weekday::Mon),
+    ("tue", Weekday::Tue),
+    ("tuesday", Weekday::Tue),
+    ("wed", Weekday::Wed),
+    ("wednesday", Weekday::Wed),
+    ("thu", Weekday::Thu),
+    ("thursday", Weekday::Thu),
+    ("fri", Weekday::Fri),
+    ("friday", Weekday::Fri),
+    ("sat", Weekday::Sat),
+    ("saturday", Weekday::Sat),
+    ("sun", Weekday::Sun),
+    ("sunday", Weekday::Sun),
+    ("пн", Weekday::Mon),
+    ("пон", Weekday::Mon),
+    ("понеділок", Weekday::Mon),
+    ("понеділка", Weekday::Mon),
+    ("вт", Weekday::Tue),
+    ("вів", Weekday::Tue),
+    ("вівторок", Weekday::Tue),
+    ("вівторка", Weekday::Tue),
+    ("ср", Weekday::Wed),
+    ("сер", Weekday::Wed),
+    ("середа", Weekday::Wed),
+    ("середу", Weekday::Wed),
+    ("середи", Weekday::Wed),
+    ("чт", Weekday::Thu),
+    ("чет", Weekday::Thu),
+    ("четвер", Weekday::Thu),
+    ("четверга", Weekday::Thu),
+    ("пт", Weekday::Fri),
+    ("пят", Weekday::Fri),
+    ("п'ят", Weekday::Fri),
+    ("пятниця", Weekday::Fri),
+    ("п'ятниця", Weekday::Fri),
+    ("пятницю", Weekday::Fri),
+    ("п'ятницю", Weekday::Fri),
+    ("пятниці", Weekday::Fri),
+    ("п'ятниці", Weekday::Fri),
+    ("сб", Weekday::Sat),
+    ("субота", Weekday::Sat),
+    ("суботу", Weekday::Sat),
+    ("суботи", Weekday::Sat),
+    ("нд", Weekday::Sun),
+    ("неділя", Weekday::Sun),
+    ("неділю", Weekday::Sun),
+    ("неділі", Weekday::Sun),
+];
+
+/// Lowercase `word` with any apostrophe, which comes in several flavours
+/// depending on the keyboard, turned into `'`.
+fn normalize(word: &str) -> String {
+    word.to_lowercase().replace(['’', 'ʼ', '`'], "'")
+}
+
+/// Words that move the weekday after them to the following week.
+fn is_next(word: &str) -> bool {
+    word == "next" || word.starts_with("наступн")
+}
+
+fn weekday(word: &str) -> Option<Weekday> {
+    WEEKDAYS.iter().find(|(w, _)| *w == word).map(|(_, d)| *d)
+}
+
+fn date(word: &str) -> Result<When, Invalid> {
+    let invalid = || Invalid::Date(word.into());
+    let parts: Vec<&str> = word.split('.').collect();
+    let number = |s: &str| s.parse::<u32>().map_err(|_| invalid());
+    match parts[..] {
+        [day, month] => {
+            let (day, month) = (number(day)?, number(month)?);
+            // Any leap year will do, so that 29.02 passes
+            NaiveDate::from_ymd_opt(2024, month, day).ok_or_else(invalid)?;
+            Ok(When::DayMonth(day, month))
+        }
+        [_, _, _] => NaiveDate::parse_from_str(word, "%d.%m.%Y")
+            .map(When::Date)
+            .map_err(|_| invalid()),
+        _ => Err(invalid()),
+    }
+}
+
+impl TryFrom<&str> for Expr {
+    type Error = Invalid;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let mut expr = Expr::default();
+        let mut words = value.split_whitespace();
+
+        while let Some(word) = words.next() {
+            let lower = normalize(word);
+
+            let when = if let Some((_, days)) = OFFSETS.iter().find(|(w, _)| *w == lower) {
+                When::Offset(*days)
+            } else if let Some(day) = weekday(&lower) {
+                When::Weekday { day, next: false }
+            } else if is_next(&lower) {
+                let Some(following) = words.next() else {
+                    return Err(Invalid::Date(word.into()));
+                };
+                match weekday(&normalize(following)) {
+                    Some(day) => When::Weekday { day, next: true },
+                    None => return Err(Invalid::Date(format!("{} {}", word, following))),
+                }
+            } else if let Some(days) = lower.strip_prefix(['+', '-']) {
+                let days: i64 = days
+                    .parse()
+                    .ok()
+                    .filter(|days| *days <= MAX_OFFSET)
+                    .ok_or_else(|| Invalid::Date(word.into()))?;
+                When::Offset(if lower.starts_with('-') { -days } else { days })
+            } else if lower.contains('.') && lower.starts_with(|c: char| c.is_ascii_digit()) {
+                date(&lower)?
+            } else if lower == "_" {
+                continue;
+            } else if let Ok(slot) = Slot::try_from(word) {
+                if expr.slot.replace(slot).is_some() {
+                    return Err(Invalid::Slot(word.into()));
+                }
+                continue;
+            } else if lower.chars().all(|c| c.is_ascii_digit()) {
+                return Err(Invalid::Slot(word.into()));
+            } else {
+                expr.words.push(word.into());
+                continue;
+            };
+
+            if expr.date.replace(when).is_some() {
+                return Err(Invalid::Date(word.into()));
+            }
+        }
+        Ok(expr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(day: u32, month: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2023, month, day).unwrap()
+    }
+
+    fn when(value: &str) -> When {
+        Expr::try_from(value).unwrap().date.unwrap()
+    }
+
+    #[test]
+    fn relative_dates() {
+        // Tuesday
+        let today = date(10, 10);
+        let cases = [
+            ("today", date(10, 10)),
+            ("Tomorrow", date(11, 10)),
+            ("завтра", date(11, 10)),
+            ("післязавтра", date(12, 10)),
+            ("вчора", date(9, 10)),
+            ("+2", date(12, 10)),
+            ("-10", date(30, 9)),
+            ("tue", date(10, 10)),
+            ("mon", date(16, 10)),
+            ("пт", date(13, 10)),
+            ("П’ятницю", date(13, 10)),
+            ("next fri", date(20, 10)),
+            ("наступного понеділка", date(16, 10)),
+            ("наступний вт", date(17, 10)),
+            ("17.10", date(17, 10)),
+            ("01.11.2023", date(1, 11)),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(when(value).resolve(today), Some(expected), "{}", value);
+        }
+        assert_eq!(When::Offset(i64::MAX).resolve(today), None);
+        assert_eq!(When::Offset(-200000000000000).resolve(today), None);
+    }
+
+    #[test]
+    fn any_order() {
+        let expected = Expr {
+            slot: Some(Slot::II),
+            date: Some(When::Offset(1)),
+            words: vec!["K-25".into()],
+        };
+        for value in ["2 tomorrow K-25", "K-25 завтра 2", "tomorrow K-25 II"] {
+            assert_eq!(Expr::try_from(value), Ok(expected.clone()), "{}", value);
+        }
+        assert_eq!(
+            Expr::try_from("_ 17.10"),
+            Ok(Expr {
+                slot: None,
+                date: Some(When::DayMonth(17, 10)),
+                words: vec![],
+            })
+        );
+        assert_eq!(Expr::try_from(""), Ok(Expr::default()));
+    }
+
+    #[test]
+    fn invalid_words() {
+        let cases = [
+            ("5", Invalid::Slot("5".into())),
+            ("1 2", Invalid::Slot("2".into())),
+            ("32.10.2023", Invalid::Date("32.10.2023".into())),
+            ("31.02", Invalid::Date("31.02".into())),
+            ("1.2.3.4", Invalid::Date("1.2.3.4".into())),
+            ("+x", Invalid::Date("+x".into())),
+            ("+3661", Invalid::Date("+3661".into())),
+            ("+200000000000000", Invalid::Date("+200000000000000".into())),
+            ("-95005491", Invalid::Date("-95005491".into())),
+            ("next K-25", Invalid::Date("next K-25".into())),
+            ("next", Invalid::Date("next".into())),
+            ("mon tomorrow", Invalid::Date("tomorrow".into())),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(Expr::try_from(value), Err(expected), "{}", value);
+        }
+    }
+}
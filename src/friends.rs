@@ -0,0 +1,301 @@
+use crate::bot::{self, failed, Error, Reply};
+use crate::i18n::{Language, Msg};
+use crate::store::ScheduleStore;
+use teloxide::types::{ChatId, InlineKeyboardButton, InlineKeyboardMarkup};
+
+/// Callback data prefix of the buttons under a friend request.
+pub const PREFIX: &str = "fr:";
+
+/// A message for another chat than the one answered, e.g. a friend request.
+pub type Letter = (ChatId, Reply);
+
+/// Lowercase username in `word`, e.g. `bob_k` of `@Bob_K`.
+pub(crate) fn username(word: &str) -> Option<String> {
+    let name = word.strip_prefix('@')?;
+    let valid = !name.is_empty()
+        && name.len() <= 32
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    valid.then(|| name.to_ascii_lowercase())
+}
+
+/// Keeps the username of the user of private chat `chat_id` up to date, so
+/// that friends can find them by it. Users without a group are left out.
+pub async fn remember<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+    username: Option<&str>,
+) -> sqlx::Result<()> {
+    let username = username.map(str::to_ascii_lowercase);
+    match store.get_username(&chat_id).await {
+        Ok(known) if known == username => Ok(()),
+        Ok(_) => store.set_username(&chat_id, username.as_deref()).await,
+        Err(sqlx::Error::RowNotFound) => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+/// `@bob`, or the id of a chat without a username.
+async fn name<S: ScheduleStore>(store: &S, chat_id: ChatId) -> String {
+    match store.get_username(&chat_id).await {
+        Ok(Some(username)) => format!("@{}", username),
+        _ => chat_id.to_string(),
+    }
+}
+
+/// Whether `a` and `b` both agreed to share their free time with each other.
+pub(crate) async fn mutual<S: ScheduleStore>(
+    store: &S,
+    a: ChatId,
+    b: ChatId,
+) -> sqlx::Result<bool> {
+    Ok(store.get_friends(&a).await?.contains(&b) && store.get_friends(&b).await?.contains(&a))
+}
+
+/// `/friend`: the friends of `sender`; `/friend @bob` asks Bob to share free
+/// time, and `/friend - @bob` stops sharing it both ways.
+///
+/// `sender_name` is how Bob is told who asks. The request to Bob comes back
+/// along with the reply, for the caller to send.
+pub async fn execute<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+    sender: ChatId,
+    sender_name: &str,
+    language: Language,
+    args: &str,
+) -> Result<(Reply, Option<Letter>), Error> {
+    let words: Vec<&str> = args.split_whitespace().collect();
+    let (remove, word) = match words[..] {
+        [] => {
+            return list(store, chat_id, sender, language)
+                .await
+                .map(|r| (r, None))
+        }
+        ["-", word] => (true, word),
+        [word] => (false, word),
+        _ => return Err(Error::Usage(chat_id, "friend")),
+    };
+    let username = username(word).ok_or(Error::Usage(chat_id, "friend"))?;
+    let friend = store
+        .find_username(&username)
+        .await
+        .map_err(failed(chat_id, "find username"))?
+        .ok_or_else(|| Error::UnknownUser(chat_id, format!("@{}", username)))?;
+    if friend == sender {
+        return Err(Error::InvalidArgument(chat_id, word.into()));
+    }
+    let shown = format!("@{}", username);
+
+    if remove {
+        store
+            .remove_friend(&sender, &friend)
+            .await
+            .map_err(failed(chat_id, "remove friend"))?;
+        return Ok((Reply::Text(language.tr(Msg::FriendRemoved(&shown))), None));
+    }
+
+    store
+        .add_friend(&sender, &friend)
+        .await
+        .map_err(failed(chat_id, "add friend"))?;
+    let asked = store
+        .get_friends(&friend)
+        .await
+        .map_err(failed(chat_id, "get friends"))?;
+    if asked.contains(&sender) {
+        return Ok((Reply::Text(language.tr(Msg::FriendAdded(&shown))), None));
+    }
+
+    let theirs = bot::language(store, friend, None).await;
+    let keyboard = InlineKeyboardMarkup::new([[
+        InlineKeyboardButton::callback(
+            theirs.tr(Msg::AcceptFriend),
+            format!("{}yes:{}", PREFIX, sender),
+        ),
+        InlineKeyboardButton::callback(
+            theirs.tr(Msg::DeclineFriend),
+            format!("{}no:{}", PREFIX, sender),
+        ),
+    ]]);
+    let request = Reply::Menu(theirs.tr(Msg::FriendRequest(sender_name)), keyboard);
+    let reply = Reply::Text(language.tr(Msg::FriendAsked(&shown)));
+    Ok((reply, Some((friend, request))))
+}
+
+/// Friends of `sender`, then those yet to agree.
+async fn list<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+    sender: ChatId,
+    language: Language,
+) -> Result<Reply, Error> {
+    let friends = store
+        .get_friends(&sender)
+        .await
+        .map_err(failed(chat_id, "get friends"))?;
+    if friends.is_empty() {
+        return Ok(Reply::Text(language.tr(Msg::NoFriends)));
+    }
+    let mut message = language.tr(Msg::FriendList);
+    for friend in friends {
+        let agreed = mutual(store, sender, friend)
+            .await
+            .map_err(failed(chat_id, "get friends"))?;
+        message.push('\n');
+        message.push_str(&name(store, friend).await);
+        if !agreed {
+            message.push_str(&format!(" ({})", language.tr(Msg::FriendPending)));
+        }
+    }
+    Ok(Reply::Text(message))
+}
+
+/// Handles a press on the buttons under a friend request by the user of
+/// private chat `chat_id`, telling whoever asked when they agree.
+pub async fn answer<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+    language: Language,
+    data: &str,
+) -> Result<(Reply, Option<Letter>), Error> {
+    let parsed = data.strip_prefix(PREFIX).and_then(|rest| {
+        let (answer, id) = rest.split_once(':')?;
+        Some((answer == "yes", ChatId(id.parse().ok()?)))
+    });
+    let Some((accepted, friend)) = parsed else {
+        return Ok((Reply::Text(language.tr(Msg::FriendRequestExpired)), None));
+    };
+    let asked = store
+        .get_friends(&friend)
+        .await
+        .map_err(failed(chat_id, "get friends"))?;
+    if !asked.contains(&chat_id) {
+        return Ok((Reply::Text(language.tr(Msg::FriendRequestExpired)), None));
+    }
+    let shown = name(store, friend).await;
+    if !accepted {
+        store
+            .remove_friend(&chat_id, &friend)
+            .await
+            .map_err(failed(chat_id, "remove friend"))?;
+        return Ok((Reply::Text(language.tr(Msg::FriendDeclined(&shown))), None));
+    }
+
+    store
+        .add_friend(&chat_id, &friend)
+        .await
+        .map_err(failed(chat_id, "add friend"))?;
+    let theirs = bot::language(store, friend, None).await;
+    let mine = name(store, chat_id).await;
+    let letter = Reply::Text(theirs.tr(Msg::FriendAdded(&mine)));
+    Ok((
+        Reply::Text(language.tr(Msg::FriendAdded(&shown))),
+        Some((friend, letter)),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Group, User};
+    use crate::store::MemoryStore;
+
+    const ALICE: ChatId = ChatId(7);
+    const BOB: ChatId = ChatId(8);
+
+    /// Alice and Bob in K-25, known by their usernames.
+    async fn store() -> MemoryStore {
+        let store = MemoryStore::new();
+        let user = User {
+            institution: 0,
+            group: Group::try_from("K-25").unwrap(),
+        };
+        for (chat, name) in [(ALICE, "Alice"), (BOB, "Bob_K")] {
+            store.add_user(&chat, &user).await.unwrap();
+            remember(&store, chat, Some(name)).await.unwrap();
+        }
+        store
+    }
+
+    fn text(reply: Reply) -> String {
+        match reply {
+            Reply::Text(text) | Reply::Menu(text, _) => text,
+            other => panic!("Unexpected reply {:?}", other),
+        }
+    }
+
+    #[test]
+    fn usernames() {
+        assert_eq!(username("@Bob_K"), Some("bob_k".into()));
+        assert_eq!(username("Bob"), None);
+        assert_eq!(username("@bob-k"), None);
+        assert_eq!(username("@"), None);
+    }
+
+    #[tokio::test]
+    async fn friends_agree_before_sharing() {
+        let store = store().await;
+        let friend =
+            |args: &'static str| execute(&store, ALICE, ALICE, "@alice", Language::En, args);
+
+        let (reply, letter) = friend("@BOB_K").await.unwrap();
+        assert_eq!(
+            text(reply),
+            "Asked @bob_k, you'll be friends once they agree."
+        );
+        let (chat, request) = letter.unwrap();
+        assert_eq!(chat, BOB);
+        let Reply::Menu(_, keyboard) = &request else {
+            panic!("Not a menu");
+        };
+        assert!(!mutual(&store, ALICE, BOB).await.unwrap());
+        assert_eq!(
+            text(friend("").await.unwrap().0),
+            "Your friends:\n@bob_k (waiting)"
+        );
+
+        // Only the request that was sent can be answered
+        let forged = format!("{}yes:{}", PREFIX, BOB);
+        let (reply, _) = answer(&store, ALICE, Language::En, &forged).await.unwrap();
+        assert_eq!(text(reply), "This request is no longer valid.");
+
+        let teloxide::types::InlineKeyboardButtonKind::CallbackData(data) =
+            &keyboard.inline_keyboard[0][0].kind
+        else {
+            panic!("Not a callback button");
+        };
+        let (reply, letter) = answer(&store, BOB, Language::En, data).await.unwrap();
+        assert_eq!(text(reply), "You and @alice are friends now.");
+        assert_eq!(letter.unwrap().0, ALICE);
+        assert!(mutual(&store, ALICE, BOB).await.unwrap());
+        assert_eq!(text(friend("").await.unwrap().0), "Your friends:\n@bob_k");
+
+        let (reply, _) = friend("- @bob_k").await.unwrap();
+        assert_eq!(text(reply), "You and @bob_k no longer share free time.");
+        assert!(store.get_friends(&BOB).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn friend_checks_arguments() {
+        let store = store().await;
+        let friend =
+            |args: &'static str| execute(&store, ALICE, ALICE, "@alice", Language::En, args);
+        assert!(matches!(
+            friend("@carol").await,
+            Err(Error::UnknownUser(_, _))
+        ));
+        assert!(matches!(
+            friend("@alice").await,
+            Err(Error::InvalidArgument(_, _))
+        ));
+        assert!(matches!(
+            friend("bob_k").await,
+            Err(Error::Usage(_, "friend"))
+        ));
+
+        // A username moves on to whoever takes it
+        remember(&store, ALICE, Some("bob_k")).await.unwrap();
+        assert_eq!(store.find_username("bob_k").await.unwrap(), Some(ALICE));
+        assert_eq!(store.get_username(&BOB).await.unwrap(), None);
+    }
+}
@@ -0,0 +1,78 @@
+use chrono::NaiveDate;
+use teloxide::utils::markdown as md;
+
+/// Points of a subject in a semester against what it is worth, along with
+/// the grades they add up from when they are to be listed.
+pub struct Total {
+    title: String,
+    points: i64,
+    max: i64,
+    grades: Vec<Grade>,
+}
+
+impl Total {
+    pub fn new(title: String, points: i64, max: i64, grades: Vec<Grade>) -> Total {
+        Total {
+            title,
+            points,
+            max,
+            grades,
+        }
+    }
+}
+
+impl std::fmt::Display for Total {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let book = emojis::get_by_shortcode("blue_book").unwrap();
+        write!(
+            f,
+            "{} {} {}",
+            book,
+            md::bold(&md::escape(&self.title)),
+            md::escape(&format!("{}/{}", self.points, self.max))
+        )?;
+        if self.max > 0 {
+            let percent = self.points * 100 / self.max;
+            write!(f, " · {}", md::escape(&format!("{}%", percent)))?;
+        }
+        for g in &self.grades {
+            write!(f, "\n{}", g)?;
+        }
+        Ok(())
+    }
+}
+
+/// Points logged for a subject, as the reader sees them.
+pub struct Grade {
+    id: i64,
+    date: NaiveDate,
+    points: i64,
+    comment: String,
+}
+
+impl Grade {
+    pub fn new(id: i64, date: NaiveDate, points: i64, comment: String) -> Grade {
+        Grade {
+            id,
+            date,
+            points,
+            comment,
+        }
+    }
+}
+
+impl std::fmt::Display for Grade {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            md::escape(&format!("#{}", self.id)),
+            md::escape(&self.date.format("%d.%m").to_string()),
+            md::bold(&md::escape(&format!("+{}", self.points)))
+        )?;
+        if !self.comment.is_empty() {
+            write!(f, " {}", md::escape(&self.comment))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,312 @@
+use crate::bot::{failed, user, Error, Reply};
+use crate::clock::Clock;
+use crate::data::{Grade, Subject};
+use crate::gradebook;
+use crate::homework;
+use crate::i18n::{Language, Msg};
+use crate::store::ScheduleStore;
+use chrono::NaiveDate;
+use teloxide::types::ChatId;
+use teloxide::utils::markdown as md;
+
+/// Longest comment on a grade, in characters.
+const COMMENT_LENGTH: usize = 100;
+
+/// `/grade`: the points `sender` logged this semester against what every
+/// subject is worth; `/grade <subject>` lists those of one subject,
+/// `/grade <subject> <points> [comment]` logs more and `/grade - <id>`
+/// removes them.
+pub async fn execute<S: ScheduleStore>(
+    store: &S,
+    clock: &dyn Clock,
+    chat_id: ChatId,
+    sender: ChatId,
+    language: Language,
+    args: &str,
+) -> Result<Reply, Error> {
+    if chat_id != sender {
+        return Err(Error::OnlyPrivate(chat_id));
+    }
+    let (user, institution) = user(store, chat_id).await?;
+    let subjects = store
+        .get_all_subjects(institution.id)
+        .await
+        .map_err(failed(chat_id, "get subjects"))?;
+    let semesters = store
+        .get_semesters(institution.id)
+        .await
+        .map_err(failed(chat_id, "get semesters"))?;
+    let today = clock
+        .now()
+        .with_timezone(&institution.timezone)
+        .date_naive();
+    let words: Vec<&str> = args.split_whitespace().collect();
+    let book = Book {
+        store,
+        chat_id,
+        sender,
+        language,
+        institution: institution.id,
+        subjects: &subjects,
+        semester: semesters.around(today),
+    };
+    if let ["-", id] = words[..] {
+        let id = id.parse().map_err(|_| Error::Usage(chat_id, "grade"))?;
+        return book.remove(id).await;
+    }
+    if words.is_empty() {
+        return book.summary(None).await;
+    }
+
+    let mine: Vec<Subject> = subjects
+        .iter()
+        .filter(|s| s.group == user.group)
+        .cloned()
+        .collect();
+    let (subject, rest) = homework::find(&mine, &words)
+        .ok_or_else(|| Error::InvalidSubject(chat_id, words[0].to_string()))?;
+    let Some((points, comment)) = rest.split_first() else {
+        return book.summary(Some(subject)).await;
+    };
+    let points: u32 = points
+        .parse()
+        .map_err(|_| Error::InvalidArgument(chat_id, points.to_string()))?;
+    let comment = comment.join(" ");
+    if comment.chars().count() > COMMENT_LENGTH {
+        return Err(Error::TooLong(chat_id, COMMENT_LENGTH));
+    }
+    let grade = Grade {
+        id: 0,
+        subject_id: subject.id,
+        date: today,
+        points: points.into(),
+        comment,
+    };
+    store
+        .add_grade(&sender, institution.id, &grade)
+        .await
+        .map_err(failed(chat_id, "add grade"))?;
+    let (total, max) = book.total(subject.id).await?;
+    Ok(Reply::Text(language.tr(Msg::GradeAdded {
+        title: &subject.title,
+        points: &points.to_string(),
+        total: &total.to_string(),
+        max: &max.to_string(),
+    })))
+}
+
+/// The grades of a chat in the semester of today.
+struct Book<'a, S> {
+    store: &'a S,
+    chat_id: ChatId,
+    sender: ChatId,
+    language: Language,
+    institution: i64,
+    subjects: &'a [Subject],
+    semester: (NaiveDate, NaiveDate),
+}
+
+impl<S: ScheduleStore> Book<'_, S> {
+    fn title(&self, id: i64) -> &str {
+        self.subjects
+            .iter()
+            .find(|s| s.id == id)
+            .map_or("", |s| s.title.as_str())
+    }
+
+    /// Every grade of `sender`, by subject and date.
+    async fn grades(&self) -> Result<Vec<Grade>, Error> {
+        self.store
+            .get_grades(&self.sender, self.institution)
+            .await
+            .map_err(failed(self.chat_id, "get grades"))
+    }
+
+    /// Grades of `sender` in the semester, by subject and date.
+    async fn semester(&self) -> Result<Vec<Grade>, Error> {
+        let (from, to) = self.semester;
+        let mut grades = self.grades().await?;
+        grades.retain(|g| from <= g.date && g.date <= to);
+        Ok(grades)
+    }
+
+    async fn max(&self, subject_id: i64) -> Result<i64, Error> {
+        self.store
+            .get_max_points(self.institution, subject_id)
+            .await
+            .map_err(failed(self.chat_id, "get max points"))
+    }
+
+    /// Points of a subject in the semester and what the subject is worth.
+    async fn total(&self, subject_id: i64) -> Result<(i64, i64), Error> {
+        let total = self
+            .semester()
+            .await?
+            .iter()
+            .filter(|g| g.subject_id == subject_id)
+            .map(|g| g.points)
+            .sum();
+        Ok((total, self.max(subject_id).await?))
+    }
+
+    /// Totals of the subjects with grades in the semester, or of `only` with
+    /// its grades listed.
+    async fn summary(&self, only: Option<&Subject>) -> Result<Reply, Error> {
+        let grades = self.semester().await?;
+        let mut ids: Vec<i64> = grades.iter().map(|g| g.subject_id).collect();
+        ids.dedup();
+        if let Some(subject) = only {
+            ids = vec![subject.id];
+        }
+        if ids.is_empty() {
+            return Ok(Reply::Text(self.language.tr(Msg::NoGrades)));
+        }
+
+        let (from, to) = self.semester;
+        let mut message = md::escape(&self.language.tr(Msg::GradeSummary {
+            from: &from.format("%d.%m.%Y").to_string(),
+            to: &to.format("%d.%m.%Y").to_string(),
+        }));
+        for id in ids {
+            let mine: Vec<&Grade> = grades.iter().filter(|g| g.subject_id == id).collect();
+            let listed = match only {
+                Some(_) => mine
+                    .iter()
+                    .map(|g| gradebook::Grade::new(g.id, g.date, g.points, g.comment.clone()))
+                    .collect(),
+                None => vec![],
+            };
+            let total = gradebook::Total::new(
+                self.title(id).to_string(),
+                mine.iter().map(|g| g.points).sum(),
+                self.max(id).await?,
+                listed,
+            );
+            message.push_str(&format!("\n{}", total));
+        }
+        Ok(Reply::Markdown(message))
+    }
+
+    async fn remove(&self, id: i64) -> Result<Reply, Error> {
+        let grade = self
+            .grades()
+            .await?
+            .into_iter()
+            .find(|g| g.id == id)
+            .ok_or_else(|| Error::InvalidArgument(self.chat_id, id.to_string()))?;
+        self.store
+            .remove_grade(&self.sender, self.institution, id)
+            .await
+            .map_err(failed(self.chat_id, "remove grade"))?;
+        Ok(Reply::Text(self.language.tr(Msg::GradeRemoved {
+            title: self.title(grade.subject_id),
+            points: &grade.points.to_string(),
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::TestClock;
+    use crate::data::{Group, User};
+    use crate::store::MemoryStore;
+    use chrono::{Duration, TimeZone, Utc};
+
+    const CHAT: ChatId = ChatId(7);
+
+    /// K-25 with Algebra worth 60 points and Logic, the chat in it, on
+    /// Monday, 9 Oct 2023.
+    async fn setup() -> (MemoryStore, TestClock) {
+        let store = MemoryStore::new();
+        let group = Group::try_from("K-25").unwrap();
+        for (id, title) in [(0, "Algebra"), (1, "Logic")] {
+            let subject = Subject {
+                id,
+                title: title.into(),
+                group: group.clone(),
+                optional: false,
+            };
+            store.add_subject(0, &subject).await.unwrap();
+        }
+        store.set_max_points(0, 0, 60).await.unwrap();
+        let user = User {
+            institution: 0,
+            group,
+        };
+        store.add_user(&CHAT, &user).await.unwrap();
+        let clock = TestClock::new(Utc.with_ymd_and_hms(2023, 10, 9, 6, 0, 0).unwrap());
+        (store, clock)
+    }
+
+    async fn grade(store: &MemoryStore, clock: &TestClock, args: &str) -> Result<String, Error> {
+        match execute(store, clock, CHAT, CHAT, Language::En, args).await? {
+            Reply::Text(text) | Reply::Markdown(text) => Ok(text),
+            other => panic!("Unexpected reply {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn points_add_up_per_semester() {
+        let (store, clock) = setup().await;
+        assert_eq!(
+            grade(&store, &clock, "alg 8 test 1").await.unwrap(),
+            "Logged 8 points for Algebra, 8 of 60 this semester."
+        );
+        clock.advance(Duration::days(7));
+        assert_eq!(
+            grade(&store, &clock, "algebra 12").await.unwrap(),
+            "Logged 12 points for Algebra, 20 of 60 this semester."
+        );
+        grade(&store, &clock, "logic 50").await.unwrap();
+        assert_eq!(
+            grade(&store, &clock, "").await.unwrap(),
+            "Semester 01\\.09\\.2023–31\\.01\\.2024:\n\
+             📘 *Algebra* 20/60 · 33%\n\
+             📘 *Logic* 50/100 · 50%"
+        );
+        assert_eq!(
+            grade(&store, &clock, "algebra").await.unwrap(),
+            "Semester 01\\.09\\.2023–31\\.01\\.2024:\n\
+             📘 *Algebra* 20/60 · 33%\n\
+             \\#1 09\\.10 *\\+8* test 1\n\
+             \\#2 16\\.10 *\\+12*"
+        );
+
+        assert_eq!(
+            grade(&store, &clock, "- 1").await.unwrap(),
+            "Removed 8 points for Algebra."
+        );
+        assert!(matches!(
+            grade(&store, &clock, "- 1").await,
+            Err(Error::InvalidArgument(_, _))
+        ));
+        assert!(matches!(
+            grade(&store, &clock, "algebra -3").await,
+            Err(Error::InvalidArgument(_, _))
+        ));
+
+        // Grades of the last semester stay behind
+        clock.advance(Duration::days(120));
+        assert_eq!(
+            grade(&store, &clock, "").await.unwrap(),
+            "No grades logged this semester."
+        );
+    }
+
+    #[tokio::test]
+    async fn grades_stay_out_of_group_chats() {
+        let (store, clock) = setup().await;
+        let group = ChatId(-100);
+        let user = User {
+            institution: 0,
+            group: Group::try_from("K-25").unwrap(),
+        };
+        store.add_user(&group, &user).await.unwrap();
+        for args in ["", "algebra 8"] {
+            let result = execute(&store, &clock, group, CHAT, Language::En, args).await;
+            assert!(matches!(result, Err(Error::OnlyPrivate(chat)) if chat == group));
+        }
+        assert!(store.get_grades(&CHAT, 0).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,582 @@
+use crate::announce::{self, Outbox};
+use crate::bot::{failed, language, user, Error, Reply};
+use crate::clock::Clock;
+use crate::data::{Grant, Homework, Institution, Subject};
+use crate::expr::Expr;
+use crate::i18n::{Language, Msg};
+use crate::store::ScheduleStore;
+use chrono::{Duration, NaiveDate, NaiveTime};
+use std::sync::Arc;
+use teloxide::types::ChatId;
+
+/// Longest homework text taken.
+const TEXT_LENGTH: usize = 500;
+
+/// How far ahead `/hw` lists homework, about a term.
+const LIST_DAYS: i64 = 120;
+
+/// Time of the evening before the due date when reminders go out, where the
+/// institution is.
+const REMIND_AT: NaiveTime = match NaiveTime::from_hms_opt(18, 0, 0) {
+    Some(time) => time,
+    None => panic!("Invalid reminder time"),
+};
+
+/// How often homework is checked for reminders to send.
+const INTERVAL: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
+/// `/hw`, run in `chat_id` by `sender`: lists the homework the chat sees, or
+/// adds, completes or deletes some.
+pub async fn execute<S: ScheduleStore>(
+    store: &S,
+    clock: &dyn Clock,
+    chat_id: ChatId,
+    sender: ChatId,
+    language: Language,
+    args: &str,
+) -> Result<Reply, Error> {
+    let words: Vec<&str> = args.split_whitespace().collect();
+    let reply = match words[..] {
+        [] | ["list"] => list(store, clock, chat_id, sender, language).await?,
+        ["add", ref rest @ ..] => add(store, clock, chat_id, language, rest).await?,
+        ["done", id] => done(store, chat_id, sender, language, id, true).await?,
+        ["undo", id] => done(store, chat_id, sender, language, id, false).await?,
+        ["delete", id] => delete(store, chat_id, sender, language, id).await?,
+        _ => return Err(Error::Usage(chat_id, "hw")),
+    };
+    Ok(Reply::Text(reply))
+}
+
+/// Today where the institution is.
+fn today(clock: &dyn Clock, institution: &Institution) -> NaiveDate {
+    clock
+        .now()
+        .with_timezone(&institution.timezone)
+        .date_naive()
+}
+
+/// `add [private] <subject> <due date> <text>`.
+async fn add<S: ScheduleStore>(
+    store: &S,
+    clock: &dyn Clock,
+    chat_id: ChatId,
+    language: Language,
+    words: &[&str],
+) -> Result<String, Error> {
+    let (user, institution) = user(store, chat_id).await?;
+    let (shared, words) = match words {
+        ["private", rest @ ..] => (false, rest),
+        _ => (true, words),
+    };
+    let subjects: Vec<Subject> = store
+        .get_all_subjects(institution.id)
+        .await
+        .map_err(failed(chat_id, "get subjects"))?
+        .into_iter()
+        .filter(|s| s.group == user.group)
+        .collect();
+    let Some((subject, words)) = find(&subjects, words) else {
+        let word = words.first().ok_or(Error::Usage(chat_id, "hw"))?;
+        return Err(Error::InvalidSubject(chat_id, word.to_string()));
+    };
+
+    // The shortest run of words that makes a date, the text follows
+    let found = (1..=words.len()).find_map(|end| {
+        let text = words[..end].join(" ");
+        match Expr::try_from(text.as_str()) {
+            Ok(e) if e.words.is_empty() && e.slot.is_none() => Some((e.date?, text, end)),
+            _ => None,
+        }
+    });
+    let Some((when, date, end)) = found else {
+        return Err(Error::Usage(chat_id, "hw"));
+    };
+    let today = today(clock, &institution);
+    let due = when
+        .resolve(today)
+        .filter(|due| *due >= today)
+        .ok_or(Error::InvalidDate(chat_id, date))?;
+    let text = words[end..].join(" ");
+    if text.is_empty() {
+        return Err(Error::Usage(chat_id, "hw"));
+    }
+    if text.chars().count() > TEXT_LENGTH {
+        return Err(Error::TooLong(chat_id, TEXT_LENGTH));
+    }
+
+    let homework = Homework {
+        id: 0,
+        subject_id: subject.id,
+        due,
+        text,
+        author: chat_id,
+        shared,
+    };
+    let id = store
+        .add_homework(institution.id, &homework)
+        .await
+        .map_err(failed(chat_id, "add homework"))?;
+    let id = id.to_string();
+    let due = language.day(due, "%d.%m");
+    Ok(if shared {
+        language.tr(Msg::HomeworkShared {
+            id: &id,
+            title: &subject.title,
+            due: &due,
+            group: user.group.as_str(),
+        })
+    } else {
+        language.tr(Msg::HomeworkAdded {
+            id: &id,
+            title: &subject.title,
+            due: &due,
+        })
+    })
+}
+
+/// The subject the words start with, by its title or the start of it, along
+/// with the words after it. Longer titles win, so that `Algebra 2` is picked
+/// over `Algebra`.
+pub(crate) fn find<'a, 'w>(
+    subjects: &'a [Subject],
+    words: &'w [&'w str],
+) -> Option<(&'a Subject, &'w [&'w str])> {
+    (1..=words.len()).rev().find_map(|end| {
+        let name = words[..end].join(" ").to_lowercase();
+        let exact = subjects.iter().find(|s| s.title.to_lowercase() == name);
+        let mut starting = subjects
+            .iter()
+            .filter(|s| s.title.to_lowercase().starts_with(&name));
+        let subject = exact.or_else(|| match (starting.next(), starting.next()) {
+            (Some(subject), None) => Some(subject),
+            _ => None,
+        })?;
+        Some((subject, &words[end..]))
+    })
+}
+
+/// Homework `chat_id` sees from today on, one per line, marked done when
+/// `sender` has done it.
+async fn list<S: ScheduleStore>(
+    store: &S,
+    clock: &dyn Clock,
+    chat_id: ChatId,
+    sender: ChatId,
+    language: Language,
+) -> Result<String, Error> {
+    let (user, institution) = user(store, chat_id).await?;
+    let today = today(clock, &institution);
+    let due = store
+        .get_due_homework(
+            institution.id,
+            &user.group,
+            &chat_id,
+            today,
+            today + Duration::days(LIST_DAYS),
+        )
+        .await
+        .map_err(failed(chat_id, "get homework"))?;
+    if due.is_empty() {
+        return Ok(language.tr(Msg::NoHomework));
+    }
+
+    let mut message = language.tr(Msg::HomeworkList);
+    for homework in due {
+        let subject = store
+            .get_subject(institution.id, homework.subject_id)
+            .await
+            .map_err(failed(chat_id, "get subject"))?;
+        let done = store
+            .get_done(homework.id)
+            .await
+            .map_err(failed(chat_id, "get done homework"))?
+            .contains(&sender);
+        message.push_str(&format!(
+            "\n{} #{} {} {}: {}{}",
+            if done { "✅" } else { "📝" },
+            homework.id,
+            language.day(homework.due, "%d.%m"),
+            subject.title,
+            homework.text,
+            if homework.shared { "" } else { " 🔒" },
+        ));
+    }
+    Ok(message)
+}
+
+/// The homework `id` stands for, as long as `chat_id` sees it, along with
+/// its subject and the institution of the chat.
+async fn visible<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+    id: &str,
+) -> Result<(Homework, Subject, Institution), Error> {
+    let invalid = || Error::InvalidHomework(chat_id, id.into());
+    let number = id.trim_start_matches('#').parse().map_err(|_| invalid())?;
+    let (user, institution) = user(store, chat_id).await?;
+    let homework = match store.get_homework(institution.id, number).await {
+        Ok(homework) => homework,
+        Err(sqlx::Error::RowNotFound) => return Err(invalid()),
+        Err(err) => return Err(failed(chat_id, "get homework")(err)),
+    };
+    let subject = store
+        .get_subject(institution.id, homework.subject_id)
+        .await
+        .map_err(failed(chat_id, "get subject"))?;
+    let seen = homework.author == chat_id || (homework.shared && subject.group == user.group);
+    if !seen {
+        return Err(invalid());
+    }
+    Ok((homework, subject, institution))
+}
+
+/// `done <id>` or `undo <id>`, for `sender` only, even in a group chat.
+async fn done<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+    sender: ChatId,
+    language: Language,
+    id: &str,
+    done: bool,
+) -> Result<String, Error> {
+    let (homework, subject, _) = visible(store, chat_id, id).await?;
+    store
+        .set_done(&sender, homework.id, done)
+        .await
+        .map_err(failed(chat_id, "set homework done"))?;
+    let id = homework.id.to_string();
+    Ok(language.tr(if done {
+        Msg::HomeworkDone {
+            id: &id,
+            title: &subject.title,
+        }
+    } else {
+        Msg::HomeworkUndone {
+            id: &id,
+            title: &subject.title,
+        }
+    }))
+}
+
+/// `delete <id>`, left to the chat that added the homework and to editors
+/// of the group of its subject.
+async fn delete<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+    sender: ChatId,
+    language: Language,
+    id: &str,
+) -> Result<String, Error> {
+    let (homework, subject, institution) = visible(store, chat_id, id).await?;
+    if homework.author != chat_id {
+        let grants: Vec<Grant> = store
+            .get_grants(&sender)
+            .await
+            .map_err(failed(chat_id, "get grants"))?;
+        if !grants
+            .iter()
+            .any(|g| g.covers(institution.id, &subject.group))
+        {
+            return Err(Error::NotAuthor(chat_id));
+        }
+    }
+    store
+        .remove_homework(institution.id, homework.id)
+        .await
+        .map_err(failed(chat_id, "remove homework"))?;
+    Ok(language.tr(Msg::HomeworkDeleted {
+        id: &homework.id.to_string(),
+        title: &subject.title,
+    }))
+}
+
+/// Reminders of homework due soon, for every chat that sees it and hasn't
+/// done it yet, each in the language of the chat. They go out from
+/// [`REMIND_AT`] on the evening before the due date, only once.
+pub async fn reminders<S: ScheduleStore>(
+    store: &S,
+    clock: &dyn Clock,
+) -> sqlx::Result<Vec<(ChatId, String)>> {
+    let mut letters = vec![];
+    for (institution, homework) in store.get_unreminded().await? {
+        let institution = store.get_institution(institution).await?;
+        let now = clock.now().with_timezone(&institution.timezone);
+        let from = (homework.due - Duration::days(1)).and_time(REMIND_AT);
+        if now.date_naive() > homework.due {
+            // Too late to remind of
+            store.set_reminded(homework.id).await?;
+            continue;
+        }
+        if now.naive_local() < from {
+            continue;
+        }
+
+        let subject = store
+            .get_subject(institution.id, homework.subject_id)
+            .await?;
+        let chats = match homework.shared {
+            true => store.get_chats(institution.id, &subject.group).await?,
+            false => vec![homework.author],
+        };
+        let done = store.get_done(homework.id).await?;
+        for chat in chats.into_iter().filter(|c| !done.contains(c)) {
+            let language = language(store, chat, None).await;
+            let text = language.tr(Msg::HomeworkDue {
+                id: &homework.id.to_string(),
+                title: &subject.title,
+                due: &language.day(homework.due, "%d.%m"),
+                text: &homework.text,
+            });
+            letters.push((chat, text));
+        }
+        store.set_reminded(homework.id).await?;
+    }
+    Ok(letters)
+}
+
+/// Sends homework reminders through `outbox` for as long as the bot runs.
+pub async fn watch<S: ScheduleStore>(store: Arc<S>, clock: Arc<dyn Clock>, outbox: Outbox) {
+    let mut interval = tokio::time::interval(INTERVAL);
+    loop {
+        interval.tick().await;
+        let letters = match reminders(store.as_ref(), clock.as_ref()).await {
+            Ok(letters) => letters,
+            Err(err) => {
+                log::error!("Failed to gather homework reminders: {:?}", err);
+                continue;
+            }
+        };
+        if letters.is_empty() {
+            continue;
+        }
+        let outcomes = outbox.deliver(letters).await;
+        if let Err(err) = announce::clean_up(store.as_ref(), &outcomes).await {
+            log::error!("Failed to forget unreachable chats: {:?}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::TestClock;
+    use crate::data::{Day, Group, Repeat, Role, Schedule, Slot, User};
+    use crate::store::MemoryStore;
+    use crate::timetable::{self, Span, View};
+    use chrono::{TimeZone, Utc};
+
+    const ALICE: ChatId = ChatId(7);
+    const BOB: ChatId = ChatId(8);
+    const HEADMAN: ChatId = ChatId(9);
+
+    fn k25() -> Group {
+        Group::try_from("K-25").unwrap()
+    }
+
+    /// K-25 with Algebra on Mondays and Algebra 2 nobody takes, two students
+    /// and an editor who isn't in the group, on Tuesday, 10 Oct 2023, 09:00.
+    async fn setup() -> (MemoryStore, TestClock) {
+        let store = MemoryStore::new();
+        store.add_group(0, &k25()).await.unwrap();
+        for (id, title) in [(0, "Algebra"), (1, "Algebra 2"), (2, "Logic")] {
+            let subject = Subject {
+                id,
+                title: title.into(),
+                group: k25(),
+                optional: false,
+            };
+            store.add_subject(0, &subject).await.unwrap();
+        }
+        let class = Schedule {
+            subject_id: 0,
+            day: Day::Mon,
+            repeat: Repeat::Both,
+            slot: Slot::I,
+        };
+        store.add_schedule(0, &class).await.unwrap();
+        let user = User {
+            institution: 0,
+            group: k25(),
+        };
+        for id in [ALICE, BOB] {
+            store.add_user(&id, &user).await.unwrap();
+        }
+        let grant = Grant {
+            institution: 0,
+            group: Some(k25()),
+            role: Role::Editor,
+        };
+        store.add_grant(&HEADMAN, &grant).await.unwrap();
+        let clock = TestClock::new(Utc.with_ymd_and_hms(2023, 10, 10, 6, 0, 0).unwrap());
+        (store, clock)
+    }
+
+    async fn hw(store: &MemoryStore, clock: &TestClock, chat: ChatId, args: &str) -> String {
+        match execute(store, clock, chat, chat, Language::En, args).await {
+            Ok(Reply::Text(text)) => text,
+            other => panic!("Unexpected reply {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn shared_and_private_homework() {
+        let (store, clock) = setup().await;
+        assert_eq!(
+            hw(&store, &clock, ALICE, "add algebra next mon Exercises 1-5").await,
+            "Added homework #1 for Algebra due Mon 16.10, everyone in K-25 sees it."
+        );
+        assert_eq!(
+            hw(
+                &store,
+                &clock,
+                ALICE,
+                "add private algebra 2 16.10 Read ch. 3"
+            )
+            .await,
+            "Added homework #2 for Algebra 2 due Mon 16.10, only this chat sees it."
+        );
+
+        assert_eq!(
+            hw(&store, &clock, BOB, "").await,
+            "Homework:\n📝 #1 Mon 16.10 Algebra: Exercises 1-5"
+        );
+        assert_eq!(
+            hw(&store, &clock, BOB, "done 1").await,
+            "Homework #1 for Algebra is done."
+        );
+        assert_eq!(
+            hw(&store, &clock, ALICE, "list").await,
+            "Homework:\n📝 #1 Mon 16.10 Algebra: Exercises 1-5\n📝 #2 Mon 16.10 Algebra 2: Read ch. 3 🔒"
+        );
+        let result = execute(&store, &clock, BOB, BOB, Language::En, "done 2").await;
+        assert!(matches!(result, Err(Error::InvalidHomework(_, id)) if id == "2"));
+
+        let view = View {
+            span: Span::Day,
+            date: NaiveDate::from_ymd_opt(2023, 10, 16),
+            group: None,
+        };
+        let Some(Reply::Page(text, _)) =
+            timetable::render(&store, &clock, BOB, BOB, Language::En, view)
+                .await
+                .unwrap()
+        else {
+            panic!("Not a page");
+        };
+        assert!(text.contains("Algebra\n✅ \\#1 Exercises 1\\-5"));
+        assert!(!text.contains("Read"));
+    }
+
+    #[tokio::test]
+    async fn done_is_up_to_each_member_of_a_group_chat() {
+        let (store, clock) = setup().await;
+        let chat = ChatId(-100);
+        let user = User {
+            institution: 0,
+            group: k25(),
+        };
+        store.add_user(&chat, &user).await.unwrap();
+        let hw = |sender, args| execute(&store, &clock, chat, sender, Language::En, args);
+        hw(ALICE, "add Logic tomorrow Proofs").await.unwrap();
+        hw(ALICE, "done 1").await.unwrap();
+
+        assert_eq!(store.get_done(1).await.unwrap(), vec![ALICE]);
+        let Reply::Text(text) = hw(ALICE, "").await.unwrap() else {
+            panic!("Not a text");
+        };
+        assert_eq!(text, "Homework:\n✅ #1 Wed 11.10 Logic: Proofs");
+        let Reply::Text(text) = hw(BOB, "").await.unwrap() else {
+            panic!("Not a text");
+        };
+        assert_eq!(text, "Homework:\n📝 #1 Wed 11.10 Logic: Proofs");
+
+        hw(ALICE, "add algebra next mon Exercises").await.unwrap();
+        hw(ALICE, "done 2").await.unwrap();
+        let view = View {
+            span: Span::Day,
+            date: NaiveDate::from_ymd_opt(2023, 10, 16),
+            group: None,
+        };
+        let page =
+            |sender| timetable::render(&store, &clock, chat, sender, Language::En, view.clone());
+        let Some(Reply::Page(text, _)) = page(ALICE).await.unwrap() else {
+            panic!("Not a page");
+        };
+        assert!(text.contains("Algebra\n✅ \\#2 Exercises"));
+        let Some(Reply::Page(text, _)) = page(BOB).await.unwrap() else {
+            panic!("Not a page");
+        };
+        assert!(!text.contains('✅'));
+    }
+
+    #[tokio::test]
+    async fn add_checks_arguments() {
+        let (store, clock) = setup().await;
+        for (args, expected) in [
+            ("add Drawing tomorrow Sketch", "invalid subject"),
+            ("add Algebra yesterday Late", "invalid date"),
+            ("add Algebra Exercises", "usage"),
+            ("add Algebra tomorrow", "usage"),
+            ("remove 1", "usage"),
+        ] {
+            let result = execute(&store, &clock, ALICE, ALICE, Language::En, args).await;
+            let found = match result {
+                Err(Error::InvalidSubject(..)) => "invalid subject",
+                Err(Error::InvalidDate(..)) => "invalid date",
+                Err(Error::Usage(_, "hw")) => "usage",
+                other => panic!("Unexpected result {:?}", other),
+            };
+            assert_eq!(found, expected, "{}", args);
+        }
+        let long = format!("add Logic tomorrow {}", "a".repeat(TEXT_LENGTH + 1));
+        let result = execute(&store, &clock, ALICE, ALICE, Language::En, &long).await;
+        assert!(matches!(result, Err(Error::TooLong(_, TEXT_LENGTH))));
+    }
+
+    #[tokio::test]
+    async fn only_authors_and_editors_delete() {
+        let (store, clock) = setup().await;
+        hw(&store, &clock, ALICE, "add Logic tomorrow Proofs").await;
+        hw(&store, &clock, ALICE, "add Logic fri Truth tables").await;
+
+        let result = execute(&store, &clock, BOB, BOB, Language::En, "delete 1").await;
+        assert!(matches!(result, Err(Error::NotAuthor(_))));
+        assert_eq!(
+            hw(&store, &clock, ALICE, "delete #1").await,
+            "Deleted homework #1 for Logic."
+        );
+        let reply = execute(&store, &clock, BOB, HEADMAN, Language::En, "delete 2").await;
+        assert!(matches!(reply, Ok(Reply::Text(_))));
+        assert_eq!(
+            hw(&store, &clock, BOB, "").await,
+            "No homework is due. Add some with /hw add <subject> <due date> <text>."
+        );
+    }
+
+    #[tokio::test]
+    async fn reminders_go_out_the_evening_before() {
+        let (store, clock) = setup().await;
+        hw(&store, &clock, ALICE, "add Logic tomorrow Proofs").await;
+        hw(&store, &clock, ALICE, "add private Logic tomorrow Mine").await;
+        hw(&store, &clock, ALICE, "add Logic 13.10 Later").await;
+        hw(&store, &clock, BOB, "done 1").await;
+        store
+            .set_language(&ALICE, Some(Language::Uk))
+            .await
+            .unwrap();
+
+        assert!(reminders(&store, &clock).await.unwrap().is_empty());
+        // 18:00 in Kyiv
+        clock.advance(Duration::hours(9));
+        let letters = reminders(&store, &clock).await.unwrap();
+        let chats: Vec<ChatId> = letters.iter().map(|(chat, _)| *chat).collect();
+        assert_eq!(chats, vec![ALICE, ALICE]);
+        assert!(letters[0].1.starts_with("Нагадування"));
+        assert!(reminders(&store, &clock).await.unwrap().is_empty());
+
+        // The bot was down until after the last one was due
+        clock.advance(Duration::days(4));
+        assert!(reminders(&store, &clock).await.unwrap().is_empty());
+        assert!(store.get_unreminded().await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,1035 @@
+use crate::data::{ExamKind, Repeat};
+use anyhow::anyhow;
+use chrono::{Datelike, NaiveDate, Weekday};
+
+/// Language the bot talks to a user in.
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub enum Language {
+    #[default]
+    En,
+    Uk,
+}
+
+impl TryFrom<&str> for Language {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "en" => Ok(Language::En),
+            "uk" | "ua" => Ok(Language::Uk),
+            other => Err(anyhow!("Not a language: {}", other)),
+        }
+    }
+}
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::En, Language::Uk];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Language::En => "en",
+            Language::Uk => "uk",
+        }
+    }
+
+    /// Language for Telegram's `language_code` of a user, e.g. `uk` or `en-US`.
+    pub fn from_telegram(code: Option<&str>) -> Language {
+        code.and_then(|code| code.split('-').next())
+            .and_then(|code| Language::try_from(code).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn weekday(self, day: Weekday) -> &'static str {
+        use Weekday::*;
+        match self {
+            Language::En => match day {
+                Mon => "Mon",
+                Tue => "Tue",
+                Wed => "Wed",
+                Thu => "Thu",
+                Fri => "Fri",
+                Sat => "Sat",
+                Sun => "Sun",
+            },
+            Language::Uk => match day {
+                Mon => "Пн",
+                Tue => "Вт",
+                Wed => "Ср",
+                Thu => "Чт",
+                Fri => "Пт",
+                Sat => "Сб",
+                Sun => "Нд",
+            },
+        }
+    }
+
+    /// Weeks a class takes place in, e.g. `odd weeks`.
+    pub fn weeks(self, repeat: Repeat) -> &'static str {
+        use Repeat::*;
+        match self {
+            Language::En => match repeat {
+                Odd => "odd weeks",
+                Even => "even weeks",
+                Both => "every week",
+            },
+            Language::Uk => match repeat {
+                Odd => "непарні тижні",
+                Even => "парні тижні",
+                Both => "щотижня",
+            },
+        }
+    }
+
+    /// Name of a kind of exam, capitalized.
+    pub fn kind(self, kind: ExamKind) -> &'static str {
+        use ExamKind::*;
+        match self {
+            Language::En => match kind {
+                Exam => "Exam",
+                Credit => "Credit",
+                Consultation => "Consultation",
+            },
+            Language::Uk => match kind {
+                Exam => "Іспит",
+                Credit => "Залік",
+                Consultation => "Консультація",
+            },
+        }
+    }
+
+    /// Weekday of `date` followed by the rest of it in `format`, e.g. `Mon 16.10`.
+    pub fn day(self, date: NaiveDate, format: &str) -> String {
+        format!("{} {}", self.weekday(date.weekday()), date.format(format))
+    }
+
+    /// Description of `command`, without the slash, for the command menu.
+    pub fn describe(self, command: &str) -> Option<&'static str> {
+        match self {
+            Language::En => None,
+            Language::Uk => match command {
+                "start" => Some("обрати заклад і групу"),
+                "config" => Some("[заклад] [група]"),
+                "subject" => Some("[пара] [дата] у будь-якому порядку, напр. 2 завтра"),
+                "today" => Some("[дата] пари на день, напр. завтра або наступна пт"),
+                "week" => Some("[дата] пари на тиждень"),
+                "timezone" => Some("<пояс> для часу пар, або - щоб скинути"),
+                "lang" => Some("<en|uk> мова бота, або - як у Telegram"),
+                "pin" => Some("закріпити розклад на сьогодні, що оновлюється щодня"),
+                "unpin" => Some("відкріпити розклад"),
+                "cancel" => Some("<дата> <пара> скасувати пару"),
+                "move" => Some("<дата> <пара> <дата> <пара> перенести пару"),
+                "setroom" => Some("<предмет> <аудиторія> де проходять пари предмета"),
+                "addlink" => Some("<дата> <пара> <посилання> [назва] додати посилання на пару"),
+                "grant" => Some("<id користувача> <owner|editor> [група] надати роль"),
+                "revoke" => Some("<id користувача> [група] забрати роль"),
+                "roles" => Some("ваш id і ролі"),
+                "history" => Some("останні зміни розкладу ваших груп"),
+                "announce" => Some("<група> <текст> надіслати повідомлення всім у групі"),
+                "hw" => Some(
+                    "домашні завдання: add [private] <предмет> <термін> <текст>, done|undo|delete <id>",
+                ),
+                "exams" => Some("іспити й заліки вашої групи"),
+                "next_exam" => Some("найближчий іспит і скільки днів до нього"),
+                "calendar" => Some("розклад та іспити файлом для календаря"),
+                "note" => Some("<предмет> <текст> ваша нотатка до предмета, або - щоб видалити"),
+                "notes" => Some("ваші нотатки до предметів"),
+                "common" => Some("<група|@друг> ... [дата] спільні вільні пари на тиждень"),
+                "enroll" => Some("[предмет] записатися на вибірковий предмет, або - предмет щоб виписатися"),
+                "now" => Some("поточна й наступна пари та кінець дня"),
+                "conflicts" => Some("пари, що накладаються у вашому розкладі"),
+                "attendance" => Some("[предмет] ваші відвідування й пропуски"),
+                "grade" => Some("[предмет] [бали] [коментар] ваші бали за семестр, або записати нові; - <id> щоб видалити"),
+                "friend" => Some("[@username] друзі, або запит ділитися вільним часом; - @username щоб припинити"),
+                _ => None,
+            },
+        }
+    }
+
+    pub fn tr(self, msg: Msg) -> String {
+        match self {
+            Language::En => en(msg),
+            Language::Uk => uk(msg),
+        }
+    }
+}
+
+/// Everything the bot says, arguments included.
+pub enum Msg<'a> {
+    SomethingWentWrong,
+    NoGroupConfigured,
+    InvalidInstitution(&'a str),
+    InvalidGroup(&'a str),
+    InvalidDate(&'a str),
+    InvalidSlot(&'a str),
+    InvalidWeekday(&'a str),
+    InvalidArgument(&'a str),
+    InvalidTimezone(&'a str),
+    InvalidLanguage(&'a str),
+    InvalidRoom(&'a str),
+    InvalidLink(&'a str),
+    /// How to use a command, `usage` being its description.
+    Usage {
+        command: &'a str,
+        usage: &'a str,
+    },
+    NotChatAdmin,
+    NotEditor,
+    NotOwner,
+    /// Heading of the list of institutions, `/config` lines follow.
+    Institutions,
+    GroupsOf {
+        institution: &'a str,
+        groups: &'a str,
+        code: &'a str,
+    },
+    Ambiguous {
+        group: &'a str,
+        codes: &'a str,
+    },
+    Saved {
+        group: &'a str,
+        institution: &'a str,
+    },
+    NoClassesOn {
+        date: &'a str,
+        title: &'a str,
+    },
+    NoSubject,
+    TimesShownIn(&'a str),
+    Language,
+    PickInstitution,
+    PickFaculty,
+    PickYear,
+    PickGroup,
+    PickSubgroup,
+    WholeGroup,
+    Other,
+    Year(&'a str),
+    NoGroupsYet(&'a str),
+    MenuExpired,
+    WeekOf(&'a str),
+    NoClasses,
+    NoClassesBecause(&'a str),
+    Prev,
+    Today,
+    Next,
+    DayView,
+    WeekView,
+    OddWeek,
+    EvenWeek,
+    Subscribe,
+    ButtonExpired,
+    SlotTitle {
+        group: &'a str,
+        slot: u8,
+        date: &'a str,
+    },
+    WeekTitle {
+        group: &'a str,
+        date: &'a str,
+    },
+    PickGroupFirst,
+    CantPin,
+    Unpinned,
+    Cancelled {
+        title: &'a str,
+        date: &'a str,
+        slot: u8,
+    },
+    Moved {
+        title: &'a str,
+        date: &'a str,
+        slot: u8,
+    },
+    SlotTaken(&'a str),
+    SeveralClasses,
+    RoomSet {
+        title: &'a str,
+        room: &'a str,
+    },
+    LinkAdded(&'a str),
+    Granted {
+        user: &'a str,
+        role: &'a str,
+        scope: &'a str,
+    },
+    Revoked {
+        user: &'a str,
+        scope: &'a str,
+    },
+    /// First line of `/roles`, the roles follow.
+    YourId(&'a str),
+    NoRoles,
+    NoHistory,
+    InvalidFile(&'a str),
+    CantImport {
+        name: &'a str,
+        problems: &'a str,
+    },
+    NothingToImport(&'a str),
+    ImportPreview {
+        name: &'a str,
+        changes: &'a str,
+    },
+    /// Last line of a list cut short, with how many lines are left out.
+    AndMore(&'a str),
+    ApplyImport,
+    CancelImport,
+    Imported {
+        name: &'a str,
+        subjects: &'a str,
+        classes: &'a str,
+        exams: &'a str,
+    },
+    ImportCancelled(&'a str),
+    UploadExpired,
+    TooLong(&'a str),
+    Announcement {
+        group: &'a str,
+        text: &'a str,
+    },
+    Announcing {
+        group: &'a str,
+        count: &'a str,
+    },
+    Announced {
+        group: &'a str,
+        delivered: &'a str,
+        failed: &'a str,
+        removed: &'a str,
+    },
+    NoChats(&'a str),
+    InvalidSubject(&'a str),
+    InvalidHomework(&'a str),
+    NotAuthor,
+    HomeworkShared {
+        id: &'a str,
+        title: &'a str,
+        due: &'a str,
+        group: &'a str,
+    },
+    HomeworkAdded {
+        id: &'a str,
+        title: &'a str,
+        due: &'a str,
+    },
+    /// Heading of `/hw`, the homework follows one per line.
+    HomeworkList,
+    NoHomework,
+    HomeworkDone {
+        id: &'a str,
+        title: &'a str,
+    },
+    HomeworkUndone {
+        id: &'a str,
+        title: &'a str,
+    },
+    HomeworkDeleted {
+        id: &'a str,
+        title: &'a str,
+    },
+    /// Reminder sent the evening before the due date.
+    HomeworkDue {
+        id: &'a str,
+        title: &'a str,
+        due: &'a str,
+        text: &'a str,
+    },
+    /// Heading of `/exams`, the exams follow one per line.
+    ExamList,
+    NoExams,
+    NextExam {
+        exam: &'a str,
+        days: &'a str,
+    },
+    /// Reminder sent some days before an exam.
+    ExamCountdown {
+        exam: &'a str,
+        days: &'a str,
+    },
+    NoteSaved {
+        title: &'a str,
+    },
+    NoteRemoved {
+        title: &'a str,
+    },
+    /// Heading of `/notes`, the notes follow one per line.
+    NoteList,
+    NoNotes,
+    UnknownUser(&'a str),
+    NotFriend(&'a str),
+    /// Heading of `/common`, a line of free slots follows for every weekday.
+    CommonFree {
+        groups: &'a str,
+        week: &'a str,
+    },
+    FriendAsked(&'a str),
+    /// Sent to whoever is asked by `/friend`, with buttons to answer.
+    FriendRequest(&'a str),
+    AcceptFriend,
+    DeclineFriend,
+    FriendAdded(&'a str),
+    FriendDeclined(&'a str),
+    FriendRemoved(&'a str),
+    FriendRequestExpired,
+    /// Heading of `/friend`, the friends follow one per line.
+    FriendList,
+    /// Mark of a friend who hasn't agreed yet.
+    FriendPending,
+    NoFriends,
+    /// Two classes at the same time, e.g. in `/conflicts`.
+    Clash {
+        day: &'a str,
+        slot: &'a str,
+        weeks: &'a str,
+        first: &'a str,
+        second: &'a str,
+    },
+    /// Heading of `/conflicts`, the clashes follow one per line.
+    ClashList,
+    NoClashes,
+    /// Heading of the clashes of a subject just enrolled in.
+    ClashWarning,
+    Enrolled(&'a str),
+    Unenrolled(&'a str),
+    /// Heading of `/enroll`, the optional subjects follow one per line.
+    ElectiveList,
+    /// Mark of an optional subject the chat is enrolled in.
+    EnrolledMark,
+    NoElectives,
+    NowClass {
+        title: &'a str,
+        minutes: &'a str,
+    },
+    /// The next class today, `room` empty when it isn't known.
+    NextClass {
+        title: &'a str,
+        start: &'a str,
+        minutes: &'a str,
+        room: &'a str,
+    },
+    DayEnds(&'a str),
+    DayOver,
+    /// Buttons under a class, prefixed with its title when there are several.
+    Attended(&'a str),
+    Missed(&'a str),
+    MarkedAttended {
+        title: &'a str,
+        date: &'a str,
+    },
+    MarkedMissed {
+        title: &'a str,
+        date: &'a str,
+    },
+    /// Reminder at the end of a class, with the titles of its subjects.
+    ClassOver(&'a str),
+    /// Heading of `/attendance`, the subjects follow one per line.
+    AttendanceList(&'a str),
+    AttendanceLine {
+        title: &'a str,
+        attended: &'a str,
+        missed: &'a str,
+    },
+    /// Warning of a student one absence away from the limit, or past it.
+    AbsenceWarning {
+        title: &'a str,
+        missed: &'a str,
+        limit: &'a str,
+    },
+    NoAttendance,
+    AttendanceExpired,
+    GradeAdded {
+        title: &'a str,
+        points: &'a str,
+        total: &'a str,
+        max: &'a str,
+    },
+    GradeRemoved {
+        title: &'a str,
+        points: &'a str,
+    },
+    /// Heading of `/grade`, the subjects follow one per line.
+    GradeSummary {
+        from: &'a str,
+        to: &'a str,
+    },
+    NoGrades,
+    /// Grades or attendance asked for in a group chat.
+    OnlyPrivate,
+}
+
+fn en(msg: Msg) -> String {
+    use Msg::*;
+    match msg {
+        SomethingWentWrong => "Something went wrong".into(),
+        NoGroupConfigured => {
+            "Please pick your group with /start or `/config <institution> <group>`".into()
+        }
+        InvalidInstitution(value) => format!(
+            "Invalid institution: {}. Send /config to see the list.",
+            value
+        ),
+        InvalidGroup(value) => format!("Invalid group: {}.", value),
+        InvalidDate(value) => format!("Invalid date: {}.", value),
+        InvalidSlot(value) => format!("Invalid slot: {}.", value),
+        InvalidWeekday(value) => format!("Invalid weekday: {}.", value),
+        InvalidArgument(value) => format!(
+            "Not a slot or a date: {}. Try something like `/subject 2 tomorrow`.",
+            value
+        ),
+        InvalidTimezone(value) => format!(
+            "Invalid time zone: {}. Try something like Europe/Warsaw.",
+            value
+        ),
+        InvalidLanguage(value) => format!("Unknown language: {}. Try en or uk.", value),
+        InvalidRoom(value) => format!("Room name is too long: {}.", value),
+        InvalidLink(value) => format!("Not a web link: {}.", value),
+        Usage { command, usage } => format!("Usage: /{} {}", command, usage),
+        NotChatAdmin => "Only admins of this chat can change its settings.".into(),
+        NotEditor => "Only editors of this group can change its timetable.".into(),
+        NotOwner => "Only owners can hand out and take away roles.".into(),
+        Institutions => "Pick your institution with /config <institution>:".into(),
+        GroupsOf {
+            institution,
+            groups,
+            code,
+        } => format!(
+            "Groups of {}: {}.\nPick yours with /config {} <group>",
+            institution, groups, code
+        ),
+        Ambiguous { group, codes } => format!(
+            "{} exists in several institutions, please pick one: /config <{}> {}",
+            group, codes, group
+        ),
+        Saved { group, institution } => format!("Saved: {}, {}.", group, institution),
+        NoClassesOn { date, title } => format!("No classes on {}: {}.", date, title),
+        NoSubject => "No such subject is found.".into(),
+        TimesShownIn(zone) => format!("Class times are now shown in {}.", zone),
+        Language => {
+            "Language: English. Change it with /lang <en|uk>, or /lang - to follow Telegram.".into()
+        }
+        PickInstitution => "Pick your institution:".into(),
+        PickFaculty => "Pick your faculty:".into(),
+        PickYear => "Pick your year:".into(),
+        PickGroup => "Pick your group:".into(),
+        PickSubgroup => "Pick your subgroup:".into(),
+        WholeGroup => "Whole group".into(),
+        Other => "Other".into(),
+        Year(year) => format!("Year {}", year),
+        NoGroupsYet(institution) => format!("{} has no groups yet.", institution),
+        MenuExpired => "This menu has expired, send /start to begin again.".into(),
+        WeekOf(date) => format!("Week of {}", date),
+        NoClasses => "No classes.".into(),
+        NoClassesBecause(title) => format!("No classes: {}.", title),
+        Prev => "◀ prev".into(),
+        Today => "today".into(),
+        Next => "next ▶".into(),
+        DayView => "Day".into(),
+        WeekView => "Week".into(),
+        OddWeek => "odd week".into(),
+        EvenWeek => "even week".into(),
+        Subscribe => "Subscribe to the calendar".into(),
+        ButtonExpired => "This button has expired, send /today again.".into(),
+        SlotTitle { group, slot, date } => format!("{}: slot {}, {}", group, slot, date),
+        WeekTitle { group, date } => format!("{}: week of {}", group, date),
+        PickGroupFirst => "Pick your group first".into(),
+        CantPin => "Couldn't pin the timetable, please let me pin messages in this chat.".into(),
+        Unpinned => "The timetable is no longer pinned.".into(),
+        Cancelled { title, date, slot } => {
+            format!("Cancelled {} on {}, slot {}.", title, date, slot)
+        }
+        Moved { title, date, slot } => format!("Moved {} to {}, slot {}.", title, date, slot),
+        SlotTaken(titles) => format!("Slot is taken by {}.", titles),
+        SeveralClasses => {
+            "Several classes take place in this slot, so there is no telling which one is meant."
+                .into()
+        }
+        RoomSet { title, room } => format!("{} is now in {}.", title, room),
+        LinkAdded(title) => format!("Added the link to {}.", title),
+        Granted { user, role, scope } => format!("{} is now an {} of {}.", user, role, scope),
+        Revoked { user, scope } => format!("{} no longer has a role over {}.", user, scope),
+        YourId(id) => format!("Your id: {}", id),
+        NoRoles => "You have no roles yet.".into(),
+        NoHistory => "Nothing has changed yet.".into(),
+        InvalidFile(name) => format!(
+            "Can't read {}: send a .packed, .csv, .json or .ics file of up to 1 MB in UTF-8.",
+            name
+        ),
+        CantImport { name, problems } => format!("Can't import {}:\n{}", name, problems),
+        NothingToImport(name) => format!(
+            "{} matches the timetable, there is nothing to change.",
+            name
+        ),
+        ImportPreview { name, changes } => {
+            format!("Changes from {}:\n{}\n\nApply them?", name, changes)
+        }
+        AndMore(count) => format!("…and {} more", count),
+        ApplyImport => "Apply".into(),
+        CancelImport => "Cancel".into(),
+        Imported {
+            name,
+            subjects,
+            classes,
+            exams,
+        } => format!(
+            "Imported {}: {} subjects, {} classes, {} exams.",
+            name, subjects, classes, exams
+        ),
+        ImportCancelled(name) => format!("Didn't import {}.", name),
+        UploadExpired => "This upload has expired, please send the file again.".into(),
+        TooLong(limit) => format!("The text is too long, keep it under {} characters.", limit),
+        Announcement { group, text } => format!("Announcement for {}:\n\n{}", group, text),
+        Announcing { group, count } => {
+            format!("Sending the announcement to {} chats of {}…", count, group)
+        }
+        Announced {
+            group,
+            delivered,
+            failed,
+            removed,
+        } => format!(
+            "Announcement for {}: delivered to {}, failed for {}. Forgot {} chats that can't be reached anymore.",
+            group, delivered, failed, removed
+        ),
+        NoChats(group) => format!("No one has picked {} yet.", group),
+        InvalidSubject(value) => format!("Your group has no subject called {}.", value),
+        InvalidHomework(value) => format!("No homework {} found, see /hw for the list.", value),
+        NotAuthor => {
+            "Only the chat that added this homework or editors of the group can delete it.".into()
+        }
+        HomeworkShared {
+            id,
+            title,
+            due,
+            group,
+        } => format!(
+            "Added homework #{} for {} due {}, everyone in {} sees it.",
+            id, title, due, group
+        ),
+        HomeworkAdded { id, title, due } => format!(
+            "Added homework #{} for {} due {}, only this chat sees it.",
+            id, title, due
+        ),
+        HomeworkList => "Homework:".into(),
+        NoHomework => {
+            "No homework is due. Add some with /hw add <subject> <due date> <text>.".into()
+        }
+        HomeworkDone { id, title } => format!("Homework #{} for {} is done.", id, title),
+        HomeworkUndone { id, title } => {
+            format!("Homework #{} for {} is no longer done.", id, title)
+        }
+        HomeworkDeleted { id, title } => format!("Deleted homework #{} for {}.", id, title),
+        HomeworkDue {
+            id,
+            title,
+            due,
+            text,
+        } => format!(
+            "Reminder: homework #{} for {} is due {}:\n{}\n\nMark it with /hw done {}",
+            id, title, due, text, id
+        ),
+        ExamList => "Exam session:".into(),
+        NoExams => "No exams are scheduled for your group.".into(),
+        NextExam { exam, days } => format!("Next up, days left: {}\n{}", days, exam),
+        ExamCountdown { exam, days } => format!("Reminder, days left: {}\n{}", days, exam),
+        NoteSaved { title } => format!("Saved your note on {}.", title),
+        NoteRemoved { title } => format!("Removed your note on {}.", title),
+        NoteList => "Your notes:".into(),
+        NoNotes => "You have no notes yet. Add one with /note <subject> <text>.".into(),
+        UnknownUser(name) => format!(
+            "The bot doesn't know {}, they have to message it and pick a group first.",
+            name
+        ),
+        NotFriend(name) => format!(
+            "{} hasn't agreed to share free time with you, ask with /friend {}.",
+            name, name
+        ),
+        CommonFree { groups, week } => format!("Free for {} in the week of {}:", groups, week),
+        FriendAsked(name) => format!("Asked {}, you'll be friends once they agree.", name),
+        FriendRequest(name) => format!(
+            "{} would like to share free time with you, to find it with /common. Agree?",
+            name
+        ),
+        AcceptFriend => "Agree".into(),
+        DeclineFriend => "Decline".into(),
+        FriendAdded(name) => format!("You and {} are friends now.", name),
+        FriendDeclined(name) => format!("Declined the request of {}.", name),
+        FriendRemoved(name) => format!("You and {} no longer share free time.", name),
+        FriendRequestExpired => "This request is no longer valid.".into(),
+        FriendList => "Your friends:".into(),
+        FriendPending => "waiting".into(),
+        NoFriends => "You have no friends here yet, ask one with /friend @username.".into(),
+        Clash {
+            day,
+            slot,
+            weeks,
+            first,
+            second,
+        } => format!("{} slot {}, {}: {} and {}", day, slot, weeks, first, second),
+        ClashList => "These classes clash:".into(),
+        NoClashes => "None of your classes clash.".into(),
+        ClashWarning => "Mind that it clashes with:".into(),
+        Enrolled(title) => format!("Enrolled in {}.", title),
+        Unenrolled(title) => format!("Dropped {}.", title),
+        ElectiveList => "Electives of your group:".into(),
+        EnrolledMark => "enrolled".into(),
+        NoElectives => "Your group has no electives.".into(),
+        NowClass { title, minutes } => format!("Now: {}, {} min left.", title, minutes),
+        NextClass {
+            title,
+            start,
+            minutes,
+            room,
+        } => match room {
+            "" => format!("Next: {} at {}, in {} min.", title, start, minutes),
+            room => format!(
+                "Next: {} at {} in room {}, in {} min.",
+                title, start, room, minutes
+            ),
+        },
+        DayEnds(end) => format!("Classes end at {}.", end),
+        DayOver => "No more classes today.".into(),
+        Attended(title) => format!("✅ {}attended", title),
+        Missed(title) => format!("❌ {}missed", title),
+        MarkedAttended { title, date } => format!("{} on {}: attended.", title, date),
+        MarkedMissed { title, date } => format!("{} on {}: missed.", title, date),
+        ClassOver(titles) => format!("{} is over. Did you attend?", titles),
+        AttendanceList(limit) => format!("Attendance, {} absences allowed per subject:", limit),
+        AttendanceLine {
+            title,
+            attended,
+            missed,
+        } => format!("{}: ✅ {}, ❌ {}", title, attended, missed),
+        AbsenceWarning {
+            title,
+            missed,
+            limit,
+        } => format!(
+            "⚠️ {} of {} absences allowed used in {}.",
+            missed, limit, title
+        ),
+        NoAttendance => "No classes marked yet, use the buttons under /subject.".into(),
+        AttendanceExpired => "This button is no longer valid.".into(),
+        GradeAdded {
+            title,
+            points,
+            total,
+            max,
+        } => format!(
+            "Logged {} points for {}, {} of {} this semester.",
+            points, title, total, max
+        ),
+        GradeRemoved { title, points } => format!("Removed {} points for {}.", points, title),
+        GradeSummary { from, to } => format!("Semester {}–{}:", from, to),
+        NoGrades => "No grades logged this semester.".into(),
+        OnlyPrivate => "Grades and attendance are yours alone, ask for them in a private chat with me.".into(),
+    }
+}
+
+fn uk(msg: Msg) -> String {
+    use Msg::*;
+    match msg {
+        SomethingWentWrong => "Щось пішло не так".into(),
+        NoGroupConfigured => {
+            "Оберіть свою групу через /start або `/config <заклад> <група>`".into()
+        }
+        InvalidInstitution(value) => format!(
+            "Невідомий заклад: {}. Надішліть /config, щоб побачити список.",
+            value
+        ),
+        InvalidGroup(value) => format!("Невідома група: {}.", value),
+        InvalidDate(value) => format!("Неправильна дата: {}.", value),
+        InvalidSlot(value) => format!("Неправильна пара: {}.", value),
+        InvalidWeekday(value) => format!("Неправильний день тижня: {}.", value),
+        InvalidArgument(value) => format!(
+            "Це не пара і не дата: {}. Спробуйте щось на кшталт `/subject 2 завтра`.",
+            value
+        ),
+        InvalidTimezone(value) => format!(
+            "Невідомий часовий пояс: {}. Спробуйте щось на кшталт Europe/Warsaw.",
+            value
+        ),
+        InvalidLanguage(value) => format!("Невідома мова: {}. Спробуйте en або uk.", value),
+        InvalidRoom(value) => format!("Задовга назва аудиторії: {}.", value),
+        InvalidLink(value) => format!("Це не веб-посилання: {}.", value),
+        Usage { command, usage } => format!("Використання: /{} {}", command, usage),
+        NotChatAdmin => "Змінювати налаштування чату можуть лише його адміністратори.".into(),
+        NotEditor => "Змінювати розклад групи можуть лише її редактори.".into(),
+        NotOwner => "Надавати й забирати ролі можуть лише власники.".into(),
+        Institutions => "Оберіть свій заклад командою /config <заклад>:".into(),
+        GroupsOf {
+            institution,
+            groups,
+            code,
+        } => format!(
+            "Групи закладу {}: {}.\nОберіть свою командою /config {} <група>",
+            institution, groups, code
+        ),
+        Ambiguous { group, codes } => format!(
+            "Група {} є в кількох закладах, оберіть один: /config <{}> {}",
+            group, codes, group
+        ),
+        Saved { group, institution } => format!("Збережено: {}, {}.", group, institution),
+        NoClassesOn { date, title } => format!("Пар немає {}: {}.", date, title),
+        NoSubject => "Такої пари не знайдено.".into(),
+        TimesShownIn(zone) => format!("Час пар тепер показано за поясом {}.", zone),
+        Language => {
+            "Мова: українська. Змінити: /lang <en|uk>, або /lang -, щоб як у Telegram.".into()
+        }
+        PickInstitution => "Оберіть свій заклад:".into(),
+        PickFaculty => "Оберіть факультет:".into(),
+        PickYear => "Оберіть курс:".into(),
+        PickGroup => "Оберіть групу:".into(),
+        PickSubgroup => "Оберіть підгрупу:".into(),
+        WholeGroup => "Уся група".into(),
+        Other => "Інше".into(),
+        Year(year) => format!("{} курс", year),
+        NoGroupsYet(institution) => format!("У закладі {} ще немає груп.", institution),
+        MenuExpired => "Це меню застаріло, надішліть /start, щоб почати знову.".into(),
+        WeekOf(date) => format!("Тиждень з {}", date),
+        NoClasses => "Пар немає.".into(),
+        NoClassesBecause(title) => format!("Пар немає: {}.", title),
+        Prev => "◀ назад".into(),
+        Today => "сьогодні".into(),
+        Next => "далі ▶".into(),
+        DayView => "День".into(),
+        WeekView => "Тиждень".into(),
+        OddWeek => "непарний тиждень".into(),
+        EvenWeek => "парний тиждень".into(),
+        Subscribe => "Підписатися на календар".into(),
+        ButtonExpired => "Ця кнопка застаріла, надішліть /today ще раз.".into(),
+        SlotTitle { group, slot, date } => format!("{}: {} пара, {}", group, slot, date),
+        WeekTitle { group, date } => format!("{}: тиждень з {}", group, date),
+        PickGroupFirst => "Спершу оберіть групу".into(),
+        CantPin => {
+            "Не вдалося закріпити розклад, дозвольте мені закріплювати повідомлення в цьому чаті."
+                .into()
+        }
+        Unpinned => "Розклад більше не закріплено.".into(),
+        Cancelled { title, date, slot } => {
+            format!("Скасовано {}: {}, {} пара.", title, date, slot)
+        }
+        Moved { title, date, slot } => {
+            format!("Перенесено {} на {}, {} пара.", title, date, slot)
+        }
+        SlotTaken(titles) => format!("Цю пару вже зайнято: {}.", titles),
+        SeveralClasses => {
+            "У цій парі кілька занять, тож незрозуміло, яке з них мається на увазі.".into()
+        }
+        RoomSet { title, room } => format!("{} тепер в аудиторії {}.", title, room),
+        LinkAdded(title) => format!("Додано посилання до {}.", title),
+        Granted { user, role, scope } => format!("{} тепер має роль {} у {}.", user, role, scope),
+        Revoked { user, scope } => format!("{} більше не має ролі у {}.", user, scope),
+        YourId(id) => format!("Ваш id: {}", id),
+        NoRoles => "У вас ще немає ролей.".into(),
+        NoHistory => "Змін ще не було.".into(),
+        InvalidFile(name) => format!(
+            "Не вдалося прочитати {}: надішліть файл .packed, .csv, .json або .ics до 1 МБ у UTF-8.",
+            name
+        ),
+        CantImport { name, problems } => {
+            format!("Не вдалося імпортувати {}:\n{}", name, problems)
+        }
+        NothingToImport(name) => format!("{} збігається з розкладом, змінювати нічого.", name),
+        ImportPreview { name, changes } => {
+            format!("Зміни з {}:\n{}\n\nЗастосувати їх?", name, changes)
+        }
+        AndMore(count) => format!("…і ще {}", count),
+        ApplyImport => "Застосувати".into(),
+        CancelImport => "Скасувати".into(),
+        Imported {
+            name,
+            subjects,
+            classes,
+            exams,
+        } => format!(
+            "Імпортовано {}: предметів — {}, занять — {}, іспитів — {}.",
+            name, subjects, classes, exams
+        ),
+        ImportCancelled(name) => format!("{} не імпортовано.", name),
+        UploadExpired => "Це завантаження застаріло, надішліть файл ще раз.".into(),
+        TooLong(limit) => format!("Задовгий текст, вкладіться в {} символів.", limit),
+        Announcement { group, text } => format!("Оголошення для {}:\n\n{}", group, text),
+        Announcing { group, count } => {
+            format!("Надсилаю оголошення до {} чатів групи {}…", count, group)
+        }
+        Announced {
+            group,
+            delivered,
+            failed,
+            removed,
+        } => format!(
+            "Оголошення для {}: доставлено — {}, не вдалося — {}. Забуто недосяжних чатів: {}.",
+            group, delivered, failed, removed
+        ),
+        NoChats(group) => format!("Групу {} ще ніхто не обрав.", group),
+        InvalidSubject(value) => format!("У вашій групі немає предмета {}.", value),
+        InvalidHomework(value) => {
+            format!("Завдання {} не знайдено, список — у /hw.", value)
+        }
+        NotAuthor => {
+            "Видалити це завдання може лише чат, що його додав, або редактори групи.".into()
+        }
+        HomeworkShared {
+            id,
+            title,
+            due,
+            group,
+        } => format!(
+            "Додано завдання #{} з {} на {}, його бачать усі в {}.",
+            id, title, due, group
+        ),
+        HomeworkAdded { id, title, due } => format!(
+            "Додано завдання #{} з {} на {}, його бачить лише цей чат.",
+            id, title, due
+        ),
+        HomeworkList => "Домашні завдання:".into(),
+        NoHomework => {
+            "Завдань немає. Додайте: /hw add <предмет> <термін> <текст>.".into()
+        }
+        HomeworkDone { id, title } => format!("Завдання #{} з {} виконано.", id, title),
+        HomeworkUndone { id, title } => {
+            format!("Завдання #{} з {} знову не виконано.", id, title)
+        }
+        HomeworkDeleted { id, title } => format!("Видалено завдання #{} з {}.", id, title),
+        HomeworkDue {
+            id,
+            title,
+            due,
+            text,
+        } => format!(
+            "Нагадування: завдання #{} з {} на {}:\n{}\n\nПозначте виконаним: /hw done {}",
+            id, title, due, text, id
+        ),
+        ExamList => "Сесія:".into(),
+        NoExams => "Для вашої групи іспитів не заплановано.".into(),
+        NextExam { exam, days } => format!("Найближче, днів залишилося: {}\n{}", days, exam),
+        ExamCountdown { exam, days } => {
+            format!("Нагадування, днів залишилося: {}\n{}", days, exam)
+        }
+        NoteSaved { title } => format!("Нотатку до {} збережено.", title),
+        NoteRemoved { title } => format!("Нотатку до {} видалено.", title),
+        NoteList => "Ваші нотатки:".into(),
+        NoNotes => "Нотаток поки немає. Додайте: /note <предмет> <текст>.".into(),
+        UnknownUser(name) => format!(
+            "Бот не знає {}, спершу треба написати йому й обрати групу.",
+            name
+        ),
+        NotFriend(name) => format!(
+            "{} не погодився ділитися вільним часом з вами, запитайте: /friend {}.",
+            name, name
+        ),
+        CommonFree { groups, week } => {
+            format!("Вільно для {} на тижні з {}:", groups, week)
+        }
+        FriendAsked(name) => format!("Запит надіслано {}, ви станете друзями, щойно він погодиться.", name),
+        FriendRequest(name) => format!(
+            "{} хоче ділитися з вами вільним часом, щоб шукати його через /common. Погоджуєтеся?",
+            name
+        ),
+        AcceptFriend => "Погодитися".into(),
+        DeclineFriend => "Відхилити".into(),
+        FriendAdded(name) => format!("Тепер ви з {} друзі.", name),
+        FriendDeclined(name) => format!("Запит від {} відхилено.", name),
+        FriendRemoved(name) => format!("Ви з {} більше не ділитеся вільним часом.", name),
+        FriendRequestExpired => "Цей запит уже недійсний.".into(),
+        FriendList => "Ваші друзі:".into(),
+        FriendPending => "очікує".into(),
+        NoFriends => "Друзів поки немає, запитайте когось: /friend @username.".into(),
+        Clash {
+            day,
+            slot,
+            weeks,
+            first,
+            second,
+        } => format!("{}, пара {}, {}: {} і {}", day, slot, weeks, first, second),
+        ClashList => "Ці пари накладаються:".into(),
+        NoClashes => "Ваші пари не накладаються.".into(),
+        ClashWarning => "Зважте, що він накладається на:".into(),
+        Enrolled(title) => format!("Ви записалися на {}.", title),
+        Unenrolled(title) => format!("Ви виписалися з {}.", title),
+        ElectiveList => "Вибіркові предмети вашої групи:".into(),
+        EnrolledMark => "записані".into(),
+        NoElectives => "У вашій групі немає вибіркових предметів.".into(),
+        NowClass { title, minutes } => {
+            format!("Зараз: {}, залишилося {} хв.", title, minutes)
+        }
+        NextClass {
+            title,
+            start,
+            minutes,
+            room,
+        } => match room {
+            "" => format!("Далі: {} о {}, за {} хв.", title, start, minutes),
+            room => format!(
+                "Далі: {} о {} в аудиторії {}, за {} хв.",
+                title, start, room, minutes
+            ),
+        },
+        DayEnds(end) => format!("Пари закінчуються о {}.", end),
+        DayOver => "На сьогодні пари скінчилися.".into(),
+        Attended(title) => format!("✅ {}був", title),
+        Missed(title) => format!("❌ {}пропустив", title),
+        MarkedAttended { title, date } => format!("{}, {}: присутність.", title, date),
+        MarkedMissed { title, date } => format!("{}, {}: пропуск.", title, date),
+        ClassOver(titles) => format!("Пара {} скінчилася. Ви були на ній?", titles),
+        AttendanceList(limit) => format!(
+            "Відвідування, дозволено {} пропусків з предмета:",
+            limit
+        ),
+        AttendanceLine {
+            title,
+            attended,
+            missed,
+        } => format!("{}: ✅ {}, ❌ {}", title, attended, missed),
+        AbsenceWarning {
+            title,
+            missed,
+            limit,
+        } => format!(
+            "⚠️ Використано {} з {} дозволених пропусків з {}.",
+            missed, limit, title
+        ),
+        NoAttendance => "Ще немає відміток, скористайтеся кнопками під /subject.".into(),
+        AttendanceExpired => "Ця кнопка вже недійсна.".into(),
+        GradeAdded {
+            title,
+            points,
+            total,
+            max,
+        } => format!(
+            "Записано {} балів з {}, {} з {} за семестр.",
+            points, title, total, max
+        ),
+        GradeRemoved { title, points } => format!("Видалено {} балів з {}.", points, title),
+        GradeSummary { from, to } => format!("Семестр {}–{}:", from, to),
+        NoGrades => "У цьому семестрі ще немає оцінок.".into(),
+        OnlyPrivate => "Оцінки й відвідування бачите лише ви, питайте про них в особистому чаті зі мною.".into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn language_from_telegram() {
+        assert_eq!(Language::from_telegram(Some("uk")), Language::Uk);
+        assert_eq!(Language::from_telegram(Some("en-US")), Language::En);
+        assert_eq!(Language::from_telegram(Some("pl")), Language::En);
+        assert_eq!(Language::from_telegram(None), Language::En);
+    }
+
+    #[test]
+    fn catalogs_differ() {
+        for msg in [Msg::NoSubject, Msg::Year("2"), Msg::NoClassesBecause("x")] {
+            let en = Language::En.tr(msg);
+            assert!(!en.is_empty());
+        }
+        assert_eq!(Language::Uk.tr(Msg::Year("2")), "2 курс");
+        assert_ne!(
+            Language::En.tr(Msg::SomethingWentWrong),
+            Language::Uk.tr(Msg::SomethingWentWrong)
+        );
+    }
+}
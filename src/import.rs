@@ -0,0 +1,1027 @@
+use crate::conflicts;
+use crate::data::{
+    unpack_str, Day, Exam, ExamKind, Group, Institution, Repeat, Schedule, Slot, Subject,
+};
+use crate::timetable;
+use anyhow::anyhow;
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Weeks of a calendar event without an end that are looked at to tell its
+/// parity, about a semester.
+const HORIZON: i64 = 26;
+
+/// Formats a timetable file can be in, told apart by its extension.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Format {
+    /// `subjects.packed` or `schedule.packed`, as read by `setup`.
+    Packed,
+    /// One row per class, or per subject without classes, or else one row
+    /// per exam when there is a `kind` column.
+    Csv,
+    /// `{"subjects": [...], "schedule": [...], "exams": [...]}`.
+    Json,
+    /// Weekly events of a calendar, timed by the bells, and one-off events
+    /// of the exam session.
+    Ics,
+}
+
+impl TryFrom<&str> for Format {
+    type Error = anyhow::Error;
+
+    /// Format of a file called `value`.
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let extension = value.rsplit_once('.').map(|(_, e)| e.to_lowercase());
+        match extension.as_deref() {
+            Some("packed") => Ok(Format::Packed),
+            Some("csv") => Ok(Format::Csv),
+            Some("json") => Ok(Format::Json),
+            Some("ics") => Ok(Format::Ics),
+            _ => Err(anyhow!("Not a timetable file: {}", value)),
+        }
+    }
+}
+
+/// Subjects, weekly classes and exams read from a file.
+///
+/// Applying it upserts the subjects and replaces all classes of every subject
+/// that has some in the file, and likewise all exams of every subject that
+/// has some, leaving the rest of the timetable as it is.
+#[derive(Debug, Default, Clone)]
+pub struct Import {
+    pub subjects: Vec<Subject>,
+    pub schedule: Vec<Schedule>,
+    pub exams: Vec<Exam>,
+}
+
+impl Import {
+    /// Ids of the subjects whose classes the import replaces, in file order.
+    pub fn scheduled(&self) -> Vec<i64> {
+        let mut ids: Vec<i64> = vec![];
+        for class in &self.schedule {
+            if !ids.contains(&class.subject_id) {
+                ids.push(class.subject_id);
+            }
+        }
+        ids
+    }
+
+    /// Ids of the subjects whose exams the import replaces, in file order.
+    pub fn examined(&self) -> Vec<i64> {
+        let mut ids: Vec<i64> = vec![];
+        for exam in &self.exams {
+            if !ids.contains(&exam.subject_id) {
+                ids.push(exam.subject_id);
+            }
+        }
+        ids
+    }
+}
+
+/// Reads file `name` of `institution` and checks it against the `existing`
+/// subjects, returning everything wrong with it otherwise.
+///
+/// Calendar events go to the group in their categories, or else to `group`.
+pub fn read(
+    name: &str,
+    text: &str,
+    institution: &Institution,
+    group: &Group,
+    existing: &[Subject],
+) -> Result<Import, Vec<String>> {
+    let format = Format::try_from(name).map_err(|err| vec![err.to_string()])?;
+    let (import, mut problems) = match format {
+        Format::Packed => packed(text),
+        Format::Csv => csv(text),
+        Format::Json => json(text),
+        Format::Ics => ics(text, institution, group, existing),
+    };
+    problems.extend(lint(&import, existing));
+    let empty = import.subjects.is_empty() && import.schedule.is_empty() && import.exams.is_empty();
+    if problems.is_empty() && empty {
+        problems.push("No subjects, classes or exams found".into());
+    }
+    if problems.is_empty() {
+        Ok(import)
+    } else {
+        Err(problems)
+    }
+}
+
+/// Packed subjects, or a packed schedule when the first record starts with a day.
+fn packed(text: &str) -> (Import, Vec<String>) {
+    let first = text.lines().find(|line| !line.trim().is_empty());
+    if first.is_some_and(|line| Day::try_from(line.trim()).is_ok()) {
+        let (schedule, problems) = unpack_str(text, 4);
+        let import = Import {
+            schedule,
+            ..Default::default()
+        };
+        (import, problems)
+    } else {
+        let (subjects, problems) = unpack_str(text, 4);
+        let import = Import {
+            subjects,
+            ..Default::default()
+        };
+        (import, problems)
+    }
+}
+
+/// Rows of `id,title,group,optional,day,repeat,slot` under a header naming
+/// the columns, of which only `id`, `title` and `group` are required.
+///
+/// Rows of the same subject repeat its columns, one for each of its classes.
+fn csv(text: &str) -> (Import, Vec<String>) {
+    let mut import = Import::default();
+    let mut problems = vec![];
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty());
+    let Some((_, header)) = lines.next() else {
+        return (import, problems);
+    };
+    let header = match fields(header) {
+        Ok(header) => header,
+        Err(err) => return (import, vec![format!("Line 1: {}", err)]),
+    };
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|h| h.trim().eq_ignore_ascii_case(name))
+    };
+    if column("kind").is_some() {
+        return csv_exams(&header, lines);
+    }
+    let (Some(id), Some(title), Some(group)) = (column("id"), column("title"), column("group"))
+    else {
+        return (
+            import,
+            vec!["Line 1: Expected id, title and group columns".into()],
+        );
+    };
+    let (optional, day, repeat, slot) = (
+        column("optional"),
+        column("day"),
+        column("repeat"),
+        column("slot"),
+    );
+
+    for (index, line) in lines {
+        let row = fields(line).and_then(|row| {
+            let value = |column: Option<usize>| {
+                column
+                    .and_then(|c| row.get(c))
+                    .map_or("", |value| value.trim())
+            };
+            let subject = Subject {
+                id: value(Some(id)).parse()?,
+                title: value(Some(title)).into(),
+                group: Group::try_from(value(Some(group)))?,
+                optional: value(optional) == "true",
+            };
+            let class = match value(day) {
+                "" => None,
+                day => Some(Schedule {
+                    subject_id: subject.id,
+                    day: Day::try_from(day)?,
+                    repeat: match value(repeat) {
+                        "" => Repeat::Both,
+                        repeat => Repeat::try_from(repeat)?,
+                    },
+                    slot: Slot::try_from(value(slot))?,
+                }),
+            };
+            Ok((subject, class))
+        });
+        match row {
+            Ok((subject, class)) => {
+                match import.subjects.iter().find(|s| s.id == subject.id) {
+                    Some(known) if !same(known, &subject) => problems.push(format!(
+                        "Line {}: Subject {} differs from an earlier line",
+                        index + 1,
+                        subject.id
+                    )),
+                    Some(_) => {}
+                    None => import.subjects.push(subject),
+                }
+                import.schedule.extend(class);
+            }
+            Err(err) => problems.push(format!("Line {}: {}", index + 1, err)),
+        }
+    }
+
+    (import, problems)
+}
+
+/// Rows of `subject_id,kind,date,time,room,lecturer` under `header`, of
+/// which `room` and `lecturer` may be left out.
+fn csv_exams<'a>(
+    header: &[String],
+    lines: impl Iterator<Item = (usize, &'a str)>,
+) -> (Import, Vec<String>) {
+    let mut import = Import::default();
+    let mut problems = vec![];
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|h| h.trim().eq_ignore_ascii_case(name))
+    };
+    let (Some(subject_id), Some(kind), Some(date), Some(time)) = (
+        column("subject_id"),
+        column("kind"),
+        column("date"),
+        column("time"),
+    ) else {
+        return (
+            import,
+            vec!["Line 1: Expected subject_id, kind, date and time columns".into()],
+        );
+    };
+    let (room, lecturer) = (column("room"), column("lecturer"));
+
+    for (index, line) in lines {
+        let exam = fields(line).and_then(|row| {
+            let value = |column: Option<usize>| {
+                column
+                    .and_then(|c| row.get(c))
+                    .map_or("", |value| value.trim())
+            };
+            Ok(Exam {
+                id: 0,
+                subject_id: value(Some(subject_id)).parse()?,
+                kind: ExamKind::try_from(value(Some(kind)))?,
+                starts: starts(value(Some(date)), value(Some(time)))?,
+                room: value(room).into(),
+                lecturer: value(lecturer).into(),
+            })
+        });
+        match exam {
+            Ok(exam) => import.exams.push(exam),
+            Err(err) => problems.push(format!("Line {}: {}", index + 1, err)),
+        }
+    }
+
+    (import, problems)
+}
+
+/// Local start of an exam on `date`, as `2024-01-15` or `15.01.2024`, at
+/// `time`, as `09:00`.
+fn starts(date: &str, time: &str) -> anyhow::Result<NaiveDateTime> {
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .or_else(|_| NaiveDate::parse_from_str(date, "%d.%m.%Y"))
+        .map_err(|_| anyhow!("Not a date: {}", date))?;
+    let time =
+        NaiveTime::parse_from_str(time, "%H:%M").map_err(|_| anyhow!("Not a time: {}", time))?;
+    Ok(date.and_time(time))
+}
+
+/// Fields of a CSV line, which may be quoted with `"` and contain `""`.
+fn fields(line: &str) -> anyhow::Result<Vec<String>> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(anyhow!("Unclosed quote"));
+    }
+    fields.push(field);
+    Ok(fields)
+}
+
+#[derive(Deserialize)]
+struct JsonFile {
+    #[serde(default)]
+    subjects: Vec<JsonSubject>,
+    #[serde(default)]
+    schedule: Vec<JsonClass>,
+    #[serde(default)]
+    exams: Vec<JsonExam>,
+}
+
+#[derive(Deserialize)]
+struct JsonSubject {
+    id: i64,
+    title: String,
+    group: String,
+    #[serde(default)]
+    optional: bool,
+}
+
+#[derive(Deserialize)]
+struct JsonClass {
+    subject_id: i64,
+    day: String,
+    #[serde(default)]
+    repeat: Option<String>,
+    slot: u8,
+}
+
+#[derive(Deserialize)]
+struct JsonExam {
+    subject_id: i64,
+    kind: String,
+    date: String,
+    time: String,
+    #[serde(default)]
+    room: String,
+    #[serde(default)]
+    lecturer: String,
+}
+
+fn json(text: &str) -> (Import, Vec<String>) {
+    let mut import = Import::default();
+    let file: JsonFile = match serde_json::from_str(text) {
+        Ok(file) => file,
+        Err(err) => return (import, vec![err.to_string()]),
+    };
+    let mut problems = vec![];
+    for (index, subject) in file.subjects.into_iter().enumerate() {
+        match Group::try_from(subject.group.as_str()) {
+            Ok(group) => import.subjects.push(Subject {
+                id: subject.id,
+                title: subject.title,
+                group,
+                optional: subject.optional,
+            }),
+            Err(err) => problems.push(format!("Subject {}: {}", index + 1, err)),
+        }
+    }
+    for (index, class) in file.schedule.into_iter().enumerate() {
+        let schedule = || -> anyhow::Result<Schedule> {
+            Ok(Schedule {
+                subject_id: class.subject_id,
+                day: Day::try_from(class.day.as_str())?,
+                repeat: Repeat::try_from(class.repeat.as_deref().unwrap_or("Both"))?,
+                slot: Slot::try_from(class.slot.to_string().as_str())?,
+            })
+        };
+        match schedule() {
+            Ok(schedule) => import.schedule.push(schedule),
+            Err(err) => problems.push(format!("Class {}: {}", index + 1, err)),
+        }
+    }
+    for (index, exam) in file.exams.into_iter().enumerate() {
+        let read = || -> anyhow::Result<Exam> {
+            Ok(Exam {
+                id: 0,
+                subject_id: exam.subject_id,
+                kind: ExamKind::try_from(exam.kind.as_str())?,
+                starts: starts(&exam.date, &exam.time)?,
+                room: exam.room,
+                lecturer: exam.lecturer,
+            })
+        };
+        match read() {
+            Ok(read) => import.exams.push(read),
+            Err(err) => problems.push(format!("Exam {}: {}", index + 1, err)),
+        }
+    }
+
+    (import, problems)
+}
+
+/// Weekly events of a calendar, each starting when a slot does, along with
+/// one-off events whose categories name a kind of exam.
+///
+/// Events repeating weekly on days of one parity only, once their
+/// `EXDATE`s are left out, take that parity.
+/// Subjects are told apart by title and group, reusing the ids of `existing`
+/// ones and numbering new ones after them.
+fn ics(
+    text: &str,
+    institution: &Institution,
+    group: &Group,
+    existing: &[Subject],
+) -> (Import, Vec<String>) {
+    let mut import = Import::default();
+    let mut problems = vec![];
+    let mut next_id = existing.iter().map(|s| s.id + 1).max().unwrap_or(0);
+
+    for event in events(text) {
+        let summary = event.get("SUMMARY").cloned().unwrap_or_default();
+        let categories: Vec<&str> = event
+            .get("CATEGORIES")
+            .map_or(vec![], |c| c.split(',').map(str::trim).collect());
+        let kind = categories.iter().find_map(|c| ExamKind::try_from(*c).ok());
+        let group = categories
+            .iter()
+            .filter(|c| ExamKind::try_from(**c).is_err())
+            .find_map(|c| Group::try_from(*c).ok())
+            .unwrap_or_else(|| group.clone());
+        let read = match kind {
+            Some(kind) if !event.contains_key("RRULE") => {
+                exam(&event, kind, institution).map(Event::Exam)
+            }
+            _ => class(&event, institution).map(Event::Class),
+        };
+        let read = match read {
+            Ok(read) => read,
+            Err(err) => {
+                problems.push(format!("Event {}: {}", summary, err));
+                continue;
+            }
+        };
+
+        let known = |s: &Subject| s.title == summary && s.group == group;
+        if !import.subjects.iter().any(known) {
+            let subject = existing
+                .iter()
+                .find(|s| known(s))
+                .cloned()
+                .unwrap_or_else(|| {
+                    next_id += 1;
+                    Subject {
+                        id: next_id - 1,
+                        title: summary.clone(),
+                        group: group.clone(),
+                        optional: false,
+                    }
+                });
+            import.subjects.push(subject);
+        }
+        let subject_id = import
+            .subjects
+            .iter()
+            .find(|s| known(s))
+            .map_or(0, |s| s.id);
+        match read {
+            Event::Class(class) => import.schedule.push(Schedule {
+                subject_id,
+                ..class
+            }),
+            Event::Exam(exam) => import.exams.push(Exam { subject_id, ..exam }),
+        }
+    }
+
+    (import, problems)
+}
+
+/// A calendar event read by [`ics`], of no subject yet.
+enum Event {
+    Class(Schedule),
+    Exam(Exam),
+}
+
+/// The weekly class a calendar event stands for.
+fn class(event: &HashMap<String, String>, institution: &Institution) -> anyhow::Result<Schedule> {
+    let start = event.get("DTSTART").ok_or(anyhow!("Missing DTSTART"))?;
+    let start = local(start, &institution.timezone)?;
+    let slot = Slot::ALL
+        .into_iter()
+        .find(|slot| institution.bells.times(*slot).0 == start.time())
+        .ok_or(anyhow!(
+            "Starts at {}, not when a slot does",
+            start.time().format("%H:%M")
+        ))?;
+    let date = start.date();
+    let dt = timetable::noon(date, &institution.timezone);
+    let day = Day::try_from(&dt)?;
+
+    let rule = event.get("RRULE").map_or("", String::as_str);
+    let rule: HashMap<&str, &str> = rule
+        .split(';')
+        .filter_map(|part| part.split_once('='))
+        .collect();
+    if rule.get("FREQ") != Some(&"WEEKLY") {
+        return Err(anyhow!("Doesn't repeat weekly"));
+    }
+    let interval = rule.get("INTERVAL").copied().unwrap_or("1");
+    let weeks = match interval.parse::<i64>() {
+        Ok(weeks @ 1..) => weeks,
+        _ => return Err(anyhow!("Not an interval: {}", interval)),
+    };
+    let until = match rule.get("UNTIL") {
+        Some(until) if until.contains('T') => Some(local(until, &institution.timezone)?),
+        Some(until) => Some(local(
+            &format!("VALUE=DATE|{}", until),
+            &institution.timezone,
+        )?),
+        None => None,
+    };
+    let count = match rule.get("COUNT") {
+        Some(count) => count.parse::<i64>()?.min(HORIZON),
+        None => HORIZON,
+    };
+    let mut excluded = vec![];
+    for line in event.get("EXDATE").map_or("", String::as_str).lines() {
+        let (params, values) = line.split_once('|').unwrap_or(("", line));
+        for value in values.split(',') {
+            let value = format!("{}|{}", params, value.trim());
+            excluded.push(local(&value, &institution.timezone)?.date());
+        }
+    }
+
+    // Parity is by week of the month, so it takes the dates themselves to
+    // tell which weeks a class is on
+    let parities: Vec<Repeat> = (0..count)
+        .map_while(|n| date.checked_add_signed(Duration::try_weeks(n.checked_mul(weeks)?)?))
+        .take_while(|date| until.is_none_or(|until| *date <= until.date()))
+        .filter(|date| !excluded.contains(date))
+        .map(|date| Repeat::from(&timetable::noon(date, &institution.timezone)))
+        .collect();
+    let repeat = match (
+        parities.contains(&Repeat::Odd),
+        parities.contains(&Repeat::Even),
+    ) {
+        (true, true) if weeks == 1 => Repeat::Both,
+        (true, true) => return Err(anyhow!("Repeats every {} weeks", weeks)),
+        (true, false) => Repeat::Odd,
+        (false, true) => Repeat::Even,
+        (false, false) => return Err(anyhow!("Never takes place")),
+    };
+    Ok(Schedule {
+        subject_id: 0,
+        day,
+        repeat,
+        slot,
+    })
+}
+
+/// The exam a one-off calendar event stands for, held in its `LOCATION` by
+/// the lecturer in its `DESCRIPTION`.
+fn exam(
+    event: &HashMap<String, String>,
+    kind: ExamKind,
+    institution: &Institution,
+) -> anyhow::Result<Exam> {
+    let start = event.get("DTSTART").ok_or(anyhow!("Missing DTSTART"))?;
+    let text = |name: &str| event.get(name).map_or("", |v| v.trim()).to_string();
+    Ok(Exam {
+        id: 0,
+        subject_id: 0,
+        kind,
+        starts: local(start, &institution.timezone)?,
+        room: text("LOCATION"),
+        lecturer: text("DESCRIPTION"),
+    })
+}
+
+/// Properties of every `VEVENT` in a calendar, by name without parameters,
+/// except that `DTSTART` and `EXDATE` keep their `TZID` or `VALUE` ahead of
+/// a `|`. `EXDATE`s given more than once are kept a line each.
+fn events(text: &str) -> Vec<HashMap<String, String>> {
+    // Long lines are folded by starting their continuations with a space
+    let mut lines: Vec<String> = vec![];
+    for line in text.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.trim_end().into()),
+        }
+    }
+
+    let mut events = vec![];
+    let mut event: Option<HashMap<String, String>> = None;
+    for line in lines {
+        match line.as_str() {
+            "BEGIN:VEVENT" => event = Some(HashMap::new()),
+            "END:VEVENT" => events.extend(event.take()),
+            _ => {
+                let (Some(event), Some((key, value))) = (event.as_mut(), line.split_once(':'))
+                else {
+                    continue;
+                };
+                let (name, params) = key.split_once(';').unwrap_or((key, ""));
+                let value = value.replace("\\,", ",").replace("\\;", ";");
+                let name = name.to_uppercase();
+                let value = match name.as_str() {
+                    "DTSTART" | "EXDATE" => format!("{}|{}", params, value),
+                    _ => value,
+                };
+                match event.get_mut("EXDATE") {
+                    Some(exdates) if name == "EXDATE" => {
+                        exdates.push('\n');
+                        exdates.push_str(&value);
+                    }
+                    _ => {
+                        event.insert(name, value);
+                    }
+                }
+            }
+        }
+    }
+    events
+}
+
+/// Local date and time of a `DTSTART` as kept by [`events`], in `timezone`
+/// unless given in UTC.
+fn local(start: &str, timezone: &chrono_tz::Tz) -> anyhow::Result<NaiveDateTime> {
+    let (params, value) = start.split_once('|').unwrap_or(("", start));
+    if params.contains("VALUE=DATE") && !params.contains("VALUE=DATE-TIME") {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d")?;
+        return Ok(date.and_time(NaiveTime::MIN));
+    }
+    match value.strip_suffix('Z') {
+        Some(utc) => {
+            let utc = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S")?;
+            Ok(Utc
+                .from_utc_datetime(&utc)
+                .with_timezone(timezone)
+                .naive_local())
+        }
+        None => Ok(NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")?),
+    }
+}
+
+/// What is wrong with `import` as a whole, given the `existing` subjects.
+pub fn lint(import: &Import, existing: &[Subject]) -> Vec<String> {
+    let mut problems = vec![];
+    for (index, subject) in import.subjects.iter().enumerate() {
+        if import.subjects[..index].iter().any(|s| s.id == subject.id) {
+            problems.push(format!("Subject {} is given twice", subject.id));
+        }
+        if subject.title.trim().is_empty() {
+            problems.push(format!("Subject {} has no title", subject.id));
+        }
+    }
+    for (index, class) in import.schedule.iter().enumerate() {
+        let id = class.subject_id;
+        if !import.subjects.iter().chain(existing).any(|s| s.id == id) {
+            problems.push(format!(
+                "{} is of subject {}, which is neither in the file nor in the timetable",
+                describe(class),
+                id
+            ));
+        }
+        let overlaps =
+            |other: &Schedule| other.subject_id == id && conflicts::overlap(other, class).is_some();
+        if import.schedule[..index].iter().any(overlaps) {
+            problems.push(format!(
+                "Subject {} is given twice on {}",
+                id,
+                describe(class)
+            ));
+        }
+    }
+    for (index, exam) in import.exams.iter().enumerate() {
+        let id = exam.subject_id;
+        if !import.subjects.iter().chain(existing).any(|s| s.id == id) {
+            problems.push(format!(
+                "{} is of subject {}, which is neither in the file nor in the timetable",
+                exam.describe(),
+                id
+            ));
+        }
+        if import.exams[..index].iter().any(|e| e.same(exam)) {
+            problems.push(format!("Subject {} has {} twice", id, exam.describe()));
+        }
+    }
+    problems
+}
+
+/// Lines telling what applying `import` to the timetable of `subjects`,
+/// `schedule` and `exams` changes: `+` added, `−` removed and `~` changed.
+pub fn diff(
+    import: &Import,
+    subjects: &[Subject],
+    schedule: &[Schedule],
+    exams: &[Exam],
+) -> Vec<String> {
+    let mut lines = vec![];
+    for subject in &import.subjects {
+        match subjects.iter().find(|s| s.id == subject.id) {
+            None => lines.push(format!("+ {}", show(subject))),
+            Some(old) if !same(old, subject) => {
+                lines.push(format!("~ {} → {}", show(old), show(subject)))
+            }
+            Some(_) => {}
+        }
+    }
+
+    let title = |id: i64| {
+        import
+            .subjects
+            .iter()
+            .chain(subjects)
+            .find(|s| s.id == id)
+            .map_or(String::new(), |s| s.title.clone())
+    };
+    for id in import.scheduled() {
+        let title = title(id);
+        let old: Vec<&Schedule> = schedule.iter().filter(|c| c.subject_id == id).collect();
+        let new: Vec<&Schedule> = import
+            .schedule
+            .iter()
+            .filter(|c| c.subject_id == id)
+            .collect();
+        for class in &old {
+            if !new.iter().any(|c| same_class(c, class)) {
+                lines.push(format!("− {}: {}", title, describe(class)));
+            }
+        }
+        for class in &new {
+            if !old.iter().any(|c| same_class(c, class)) {
+                lines.push(format!("+ {}: {}", title, describe(class)));
+            }
+        }
+    }
+
+    for id in import.examined() {
+        let title = title(id);
+        let old: Vec<&Exam> = exams.iter().filter(|e| e.subject_id == id).collect();
+        let new: Vec<&Exam> = import.exams.iter().filter(|e| e.subject_id == id).collect();
+        for exam in &old {
+            if !new.iter().any(|e| e.same(exam)) {
+                lines.push(format!("− {}: {}", title, exam.describe()));
+            }
+        }
+        for exam in &new {
+            if !old.iter().any(|e| e.same(exam)) {
+                lines.push(format!("+ {}: {}", title, exam.describe()));
+            }
+        }
+    }
+    lines
+}
+
+/// `Mon slot 1, Both`, the way classes are shown in the diff and audit log.
+pub fn describe(class: &Schedule) -> String {
+    format!(
+        "{:?} slot {}, {:?}",
+        class.day, class.slot as u8, class.repeat
+    )
+}
+
+/// `3 Algebra (K-25)`, with optional subjects marked.
+fn show(subject: &Subject) -> String {
+    let optional = if subject.optional { ", optional" } else { "" };
+    format!(
+        "{} {} ({}{})",
+        subject.id, subject.title, subject.group, optional
+    )
+}
+
+fn same(a: &Subject, b: &Subject) -> bool {
+    a.id == b.id && a.title == b.title && a.group == b.group && a.optional == b.optional
+}
+
+fn same_class(a: &Schedule, b: &Schedule) -> bool {
+    a.subject_id == b.subject_id && a.day == b.day && a.repeat == b.repeat && a.slot == b.slot
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Bells;
+
+    fn institution() -> Institution {
+        Institution {
+            id: 0,
+            code: "default".into(),
+            name: "Default".into(),
+            timezone: chrono_tz::Europe::Kiev,
+            bells: Bells::default(),
+        }
+    }
+
+    fn k25() -> Group {
+        Group::try_from("K-25").unwrap()
+    }
+
+    fn subject(id: i64, title: &str) -> Subject {
+        Subject {
+            id,
+            title: title.into(),
+            group: k25(),
+            optional: false,
+        }
+    }
+
+    fn read(name: &str, text: &str, existing: &[Subject]) -> Result<Import, Vec<String>> {
+        super::read(name, text, &institution(), &k25(), existing)
+    }
+
+    #[test]
+    fn formats_by_extension() {
+        assert_eq!(Format::try_from("subjects.packed").unwrap(), Format::Packed);
+        assert_eq!(Format::try_from("K-25.CSV").unwrap(), Format::Csv);
+        assert_eq!(Format::try_from("a.b.json").unwrap(), Format::Json);
+        assert_eq!(Format::try_from("calendar.ics").unwrap(), Format::Ics);
+        assert!(Format::try_from("timetable.pdf").is_err());
+        assert!(Format::try_from("ics").is_err());
+    }
+
+    #[test]
+    fn packed_files() {
+        let subjects = read(
+            "subjects.packed",
+            "0\nAlgebra\nK-25\n-\n\n1\nLogic\nK-25\ntrue\n",
+            &[],
+        );
+        let subjects = subjects.unwrap().subjects;
+        assert_eq!(subjects.len(), 2);
+        assert!(subjects[1].optional);
+
+        let schedule = read(
+            "schedule.packed",
+            "Mon\n0\nBoth\n2\n\nTue\n1\nOdd\nIV\n",
+            &[],
+        );
+        assert_eq!(
+            schedule.unwrap_err(),
+            vec![
+                "Mon slot 2, Both is of subject 0, which is neither in the file nor in the timetable",
+                "Tue slot 4, Odd is of subject 1, which is neither in the file nor in the timetable",
+            ]
+        );
+
+        let existing = [subject(0, "Algebra"), subject(1, "Logic")];
+        let broken = read(
+            "schedule.packed",
+            "Mon\n0\nBoth\n2\n\nSun\n1\nOdd\n1\n",
+            &existing,
+        );
+        assert_eq!(broken.unwrap_err(), vec!["Record 2: Not a day: Sun"]);
+    }
+
+    #[test]
+    fn csv_rows() {
+        let text = "id,title,group,day,repeat,slot\n\
+                    0,\"Algebra, linear\",K-25,Mon,,1\n\
+                    0,\"Algebra, linear\",K-25,Wed,Odd,2\n\
+                    1,Logic,K-25,,,\n";
+        let import = read("K-25.csv", text, &[]).unwrap();
+        assert_eq!(import.subjects.len(), 2);
+        assert_eq!(import.subjects[0].title, "Algebra, linear");
+        assert_eq!(import.schedule.len(), 2);
+        assert_eq!(import.schedule[1].repeat, Repeat::Odd);
+
+        let text = "id,title,group,day,slot\n0,Algebra,K-25,Mon,1\n0,Logic,K-25,Tue,2\n1,Logic,K-25,Tue,5\n";
+        assert_eq!(
+            read("K-25.csv", text, &[]).unwrap_err(),
+            vec![
+                "Line 3: Subject 0 differs from an earlier line",
+                "Line 4: Not a slot: 5",
+            ]
+        );
+        assert!(read("K-25.csv", "name\nAlgebra\n", &[]).is_err());
+    }
+
+    #[test]
+    fn json_document() {
+        let text = r#"{
+            "subjects": [{"id": 0, "title": "Algebra", "group": "K-25"}],
+            "schedule": [
+                {"subject_id": 0, "day": "Mon", "slot": 1},
+                {"subject_id": 0, "day": "Mon", "repeat": "Odd", "slot": 1}
+            ]
+        }"#;
+        assert_eq!(
+            read("t.json", text, &[]).unwrap_err(),
+            vec!["Subject 0 is given twice on Mon slot 1, Odd"]
+        );
+        assert!(read("t.json", "[]", &[]).is_err());
+        assert_eq!(
+            read("t.json", "{}", &[]).unwrap_err(),
+            vec!["No subjects, classes or exams found"]
+        );
+    }
+
+    #[test]
+    fn exam_files() {
+        let existing = [subject(0, "Algebra")];
+        let text = "subject_id,kind,date,time,room
+                    0,exam,2024-01-15,09:00,204
+                    0,Consultation,12.01.2024,14:30,
+";
+        let import = read("session.csv", text, &existing).unwrap();
+        let exams: Vec<String> = import.exams.iter().map(Exam::describe).collect();
+        assert_eq!(
+            exams,
+            vec![
+                "exam 2024-01-15 09:00, room 204",
+                "consultation 2024-01-12 14:30"
+            ]
+        );
+
+        let text = "subject_id,kind,date,time
+0,test,2024-01-15,09:00
+1,exam,2024-01-15,9
+";
+        assert_eq!(
+            read("session.csv", text, &existing).unwrap_err(),
+            vec!["Line 2: Not a kind of exam: test", "Line 3: Not a time: 9"]
+        );
+
+        let text = r#"{"exams": [
+            {"subject_id": 0, "kind": "credit", "date": "2024-01-15", "time": "09:00", "lecturer": "O. Petrenko"},
+            {"subject_id": 0, "kind": "credit", "date": "2024-01-15", "time": "09:00", "lecturer": "O. Petrenko"}
+        ]}"#;
+        assert_eq!(
+            read("session.json", text, &existing).unwrap_err(),
+            vec!["Subject 0 has credit 2024-01-15 09:00, O. Petrenko twice"]
+        );
+    }
+
+    #[test]
+    fn calendar_events() {
+        let text = "BEGIN:VCALENDAR\r\n\
+                    BEGIN:VEVENT\r\n\
+                    SUMMARY:Algebra\r\n\
+                    DTSTART;TZID=Europe/Kiev:20231016T084000\r\n\
+                    RRULE:FREQ=WEEKLY;BYDAY=MO\r\n\
+                    END:VEVENT\r\n\
+                    BEGIN:VEVENT\r\n\
+                    SUMMARY:Physical educ\r\n ation\r\n\
+                    DTSTART:20231017T073500Z\r\n\
+                    RRULE:FREQ=WEEKLY;COUNT=4\r\n\
+                    EXDATE:20231024T073500Z\r\n\
+                    EXDATE:20231107T073500Z\r\n\
+                    CATEGORIES:K-26\r\n\
+                    END:VEVENT\r\n\
+                    END:VCALENDAR\r\n";
+        let existing = [subject(4, "Algebra")];
+        let import = read("k25.ics", text, &existing).unwrap();
+        assert_eq!(import.subjects.len(), 2);
+        assert_eq!(import.subjects[0].id, 4);
+        assert_eq!(import.subjects[1].id, 5);
+        assert_eq!(import.subjects[1].title, "Physical education");
+        assert_eq!(import.subjects[1].group.as_str(), "K-26");
+        let classes: Vec<String> = import.schedule.iter().map(describe).collect();
+        assert_eq!(classes, vec!["Mon slot 1, Both", "Tue slot 2, Even"]);
+
+        let text = "BEGIN:VEVENT\nSUMMARY:Algebra\nDTSTART:20240115T090000\n\
+                    CATEGORIES:Exam\nLOCATION:204\nEND:VEVENT\n";
+        let import = read("k25.ics", text, &existing).unwrap();
+        assert_eq!(import.subjects[0].id, 4);
+        assert!(import.schedule.is_empty());
+        assert_eq!(
+            import.exams[0].describe(),
+            "exam 2024-01-15 09:00, room 204"
+        );
+
+        let text =
+            "BEGIN:VEVENT\nSUMMARY:Late\nDTSTART:20231016T090000\nRRULE:FREQ=WEEKLY\nEND:VEVENT\n";
+        assert_eq!(
+            read("k25.ics", text, &[]).unwrap_err(),
+            vec!["Event Late: Starts at 09:00, not when a slot does"]
+        );
+
+        // Week parity goes by the week of the month, which every other week
+        // doesn't keep to: 16.10 and 30.10 are even, 13.11 is odd
+        let text = "BEGIN:VEVENT\nSUMMARY:Algebra\nDTSTART:20231016T084000\n\
+                    RRULE:FREQ=WEEKLY;INTERVAL=2\nEND:VEVENT\n";
+        assert_eq!(
+            read("k25.ics", text, &[]).unwrap_err(),
+            vec!["Event Algebra: Repeats every 2 weeks"]
+        );
+    }
+
+    #[test]
+    fn diff_of_changes() {
+        let subjects = [subject(0, "Algebra"), subject(1, "Logic")];
+        let class = |subject_id, day, slot| Schedule {
+            subject_id,
+            day,
+            repeat: Repeat::Both,
+            slot,
+        };
+        let schedule = [class(0, Day::Mon, Slot::I), class(1, Day::Tue, Slot::II)];
+        let exam = |kind, room: &str| Exam {
+            id: 0,
+            subject_id: 1,
+            kind,
+            starts: NaiveDate::from_ymd_opt(2024, 1, 15)
+                .unwrap()
+                .and_hms_opt(9, 0, 0)
+                .unwrap(),
+            room: room.into(),
+            lecturer: String::new(),
+        };
+        let exams = [
+            exam(ExamKind::Exam, "204"),
+            exam(ExamKind::Consultation, ""),
+        ];
+        let import = Import {
+            subjects: vec![subject(0, "Linear algebra"), subject(2, "Geometry")],
+            schedule: vec![class(0, Day::Mon, Slot::I), class(0, Day::Fri, Slot::III)],
+            exams: vec![
+                exam(ExamKind::Exam, "301"),
+                exam(ExamKind::Consultation, ""),
+            ],
+        };
+        assert_eq!(
+            diff(&import, &subjects, &schedule, &exams),
+            vec![
+                "~ 0 Algebra (K-25) → 0 Linear algebra (K-25)",
+                "+ 2 Geometry (K-25)",
+                "+ Linear algebra: Fri slot 3, Both",
+                "− Logic: exam 2024-01-15 09:00, room 204",
+                "+ Logic: exam 2024-01-15 09:00, room 301",
+            ]
+        );
+    }
+}
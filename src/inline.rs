@@ -0,0 +1,314 @@
+use crate::bot::{expr, failed, Error};
+use crate::clock::Clock;
+use crate::data::{Group, Institution, Slot};
+use crate::i18n::{Language, Msg};
+use crate::store::ScheduleStore;
+use crate::timetable::{self, Span};
+use chrono::NaiveDate;
+use teloxide::types::ChatId;
+use teloxide::utils::markdown as md;
+
+/// A result offered in inline mode, with the Markdown `text` it sends.
+#[derive(PartialEq, Debug)]
+pub struct Article {
+    pub id: &'static str,
+    pub title: String,
+    pub text: String,
+}
+
+/// Answers an inline `query` like `tomorrow`, `K-25 mon` or `2 13.10.2023` from
+/// the user behind `chat_id` with their slot, day and week.
+///
+/// Slots and dates are parsed the way `/subject` does, any other word names a
+/// group of the user's own institution or one that only a single institution has.
+pub async fn answer<S: ScheduleStore>(
+    store: &S,
+    clock: &dyn Clock,
+    chat_id: ChatId,
+    language: Language,
+    query: &str,
+) -> Result<Vec<Article>, Error> {
+    let user = match store.get_user(&chat_id).await {
+        Ok(ok) => Some(ok),
+        Err(sqlx::Error::RowNotFound) => None,
+        Err(err) => return Err(failed(chat_id, "get user")(err)),
+    };
+
+    let expr = expr(chat_id, query)?;
+    let mut found = None;
+    for word in &expr.words {
+        match find(store, chat_id, user.as_ref(), word).await? {
+            Some(value) => found = Some(value),
+            None => return Err(Error::InvalidGroup(chat_id, word.clone())),
+        }
+    }
+
+    let (institution, group) = match (found, user) {
+        (Some(found), _) => found,
+        (None, Some(user)) => {
+            let institution = store
+                .get_institution(user.institution)
+                .await
+                .map_err(failed(chat_id, "get user institution"))?;
+            (institution, user.group)
+        }
+        (None, None) => return Err(Error::NoGroupConfigured(chat_id)),
+    };
+    let reader = timetable::reader(store, chat_id, &institution).await?;
+
+    let now = clock.now().with_timezone(&institution.timezone);
+    let date = match expr.date {
+        Some(when) => when
+            .resolve(now.date_naive())
+            .ok_or_else(|| Error::InvalidDate(chat_id, query.into()))?,
+        None => now.date_naive(),
+    };
+    let slot = expr.slot.unwrap_or_else(|| institution.bells.slot_at(&now));
+
+    let text = at(
+        store,
+        chat_id,
+        language,
+        &institution,
+        &group,
+        reader,
+        date,
+        slot,
+    )
+    .await?;
+    let mut articles = vec![Article {
+        id: "slot",
+        title: language.tr(Msg::SlotTitle {
+            group: group.as_str(),
+            slot: slot as u8,
+            date: &language.day(date, "%d.%m"),
+        }),
+        text,
+    }];
+    let week = Msg::WeekTitle {
+        group: group.as_str(),
+        date: &date.format("%d.%m.%Y").to_string(),
+    };
+    for (id, span, title) in [
+        (
+            "day",
+            Span::Day,
+            format!("{}: {}", group, language.day(date, "%d.%m.%Y")),
+        ),
+        ("week", Span::Week, language.tr(week)),
+    ] {
+        let text = timetable::page(
+            store,
+            chat_id,
+            chat_id,
+            language,
+            &institution,
+            &group,
+            reader,
+            span,
+            date,
+        )
+        .await?;
+        articles.push(Article { id, title, text });
+    }
+    Ok(articles)
+}
+
+/// The institution and group named by `word`, preferring the user's institution.
+async fn find<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+    user: Option<&crate::data::User>,
+    word: &str,
+) -> Result<Option<(Institution, Group)>, Error> {
+    let Ok(group) = Group::try_from(word) else {
+        return Ok(None);
+    };
+    let institutions = store
+        .get_institutions()
+        .await
+        .map_err(failed(chat_id, "get institutions"))?;
+
+    let mut found = vec![];
+    for institution in institutions {
+        let groups = store
+            .get_groups(institution.id)
+            .await
+            .map_err(failed(chat_id, "get groups"))?;
+        if groups.contains(&group) {
+            if user.is_some_and(|u| u.institution == institution.id) {
+                return Ok(Some((institution, group)));
+            }
+            found.push(institution);
+        }
+    }
+    match found.len() {
+        1 => Ok(Some((found.remove(0), group))),
+        _ => Ok(None),
+    }
+}
+
+/// Classes of `group` in `slot` on `date`.
+#[allow(clippy::too_many_arguments)]
+async fn at<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+    language: Language,
+    institution: &Institution,
+    group: &Group,
+    reader: chrono_tz::Tz,
+    date: NaiveDate,
+    slot: Slot,
+) -> Result<String, Error> {
+    let mut text = timetable::header(&language.day(date, "%d.%m.%Y"), group);
+    text.push('\n');
+
+    let holiday = store
+        .get_holiday(institution.id, date)
+        .await
+        .map_err(failed(chat_id, "get holiday"))?;
+    if let Some(holiday) = holiday {
+        text.push_str(&md::escape(
+            &language.tr(Msg::NoClassesBecause(&holiday.title)),
+        ));
+        return Ok(text);
+    }
+
+    let lessons = timetable::lessons(
+        store,
+        chat_id,
+        chat_id,
+        institution,
+        group,
+        reader,
+        date,
+        slot,
+    )
+    .await?;
+    if lessons.is_empty() {
+        text.push_str(&md::escape(&language.tr(Msg::NoClasses)));
+    }
+    let lines: Vec<String> = lessons.iter().map(ToString::to_string).collect();
+    text.push_str(&lines.join("\n"));
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::TestClock;
+    use crate::data::{Bells, Day, Repeat, Schedule, Subject, User};
+    use crate::store::MemoryStore;
+    use chrono::{TimeZone, Utc};
+
+    const CHAT: ChatId = ChatId(42);
+
+    fn group(name: &str) -> Group {
+        Group::try_from(name).unwrap()
+    }
+
+    /// K-25 has a class in the second slot on Mondays. Tuesday, 10 Oct 2023, 9:00 in Kyiv.
+    async fn setup() -> (MemoryStore, TestClock) {
+        let store = MemoryStore::new();
+        for name in ["K-25", "K-26"] {
+            store.add_group(0, &group(name)).await.unwrap();
+        }
+        store
+            .add_subject(
+                0,
+                &Subject {
+                    id: 0,
+                    title: "Test title".into(),
+                    group: group("K-25"),
+                    optional: false,
+                },
+            )
+            .await
+            .unwrap();
+        store
+            .add_schedule(
+                0,
+                &Schedule {
+                    subject_id: 0,
+                    day: Day::Mon,
+                    repeat: Repeat::Both,
+                    slot: Slot::II,
+                },
+            )
+            .await
+            .unwrap();
+        let clock = TestClock::new(Utc.with_ymd_and_hms(2023, 10, 10, 6, 0, 0).unwrap());
+        (store, clock)
+    }
+
+    #[tokio::test]
+    async fn answer_names_group_and_weekday() {
+        let (store, clock) = setup().await;
+
+        let articles = answer(&store, &clock, CHAT, Language::En, "K-25 mon")
+            .await
+            .unwrap();
+        let ids: Vec<&str> = articles.iter().map(|a| a.id).collect();
+        assert_eq!(ids, ["slot", "day", "week"]);
+        assert_eq!(articles[0].title, "K-25: slot 1, Mon 16.10");
+        assert!(articles[0].text.contains("No classes"));
+        assert_eq!(articles[1].title, "K-25: Mon 16.10.2023");
+        assert!(articles[1].text.contains("10:35–12:10 Test title"));
+        assert_eq!(articles[2].title, "K-25: week of 16.10.2023");
+
+        let articles = answer(&store, &clock, CHAT, Language::En, "2 пн K-25")
+            .await
+            .unwrap();
+        assert!(articles[0].text.contains("Test title"));
+    }
+
+    #[tokio::test]
+    async fn answer_defaults_to_users_group() {
+        let (store, clock) = setup().await;
+        let result = answer(&store, &clock, CHAT, Language::En, "tomorrow").await;
+        assert!(matches!(result, Err(Error::NoGroupConfigured(CHAT))));
+
+        let user = User {
+            institution: 0,
+            group: group("K-26"),
+        };
+        store.add_user(&CHAT, &user).await.unwrap();
+        let articles = answer(&store, &clock, CHAT, Language::En, "tomorrow")
+            .await
+            .unwrap();
+        assert_eq!(articles[1].title, "K-26: Wed 11.10.2023");
+
+        let result = answer(&store, &clock, CHAT, Language::En, "K-99").await;
+        assert!(matches!(result, Err(Error::InvalidGroup(CHAT, word)) if word == "K-99"));
+    }
+
+    #[tokio::test]
+    async fn answer_prefers_users_institution() {
+        let (store, clock) = setup().await;
+        store
+            .add_institution(&Institution {
+                id: 1,
+                code: "other".into(),
+                name: "Other".into(),
+                timezone: chrono_tz::Europe::Lisbon,
+                bells: Bells::default(),
+            })
+            .await
+            .unwrap();
+        store.add_group(1, &group("K-25")).await.unwrap();
+
+        // Ambiguous without a configured institution
+        let result = answer(&store, &clock, CHAT, Language::En, "K-25").await;
+        assert!(matches!(result, Err(Error::InvalidGroup(CHAT, _))));
+
+        let user = User {
+            institution: 1,
+            group: group("K-25"),
+        };
+        store.add_user(&CHAT, &user).await.unwrap();
+        let articles = answer(&store, &clock, CHAT, Language::En, "K-25 mon")
+            .await
+            .unwrap();
+        assert!(!articles[1].text.contains("Test title"));
+    }
+}
@@ -1,5 +1,31 @@
+pub mod announce;
+pub mod api;
+pub mod attendance;
+pub mod audit;
 pub mod bot;
+pub mod calendar;
+pub mod clock;
+pub mod common;
 pub mod config;
+pub mod conflicts;
 pub mod data;
 pub mod db;
 pub mod display;
+pub mod editor;
+pub mod exams;
+pub mod expr;
+pub mod friends;
+pub mod gradebook;
+pub mod grades;
+pub mod homework;
+pub mod i18n;
+pub mod import;
+pub mod inline;
+pub mod notes;
+pub mod now;
+pub mod onboarding;
+pub mod pin;
+pub mod store;
+pub mod timetable;
+pub mod upload;
+pub mod web;
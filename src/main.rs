@@ -4,5 +4,22 @@ async fn main() {
     log::trace!("Starting schedule bot");
 
     let config = schedule_bot::config::get();
-    schedule_bot::bot::run(config.token).await;
+    let clock = std::sync::Arc::new(schedule_bot::clock::SystemClock);
+    let db = schedule_bot::db::Database::connect(&config.database)
+        .await
+        .expect("Failed to connect to database")
+        .with_clock(clock.clone());
+    db.migrate().await.expect("Failed to run migrations");
+
+    if let Some(listen) = config.listen {
+        let address = listen.parse().expect("Invalid address to listen on");
+        let api = schedule_bot::api::Api {
+            store: db.clone(),
+            clock: clock.clone(),
+        };
+        let api = std::sync::Arc::new(api);
+        let router = schedule_bot::api::router(api.clone()).merge(schedule_bot::web::router(api));
+        tokio::spawn(schedule_bot::api::serve(address, router));
+    }
+    schedule_bot::bot::run(config.token, db, clock).await;
 }
@@ -0,0 +1,250 @@
+use crate::bot::{failed, user, Error, Reply};
+use crate::homework;
+use crate::i18n::{Language, Msg};
+use crate::store::ScheduleStore;
+use teloxide::types::ChatId;
+
+/// Longest note taken.
+const TEXT_LENGTH: usize = 200;
+
+/// `/note <subject> <text>`, or `/note <subject> -` to remove it: the
+/// sender's own note on a subject of the chat's group, shown under its
+/// classes.
+pub async fn execute<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+    sender: ChatId,
+    language: Language,
+    args: &str,
+) -> Result<Reply, Error> {
+    let words: Vec<&str> = args.split_whitespace().collect();
+    let (user, institution) = user(store, chat_id).await?;
+    let subjects: Vec<_> = store
+        .get_all_subjects(institution.id)
+        .await
+        .map_err(failed(chat_id, "get subjects"))?
+        .into_iter()
+        .filter(|s| s.group == user.group)
+        .collect();
+    let Some((subject, words)) = homework::find(&subjects, &words) else {
+        let word = words.first().ok_or(Error::Usage(chat_id, "note"))?;
+        return Err(Error::InvalidSubject(chat_id, word.to_string()));
+    };
+    let text = match words {
+        [] => return Err(Error::Usage(chat_id, "note")),
+        ["-"] => None,
+        _ => Some(words.join(" ")),
+    };
+    if text
+        .as_ref()
+        .is_some_and(|t| t.chars().count() > TEXT_LENGTH)
+    {
+        return Err(Error::TooLong(chat_id, TEXT_LENGTH));
+    }
+
+    store
+        .set_note(&sender, institution.id, subject.id, text.as_deref())
+        .await
+        .map_err(failed(chat_id, "set note"))?;
+    Ok(Reply::Text(language.tr(match text {
+        Some(_) => Msg::NoteSaved {
+            title: &subject.title,
+        },
+        None => Msg::NoteRemoved {
+            title: &subject.title,
+        },
+    })))
+}
+
+/// `/notes`: every note of the sender, by subject.
+pub async fn list<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+    sender: ChatId,
+    language: Language,
+) -> Result<Reply, Error> {
+    let (_, institution) = user(store, chat_id).await?;
+    let notes = store
+        .get_notes(&sender, institution.id)
+        .await
+        .map_err(failed(chat_id, "get notes"))?;
+    if notes.is_empty() {
+        return Ok(Reply::Text(language.tr(Msg::NoNotes)));
+    }
+    let subjects = store
+        .get_all_subjects(institution.id)
+        .await
+        .map_err(failed(chat_id, "get subjects"))?;
+    let mut message = language.tr(Msg::NoteList);
+    for note in notes {
+        let title = subjects
+            .iter()
+            .find(|s| s.id == note.subject_id)
+            .map_or("?", |s| s.title.as_str());
+        message.push_str(&format!("\n{}: {}", title, note.text));
+    }
+    Ok(Reply::Text(message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::TestClock;
+    use crate::data::{Day, Group, Repeat, Schedule, Slot, Subject, User};
+    use crate::store::MemoryStore;
+    use crate::timetable::{self, Span, View};
+    use chrono::{NaiveDate, TimeZone, Utc};
+
+    const ALICE: ChatId = ChatId(7);
+    const BOB: ChatId = ChatId(8);
+    const CHAT: ChatId = ChatId(-100);
+
+    fn k25() -> Group {
+        Group::try_from("K-25").unwrap()
+    }
+
+    /// K-25 with Algebra on Mondays and Logic, Alice and Bob in it along with
+    /// their group chat.
+    async fn store() -> MemoryStore {
+        let store = MemoryStore::new();
+        store.add_group(0, &k25()).await.unwrap();
+        for (id, title) in [(0, "Algebra"), (1, "Logic")] {
+            let subject = Subject {
+                id,
+                title: title.into(),
+                group: k25(),
+                optional: false,
+            };
+            store.add_subject(0, &subject).await.unwrap();
+        }
+        let class = Schedule {
+            subject_id: 0,
+            day: Day::Mon,
+            repeat: Repeat::Both,
+            slot: Slot::I,
+        };
+        store.add_schedule(0, &class).await.unwrap();
+        let user = User {
+            institution: 0,
+            group: k25(),
+        };
+        for id in [ALICE, BOB, CHAT] {
+            store.add_user(&id, &user).await.unwrap();
+        }
+        store
+    }
+
+    async fn note(store: &MemoryStore, chat: ChatId, args: &str) -> Result<String, Error> {
+        match execute(store, chat, chat, Language::En, args).await? {
+            Reply::Text(text) => Ok(text),
+            other => panic!("Unexpected reply {:?}", other),
+        }
+    }
+
+    async fn notes(store: &MemoryStore, chat: ChatId) -> String {
+        match list(store, chat, chat, Language::En).await.unwrap() {
+            Reply::Text(text) => text,
+            other => panic!("Unexpected reply {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn notes_are_kept_per_chat() {
+        let store = store().await;
+        assert_eq!(
+            note(&store, ALICE, "alg bring a calculator").await.unwrap(),
+            "Saved your note on Algebra."
+        );
+        note(&store, ALICE, "Logic prefers email").await.unwrap();
+        note(&store, ALICE, "logic answers email").await.unwrap();
+        assert_eq!(
+            notes(&store, ALICE).await,
+            "Your notes:\nAlgebra: bring a calculator\nLogic: answers email"
+        );
+        assert_eq!(
+            notes(&store, BOB).await,
+            "You have no notes yet. Add one with /note <subject> <text>."
+        );
+
+        let view = View {
+            span: Span::Day,
+            date: NaiveDate::from_ymd_opt(2023, 10, 16),
+            group: None,
+        };
+        let clock = TestClock::new(Utc.with_ymd_and_hms(2023, 10, 10, 6, 0, 0).unwrap());
+        let page = |chat| timetable::render(&store, &clock, chat, chat, Language::En, view.clone());
+        let Some(Reply::Page(text, _)) = page(ALICE).await.unwrap() else {
+            panic!("Not a page");
+        };
+        assert!(text.contains("Algebra\n📌 _bring a calculator_"));
+        let Some(Reply::Page(text, _)) = page(BOB).await.unwrap() else {
+            panic!("Not a page");
+        };
+        assert!(!text.contains("calculator"));
+
+        assert_eq!(
+            note(&store, ALICE, "algebra -").await.unwrap(),
+            "Removed your note on Algebra."
+        );
+        assert_eq!(
+            notes(&store, ALICE).await,
+            "Your notes:\nLogic: answers email"
+        );
+    }
+
+    #[tokio::test]
+    async fn notes_in_a_group_chat_are_kept_per_sender() {
+        let store = store().await;
+        let note = |sender, args| execute(&store, CHAT, sender, Language::En, args);
+        note(ALICE, "algebra bring a calculator").await.unwrap();
+        note(BOB, "algebra sit in the back").await.unwrap();
+        let notes = |sender| list(&store, CHAT, sender, Language::En);
+        assert_eq!(
+            notes(ALICE).await.unwrap(),
+            Reply::Text("Your notes:\nAlgebra: bring a calculator".into())
+        );
+        assert_eq!(
+            notes(BOB).await.unwrap(),
+            Reply::Text("Your notes:\nAlgebra: sit in the back".into())
+        );
+        assert!(store.get_notes(&CHAT, 0).await.unwrap().is_empty());
+
+        let view = View {
+            span: Span::Day,
+            date: NaiveDate::from_ymd_opt(2023, 10, 16),
+            group: None,
+        };
+        let clock = TestClock::new(Utc.with_ymd_and_hms(2023, 10, 10, 6, 0, 0).unwrap());
+        let Some(Reply::Page(text, _)) =
+            timetable::render(&store, &clock, CHAT, BOB, Language::En, view)
+                .await
+                .unwrap()
+        else {
+            panic!("Not a page");
+        };
+        assert!(text.contains("sit in the back"));
+        assert!(!text.contains("calculator"));
+    }
+
+    #[tokio::test]
+    async fn note_checks_arguments() {
+        let store = store().await;
+        for (args, expected) in [
+            ("", "usage"),
+            ("Algebra", "usage"),
+            ("Drawing pencils", "invalid subject"),
+        ] {
+            let found = match note(&store, ALICE, args).await {
+                Err(Error::Usage(_, "note")) => "usage",
+                Err(Error::InvalidSubject(..)) => "invalid subject",
+                other => panic!("Unexpected result {:?}", other),
+            };
+            assert_eq!(found, expected, "{}", args);
+        }
+        let long = format!("Logic {}", "a".repeat(TEXT_LENGTH + 1));
+        assert!(matches!(
+            note(&store, ALICE, &long).await,
+            Err(Error::TooLong(_, TEXT_LENGTH))
+        ));
+    }
+}
@@ -0,0 +1,171 @@
+use crate::bot::{failed, user, Error, Reply};
+use crate::clock::Clock;
+use crate::data::{Slot, Subject};
+use crate::i18n::{Language, Msg};
+use crate::store::ScheduleStore;
+use crate::timetable;
+use chrono::NaiveTime;
+use teloxide::types::ChatId;
+
+/// Whole minutes from `from` until `to`, a started minute counted as one.
+fn minutes(from: NaiveTime, to: NaiveTime) -> i64 {
+    ((to - from).num_seconds() + 59) / 60
+}
+
+/// `Algebra, Logic`.
+fn titles(subjects: &[Subject]) -> String {
+    let titles: Vec<&str> = subjects.iter().map(|s| s.title.as_str()).collect();
+    titles.join(", ")
+}
+
+/// `/now`: the class of the group going on with the minutes left of it, the
+/// next one with its start and room, and when the classes of the day end.
+pub async fn execute<S: ScheduleStore>(
+    store: &S,
+    clock: &dyn Clock,
+    chat_id: ChatId,
+    language: Language,
+) -> Result<Reply, Error> {
+    let (user, institution) = user(store, chat_id).await?;
+    let now = clock
+        .now()
+        .with_timezone(&institution.timezone)
+        .naive_local();
+    let (date, time) = (now.date(), now.time());
+    let holiday = store
+        .get_holiday(institution.id, date)
+        .await
+        .map_err(failed(chat_id, "get holiday"))?;
+    if let Some(holiday) = holiday {
+        return Ok(Reply::Text(
+            language.tr(Msg::NoClassesBecause(&holiday.title)),
+        ));
+    }
+
+    let mut classes: Vec<(Slot, Vec<Subject>)> = vec![];
+    for slot in Slot::ALL {
+        let subjects =
+            timetable::subjects(store, chat_id, &institution, &user.group, date, slot).await?;
+        if !subjects.is_empty() {
+            classes.push((slot, subjects));
+        }
+    }
+    let Some((last, _)) = classes.last() else {
+        return Ok(Reply::Text(language.tr(Msg::NoClasses)));
+    };
+    let reader = timetable::reader(store, chat_id, &institution).await?;
+    let shown = |slot: Slot| {
+        institution
+            .bells
+            .times_in(slot, date, &institution.timezone, &reader)
+    };
+
+    let mut lines = vec![];
+    let current = classes.iter().find(|(slot, _)| {
+        let (start, end) = institution.bells.times(*slot);
+        start <= time && time < end
+    });
+    if let Some((slot, subjects)) = current {
+        let (_, end) = institution.bells.times(*slot);
+        lines.push(language.tr(Msg::NowClass {
+            title: &titles(subjects),
+            minutes: &minutes(time, end).to_string(),
+        }));
+    }
+    let next = classes
+        .iter()
+        .find(|(slot, _)| institution.bells.times(*slot).0 > time);
+    if let Some((slot, subjects)) = next {
+        let (start, _) = institution.bells.times(*slot);
+        let room = store
+            .get_room(institution.id, subjects[0].id)
+            .await
+            .map_err(failed(chat_id, "get room"))?;
+        lines.push(language.tr(Msg::NextClass {
+            title: &titles(subjects),
+            start: &shown(*slot).0.format("%H:%M").to_string(),
+            minutes: &minutes(time, start).to_string(),
+            room: room.as_deref().unwrap_or_default(),
+        }));
+    }
+    if time < institution.bells.times(*last).1 {
+        let end = shown(*last).1.format("%H:%M").to_string();
+        lines.push(language.tr(Msg::DayEnds(&end)));
+    } else {
+        lines.push(language.tr(Msg::DayOver));
+    }
+    Ok(Reply::Text(lines.join("\n")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::TestClock;
+    use crate::data::{Day, Group, Repeat, Schedule, User};
+    use crate::store::MemoryStore;
+    use chrono::{Duration, TimeZone, Utc};
+
+    const CHAT: ChatId = ChatId(42);
+
+    /// K-25 with Algebra first thing on Mondays and Logic in room 204 in the
+    /// third slot, at 9:00 on Monday, 9 Oct 2023, in Kyiv.
+    async fn setup() -> (MemoryStore, TestClock) {
+        let store = MemoryStore::new();
+        let group = Group::try_from("K-25").unwrap();
+        for (id, title, slot) in [(0, "Algebra", Slot::I), (1, "Logic", Slot::III)] {
+            let subject = Subject {
+                id,
+                title: title.into(),
+                group: group.clone(),
+                optional: false,
+            };
+            store.add_subject(0, &subject).await.unwrap();
+            let class = Schedule {
+                subject_id: id,
+                day: Day::Mon,
+                repeat: Repeat::Both,
+                slot,
+            };
+            store.add_schedule(0, &class).await.unwrap();
+        }
+        store.set_room(0, 1, "204").await.unwrap();
+        let user = User {
+            institution: 0,
+            group,
+        };
+        store.add_user(&CHAT, &user).await.unwrap();
+        let clock = TestClock::new(Utc.with_ymd_and_hms(2023, 10, 9, 6, 0, 0).unwrap());
+        (store, clock)
+    }
+
+    async fn now(store: &MemoryStore, clock: &TestClock) -> String {
+        match execute(store, clock, CHAT, Language::En).await.unwrap() {
+            Reply::Text(text) => text,
+            other => panic!("Unexpected reply {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn now_and_next() {
+        let (store, clock) = setup().await;
+        assert_eq!(
+            now(&store, &clock).await,
+            "Now: Algebra, 75 min left.\n\
+             Next: Logic at 12:20 in room 204, in 200 min.\n\
+             Classes end at 13:55."
+        );
+
+        // The break between the two
+        clock.advance(Duration::minutes(90));
+        assert_eq!(
+            now(&store, &clock).await,
+            "Next: Logic at 12:20 in room 204, in 110 min.\nClasses end at 13:55."
+        );
+
+        clock.advance(Duration::hours(4));
+        assert_eq!(now(&store, &clock).await, "No more classes today.");
+
+        clock.advance(Duration::days(1));
+        assert_eq!(now(&store, &clock).await, "No classes.");
+    }
+}
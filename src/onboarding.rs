@@ -0,0 +1,426 @@
+use crate::bot::{failed, save, Error, Reply};
+use crate::data::{Group, Institution, User};
+use crate::i18n::{Language, Msg};
+use crate::store::{Dialogues, ScheduleStore};
+use serde::{Deserialize, Serialize};
+use teloxide::{
+    dispatching::dialogue::Dialogue,
+    types::{ChatId, InlineKeyboardButton, InlineKeyboardMarkup},
+};
+
+/// Dialogue of the `/start` flow, persisted with the rest of the data.
+pub type OnboardingDialogue<S> = Dialogue<State, Dialogues<S>>;
+
+/// Where a chat is in the `/start` flow.
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub enum State {
+    #[default]
+    Idle,
+    Institution,
+    /// Narrowing down the groups of `institution`, with a value picked for
+    /// each [`Level`] so far.
+    Group {
+        institution: i64,
+        picked: Vec<String>,
+    },
+}
+
+/// Parts of a group name the user picks one by one.
+#[derive(Clone, Copy)]
+enum Level {
+    Faculty,
+    Year,
+    Group,
+    Subgroup,
+    /// The whole name, for groups the parts above don't tell apart, e.g.
+    /// `K-25` and `K-25/`.
+    Name,
+}
+
+impl Level {
+    const ALL: [Level; 5] = [
+        Level::Faculty,
+        Level::Year,
+        Level::Group,
+        Level::Subgroup,
+        Level::Name,
+    ];
+
+    fn of(self, group: &Group) -> &str {
+        match self {
+            Level::Faculty => group.faculty(),
+            Level::Year => group.year(),
+            Level::Group => group.base(),
+            Level::Subgroup => group.subgroup(),
+            Level::Name => group.as_str(),
+        }
+    }
+
+    fn prompt(self) -> Msg<'static> {
+        match self {
+            Level::Faculty => Msg::PickFaculty,
+            Level::Year => Msg::PickYear,
+            Level::Group => Msg::PickGroup,
+            Level::Subgroup => Msg::PickSubgroup,
+            Level::Name => Msg::PickGroup,
+        }
+    }
+
+    fn label(self, language: Language, value: &str) -> String {
+        match (self, value) {
+            (Level::Subgroup, "") => language.tr(Msg::WholeGroup),
+            (_, "") => language.tr(Msg::Other),
+            (Level::Year, year) => language.tr(Msg::Year(year)),
+            (_, value) => value.into(),
+        }
+    }
+}
+
+/// Answer to `/start`, skipping the institution when there is only one.
+pub async fn start<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+    language: Language,
+) -> Result<(State, Reply), Error> {
+    let mut institutions = store
+        .get_institutions()
+        .await
+        .map_err(failed(chat_id, "get institutions"))?;
+
+    if institutions.len() == 1 {
+        return narrow(store, chat_id, language, institutions.remove(0), vec![]).await;
+    }
+
+    let buttons = institutions
+        .into_iter()
+        .map(|i| InlineKeyboardButton::callback(i.name, format!("i:{}", i.id)));
+    Ok((
+        State::Institution,
+        Reply::Menu(language.tr(Msg::PickInstitution), keyboard(buttons)),
+    ))
+}
+
+/// Applies a press of the button with callback `data` to `state`.
+///
+/// Buttons of an older menu are answered with a hint to start over.
+pub async fn pick<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+    language: Language,
+    state: State,
+    data: &str,
+) -> Result<(State, Reply), Error> {
+    let expired = Ok((State::Idle, Reply::Text(language.tr(Msg::MenuExpired))));
+    let Some((step, value)) = data.split_once(':') else {
+        return expired;
+    };
+
+    match state {
+        State::Institution if step == "i" => {
+            let Ok(id) = value.parse() else {
+                return expired;
+            };
+            let institution = match store.get_institution(id).await {
+                Ok(ok) => ok,
+                Err(sqlx::Error::RowNotFound) => return expired,
+                Err(err) => return Err(failed(chat_id, "get institution")(err)),
+            };
+            narrow(store, chat_id, language, institution, vec![]).await
+        }
+        State::Group {
+            institution,
+            mut picked,
+        } if step == picked.len().to_string() => {
+            let Some(level) = Level::ALL.get(picked.len()) else {
+                return expired;
+            };
+            let groups = store
+                .get_groups(institution)
+                .await
+                .map_err(failed(chat_id, "get groups"))?;
+            let candidates = candidates(&groups, &picked);
+            let Some(value) = value
+                .parse::<usize>()
+                .ok()
+                .and_then(|index| options(&candidates, *level).into_iter().nth(index))
+            else {
+                return expired;
+            };
+            picked.push(value.into());
+
+            let institution = store
+                .get_institution(institution)
+                .await
+                .map_err(failed(chat_id, "get institution"))?;
+            narrow(store, chat_id, language, institution, picked).await
+        }
+        _ => expired,
+    }
+}
+
+/// Asks for the next level with more than one option, or saves the group
+/// once it is the only one left.
+async fn narrow<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+    language: Language,
+    institution: Institution,
+    mut picked: Vec<String>,
+) -> Result<(State, Reply), Error> {
+    let groups = store
+        .get_groups(institution.id)
+        .await
+        .map_err(failed(chat_id, "get groups"))?;
+
+    loop {
+        let candidates = candidates(&groups, &picked);
+        match candidates[..] {
+            [] if picked.is_empty() => {
+                let message = language.tr(Msg::NoGroupsYet(&institution.name));
+                return Ok((State::Idle, Reply::Text(message)));
+            }
+            [] => {
+                let message = language.tr(Msg::MenuExpired);
+                return Ok((State::Idle, Reply::Text(message)));
+            }
+            // More than one left after the last level means the same name
+            // is listed twice, so either will do
+            [group, ..] if candidates.len() == 1 || picked.len() == Level::ALL.len() => {
+                let user = User {
+                    institution: institution.id,
+                    group: group.clone(),
+                };
+                return Ok((
+                    State::Idle,
+                    save(store, chat_id, language, user, &institution).await?,
+                ));
+            }
+            _ => {}
+        }
+
+        let Some(&level) = Level::ALL.get(picked.len()) else {
+            let message = language.tr(Msg::MenuExpired);
+            return Ok((State::Idle, Reply::Text(message)));
+        };
+        let mut options = options(&candidates, level);
+        if options.len() == 1 {
+            picked.push(options.remove(0).into());
+            continue;
+        }
+
+        let step = picked.len();
+        let buttons = options.into_iter().enumerate().map(|(index, value)| {
+            InlineKeyboardButton::callback(
+                level.label(language, value),
+                format!("{}:{}", step, index),
+            )
+        });
+        let state = State::Group {
+            institution: institution.id,
+            picked,
+        };
+        return Ok((
+            state,
+            Reply::Menu(language.tr(level.prompt()), keyboard(buttons)),
+        ));
+    }
+}
+
+/// Groups matching everything picked so far.
+fn candidates<'a>(groups: &'a [Group], picked: &[String]) -> Vec<&'a Group> {
+    groups
+        .iter()
+        .filter(|g| picked.iter().zip(Level::ALL).all(|(v, l)| l.of(g) == v))
+        .collect()
+}
+
+/// Distinct values of `level` among `candidates`, sorted.
+fn options<'a>(candidates: &[&'a Group], level: Level) -> Vec<&'a str> {
+    let mut options: Vec<&str> = candidates.iter().map(|g| level.of(g)).collect();
+    options.sort();
+    options.dedup();
+    options
+}
+
+/// Lays `buttons` out three in a row.
+fn keyboard(buttons: impl Iterator<Item = InlineKeyboardButton>) -> InlineKeyboardMarkup {
+    let buttons: Vec<InlineKeyboardButton> = buttons.collect();
+    InlineKeyboardMarkup::new(buttons.chunks(3).map(<[_]>::to_vec))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Bells;
+    use crate::store::MemoryStore;
+    use teloxide::types::InlineKeyboardButtonKind;
+
+    const CHAT: ChatId = ChatId(42);
+
+    async fn store(groups: &[&str]) -> MemoryStore {
+        let store = MemoryStore::new();
+        for group in groups {
+            store
+                .add_group(0, &Group::try_from(*group).unwrap())
+                .await
+                .unwrap();
+        }
+        store
+    }
+
+    /// Labels and callback data of the menu's buttons.
+    fn buttons(reply: &Reply) -> Vec<(String, String)> {
+        let Reply::Menu(_, markup) = reply else {
+            panic!("Not a menu: {:?}", reply);
+        };
+        markup
+            .inline_keyboard
+            .iter()
+            .flatten()
+            .map(|b| match &b.kind {
+                InlineKeyboardButtonKind::CallbackData(data) => (b.text.clone(), data.clone()),
+                other => panic!("Not a callback button: {:?}", other),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn start_walks_down_to_subgroup() {
+        let store = store(&["K-25", "K-25/1", "K-26", "K-31", "МП-21"]).await;
+
+        let (state, reply) = start(&store, CHAT, Language::En).await.unwrap();
+        assert_eq!(
+            buttons(&reply),
+            vec![("K".into(), "0:0".into()), ("МП".into(), "0:1".into())]
+        );
+
+        let (state, reply) = pick(&store, CHAT, Language::En, state, "0:0")
+            .await
+            .unwrap();
+        assert_eq!(
+            buttons(&reply),
+            vec![
+                ("Year 2".into(), "1:0".into()),
+                ("Year 3".into(), "1:1".into())
+            ]
+        );
+
+        let (state, reply) = pick(&store, CHAT, Language::En, state, "1:0")
+            .await
+            .unwrap();
+        assert_eq!(
+            buttons(&reply),
+            vec![("K-25".into(), "2:0".into()), ("K-26".into(), "2:1".into())]
+        );
+
+        let (state, reply) = pick(&store, CHAT, Language::En, state, "2:0")
+            .await
+            .unwrap();
+        assert_eq!(
+            buttons(&reply),
+            vec![
+                ("Whole group".into(), "3:0".into()),
+                ("1".into(), "3:1".into())
+            ]
+        );
+
+        let (state, reply) = pick(&store, CHAT, Language::En, state, "3:1")
+            .await
+            .unwrap();
+        assert_eq!(state, State::Idle);
+        assert_eq!(reply, Reply::Text("Saved: K-25/1, Default.".into()));
+        assert_eq!(
+            store.get_user(&CHAT).await.unwrap().group.as_str(),
+            "K-25/1"
+        );
+    }
+
+    #[tokio::test]
+    async fn start_skips_single_choices() {
+        let store = store(&["K-25", "K-31"]).await;
+        store
+            .add_institution(&Institution {
+                id: 1,
+                code: "other".into(),
+                name: "Other".into(),
+                timezone: chrono_tz::Europe::Lisbon,
+                bells: Bells::default(),
+            })
+            .await
+            .unwrap();
+
+        let (state, reply) = start(&store, CHAT, Language::En).await.unwrap();
+        assert_eq!(state, State::Institution);
+        assert_eq!(
+            buttons(&reply),
+            vec![
+                ("Default".into(), "i:0".into()),
+                ("Other".into(), "i:1".into())
+            ]
+        );
+
+        // A single faculty isn't asked about
+        let (state, reply) = pick(&store, CHAT, Language::En, state, "i:0")
+            .await
+            .unwrap();
+        assert_eq!(
+            state,
+            State::Group {
+                institution: 0,
+                picked: vec!["K".into()]
+            }
+        );
+        assert!(matches!(&reply, Reply::Menu(text, _) if text == "Pick your year:"));
+
+        let (_, reply) = pick(&store, CHAT, Language::En, State::Institution, "i:1")
+            .await
+            .unwrap();
+        assert_eq!(reply, Reply::Text("Other has no groups yet.".into()));
+    }
+
+    #[tokio::test]
+    async fn pick_rejects_stale_buttons() {
+        let store = store(&["K-25", "K-31"]).await;
+        let (state, _) = start(&store, CHAT, Language::En).await.unwrap();
+        let expired = (State::Idle, Reply::Text(Language::En.tr(Msg::MenuExpired)));
+
+        for data in ["0:0", "1:5", "1:x", "i:0", "garbage"] {
+            let result = pick(&store, CHAT, Language::En, state.clone(), data)
+                .await
+                .unwrap();
+            assert_eq!(result, expired, "{}", data);
+        }
+        let result = pick(&store, CHAT, Language::En, State::Idle, "1:0")
+            .await
+            .unwrap();
+        assert_eq!(result, expired);
+        assert!(store.get_user(&CHAT).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn start_tells_apart_groups_by_name() {
+        let store = store(&["K-25", "K-25/"]).await;
+
+        let (state, reply) = start(&store, CHAT, Language::En).await.unwrap();
+        assert_eq!(
+            state,
+            State::Group {
+                institution: 0,
+                picked: vec!["K".into(), "2".into(), "K-25".into(), "".into()]
+            }
+        );
+        assert_eq!(
+            buttons(&reply),
+            vec![
+                ("K-25".into(), "4:0".into()),
+                ("K-25/".into(), "4:1".into())
+            ]
+        );
+
+        let (state, reply) = pick(&store, CHAT, Language::En, state, "4:1")
+            .await
+            .unwrap();
+        assert_eq!(state, State::Idle);
+        assert_eq!(reply, Reply::Text("Saved: K-25/, Default.".into()));
+    }
+}
@@ -0,0 +1,216 @@
+use crate::bot::{failed, language, user, Error, Reply};
+use crate::clock::Clock;
+use crate::i18n::{Language, Msg};
+use crate::store::{Pinned, ScheduleStore};
+use crate::timetable::{self, Span};
+use chrono::NaiveDate;
+use std::sync::Arc;
+use std::time::Duration;
+use teloxide::{prelude::*, types::ParseMode, ApiError, RequestError};
+
+/// How often pinned timetables are checked for a new day.
+const INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// `/pin`: posts today's timetable of the chat and pins it in place of the
+/// one pinned before. Answered with a hint when the bot may not pin messages.
+pub async fn pin<S: ScheduleStore>(
+    bot: &Bot,
+    store: &S,
+    clock: &dyn Clock,
+    chat_id: ChatId,
+    language: Language,
+) -> Result<Option<Reply>, Error> {
+    let (date, text) = today(store, clock, chat_id, language).await?;
+    let message = bot
+        .send_message(chat_id, text)
+        .parse_mode(ParseMode::MarkdownV2)
+        .await
+        .map_err(|err| {
+            log::error!("Failed to send pinned timetable: {:?}", err);
+            Error::Some(chat_id)
+        })?;
+
+    if let Err(err) = bot
+        .pin_chat_message(chat_id, message.id)
+        .disable_notification(true)
+        .await
+    {
+        log::trace!("Failed to pin timetable in {}: {:?}", chat_id, err);
+        return Ok(Some(Reply::Text(language.tr(Msg::CantPin))));
+    }
+    unpin(bot, store, chat_id).await?;
+    store
+        .set_pinned(&Pinned {
+            chat_id,
+            message_id: message.id,
+            date,
+        })
+        .await
+        .map_err(failed(chat_id, "store pinned timetable"))?;
+    Ok(None)
+}
+
+/// Unpins the timetable pinned with `/pin`, if there is one.
+pub async fn unpin<S: ScheduleStore>(bot: &Bot, store: &S, chat_id: ChatId) -> Result<(), Error> {
+    let pinned = store
+        .get_pinned(&chat_id)
+        .await
+        .map_err(failed(chat_id, "get pinned timetable"))?;
+    if let Some(pinned) = pinned {
+        let _ = bot
+            .unpin_chat_message(chat_id)
+            .message_id(pinned.message_id)
+            .await;
+        store
+            .remove_pinned(&chat_id)
+            .await
+            .map_err(failed(chat_id, "remove pinned timetable"))?;
+    }
+    Ok(())
+}
+
+/// Markdown timetable of the chat's group for today, without buttons, and
+/// the date it is for.
+pub async fn today<S: ScheduleStore>(
+    store: &S,
+    clock: &dyn Clock,
+    chat_id: ChatId,
+    language: Language,
+) -> Result<(NaiveDate, String), Error> {
+    let (user, institution) = user(store, chat_id).await?;
+    let reader = timetable::reader(store, chat_id, &institution).await?;
+    let date = clock
+        .now()
+        .with_timezone(&institution.timezone)
+        .date_naive();
+    let text = timetable::page(
+        store,
+        chat_id,
+        chat_id,
+        language,
+        &institution,
+        &user.group,
+        reader,
+        Span::Day,
+        date,
+    )
+    .await?;
+    Ok((date, text))
+}
+
+/// Pinned timetables of a day that is over, moved on to today, each with the
+/// text to show now. Chats that no longer have a group are forgotten.
+pub async fn due<S: ScheduleStore>(
+    store: &S,
+    clock: &dyn Clock,
+) -> sqlx::Result<Vec<(Pinned, String)>> {
+    let mut due = vec![];
+    for pinned in store.get_all_pinned().await? {
+        let chat_id = pinned.chat_id;
+        let language = language(store, chat_id, None).await;
+        match today(store, clock, chat_id, language).await {
+            Ok((date, _)) if date == pinned.date => {}
+            Ok((date, text)) => due.push((Pinned { date, ..pinned }, text)),
+            Err(Error::NoGroupConfigured(_)) => store.remove_pinned(&chat_id).await?,
+            Err(err) => log::error!("Failed to render pinned timetable: {:?}", err),
+        }
+    }
+    Ok(due)
+}
+
+/// Edits every pinned timetable of a day that is over to show today.
+async fn refresh<S: ScheduleStore>(bot: &Bot, store: &S, clock: &dyn Clock) -> sqlx::Result<()> {
+    for (pinned, text) in due(store, clock).await? {
+        let result = bot
+            .edit_message_text(pinned.chat_id, pinned.message_id, text)
+            .parse_mode(ParseMode::MarkdownV2)
+            .await;
+        match result {
+            Ok(_) | Err(RequestError::Api(ApiError::MessageNotModified)) => {
+                store.set_pinned(&pinned).await?
+            }
+            // The message is gone or the bot can't reach the chat anymore
+            Err(RequestError::Api(err)) => {
+                log::trace!("Dropping pinned timetable of {}: {:?}", pinned.chat_id, err);
+                store.remove_pinned(&pinned.chat_id).await?
+            }
+            // Tried again on the next round
+            Err(err) => log::error!("Failed to edit pinned timetable: {:?}", err),
+        }
+    }
+    Ok(())
+}
+
+/// Keeps pinned timetables showing today for as long as the bot runs.
+pub async fn watch<S: ScheduleStore>(bot: Bot, store: Arc<S>, clock: Arc<dyn Clock>) {
+    let mut interval = tokio::time::interval(INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(err) = refresh(&bot, store.as_ref(), clock.as_ref()).await {
+            log::error!("Failed to refresh pinned timetables: {:?}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::TestClock;
+    use crate::data::{Group, User};
+    use crate::store::MemoryStore;
+    use chrono::{TimeZone, Utc};
+    use teloxide::types::MessageId;
+
+    const CHAT: ChatId = ChatId(-100);
+
+    #[tokio::test]
+    async fn due_moves_on_to_the_next_day() {
+        let store = MemoryStore::new();
+        let group = Group::try_from("K-25").unwrap();
+        store.add_group(0, &group).await.unwrap();
+        store
+            .add_user(
+                &CHAT,
+                &User {
+                    institution: 0,
+                    group,
+                },
+            )
+            .await
+            .unwrap();
+        // Tuesday, 10 Oct 2023, 23:30 in Kyiv
+        let clock = TestClock::new(Utc.with_ymd_and_hms(2023, 10, 10, 20, 30, 0).unwrap());
+
+        let (date, _) = today(&store, &clock, CHAT, Language::En).await.unwrap();
+        let pinned = Pinned {
+            chat_id: CHAT,
+            message_id: MessageId(1),
+            date,
+        };
+        store.set_pinned(&pinned).await.unwrap();
+        assert!(due(&store, &clock).await.unwrap().is_empty());
+
+        clock.advance(chrono::Duration::hours(1));
+        let due = due(&store, &clock).await.unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].0.date, date.succ_opt().unwrap());
+        assert!(due[0].1.contains("Wed 11\\.10\\.2023"));
+    }
+
+    #[tokio::test]
+    async fn due_forgets_chats_without_group() {
+        let store = MemoryStore::new();
+        let clock = TestClock::new(Utc.with_ymd_and_hms(2023, 10, 10, 6, 0, 0).unwrap());
+        store
+            .set_pinned(&Pinned {
+                chat_id: CHAT,
+                message_id: MessageId(1),
+                date: NaiveDate::from_ymd_opt(2023, 10, 9).unwrap(),
+            })
+            .await
+            .unwrap();
+
+        assert!(due(&store, &clock).await.unwrap().is_empty());
+        assert_eq!(store.get_pinned(&CHAT).await.unwrap(), None);
+    }
+}
@@ -0,0 +1,2142 @@
+use crate::audit::{self, Entry};
+use crate::clock::{Clock, SystemClock};
+use crate::data::{
+    Assigned, Attendance, Bells, Change, Day, Exam, Grade, Grant, Group, Holiday, Homework,
+    Institution, Meeting, Note, Repeat, Schedule, Semesters, Slot, Subject, User,
+};
+use crate::i18n::Language;
+use crate::import::{self, Import};
+use chrono::NaiveDate;
+use chrono_tz::Tz;
+use futures::future::BoxFuture;
+use serde::{de::DeserializeOwned, Serialize};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use teloxide::{
+    dispatching::dialogue::Storage,
+    types::{ChatId, MessageId},
+};
+
+/// Everything the bot needs to know about users and the timetable.
+///
+/// Implemented by [`crate::db::Database`] for production and by [`MemoryStore`]
+/// for tests. Lookups of a single row report a missing one as
+/// [`sqlx::Error::RowNotFound`], regardless of the implementation.
+///
+/// Timetable data is scoped to an institution, passed by its id. Every change
+/// is recorded in the audit log, attributed to the current [`audit::actor`].
+pub trait ScheduleStore: Send + Sync + 'static {
+    fn add_institution(&self, value: &Institution)
+        -> impl Future<Output = sqlx::Result<()>> + Send;
+
+    fn set_bells(
+        &self,
+        institution: i64,
+        bells: &Bells,
+    ) -> impl Future<Output = sqlx::Result<()>> + Send;
+
+    /// Sets how many classes of a subject a student may miss.
+    fn set_absences(
+        &self,
+        institution: i64,
+        absences: i64,
+    ) -> impl Future<Output = sqlx::Result<()>> + Send;
+
+    /// How many classes of a subject a student may miss, 3 unless set.
+    fn get_absences(&self, institution: i64) -> impl Future<Output = sqlx::Result<i64>> + Send;
+
+    /// Sets when the semesters of the institution start.
+    fn set_semesters(
+        &self,
+        institution: i64,
+        semesters: &Semesters,
+    ) -> impl Future<Output = sqlx::Result<()>> + Send;
+
+    /// When the semesters of the institution start, in September and
+    /// February unless set.
+    fn get_semesters(
+        &self,
+        institution: i64,
+    ) -> impl Future<Output = sqlx::Result<Semesters>> + Send;
+
+    fn get_institution(&self, id: i64) -> impl Future<Output = sqlx::Result<Institution>> + Send;
+
+    fn find_institution(
+        &self,
+        code: &str,
+    ) -> impl Future<Output = sqlx::Result<Institution>> + Send;
+
+    fn get_institutions(&self) -> impl Future<Output = sqlx::Result<Vec<Institution>>> + Send;
+
+    fn add_group(
+        &self,
+        institution: i64,
+        group: &Group,
+    ) -> impl Future<Output = sqlx::Result<()>> + Send;
+
+    fn get_groups(&self, institution: i64)
+        -> impl Future<Output = sqlx::Result<Vec<Group>>> + Send;
+
+    /// Grants a role to the user, replacing the one they had over the same scope.
+    fn add_grant(
+        &self,
+        id: &ChatId,
+        value: &Grant,
+    ) -> impl Future<Output = sqlx::Result<()>> + Send;
+
+    fn get_grants(&self, id: &ChatId) -> impl Future<Output = sqlx::Result<Vec<Grant>>> + Send;
+
+    /// Takes away the role over the whole institution, or over `group` of it.
+    fn remove_grant(
+        &self,
+        id: &ChatId,
+        institution: i64,
+        group: Option<&Group>,
+    ) -> impl Future<Output = sqlx::Result<()>> + Send;
+
+    fn add_user(&self, id: &ChatId, user: &User) -> impl Future<Output = sqlx::Result<()>> + Send;
+
+    fn update_user(
+        &self,
+        id: &ChatId,
+        user: &User,
+    ) -> impl Future<Output = sqlx::Result<()>> + Send;
+
+    fn get_user(&self, id: &ChatId) -> impl Future<Output = sqlx::Result<User>> + Send;
+
+    /// Forgets the chat along with its settings, e.g. once it can't be reached.
+    fn remove_user(&self, id: &ChatId) -> impl Future<Output = sqlx::Result<()>> + Send;
+
+    /// Chats configured for `group` of the institution, users and group chats alike.
+    fn get_chats(
+        &self,
+        institution: i64,
+        group: &Group,
+    ) -> impl Future<Output = sqlx::Result<Vec<ChatId>>> + Send;
+
+    /// Sets the time zone class times are shown in, `None` meaning the institution's one.
+    fn set_timezone(
+        &self,
+        id: &ChatId,
+        timezone: Option<Tz>,
+    ) -> impl Future<Output = sqlx::Result<()>> + Send;
+
+    fn get_timezone(&self, id: &ChatId) -> impl Future<Output = sqlx::Result<Option<Tz>>> + Send;
+
+    /// Sets the language the bot talks in, `None` meaning the one of the user's app.
+    fn set_language(
+        &self,
+        id: &ChatId,
+        language: Option<Language>,
+    ) -> impl Future<Output = sqlx::Result<()>> + Send;
+
+    fn get_language(
+        &self,
+        id: &ChatId,
+    ) -> impl Future<Output = sqlx::Result<Option<Language>>> + Send;
+
+    /// Sets the lowercase username the user of a private chat goes by, `None`
+    /// when they have none. The username is taken from whoever had it before.
+    fn set_username(
+        &self,
+        id: &ChatId,
+        username: Option<&str>,
+    ) -> impl Future<Output = sqlx::Result<()>> + Send;
+
+    fn get_username(
+        &self,
+        id: &ChatId,
+    ) -> impl Future<Output = sqlx::Result<Option<String>>> + Send;
+
+    /// The chat of the user going by lowercase `username`, if the bot knows them.
+    fn find_username(
+        &self,
+        username: &str,
+    ) -> impl Future<Output = sqlx::Result<Option<ChatId>>> + Send;
+
+    /// Shares the free time of the chat with `friend`. They are friends once
+    /// `friend` shares theirs back.
+    fn add_friend(
+        &self,
+        id: &ChatId,
+        friend: &ChatId,
+    ) -> impl Future<Output = sqlx::Result<()>> + Send;
+
+    /// Unlinks the two chats, both ways.
+    fn remove_friend(
+        &self,
+        id: &ChatId,
+        friend: &ChatId,
+    ) -> impl Future<Output = sqlx::Result<()>> + Send;
+
+    /// Chats the chat shares its free time with, whether they share back or not.
+    fn get_friends(&self, id: &ChatId) -> impl Future<Output = sqlx::Result<Vec<ChatId>>> + Send;
+
+    fn add_subject(
+        &self,
+        institution: i64,
+        value: &Subject,
+    ) -> impl Future<Output = sqlx::Result<()>> + Send;
+
+    fn get_subject(
+        &self,
+        institution: i64,
+        id: i64,
+    ) -> impl Future<Output = sqlx::Result<Subject>> + Send;
+
+    fn get_subjects(
+        &self,
+        institution: i64,
+        day: Day,
+        repeat: Repeat,
+        slot: Slot,
+        group: Group,
+    ) -> impl Future<Output = sqlx::Result<Vec<Subject>>> + Send;
+
+    /// Every subject of the institution, by id.
+    fn get_all_subjects(
+        &self,
+        institution: i64,
+    ) -> impl Future<Output = sqlx::Result<Vec<Subject>>> + Send;
+
+    fn add_schedule(
+        &self,
+        institution: i64,
+        value: &Schedule,
+    ) -> impl Future<Output = sqlx::Result<()>> + Send;
+
+    /// Every weekly class of the institution.
+    fn get_schedule(
+        &self,
+        institution: i64,
+    ) -> impl Future<Output = sqlx::Result<Vec<Schedule>>> + Send;
+
+    /// Applies an [`Import`] all at once: adds missing groups, upserts the
+    /// subjects and replaces the classes of the subjects it schedules and
+    /// the exams of the subjects it has exams of. Exams that stay the same
+    /// keep their ids and countdowns.
+    fn import(
+        &self,
+        institution: i64,
+        value: &Import,
+    ) -> impl Future<Output = sqlx::Result<()>> + Send;
+
+    fn add_meeting(
+        &self,
+        institution: i64,
+        value: &Meeting,
+    ) -> impl Future<Output = sqlx::Result<()>> + Send;
+
+    fn assign(
+        &self,
+        institution: i64,
+        value: &Assigned,
+    ) -> impl Future<Output = sqlx::Result<()>> + Send;
+
+    fn get_meetings(
+        &self,
+        institution: i64,
+        subject_id: i64,
+    ) -> impl Future<Output = sqlx::Result<Vec<Meeting>>> + Send;
+
+    /// An id no meeting of the institution has yet.
+    fn next_meeting_id(&self, institution: i64) -> impl Future<Output = sqlx::Result<i64>> + Send;
+
+    fn set_room(
+        &self,
+        institution: i64,
+        subject_id: i64,
+        room: &str,
+    ) -> impl Future<Output = sqlx::Result<()>> + Send;
+
+    fn get_room(
+        &self,
+        institution: i64,
+        subject_id: i64,
+    ) -> impl Future<Output = sqlx::Result<Option<String>>> + Send;
+
+    /// Adds a change to the timetable, replacing the one of the same group, date and slot.
+    fn add_change(
+        &self,
+        institution: i64,
+        value: &Change,
+    ) -> impl Future<Output = sqlx::Result<()>> + Send;
+
+    fn get_change(
+        &self,
+        institution: i64,
+        group: &Group,
+        date: NaiveDate,
+        slot: Slot,
+    ) -> impl Future<Output = sqlx::Result<Option<Change>>> + Send;
+
+    fn add_holiday(
+        &self,
+        institution: i64,
+        value: &Holiday,
+    ) -> impl Future<Output = sqlx::Result<()>> + Send;
+
+    fn get_holiday(
+        &self,
+        institution: i64,
+        date: NaiveDate,
+    ) -> impl Future<Output = sqlx::Result<Option<Holiday>>> + Send;
+
+    /// Holidays of the institution from `from` to `to` inclusive, by date.
+    fn get_holidays(
+        &self,
+        institution: i64,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> impl Future<Output = sqlx::Result<Vec<Holiday>>> + Send;
+
+    /// Adds homework, returning the id it gets. The id of `value` is ignored.
+    fn add_homework(
+        &self,
+        institution: i64,
+        value: &Homework,
+    ) -> impl Future<Output = sqlx::Result<i64>> + Send;
+
+    fn get_homework(
+        &self,
+        institution: i64,
+        id: i64,
+    ) -> impl Future<Output = sqlx::Result<Homework>> + Send;
+
+    /// Homework `chat` sees, shared with `group` or added by the chat itself,
+    /// due from `from` to `to` inclusive, by due date.
+    fn get_due_homework(
+        &self,
+        institution: i64,
+        group: &Group,
+        chat: &ChatId,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> impl Future<Output = sqlx::Result<Vec<Homework>>> + Send;
+
+    /// Removes homework along with who has done it.
+    fn remove_homework(
+        &self,
+        institution: i64,
+        id: i64,
+    ) -> impl Future<Output = sqlx::Result<()>> + Send;
+
+    /// Marks homework as done by the user, or as not done.
+    fn set_done(
+        &self,
+        id: &ChatId,
+        homework: i64,
+        done: bool,
+    ) -> impl Future<Output = sqlx::Result<()>> + Send;
+
+    /// Users that have done the homework.
+    fn get_done(&self, homework: i64) -> impl Future<Output = sqlx::Result<Vec<ChatId>>> + Send;
+
+    /// Homework nobody has been reminded of yet, along with its institution.
+    fn get_unreminded(&self) -> impl Future<Output = sqlx::Result<Vec<(i64, Homework)>>> + Send;
+
+    fn set_reminded(&self, homework: i64) -> impl Future<Output = sqlx::Result<()>> + Send;
+
+    /// Sets the note of the user on a subject, or removes it.
+    fn set_note(
+        &self,
+        id: &ChatId,
+        institution: i64,
+        subject_id: i64,
+        text: Option<&str>,
+    ) -> impl Future<Output = sqlx::Result<()>> + Send;
+
+    /// Notes of the user on subjects of the institution, by subject.
+    fn get_notes(
+        &self,
+        id: &ChatId,
+        institution: i64,
+    ) -> impl Future<Output = sqlx::Result<Vec<Note>>> + Send;
+
+    /// Enrolls the user in an optional subject, or takes them off it.
+    fn set_enrolled(
+        &self,
+        id: &ChatId,
+        institution: i64,
+        subject_id: i64,
+        enrolled: bool,
+    ) -> impl Future<Output = sqlx::Result<()>> + Send;
+
+    /// Ids of the subjects of the institution the user is enrolled in, in order.
+    fn get_enrolled(
+        &self,
+        id: &ChatId,
+        institution: i64,
+    ) -> impl Future<Output = sqlx::Result<Vec<i64>>> + Send;
+
+    /// Marks a class of a subject on a date as attended or missed by the chat.
+    fn set_attendance(
+        &self,
+        id: &ChatId,
+        institution: i64,
+        value: &Attendance,
+    ) -> impl Future<Output = sqlx::Result<()>> + Send;
+
+    /// Classes of the institution the chat marked, by subject and date.
+    fn get_attendance(
+        &self,
+        id: &ChatId,
+        institution: i64,
+    ) -> impl Future<Output = sqlx::Result<Vec<Attendance>>> + Send;
+
+    /// Chats that marked classes of the institution, by id, each with the ids
+    /// of the subjects of the institution it is enrolled in, in order.
+    fn get_tracking(
+        &self,
+        institution: i64,
+    ) -> impl Future<Output = sqlx::Result<Vec<(ChatId, Vec<i64>)>>> + Send;
+
+    /// Sets how many points a subject is worth in a semester.
+    fn set_max_points(
+        &self,
+        institution: i64,
+        subject_id: i64,
+        points: i64,
+    ) -> impl Future<Output = sqlx::Result<()>> + Send;
+
+    /// How many points a subject is worth in a semester, 100 unless set.
+    fn get_max_points(
+        &self,
+        institution: i64,
+        subject_id: i64,
+    ) -> impl Future<Output = sqlx::Result<i64>> + Send;
+
+    /// Logs points of the chat, ignoring the id of `value`, and returns the
+    /// id it gets.
+    fn add_grade(
+        &self,
+        id: &ChatId,
+        institution: i64,
+        value: &Grade,
+    ) -> impl Future<Output = sqlx::Result<i64>> + Send;
+
+    /// Removes points the chat logged, if they are there.
+    fn remove_grade(
+        &self,
+        id: &ChatId,
+        institution: i64,
+        grade: i64,
+    ) -> impl Future<Output = sqlx::Result<()>> + Send;
+
+    /// Points the chat logged for subjects of the institution, by subject,
+    /// date and id.
+    fn get_grades(
+        &self,
+        id: &ChatId,
+        institution: i64,
+    ) -> impl Future<Output = sqlx::Result<Vec<Grade>>> + Send;
+
+    /// Every exam of the institution, by start.
+    fn get_exams(&self, institution: i64) -> impl Future<Output = sqlx::Result<Vec<Exam>>> + Send;
+
+    /// Exams whose countdown isn't over, by start, along with their
+    /// institution and how many days before the exam the last reminder went
+    /// out, if any.
+    fn get_countdowns(
+        &self,
+    ) -> impl Future<Output = sqlx::Result<Vec<(i64, Exam, Option<i64>)>>> + Send;
+
+    /// Records that the reminder `days` before the exam went out, or with 0
+    /// that the countdown is over.
+    fn set_countdown(&self, exam: i64, days: i64) -> impl Future<Output = sqlx::Result<()>> + Send;
+
+    /// Serialized state of an unfinished dialogue with the chat, see [`Dialogues`].
+    fn get_dialogue(
+        &self,
+        id: &ChatId,
+    ) -> impl Future<Output = sqlx::Result<Option<String>>> + Send;
+
+    fn set_dialogue(
+        &self,
+        id: &ChatId,
+        state: &str,
+    ) -> impl Future<Output = sqlx::Result<()>> + Send;
+
+    fn remove_dialogue(&self, id: &ChatId) -> impl Future<Output = sqlx::Result<()>> + Send;
+
+    /// Pins the message, replacing the one pinned in the chat before, if any.
+    fn set_pinned(&self, value: &Pinned) -> impl Future<Output = sqlx::Result<()>> + Send;
+
+    fn get_pinned(&self, id: &ChatId) -> impl Future<Output = sqlx::Result<Option<Pinned>>> + Send;
+
+    fn get_all_pinned(&self) -> impl Future<Output = sqlx::Result<Vec<Pinned>>> + Send;
+
+    fn remove_pinned(&self, id: &ChatId) -> impl Future<Output = sqlx::Result<()>> + Send;
+
+    /// Keeps the file, replacing the one uploaded to the chat before, if any.
+    fn set_upload(&self, value: &Upload) -> impl Future<Output = sqlx::Result<()>> + Send;
+
+    fn get_upload(&self, id: &ChatId) -> impl Future<Output = sqlx::Result<Option<Upload>>> + Send;
+
+    fn remove_upload(&self, id: &ChatId) -> impl Future<Output = sqlx::Result<()>> + Send;
+
+    /// The latest `limit` entries of the audit log, oldest first, of one
+    /// institution or of everything, and of `actions` or of every action
+    /// when there are none.
+    fn get_audit(
+        &self,
+        institution: Option<i64>,
+        actions: &[&str],
+        limit: i64,
+    ) -> impl Future<Output = sqlx::Result<Vec<Entry>>> + Send;
+}
+
+/// A message pinned to a chat, showing the timetable of `date`.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Pinned {
+    pub chat_id: ChatId,
+    pub message_id: MessageId,
+    pub date: NaiveDate,
+}
+
+impl Pinned {
+    /// `message 7 of 2023-10-16`, the way audit entries show it.
+    pub(crate) fn describe(&self) -> String {
+        format!("message {} of {}", self.message_id.0, self.date)
+    }
+}
+
+/// A timetable file sent to a chat, kept until it is applied or cancelled.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Upload {
+    pub chat_id: ChatId,
+    pub name: String,
+    pub text: String,
+}
+
+/// Teloxide dialogue [`Storage`] on top of a [`ScheduleStore`], so dialogues
+/// survive restarts. States are kept as JSON.
+pub struct Dialogues<S>(pub Arc<S>);
+
+impl<S: ScheduleStore, D> Storage<D> for Dialogues<S>
+where
+    D: Serialize + DeserializeOwned + Send + 'static,
+{
+    type Error = sqlx::Error;
+
+    fn remove_dialogue(self: Arc<Self>, chat_id: ChatId) -> BoxFuture<'static, sqlx::Result<()>>
+    where
+        D: Send + 'static,
+    {
+        Box::pin(async move { self.0.remove_dialogue(&chat_id).await })
+    }
+
+    fn update_dialogue(
+        self: Arc<Self>,
+        chat_id: ChatId,
+        dialogue: D,
+    ) -> BoxFuture<'static, sqlx::Result<()>>
+    where
+        D: Send + 'static,
+    {
+        Box::pin(async move {
+            let state = serde_json::to_string(&dialogue)
+                .map_err(|e| sqlx::Error::Protocol(e.to_string()))?;
+            self.0.set_dialogue(&chat_id, &state).await
+        })
+    }
+
+    fn get_dialogue(
+        self: Arc<Self>,
+        chat_id: ChatId,
+    ) -> BoxFuture<'static, sqlx::Result<Option<D>>> {
+        Box::pin(async move {
+            match self.0.get_dialogue(&chat_id).await? {
+                Some(state) => serde_json::from_str(&state)
+                    .map(Some)
+                    .map_err(|e| sqlx::Error::Decode(Box::new(e))),
+                None => Ok(None),
+            }
+        })
+    }
+}
+
+#[derive(Default)]
+struct Tables {
+    institutions: Vec<Institution>,
+    groups: Vec<(i64, Group)>,
+    grants: Vec<(ChatId, Grant)>,
+    users: Vec<(ChatId, User)>,
+    timezones: Vec<(ChatId, Tz)>,
+    languages: Vec<(ChatId, Language)>,
+    usernames: Vec<(ChatId, String)>,
+    friends: Vec<(ChatId, ChatId)>,
+    subjects: Vec<(i64, Subject)>,
+    schedule: Vec<(i64, Schedule)>,
+    meetings: Vec<(i64, Meeting)>,
+    assigned: Vec<(i64, Assigned)>,
+    calendar: Vec<(i64, Holiday)>,
+    rooms: Vec<(i64, i64, String)>,
+    changes: Vec<(i64, Change)>,
+    homework: Vec<(i64, Homework)>,
+    done: Vec<(i64, ChatId)>,
+    reminded: Vec<i64>,
+    notes: Vec<(ChatId, i64, Note)>,
+    enrolled: Vec<(ChatId, i64, i64)>,
+    attendance: Vec<(ChatId, i64, Attendance)>,
+    absences: Vec<(i64, i64)>,
+    semesters: Vec<(i64, Semesters)>,
+    max_points: Vec<(i64, i64, i64)>,
+    grades: Vec<(ChatId, i64, Grade)>,
+    exams: Vec<(i64, Exam)>,
+    countdowns: Vec<(i64, i64)>,
+    dialogues: Vec<(ChatId, String)>,
+    pinned: Vec<Pinned>,
+    uploads: Vec<Upload>,
+    audit: Vec<Entry>,
+}
+
+impl Tables {
+    fn has_user(&self, id: &ChatId) -> bool {
+        self.users.iter().any(|(chat, _)| chat == id)
+    }
+}
+
+/// [`ScheduleStore`] that keeps everything in memory, meant for tests.
+///
+/// Just like the migrated database, it starts with the `default` institution.
+pub struct MemoryStore {
+    tables: Mutex<Tables>,
+    /// Stamps the entries of the audit log.
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        let tables = Tables {
+            institutions: vec![Institution {
+                id: 0,
+                code: "default".into(),
+                name: "Default".into(),
+                timezone: chrono_tz::Europe::Kiev,
+                bells: Bells::default(),
+            }],
+            ..Default::default()
+        };
+        MemoryStore {
+            tables: Mutex::new(tables),
+            clock: Arc::new(SystemClock),
+        }
+    }
+}
+
+impl MemoryStore {
+    pub fn new() -> MemoryStore {
+        MemoryStore::default()
+    }
+
+    /// The same store, with changes logged at the times `clock` tells.
+    pub fn with_clock(self, clock: Arc<dyn Clock>) -> MemoryStore {
+        MemoryStore { clock, ..self }
+    }
+
+    fn with<T>(&self, f: impl FnOnce(&mut Tables) -> T) -> T {
+        f(&mut self.tables.lock().unwrap())
+    }
+}
+
+/// Classes of a subject the way the audit log shows them, if there are any.
+pub(crate) fn classes<'a>(classes: impl Iterator<Item = &'a Schedule>) -> Option<String> {
+    let mut classes: Vec<String> = classes.map(import::describe).collect();
+    classes.sort();
+    Some(classes.join("; ")).filter(|c| !c.is_empty())
+}
+
+/// Exams of a subject the way the audit log shows them, if there are any.
+pub(crate) fn exams<'a>(exams: impl Iterator<Item = &'a Exam>) -> Option<String> {
+    let mut exams: Vec<String> = exams.map(Exam::describe).collect();
+    exams.sort();
+    Some(exams.join("; ")).filter(|e| !e.is_empty())
+}
+
+fn duplicate(what: &str, key: impl std::fmt::Display) -> sqlx::Error {
+    sqlx::Error::Protocol(format!("Duplicate {} {}", what, key))
+}
+
+impl ScheduleStore for MemoryStore {
+    async fn add_institution(&self, value: &Institution) -> sqlx::Result<()> {
+        self.with(|t| {
+            if t.institutions
+                .iter()
+                .any(|i| i.id == value.id || i.code == value.code)
+            {
+                return Err(duplicate("institution", &value.code));
+            }
+            t.institutions.push(value.clone());
+            let after = format!("{}, {}", value.name, value.timezone.name());
+            t.audit.push(Entry::new(
+                self.clock.now(),
+                Some(value.id),
+                "add_institution",
+                value.code.clone(),
+                None,
+                Some(after),
+            ));
+            Ok(())
+        })
+    }
+
+    async fn set_bells(&self, institution: i64, bells: &Bells) -> sqlx::Result<()> {
+        self.with(|t| {
+            let found = t
+                .institutions
+                .iter_mut()
+                .find(|i| i.id == institution)
+                .ok_or(sqlx::Error::RowNotFound)?;
+            let before = std::mem::replace(&mut found.bells, *bells);
+            t.audit.push(Entry::new(
+                self.clock.now(),
+                Some(institution),
+                "set_bells",
+                format!("institution {}", institution),
+                Some(String::from(&before)),
+                Some(String::from(bells)),
+            ));
+            Ok(())
+        })
+    }
+
+    async fn set_absences(&self, institution: i64, absences: i64) -> sqlx::Result<()> {
+        let before = self.get_absences(institution).await?;
+        self.with(|t| {
+            t.absences.retain(|(i, _)| *i != institution);
+            t.absences.push((institution, absences));
+            t.audit.push(Entry::new(
+                self.clock.now(),
+                Some(institution),
+                "set_absences",
+                format!("institution {}", institution),
+                Some(before.to_string()),
+                Some(absences.to_string()),
+            ));
+            Ok(())
+        })
+    }
+
+    async fn get_absences(&self, institution: i64) -> sqlx::Result<i64> {
+        self.with(|t| {
+            if !t.institutions.iter().any(|i| i.id == institution) {
+                return Err(sqlx::Error::RowNotFound);
+            }
+            Ok(t.absences
+                .iter()
+                .find(|(i, _)| *i == institution)
+                .map_or(3, |(_, absences)| *absences))
+        })
+    }
+
+    async fn set_semesters(&self, institution: i64, semesters: &Semesters) -> sqlx::Result<()> {
+        let before = self.get_semesters(institution).await?;
+        self.with(|t| {
+            t.semesters.retain(|(i, _)| *i != institution);
+            t.semesters.push((institution, *semesters));
+            t.audit.push(Entry::new(
+                self.clock.now(),
+                Some(institution),
+                "set_semesters",
+                format!("institution {}", institution),
+                Some(String::from(&before)),
+                Some(String::from(semesters)),
+            ));
+            Ok(())
+        })
+    }
+
+    async fn get_semesters(&self, institution: i64) -> sqlx::Result<Semesters> {
+        self.with(|t| {
+            if !t.institutions.iter().any(|i| i.id == institution) {
+                return Err(sqlx::Error::RowNotFound);
+            }
+            Ok(t.semesters
+                .iter()
+                .find(|(i, _)| *i == institution)
+                .map_or_else(Semesters::default, |(_, semesters)| *semesters))
+        })
+    }
+
+    async fn get_institution(&self, id: i64) -> sqlx::Result<Institution> {
+        self.with(|t| {
+            t.institutions
+                .iter()
+                .find(|i| i.id == id)
+                .cloned()
+                .ok_or(sqlx::Error::RowNotFound)
+        })
+    }
+
+    async fn find_institution(&self, code: &str) -> sqlx::Result<Institution> {
+        self.with(|t| {
+            t.institutions
+                .iter()
+                .find(|i| i.code == code)
+                .cloned()
+                .ok_or(sqlx::Error::RowNotFound)
+        })
+    }
+
+    async fn get_institutions(&self) -> sqlx::Result<Vec<Institution>> {
+        self.with(|t| {
+            let mut institutions = t.institutions.clone();
+            institutions.sort_by_key(|i| i.id);
+            Ok(institutions)
+        })
+    }
+
+    async fn add_group(&self, institution: i64, group: &Group) -> sqlx::Result<()> {
+        self.with(|t| {
+            if !t.groups.contains(&(institution, group.clone())) {
+                t.groups.push((institution, group.clone()));
+                t.audit.push(Entry::new(
+                    self.clock.now(),
+                    Some(institution),
+                    "add_group",
+                    group.to_string(),
+                    None,
+                    Some(group.to_string()),
+                ));
+            }
+        });
+        Ok(())
+    }
+
+    async fn get_groups(&self, institution: i64) -> sqlx::Result<Vec<Group>> {
+        self.with(|t| {
+            let mut groups: Vec<Group> = t
+                .groups
+                .iter()
+                .filter(|(i, _)| *i == institution)
+                .map(|(_, group)| group.clone())
+                .collect();
+            groups.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+            Ok(groups)
+        })
+    }
+
+    async fn add_grant(&self, id: &ChatId, value: &Grant) -> sqlx::Result<()> {
+        self.with(|t| {
+            let same = |chat: &ChatId, g: &Grant| {
+                chat == id && g.institution == value.institution && g.group == value.group
+            };
+            let before = t.grants.iter().find(|(chat, g)| same(chat, g));
+            let before = before.map(|(_, g)| String::from(&g.role));
+            t.grants.retain(|(chat, g)| !same(chat, g));
+            t.grants.push((*id, value.clone()));
+            t.audit.push(Entry::new(
+                self.clock.now(),
+                Some(value.institution),
+                "add_grant",
+                audit::scope(id, value.group.as_ref()),
+                before,
+                Some(String::from(&value.role)),
+            ));
+        });
+        Ok(())
+    }
+
+    async fn get_grants(&self, id: &ChatId) -> sqlx::Result<Vec<Grant>> {
+        self.with(|t| {
+            Ok(t.grants
+                .iter()
+                .filter(|(chat, _)| chat == id)
+                .map(|(_, g)| g.clone())
+                .collect())
+        })
+    }
+
+    async fn remove_grant(
+        &self,
+        id: &ChatId,
+        institution: i64,
+        group: Option<&Group>,
+    ) -> sqlx::Result<()> {
+        self.with(|t| {
+            let same = |chat: &ChatId, g: &Grant| {
+                chat == id && g.institution == institution && g.group.as_ref() == group
+            };
+            let Some((_, before)) = t.grants.iter().find(|(chat, g)| same(chat, g)) else {
+                return;
+            };
+            let before = String::from(&before.role);
+            t.grants.retain(|(chat, g)| !same(chat, g));
+            t.audit.push(Entry::new(
+                self.clock.now(),
+                Some(institution),
+                "remove_grant",
+                audit::scope(id, group),
+                Some(before),
+                None,
+            ));
+        });
+        Ok(())
+    }
+
+    async fn add_user(&self, id: &ChatId, user: &User) -> sqlx::Result<()> {
+        self.with(|t| {
+            if t.has_user(id) {
+                return Err(duplicate("user", id));
+            }
+            t.users.push((*id, user.clone()));
+            t.audit.push(Entry::new(
+                self.clock.now(),
+                Some(user.institution),
+                "add_user",
+                audit::chat(id),
+                None,
+                Some(user.group.to_string()),
+            ));
+            Ok(())
+        })
+    }
+
+    async fn update_user(&self, id: &ChatId, user: &User) -> sqlx::Result<()> {
+        self.with(|t| {
+            let Some((_, value)) = t.users.iter_mut().find(|(chat, _)| chat == id) else {
+                return Ok(());
+            };
+            let before = std::mem::replace(value, user.clone());
+            t.audit.push(Entry::new(
+                self.clock.now(),
+                Some(user.institution),
+                "update_user",
+                audit::chat(id),
+                Some(before.group.to_string()),
+                Some(user.group.to_string()),
+            ));
+            Ok(())
+        })
+    }
+
+    async fn get_user(&self, id: &ChatId) -> sqlx::Result<User> {
+        self.with(|t| {
+            t.users
+                .iter()
+                .find(|(chat, _)| chat == id)
+                .map(|(_, user)| user.clone())
+                .ok_or(sqlx::Error::RowNotFound)
+        })
+    }
+
+    async fn remove_user(&self, id: &ChatId) -> sqlx::Result<()> {
+        self.with(|t| {
+            let Some(index) = t.users.iter().position(|(chat, _)| chat == id) else {
+                return;
+            };
+            let (_, before) = t.users.remove(index);
+            t.timezones.retain(|(chat, _)| chat != id);
+            t.languages.retain(|(chat, _)| chat != id);
+            t.usernames.retain(|(chat, _)| chat != id);
+            t.friends.retain(|(a, b)| a != id && b != id);
+            t.audit.push(Entry::new(
+                self.clock.now(),
+                Some(before.institution),
+                "remove_user",
+                audit::chat(id),
+                Some(before.group.to_string()),
+                None,
+            ));
+        });
+        Ok(())
+    }
+
+    async fn get_chats(&self, institution: i64, group: &Group) -> sqlx::Result<Vec<ChatId>> {
+        self.with(|t| {
+            Ok(t.users
+                .iter()
+                .filter(|(_, u)| u.institution == institution && u.group == *group)
+                .map(|(chat, _)| *chat)
+                .collect())
+        })
+    }
+
+    async fn set_timezone(&self, id: &ChatId, timezone: Option<Tz>) -> sqlx::Result<()> {
+        self.with(|t| {
+            if !t.has_user(id) {
+                return Err(sqlx::Error::RowNotFound);
+            }
+            let before = t.timezones.iter().find(|(chat, _)| chat == id);
+            let before = before.map(|(_, tz)| tz.name().to_string());
+            t.timezones.retain(|(chat, _)| chat != id);
+            if let Some(timezone) = timezone {
+                t.timezones.push((*id, timezone));
+            }
+            t.audit.push(Entry::new(
+                self.clock.now(),
+                None,
+                "set_timezone",
+                audit::chat(id),
+                before,
+                timezone.map(|tz| tz.name().to_string()),
+            ));
+            Ok(())
+        })
+    }
+
+    async fn get_timezone(&self, id: &ChatId) -> sqlx::Result<Option<Tz>> {
+        self.with(|t| {
+            if !t.has_user(id) {
+                return Err(sqlx::Error::RowNotFound);
+            }
+            Ok(t.timezones
+                .iter()
+                .find(|(chat, _)| chat == id)
+                .map(|(_, timezone)| *timezone))
+        })
+    }
+
+    async fn set_language(&self, id: &ChatId, language: Option<Language>) -> sqlx::Result<()> {
+        self.with(|t| {
+            if !t.has_user(id) {
+                return Err(sqlx::Error::RowNotFound);
+            }
+            let before = t.languages.iter().find(|(chat, _)| chat == id);
+            let before = before.map(|(_, l)| l.code().to_string());
+            t.languages.retain(|(chat, _)| chat != id);
+            if let Some(language) = language {
+                t.languages.push((*id, language));
+            }
+            t.audit.push(Entry::new(
+                self.clock.now(),
+                None,
+                "set_language",
+                audit::chat(id),
+                before,
+                language.map(|l| l.code().to_string()),
+            ));
+            Ok(())
+        })
+    }
+
+    async fn get_language(&self, id: &ChatId) -> sqlx::Result<Option<Language>> {
+        self.with(|t| {
+            if !t.has_user(id) {
+                return Err(sqlx::Error::RowNotFound);
+            }
+            Ok(t.languages
+                .iter()
+                .find(|(chat, _)| chat == id)
+                .map(|(_, language)| *language))
+        })
+    }
+
+    async fn set_username(&self, id: &ChatId, username: Option<&str>) -> sqlx::Result<()> {
+        self.with(|t| {
+            if !t.has_user(id) {
+                return Err(sqlx::Error::RowNotFound);
+            }
+            let before = t.usernames.iter().find(|(chat, _)| chat == id);
+            let before = before.map(|(_, name)| name.clone());
+            t.usernames
+                .retain(|(chat, name)| chat != id && Some(name.as_str()) != username);
+            if let Some(username) = username {
+                t.usernames.push((*id, username.into()));
+            }
+            t.audit.push(Entry::new(
+                self.clock.now(),
+                None,
+                "set_username",
+                audit::chat(id),
+                before,
+                username.map(String::from),
+            ));
+            Ok(())
+        })
+    }
+
+    async fn get_username(&self, id: &ChatId) -> sqlx::Result<Option<String>> {
+        self.with(|t| {
+            if !t.has_user(id) {
+                return Err(sqlx::Error::RowNotFound);
+            }
+            Ok(t.usernames
+                .iter()
+                .find(|(chat, _)| chat == id)
+                .map(|(_, name)| name.clone()))
+        })
+    }
+
+    async fn find_username(&self, username: &str) -> sqlx::Result<Option<ChatId>> {
+        self.with(|t| {
+            Ok(t.usernames
+                .iter()
+                .find(|(_, name)| name == username)
+                .map(|(chat, _)| *chat))
+        })
+    }
+
+    async fn add_friend(&self, id: &ChatId, friend: &ChatId) -> sqlx::Result<()> {
+        self.with(|t| {
+            if !t.friends.contains(&(*id, *friend)) {
+                t.friends.push((*id, *friend));
+            }
+            t.audit.push(Entry::new(
+                self.clock.now(),
+                None,
+                "add_friend",
+                audit::chat(id),
+                None,
+                Some(audit::chat(friend)),
+            ));
+        });
+        Ok(())
+    }
+
+    async fn remove_friend(&self, id: &ChatId, friend: &ChatId) -> sqlx::Result<()> {
+        self.with(|t| {
+            t.friends
+                .retain(|link| *link != (*id, *friend) && *link != (*friend, *id));
+            t.audit.push(Entry::new(
+                self.clock.now(),
+                None,
+                "remove_friend",
+                audit::chat(id),
+                Some(audit::chat(friend)),
+                None,
+            ));
+        });
+        Ok(())
+    }
+
+    async fn get_friends(&self, id: &ChatId) -> sqlx::Result<Vec<ChatId>> {
+        self.with(|t| {
+            let mut friends: Vec<ChatId> = t
+                .friends
+                .iter()
+                .filter(|(chat, _)| chat == id)
+                .map(|(_, friend)| *friend)
+                .collect();
+            friends.sort_by_key(|f| f.0);
+            Ok(friends)
+        })
+    }
+
+    async fn add_subject(&self, institution: i64, value: &Subject) -> sqlx::Result<()> {
+        self.with(|t| {
+            if t.subjects
+                .iter()
+                .any(|(i, s)| *i == institution && s.id == value.id)
+            {
+                return Err(duplicate("subject", value.id));
+            }
+            t.subjects.push((institution, value.clone()));
+            t.audit.push(Entry::new(
+                self.clock.now(),
+                Some(institution),
+                "add_subject",
+                audit::subject(value.id),
+                None,
+                Some(format!("{}, {}", value.title, value.group)),
+            ));
+            Ok(())
+        })
+    }
+
+    async fn get_subject(&self, institution: i64, id: i64) -> sqlx::Result<Subject> {
+        self.with(|t| {
+            t.subjects
+                .iter()
+                .find(|(i, s)| *i == institution && s.id == id)
+                .map(|(_, s)| s.clone())
+                .ok_or(sqlx::Error::RowNotFound)
+        })
+    }
+
+    async fn get_subjects(
+        &self,
+        institution: i64,
+        day: Day,
+        repeat: Repeat,
+        slot: Slot,
+        group: Group,
+    ) -> sqlx::Result<Vec<Subject>> {
+        self.with(|t| {
+            let ids: Vec<i64> = t
+                .schedule
+                .iter()
+                .filter(|(i, s)| {
+                    *i == institution
+                        && s.day == day
+                        && (s.repeat == repeat || s.repeat == Repeat::Both)
+                        && s.slot == slot
+                })
+                .map(|(_, s)| s.subject_id)
+                .collect();
+            Ok(t.subjects
+                .iter()
+                .filter(|(i, s)| *i == institution && ids.contains(&s.id) && s.group == group)
+                .map(|(_, s)| s.clone())
+                .collect())
+        })
+    }
+
+    async fn get_all_subjects(&self, institution: i64) -> sqlx::Result<Vec<Subject>> {
+        self.with(|t| {
+            let mut subjects: Vec<Subject> = t
+                .subjects
+                .iter()
+                .filter(|(i, _)| *i == institution)
+                .map(|(_, s)| s.clone())
+                .collect();
+            subjects.sort_by_key(|s| s.id);
+            Ok(subjects)
+        })
+    }
+
+    async fn add_schedule(&self, institution: i64, value: &Schedule) -> sqlx::Result<()> {
+        self.with(|t| {
+            t.schedule.push((institution, value.clone()));
+            let after = format!(
+                "{:?} slot {}, {:?}",
+                value.day, value.slot as u8, value.repeat
+            );
+            t.audit.push(Entry::new(
+                self.clock.now(),
+                Some(institution),
+                "add_schedule",
+                audit::subject(value.subject_id),
+                None,
+                Some(after),
+            ));
+        });
+        Ok(())
+    }
+
+    async fn get_schedule(&self, institution: i64) -> sqlx::Result<Vec<Schedule>> {
+        self.with(|t| {
+            Ok(t.schedule
+                .iter()
+                .filter(|(i, _)| *i == institution)
+                .map(|(_, s)| s.clone())
+                .collect())
+        })
+    }
+
+    async fn import(&self, institution: i64, value: &Import) -> sqlx::Result<()> {
+        self.with(|t| {
+            for subject in &value.subjects {
+                if !t
+                    .groups
+                    .iter()
+                    .any(|(i, g)| *i == institution && *g == subject.group)
+                {
+                    t.groups.push((institution, subject.group.clone()));
+                    t.audit.push(Entry::new(
+                        self.clock.now(),
+                        Some(institution),
+                        "add_group",
+                        subject.group.to_string(),
+                        None,
+                        Some(subject.group.to_string()),
+                    ));
+                }
+                let before = t
+                    .subjects
+                    .iter()
+                    .position(|(i, s)| *i == institution && s.id == subject.id);
+                let before = before.map(|index| t.subjects.remove(index).1);
+                let after = format!("{}, {}", subject.title, subject.group);
+                let before = before.map(|s| format!("{}, {}", s.title, s.group));
+                t.subjects.push((institution, subject.clone()));
+                if before.as_ref() != Some(&after) {
+                    let target = audit::subject(subject.id);
+                    t.audit.push(Entry::new(
+                        self.clock.now(),
+                        Some(institution),
+                        "import",
+                        target,
+                        before,
+                        Some(after),
+                    ));
+                }
+            }
+            for id in value.scheduled() {
+                let before = classes(
+                    t.schedule
+                        .iter()
+                        .filter(|(i, s)| *i == institution && s.subject_id == id)
+                        .map(|(_, s)| s),
+                );
+                t.schedule
+                    .retain(|(i, s)| *i != institution || s.subject_id != id);
+                let added = value.schedule.iter().filter(|s| s.subject_id == id);
+                t.schedule
+                    .extend(added.clone().map(|s| (institution, s.clone())));
+                let after = classes(added);
+                if before != after {
+                    t.audit.push(Entry::new(
+                        self.clock.now(),
+                        Some(institution),
+                        "import",
+                        audit::subject(id),
+                        before,
+                        after,
+                    ));
+                }
+            }
+            for id in value.examined() {
+                let old: Vec<Exam> = t
+                    .exams
+                    .iter()
+                    .filter(|(i, e)| *i == institution && e.subject_id == id)
+                    .map(|(_, e)| e.clone())
+                    .collect();
+                let new: Vec<&Exam> = value.exams.iter().filter(|e| e.subject_id == id).collect();
+                for exam in old.iter().filter(|o| !new.iter().any(|e| e.same(o))) {
+                    t.exams.retain(|(_, e)| e.id != exam.id);
+                    t.countdowns.retain(|(e, _)| *e != exam.id);
+                }
+                for exam in new.iter().filter(|e| !old.iter().any(|o| o.same(e))) {
+                    let id = t.exams.iter().map(|(_, e)| e.id + 1).max().unwrap_or(1);
+                    t.exams.push((
+                        institution,
+                        Exam {
+                            id,
+                            ..(*exam).clone()
+                        },
+                    ));
+                }
+                let (before, after) = (exams(old.iter()), exams(new.into_iter()));
+                if before != after {
+                    t.audit.push(Entry::new(
+                        self.clock.now(),
+                        Some(institution),
+                        "import",
+                        audit::subject(id),
+                        before,
+                        after,
+                    ));
+                }
+            }
+        });
+        Ok(())
+    }
+
+    async fn add_meeting(&self, institution: i64, value: &Meeting) -> sqlx::Result<()> {
+        self.with(|t| {
+            if t.meetings
+                .iter()
+                .any(|(i, m)| *i == institution && m.id == value.id)
+            {
+                return Err(duplicate("meeting", value.id));
+            }
+            t.meetings.push((institution, value.clone()));
+            t.audit.push(Entry::new(
+                self.clock.now(),
+                Some(institution),
+                "add_meeting",
+                audit::meeting(value.id),
+                None,
+                Some(format!("{}, {}", value.name, value.link)),
+            ));
+            Ok(())
+        })
+    }
+
+    async fn assign(&self, institution: i64, value: &Assigned) -> sqlx::Result<()> {
+        self.with(|t| {
+            t.assigned.push((institution, value.clone()));
+            t.audit.push(Entry::new(
+                self.clock.now(),
+                Some(institution),
+                "assign",
+                audit::meeting(value.meeting_id),
+                None,
+                Some(audit::subject(value.subject_id)),
+            ));
+        });
+        Ok(())
+    }
+
+    async fn get_meetings(&self, institution: i64, subject_id: i64) -> sqlx::Result<Vec<Meeting>> {
+        self.with(|t| {
+            let ids: Vec<i64> = t
+                .assigned
+                .iter()
+                .filter(|(i, a)| *i == institution && a.subject_id == subject_id)
+                .map(|(_, a)| a.meeting_id)
+                .collect();
+            Ok(t.meetings
+                .iter()
+                .filter(|(i, m)| *i == institution && ids.contains(&m.id))
+                .map(|(_, m)| m.clone())
+                .collect())
+        })
+    }
+
+    async fn next_meeting_id(&self, institution: i64) -> sqlx::Result<i64> {
+        self.with(|t| {
+            Ok(t.meetings
+                .iter()
+                .filter(|(i, _)| *i == institution)
+                .map(|(_, m)| m.id + 1)
+                .max()
+                .unwrap_or(0))
+        })
+    }
+
+    async fn set_room(&self, institution: i64, subject_id: i64, room: &str) -> sqlx::Result<()> {
+        self.with(|t| {
+            let same = |i: &i64, s: &i64| *i == institution && *s == subject_id;
+            let before = t.rooms.iter().find(|(i, s, _)| same(i, s));
+            let before = before.map(|(_, _, room)| room.clone());
+            t.rooms.retain(|(i, s, _)| !same(i, s));
+            t.rooms.push((institution, subject_id, room.into()));
+            t.audit.push(Entry::new(
+                self.clock.now(),
+                Some(institution),
+                "set_room",
+                audit::subject(subject_id),
+                before,
+                Some(room.into()),
+            ));
+        });
+        Ok(())
+    }
+
+    async fn get_room(&self, institution: i64, subject_id: i64) -> sqlx::Result<Option<String>> {
+        self.with(|t| {
+            Ok(t.rooms
+                .iter()
+                .find(|(i, s, _)| *i == institution && *s == subject_id)
+                .map(|(_, _, room)| room.clone()))
+        })
+    }
+
+    async fn add_change(&self, institution: i64, value: &Change) -> sqlx::Result<()> {
+        self.with(|t| {
+            let same = |i: &i64, c: &Change| {
+                *i == institution
+                    && c.group == value.group
+                    && c.date == value.date
+                    && c.slot == value.slot
+            };
+            let before = t.changes.iter().find(|(i, c)| same(i, c));
+            let before = before.map(|(_, c)| audit::replaced(c.subject_id));
+            t.changes.retain(|(i, c)| !same(i, c));
+            t.changes.push((institution, value.clone()));
+            t.audit.push(Entry::new(
+                self.clock.now(),
+                Some(institution),
+                "add_change",
+                audit::class(&value.group, value.date, value.slot),
+                before,
+                Some(audit::replaced(value.subject_id)),
+            ));
+        });
+        Ok(())
+    }
+
+    async fn get_change(
+        &self,
+        institution: i64,
+        group: &Group,
+        date: NaiveDate,
+        slot: Slot,
+    ) -> sqlx::Result<Option<Change>> {
+        self.with(|t| {
+            Ok(t.changes
+                .iter()
+                .find(|(i, c)| {
+                    *i == institution && c.group == *group && c.date == date && c.slot == slot
+                })
+                .map(|(_, c)| c.clone()))
+        })
+    }
+
+    async fn add_holiday(&self, institution: i64, value: &Holiday) -> sqlx::Result<()> {
+        self.with(|t| {
+            let same = |i: &i64, h: &Holiday| *i == institution && h.date == value.date;
+            let before = t.calendar.iter().find(|(i, h)| same(i, h));
+            let before = before.map(|(_, h)| h.title.clone());
+            t.calendar.retain(|(i, h)| !same(i, h));
+            t.calendar.push((institution, value.clone()));
+            t.audit.push(Entry::new(
+                self.clock.now(),
+                Some(institution),
+                "add_holiday",
+                value.date.to_string(),
+                before,
+                Some(value.title.clone()),
+            ));
+        });
+        Ok(())
+    }
+
+    async fn get_holiday(
+        &self,
+        institution: i64,
+        date: NaiveDate,
+    ) -> sqlx::Result<Option<Holiday>> {
+        self.with(|t| {
+            Ok(t.calendar
+                .iter()
+                .find(|(i, h)| *i == institution && h.date == date)
+                .map(|(_, h)| h.clone()))
+        })
+    }
+
+    async fn get_holidays(
+        &self,
+        institution: i64,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> sqlx::Result<Vec<Holiday>> {
+        self.with(|t| {
+            let mut holidays: Vec<Holiday> = t
+                .calendar
+                .iter()
+                .filter(|(i, h)| *i == institution && from <= h.date && h.date <= to)
+                .map(|(_, h)| h.clone())
+                .collect();
+            holidays.sort_by_key(|h| h.date);
+            Ok(holidays)
+        })
+    }
+
+    async fn add_homework(&self, institution: i64, value: &Homework) -> sqlx::Result<i64> {
+        self.with(|t| {
+            if !t
+                .subjects
+                .iter()
+                .any(|(i, s)| *i == institution && s.id == value.subject_id)
+            {
+                return Err(sqlx::Error::RowNotFound);
+            }
+            let id = t.homework.iter().map(|(_, h)| h.id + 1).max().unwrap_or(1);
+            let value = Homework {
+                id,
+                ..value.clone()
+            };
+            t.audit.push(Entry::new(
+                self.clock.now(),
+                Some(institution),
+                "add_homework",
+                audit::homework(id),
+                None,
+                Some(value.describe()),
+            ));
+            t.homework.push((institution, value));
+            Ok(id)
+        })
+    }
+
+    async fn get_homework(&self, institution: i64, id: i64) -> sqlx::Result<Homework> {
+        self.with(|t| {
+            t.homework
+                .iter()
+                .find(|(i, h)| *i == institution && h.id == id)
+                .map(|(_, h)| h.clone())
+                .ok_or(sqlx::Error::RowNotFound)
+        })
+    }
+
+    async fn get_due_homework(
+        &self,
+        institution: i64,
+        group: &Group,
+        chat: &ChatId,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> sqlx::Result<Vec<Homework>> {
+        self.with(|t| {
+            let shared: Vec<i64> = t
+                .subjects
+                .iter()
+                .filter(|(i, s)| *i == institution && s.group == *group)
+                .map(|(_, s)| s.id)
+                .collect();
+            let mut homework: Vec<Homework> = t
+                .homework
+                .iter()
+                .filter(|(i, h)| {
+                    *i == institution
+                        && from <= h.due
+                        && h.due <= to
+                        && ((h.shared && shared.contains(&h.subject_id)) || h.author == *chat)
+                })
+                .map(|(_, h)| h.clone())
+                .collect();
+            homework.sort_by_key(|h| (h.due, h.id));
+            Ok(homework)
+        })
+    }
+
+    async fn remove_homework(&self, institution: i64, id: i64) -> sqlx::Result<()> {
+        self.with(|t| {
+            let Some(index) = t
+                .homework
+                .iter()
+                .position(|(i, h)| *i == institution && h.id == id)
+            else {
+                return;
+            };
+            let (_, before) = t.homework.remove(index);
+            t.done.retain(|(h, _)| *h != id);
+            t.reminded.retain(|h| *h != id);
+            t.audit.push(Entry::new(
+                self.clock.now(),
+                Some(institution),
+                "remove_homework",
+                audit::homework(id),
+                Some(before.describe()),
+                None,
+            ));
+        });
+        Ok(())
+    }
+
+    async fn set_done(&self, id: &ChatId, homework: i64, done: bool) -> sqlx::Result<()> {
+        self.with(|t| {
+            if !t.homework.iter().any(|(_, h)| h.id == homework) {
+                return Err(sqlx::Error::RowNotFound);
+            }
+            let before = t.done.contains(&(homework, *id));
+            t.done.retain(|(h, chat)| *h != homework || chat != id);
+            if done {
+                t.done.push((homework, *id));
+            }
+            t.audit.push(Entry::personal(
+                self.clock.now(),
+                "set_done",
+                format!("{} by {}", audit::homework(homework), audit::chat(id)),
+                before.then(|| "done".into()),
+                done.then(|| "done".into()),
+            ));
+            Ok(())
+        })
+    }
+
+    async fn get_done(&self, homework: i64) -> sqlx::Result<Vec<ChatId>> {
+        self.with(|t| {
+            Ok(t.done
+                .iter()
+                .filter(|(h, _)| *h == homework)
+                .map(|(_, chat)| *chat)
+                .collect())
+        })
+    }
+
+    async fn get_unreminded(&self) -> sqlx::Result<Vec<(i64, Homework)>> {
+        self.with(|t| {
+            Ok(t.homework
+                .iter()
+                .filter(|(_, h)| !t.reminded.contains(&h.id))
+                .cloned()
+                .collect())
+        })
+    }
+
+    async fn set_reminded(&self, homework: i64) -> sqlx::Result<()> {
+        self.with(|t| {
+            let Some((institution, _)) = t.homework.iter().find(|(_, h)| h.id == homework) else {
+                return;
+            };
+            let institution = *institution;
+            if !t.reminded.contains(&homework) {
+                t.reminded.push(homework);
+                t.audit.push(Entry::new(
+                    self.clock.now(),
+                    Some(institution),
+                    "set_reminded",
+                    audit::homework(homework),
+                    None,
+                    Some("reminded".into()),
+                ));
+            }
+        });
+        Ok(())
+    }
+
+    async fn set_note(
+        &self,
+        id: &ChatId,
+        institution: i64,
+        subject_id: i64,
+        text: Option<&str>,
+    ) -> sqlx::Result<()> {
+        self.with(|t| {
+            if !t
+                .subjects
+                .iter()
+                .any(|(i, s)| *i == institution && s.id == subject_id)
+            {
+                return Err(sqlx::Error::RowNotFound);
+            }
+            let mine = |(chat, i, n): &(ChatId, i64, Note)| {
+                chat == id && *i == institution && n.subject_id == subject_id
+            };
+            let before = t
+                .notes
+                .iter()
+                .find(|n| mine(n))
+                .map(|(_, _, n)| n.text.clone());
+            t.notes.retain(|n| !mine(n));
+            if let Some(text) = text {
+                let note = Note {
+                    subject_id,
+                    text: text.into(),
+                };
+                t.notes.push((*id, institution, note));
+            }
+            t.audit.push(Entry::personal(
+                self.clock.now(),
+                "set_note",
+                format!("{} by {}", audit::subject(subject_id), audit::chat(id)),
+                before.map(|_| audit::note(true)),
+                Some(audit::note(text.is_some())),
+            ));
+            Ok(())
+        })
+    }
+
+    async fn get_notes(&self, id: &ChatId, institution: i64) -> sqlx::Result<Vec<Note>> {
+        self.with(|t| {
+            let mut notes: Vec<Note> = t
+                .notes
+                .iter()
+                .filter(|(chat, i, _)| chat == id && *i == institution)
+                .map(|(_, _, n)| n.clone())
+                .collect();
+            notes.sort_by_key(|n| n.subject_id);
+            Ok(notes)
+        })
+    }
+
+    async fn set_enrolled(
+        &self,
+        id: &ChatId,
+        institution: i64,
+        subject_id: i64,
+        enrolled: bool,
+    ) -> sqlx::Result<()> {
+        self.with(|t| {
+            if !t
+                .subjects
+                .iter()
+                .any(|(i, s)| *i == institution && s.id == subject_id)
+            {
+                return Err(sqlx::Error::RowNotFound);
+            }
+            let row = (*id, institution, subject_id);
+            let before = t.enrolled.contains(&row);
+            t.enrolled.retain(|r| *r != row);
+            if enrolled {
+                t.enrolled.push(row);
+            }
+            t.audit.push(Entry::personal(
+                self.clock.now(),
+                "set_enrolled",
+                format!("{} by {}", audit::subject(subject_id), audit::chat(id)),
+                Some(before.to_string()),
+                Some(enrolled.to_string()),
+            ));
+            Ok(())
+        })
+    }
+
+    async fn get_enrolled(&self, id: &ChatId, institution: i64) -> sqlx::Result<Vec<i64>> {
+        self.with(|t| {
+            let mut ids: Vec<i64> = t
+                .enrolled
+                .iter()
+                .filter(|(chat, i, _)| chat == id && *i == institution)
+                .map(|(_, _, subject)| *subject)
+                .collect();
+            ids.sort();
+            Ok(ids)
+        })
+    }
+
+    async fn set_attendance(
+        &self,
+        id: &ChatId,
+        institution: i64,
+        value: &Attendance,
+    ) -> sqlx::Result<()> {
+        self.with(|t| {
+            if !t
+                .subjects
+                .iter()
+                .any(|(i, s)| *i == institution && s.id == value.subject_id)
+            {
+                return Err(sqlx::Error::RowNotFound);
+            }
+            let same = |(chat, i, a): &(ChatId, i64, Attendance)| {
+                chat == id
+                    && *i == institution
+                    && a.subject_id == value.subject_id
+                    && a.date == value.date
+            };
+            let before = t
+                .attendance
+                .iter()
+                .find(|row| same(row))
+                .map(|(_, _, a)| a.attended.to_string());
+            t.attendance.retain(|row| !same(row));
+            t.attendance.push((*id, institution, value.clone()));
+            t.audit.push(Entry::personal(
+                self.clock.now(),
+                "set_attendance",
+                format!(
+                    "{} on {} by {}",
+                    audit::subject(value.subject_id),
+                    value.date,
+                    audit::chat(id)
+                ),
+                before,
+                Some(value.attended.to_string()),
+            ));
+            Ok(())
+        })
+    }
+
+    async fn get_attendance(&self, id: &ChatId, institution: i64) -> sqlx::Result<Vec<Attendance>> {
+        self.with(|t| {
+            let mut marks: Vec<Attendance> = t
+                .attendance
+                .iter()
+                .filter(|(chat, i, _)| chat == id && *i == institution)
+                .map(|(_, _, a)| a.clone())
+                .collect();
+            marks.sort_by_key(|a| (a.subject_id, a.date));
+            Ok(marks)
+        })
+    }
+
+    async fn get_tracking(&self, institution: i64) -> sqlx::Result<Vec<(ChatId, Vec<i64>)>> {
+        self.with(|t| {
+            let mut chats: Vec<ChatId> = t
+                .attendance
+                .iter()
+                .filter(|(_, i, _)| *i == institution)
+                .map(|(chat, _, _)| *chat)
+                .collect();
+            chats.sort();
+            chats.dedup();
+            Ok(chats
+                .into_iter()
+                .map(|chat| {
+                    let mut ids: Vec<i64> = t
+                        .enrolled
+                        .iter()
+                        .filter(|(c, i, _)| *c == chat && *i == institution)
+                        .map(|(_, _, subject)| *subject)
+                        .collect();
+                    ids.sort();
+                    (chat, ids)
+                })
+                .collect())
+        })
+    }
+
+    async fn set_max_points(
+        &self,
+        institution: i64,
+        subject_id: i64,
+        points: i64,
+    ) -> sqlx::Result<()> {
+        let before = self.get_max_points(institution, subject_id).await?;
+        self.with(|t| {
+            t.max_points
+                .retain(|(i, s, _)| *i != institution || *s != subject_id);
+            t.max_points.push((institution, subject_id, points));
+            t.audit.push(Entry::new(
+                self.clock.now(),
+                Some(institution),
+                "set_max_points",
+                audit::subject(subject_id),
+                Some(before.to_string()),
+                Some(points.to_string()),
+            ));
+            Ok(())
+        })
+    }
+
+    async fn get_max_points(&self, institution: i64, subject_id: i64) -> sqlx::Result<i64> {
+        self.with(|t| {
+            if !t
+                .subjects
+                .iter()
+                .any(|(i, s)| *i == institution && s.id == subject_id)
+            {
+                return Err(sqlx::Error::RowNotFound);
+            }
+            Ok(t.max_points
+                .iter()
+                .find(|(i, s, _)| *i == institution && *s == subject_id)
+                .map_or(100, |(_, _, points)| *points))
+        })
+    }
+
+    async fn add_grade(&self, id: &ChatId, institution: i64, value: &Grade) -> sqlx::Result<i64> {
+        self.with(|t| {
+            if !t
+                .subjects
+                .iter()
+                .any(|(i, s)| *i == institution && s.id == value.subject_id)
+            {
+                return Err(sqlx::Error::RowNotFound);
+            }
+            let grade = t.grades.iter().map(|(_, _, g)| g.id + 1).max().unwrap_or(1);
+            let value = Grade {
+                id: grade,
+                ..value.clone()
+            };
+            t.audit.push(Entry::personal(
+                self.clock.now(),
+                "add_grade",
+                format!("{} by {}", audit::grade(grade), audit::chat(id)),
+                None,
+                Some(audit::subject(value.subject_id)),
+            ));
+            t.grades.push((*id, institution, value));
+            Ok(grade)
+        })
+    }
+
+    async fn remove_grade(&self, id: &ChatId, institution: i64, grade: i64) -> sqlx::Result<()> {
+        self.with(|t| {
+            let Some(index) = t
+                .grades
+                .iter()
+                .position(|(chat, i, g)| chat == id && *i == institution && g.id == grade)
+            else {
+                return Ok(());
+            };
+            let (_, _, before) = t.grades.remove(index);
+            t.audit.push(Entry::personal(
+                self.clock.now(),
+                "remove_grade",
+                format!("{} by {}", audit::grade(grade), audit::chat(id)),
+                Some(audit::subject(before.subject_id)),
+                None,
+            ));
+            Ok(())
+        })
+    }
+
+    async fn get_grades(&self, id: &ChatId, institution: i64) -> sqlx::Result<Vec<Grade>> {
+        self.with(|t| {
+            let mut grades: Vec<Grade> = t
+                .grades
+                .iter()
+                .filter(|(chat, i, _)| chat == id && *i == institution)
+                .map(|(_, _, g)| g.clone())
+                .collect();
+            grades.sort_by_key(|g| (g.subject_id, g.date, g.id));
+            Ok(grades)
+        })
+    }
+
+    async fn get_exams(&self, institution: i64) -> sqlx::Result<Vec<Exam>> {
+        self.with(|t| {
+            let mut exams: Vec<Exam> = t
+                .exams
+                .iter()
+                .filter(|(i, _)| *i == institution)
+                .map(|(_, e)| e.clone())
+                .collect();
+            exams.sort_by_key(|e| (e.starts, e.id));
+            Ok(exams)
+        })
+    }
+
+    async fn get_countdowns(&self) -> sqlx::Result<Vec<(i64, Exam, Option<i64>)>> {
+        self.with(|t| {
+            let mut exams: Vec<(i64, Exam, Option<i64>)> = t
+                .exams
+                .iter()
+                .filter_map(|(i, e)| {
+                    let days = t.countdowns.iter().find(|(id, _)| *id == e.id);
+                    match days {
+                        Some((_, 0)) => None,
+                        Some((_, days)) => Some((*i, e.clone(), Some(*days))),
+                        None => Some((*i, e.clone(), None)),
+                    }
+                })
+                .collect();
+            exams.sort_by_key(|(_, e, _)| (e.starts, e.id));
+            Ok(exams)
+        })
+    }
+
+    async fn set_countdown(&self, exam: i64, days: i64) -> sqlx::Result<()> {
+        self.with(|t| {
+            let Some((institution, _)) = t.exams.iter().find(|(_, e)| e.id == exam) else {
+                return;
+            };
+            let institution = *institution;
+            let before = t
+                .countdowns
+                .iter()
+                .find(|(e, _)| *e == exam)
+                .map(|(_, days)| days.to_string());
+            t.countdowns.retain(|(e, _)| *e != exam);
+            t.countdowns.push((exam, days));
+            t.audit.push(Entry::new(
+                self.clock.now(),
+                Some(institution),
+                "set_countdown",
+                audit::exam(exam),
+                before,
+                Some(days.to_string()),
+            ));
+        });
+        Ok(())
+    }
+
+    async fn get_dialogue(&self, id: &ChatId) -> sqlx::Result<Option<String>> {
+        self.with(|t| {
+            Ok(t.dialogues
+                .iter()
+                .find(|(chat, _)| chat == id)
+                .map(|(_, state)| state.clone()))
+        })
+    }
+
+    async fn set_dialogue(&self, id: &ChatId, state: &str) -> sqlx::Result<()> {
+        self.with(|t| {
+            let before = t.dialogues.iter().find(|(chat, _)| chat == id);
+            let before = before.map(|(_, state)| state.clone());
+            t.dialogues.retain(|(chat, _)| chat != id);
+            t.dialogues.push((*id, state.into()));
+            t.audit.push(Entry::new(
+                self.clock.now(),
+                None,
+                "set_dialogue",
+                audit::chat(id),
+                before,
+                Some(state.into()),
+            ));
+        });
+        Ok(())
+    }
+
+    async fn remove_dialogue(&self, id: &ChatId) -> sqlx::Result<()> {
+        self.with(|t| {
+            let Some(index) = t.dialogues.iter().position(|(chat, _)| chat == id) else {
+                return;
+            };
+            let (_, before) = t.dialogues.remove(index);
+            let entry = Entry::new(
+                self.clock.now(),
+                None,
+                "remove_dialogue",
+                audit::chat(id),
+                Some(before),
+                None,
+            );
+            t.audit.push(entry);
+        });
+        Ok(())
+    }
+
+    async fn set_pinned(&self, value: &Pinned) -> sqlx::Result<()> {
+        self.with(|t| {
+            let before = t.pinned.iter().find(|p| p.chat_id == value.chat_id);
+            let before = before.map(Pinned::describe);
+            t.pinned.retain(|p| p.chat_id != value.chat_id);
+            t.pinned.push(value.clone());
+            t.audit.push(Entry::new(
+                self.clock.now(),
+                None,
+                "set_pinned",
+                audit::chat(&value.chat_id),
+                before,
+                Some(value.describe()),
+            ));
+        });
+        Ok(())
+    }
+
+    async fn get_pinned(&self, id: &ChatId) -> sqlx::Result<Option<Pinned>> {
+        self.with(|t| Ok(t.pinned.iter().find(|p| p.chat_id == *id).cloned()))
+    }
+
+    async fn get_all_pinned(&self) -> sqlx::Result<Vec<Pinned>> {
+        self.with(|t| Ok(t.pinned.clone()))
+    }
+
+    async fn remove_pinned(&self, id: &ChatId) -> sqlx::Result<()> {
+        self.with(|t| {
+            let Some(index) = t.pinned.iter().position(|p| p.chat_id == *id) else {
+                return;
+            };
+            let before = t.pinned.remove(index);
+            let entry = Entry::new(
+                self.clock.now(),
+                None,
+                "remove_pinned",
+                audit::chat(id),
+                Some(before.describe()),
+                None,
+            );
+            t.audit.push(entry);
+        });
+        Ok(())
+    }
+
+    async fn set_upload(&self, value: &Upload) -> sqlx::Result<()> {
+        self.with(|t| {
+            let before = t.uploads.iter().find(|u| u.chat_id == value.chat_id);
+            let before = before.map(|u| u.name.clone());
+            t.uploads.retain(|u| u.chat_id != value.chat_id);
+            t.uploads.push(value.clone());
+            t.audit.push(Entry::new(
+                self.clock.now(),
+                None,
+                "set_upload",
+                audit::chat(&value.chat_id),
+                before,
+                Some(value.name.clone()),
+            ));
+        });
+        Ok(())
+    }
+
+    async fn get_upload(&self, id: &ChatId) -> sqlx::Result<Option<Upload>> {
+        self.with(|t| Ok(t.uploads.iter().find(|u| u.chat_id == *id).cloned()))
+    }
+
+    async fn remove_upload(&self, id: &ChatId) -> sqlx::Result<()> {
+        self.with(|t| {
+            let Some(index) = t.uploads.iter().position(|u| u.chat_id == *id) else {
+                return;
+            };
+            let before = t.uploads.remove(index);
+            let entry = Entry::new(
+                self.clock.now(),
+                None,
+                "remove_upload",
+                audit::chat(id),
+                Some(before.name),
+                None,
+            );
+            t.audit.push(entry);
+        });
+        Ok(())
+    }
+
+    async fn get_audit(
+        &self,
+        institution: Option<i64>,
+        actions: &[&str],
+        limit: i64,
+    ) -> sqlx::Result<Vec<Entry>> {
+        self.with(|t| {
+            let entries: Vec<Entry> = t
+                .audit
+                .iter()
+                .filter(|e| institution.is_none() || e.institution == institution)
+                .filter(|e| actions.is_empty() || actions.contains(&e.action.as_str()))
+                .cloned()
+                .collect();
+            let skip = entries.len().saturating_sub(limit.try_into().unwrap_or(0));
+            Ok(entries.into_iter().skip(skip).collect())
+        })
+    }
+}
@@ -0,0 +1,686 @@
+use crate::bot::{failed, user, Error, Reply};
+use crate::clock::Clock;
+use crate::data::{Change, Day, Group, Institution, Repeat, Slot, Subject};
+use crate::display;
+use crate::i18n::{Language, Msg};
+use crate::store::ScheduleStore;
+use anyhow::anyhow;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, TimeZone};
+use chrono_tz::Tz;
+use teloxide::types::{ChatId, InlineKeyboardButton, InlineKeyboardMarkup};
+use teloxide::utils::markdown as md;
+
+/// How much of the timetable a message shows.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Span {
+    Day,
+    Week,
+}
+
+/// What a timetable message shows, carried in the callback data of its buttons.
+#[derive(PartialEq, Debug, Clone)]
+pub struct View {
+    pub span: Span,
+    /// Today, as of the moment the view is shown, when omitted.
+    pub date: Option<NaiveDate>,
+    /// Institution and group the timetable is for, the reader's own when omitted.
+    pub group: Option<(i64, Group)>,
+}
+
+/// Prefix that tells navigation callbacks apart from the rest.
+pub const PREFIX: &str = "nav:";
+
+/// Telegram's limit on the size of callback data.
+const MAX_DATA: usize = 64;
+
+impl View {
+    pub fn today(span: Span) -> View {
+        View {
+            span,
+            date: None,
+            group: None,
+        }
+    }
+}
+
+impl TryFrom<&str> for View {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let invalid = || anyhow!("Not a view: {}", value);
+        let mut parts = value
+            .strip_prefix(PREFIX)
+            .ok_or_else(invalid)?
+            .splitn(4, ':');
+
+        let span = match parts.next() {
+            Some("d") => Span::Day,
+            Some("w") => Span::Week,
+            _ => return Err(invalid()),
+        };
+        let date = match parts.next().ok_or_else(invalid)? {
+            "-" => None,
+            date => Some(NaiveDate::parse_from_str(date, "%Y%m%d")?),
+        };
+        // Only dates the buttons could have been made for, so that stepping
+        // from one stays within the calendar
+        if date.is_some_and(|d| !(1..=9999).contains(&d.year())) {
+            return Err(invalid());
+        }
+        let group = match (parts.next(), parts.next()) {
+            (None, None) => None,
+            (Some(institution), Some(group)) => {
+                Some((institution.parse()?, Group::try_from(group)?))
+            }
+            _ => return Err(invalid()),
+        };
+        Ok(View { span, date, group })
+    }
+}
+
+/// Callback data for `value`, leaving the group out if it doesn't fit.
+impl From<&View> for String {
+    fn from(value: &View) -> Self {
+        let span = match value.span {
+            Span::Day => "d",
+            Span::Week => "w",
+        };
+        let date = value
+            .date
+            .map_or("-".into(), |d| d.format("%Y%m%d").to_string());
+        let data = format!("{}{}:{}", PREFIX, span, date);
+        match &value.group {
+            Some((institution, group)) => {
+                let full = format!("{}:{}:{}", data, institution, group);
+                if full.len() <= MAX_DATA {
+                    full
+                } else {
+                    data
+                }
+            }
+            None => data,
+        }
+    }
+}
+
+/// Renders `view` for the reader behind `chat_id`, with the notes of `sender`
+/// and buttons to move on.
+///
+/// `None` when the view names an institution or group that no longer exists.
+pub async fn render<S: ScheduleStore>(
+    store: &S,
+    clock: &dyn Clock,
+    chat_id: ChatId,
+    sender: ChatId,
+    language: Language,
+    view: View,
+) -> Result<Option<Reply>, Error> {
+    let (institution, group) = match view.group {
+        Some((id, group)) => {
+            let institution = match store.get_institution(id).await {
+                Ok(ok) => ok,
+                Err(sqlx::Error::RowNotFound) => return Ok(None),
+                Err(err) => return Err(failed(chat_id, "get institution")(err)),
+            };
+            let groups = store
+                .get_groups(id)
+                .await
+                .map_err(failed(chat_id, "get groups"))?;
+            if !groups.contains(&group) {
+                return Ok(None);
+            }
+            (institution, group)
+        }
+        None => {
+            let (user, institution) = user(store, chat_id).await?;
+            (institution, user.group)
+        }
+    };
+    let reader = reader(store, chat_id, &institution).await?;
+
+    let today = clock
+        .now()
+        .with_timezone(&institution.timezone)
+        .date_naive();
+    let date = view.date.unwrap_or(today);
+    let message = page(
+        store,
+        chat_id,
+        sender,
+        language,
+        &institution,
+        &group,
+        reader,
+        view.span,
+        date,
+    )
+    .await?;
+
+    let group = Some((institution.id, group));
+    Ok(Some(Reply::Page(
+        message,
+        keyboard(language, view.span, date, group),
+    )))
+}
+
+/// Markdown timetable of `group` for the day or week of `date`, with the
+/// notes of `sender`.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn page<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+    sender: ChatId,
+    language: Language,
+    institution: &Institution,
+    group: &Group,
+    reader: Tz,
+    span: Span,
+    date: NaiveDate,
+) -> Result<String, Error> {
+    let mut message = String::new();
+    match span {
+        Span::Day => {
+            message.push_str(&header(&language.day(date, "%d.%m.%Y"), group));
+            message.push('\n');
+            message.push_str(
+                &day(
+                    store,
+                    chat_id,
+                    sender,
+                    language,
+                    institution,
+                    group,
+                    reader,
+                    date,
+                )
+                .await?,
+            );
+        }
+        Span::Week => {
+            // The earliest date there is has no Monday before it
+            let monday = date
+                .checked_sub_signed(Duration::days(date.weekday().num_days_from_monday().into()))
+                .unwrap_or(date);
+            let title = language.tr(Msg::WeekOf(&monday.format("%d.%m.%Y").to_string()));
+            message.push_str(&header(&title, group));
+            let dates =
+                (0..5).map_while(|offset| monday.checked_add_signed(Duration::days(offset)));
+            for date in dates {
+                message.push_str("\n\n");
+                message.push_str(&md::bold(&md::escape(&language.day(date, "%d.%m"))));
+                message.push('\n');
+                message.push_str(
+                    &day(
+                        store,
+                        chat_id,
+                        sender,
+                        language,
+                        institution,
+                        group,
+                        reader,
+                        date,
+                    )
+                    .await?,
+                );
+            }
+        }
+    }
+    Ok(message)
+}
+
+/// Time zone the chat reads class times in, the institution's unless the
+/// user picked another one.
+pub(crate) async fn reader<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+    institution: &Institution,
+) -> Result<Tz, Error> {
+    match store.get_timezone(&chat_id).await {
+        Ok(timezone) => Ok(timezone.unwrap_or(institution.timezone)),
+        Err(sqlx::Error::RowNotFound) => Ok(institution.timezone),
+        Err(err) => Err(failed(chat_id, "get user time zone")(err)),
+    }
+}
+
+/// Bold first line of a timetable message.
+pub(crate) fn header(title: &str, group: &Group) -> String {
+    md::bold(&md::escape(&format!("{} · {}", title, group)))
+}
+
+/// Buttons to step back and forth from `date`, come back to today, or switch the span.
+///
+/// A step past either end of the calendar gets no button.
+fn keyboard(
+    language: Language,
+    span: Span,
+    date: NaiveDate,
+    group: Option<(i64, Group)>,
+) -> InlineKeyboardMarkup {
+    let step = match span {
+        Span::Day => Duration::days(1),
+        Span::Week => Duration::days(7),
+    };
+    let button = |msg, span, date| {
+        let view = View {
+            span,
+            date,
+            group: group.clone(),
+        };
+        InlineKeyboardButton::callback(language.tr(msg), String::from(&view))
+    };
+    let (toggle, other) = match span {
+        Span::Day => (Msg::WeekView, Span::Week),
+        Span::Week => (Msg::DayView, Span::Day),
+    };
+    let steps = [
+        date.checked_sub_signed(step)
+            .map(|prev| button(Msg::Prev, span, Some(prev))),
+        Some(button(Msg::Today, span, None)),
+        date.checked_add_signed(step)
+            .map(|next| button(Msg::Next, span, Some(next))),
+    ];
+    InlineKeyboardMarkup::new([
+        steps.into_iter().flatten().collect(),
+        vec![button(toggle, other, Some(date))],
+    ])
+}
+
+/// Classes of `group` on `date`, one per line.
+#[allow(clippy::too_many_arguments)]
+async fn day<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+    sender: ChatId,
+    language: Language,
+    institution: &Institution,
+    group: &Group,
+    reader: Tz,
+    date: NaiveDate,
+) -> Result<String, Error> {
+    let holiday = store
+        .get_holiday(institution.id, date)
+        .await
+        .map_err(failed(chat_id, "get holiday"))?;
+    if let Some(holiday) = holiday {
+        return Ok(md::escape(
+            &language.tr(Msg::NoClassesBecause(&holiday.title)),
+        ));
+    }
+
+    let mut lines = vec![];
+    for slot in Slot::ALL {
+        for subject in lessons(
+            store,
+            chat_id,
+            sender,
+            institution,
+            group,
+            reader,
+            date,
+            slot,
+        )
+        .await?
+        {
+            lines.push(subject.to_string());
+        }
+    }
+    if lines.is_empty() {
+        return Ok(md::escape(&language.tr(Msg::NoClasses)));
+    }
+    Ok(lines.join("\n"))
+}
+
+/// Noon of `date` where the institution is, to tell the weekday and week by.
+pub(crate) fn noon(date: NaiveDate, timezone: &Tz) -> DateTime<Tz> {
+    timezone
+        .from_local_datetime(&date.and_hms_opt(12, 0, 0).unwrap())
+        .earliest()
+        .unwrap()
+}
+
+/// Classes of `group` in `slot` on `date`, with times as `reader` sees them,
+/// along with the homework `chat_id` sees due at them, whether `sender` has
+/// done it, and the notes of `sender`.
+///
+/// Empty on weekends, holidays are up to the caller.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn lessons<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+    sender: ChatId,
+    institution: &Institution,
+    group: &Group,
+    reader: Tz,
+    date: NaiveDate,
+    slot: Slot,
+) -> Result<Vec<display::Subject>, Error> {
+    let subjects = subjects(store, chat_id, institution, group, date, slot).await?;
+    let time = institution
+        .bells
+        .times_in(slot, date, &institution.timezone, &reader);
+
+    let due = store
+        .get_due_homework(institution.id, group, &chat_id, date, date)
+        .await
+        .map_err(failed(chat_id, "get homework"))?;
+
+    let notes = store
+        .get_notes(&sender, institution.id)
+        .await
+        .map_err(failed(chat_id, "get notes"))?;
+
+    let mut lessons = vec![];
+    for s in subjects {
+        let meetings = store
+            .get_meetings(institution.id, s.id)
+            .await
+            .map_err(failed(chat_id, "get meetings"))?
+            .into_iter()
+            .map(|m| display::Meeting::new(m.name, Some(m.link)))
+            .collect();
+        let room = store
+            .get_room(institution.id, s.id)
+            .await
+            .map_err(failed(chat_id, "get room"))?;
+        let mut homework = vec![];
+        for h in due.iter().filter(|h| h.subject_id == s.id) {
+            let done = store
+                .get_done(h.id)
+                .await
+                .map_err(failed(chat_id, "get done homework"))?;
+            let done = done.contains(&sender);
+            homework.push(display::Homework::new(h.id, h.text.clone(), done));
+        }
+        let note = notes
+            .iter()
+            .find(|n| n.subject_id == s.id)
+            .map(|n| n.text.clone());
+        lessons.push(display::Subject::new(
+            slot, time, s.title, room, meetings, homework, note,
+        ));
+    }
+    Ok(lessons)
+}
+
+/// Subjects `group` has in `slot` on `date`, none on weekends. Changes made
+/// by editors replace the regular classes.
+pub async fn classes<S: ScheduleStore>(
+    store: &S,
+    institution: &Institution,
+    group: &Group,
+    date: NaiveDate,
+    slot: Slot,
+) -> sqlx::Result<Vec<Subject>> {
+    let dt = noon(date, &institution.timezone);
+    let Ok(day) = Day::try_from(&dt) else {
+        return Ok(vec![]);
+    };
+
+    let change = store.get_change(institution.id, group, date, slot).await?;
+    match change {
+        Some(Change {
+            subject_id: Some(id),
+            ..
+        }) => Ok(vec![store.get_subject(institution.id, id).await?]),
+        Some(Change {
+            subject_id: None, ..
+        }) => Ok(vec![]),
+        None => {
+            store
+                .get_subjects(institution.id, day, Repeat::from(&dt), slot, group.clone())
+                .await
+        }
+    }
+}
+
+/// [`classes`] on behalf of `chat_id`.
+pub(crate) async fn subjects<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+    institution: &Institution,
+    group: &Group,
+    date: NaiveDate,
+    slot: Slot,
+) -> Result<Vec<Subject>, Error> {
+    classes(store, institution, group, date, slot)
+        .await
+        .map_err(failed(chat_id, "get subjects"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::TestClock;
+    use crate::data::{Holiday, Schedule, Subject, User};
+    use crate::store::MemoryStore;
+    use chrono::Utc;
+    use teloxide::types::InlineKeyboardButtonKind;
+
+    const CHAT: ChatId = ChatId(42);
+
+    fn k25() -> Group {
+        Group::try_from("K-25").unwrap()
+    }
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2023, 10, day).unwrap()
+    }
+
+    /// K-25 with a class first thing on Mondays, on Tuesday, 10 Oct 2023.
+    async fn setup() -> (MemoryStore, TestClock) {
+        let store = MemoryStore::new();
+        store.add_group(0, &k25()).await.unwrap();
+        store
+            .add_user(
+                &CHAT,
+                &User {
+                    institution: 0,
+                    group: k25(),
+                },
+            )
+            .await
+            .unwrap();
+        store
+            .add_subject(
+                0,
+                &Subject {
+                    id: 0,
+                    title: "Test title".into(),
+                    group: k25(),
+                    optional: false,
+                },
+            )
+            .await
+            .unwrap();
+        store
+            .add_schedule(
+                0,
+                &Schedule {
+                    subject_id: 0,
+                    day: Day::Mon,
+                    repeat: Repeat::Both,
+                    slot: Slot::I,
+                },
+            )
+            .await
+            .unwrap();
+        let clock = TestClock::new(Utc.with_ymd_and_hms(2023, 10, 10, 6, 0, 0).unwrap());
+        (store, clock)
+    }
+
+    fn page(reply: Option<Reply>) -> (String, Vec<String>) {
+        let Some(Reply::Page(text, markup)) = reply else {
+            panic!("Not a page: {:?}", reply);
+        };
+        let data = markup
+            .inline_keyboard
+            .iter()
+            .flatten()
+            .map(|b| match &b.kind {
+                InlineKeyboardButtonKind::CallbackData(data) => data.clone(),
+                other => panic!("Not a callback button: {:?}", other),
+            })
+            .collect();
+        (text, data)
+    }
+
+    #[tokio::test]
+    async fn day_view_moves_between_days() {
+        let (store, clock) = setup().await;
+
+        let (text, buttons) = page(
+            render(
+                &store,
+                &clock,
+                CHAT,
+                CHAT,
+                Language::En,
+                View::today(Span::Day),
+            )
+            .await
+            .unwrap(),
+        );
+        assert!(text.contains("Tue 10\\.10\\.2023 · K\\-25"));
+        assert!(text.contains("No classes"));
+        assert_eq!(
+            buttons,
+            vec![
+                "nav:d:20231009:0:K-25",
+                "nav:d:-:0:K-25",
+                "nav:d:20231011:0:K-25",
+                "nav:w:20231010:0:K-25"
+            ]
+        );
+
+        let view = View::try_from(buttons[0].as_str()).unwrap();
+        let (text, _) = page(
+            render(&store, &clock, CHAT, CHAT, Language::En, view)
+                .await
+                .unwrap(),
+        );
+        assert!(text.contains("08:40–10:15 Test title"));
+    }
+
+    #[tokio::test]
+    async fn week_view_lists_every_day() {
+        let (store, clock) = setup().await;
+        store
+            .add_holiday(
+                0,
+                &Holiday {
+                    date: date(11),
+                    title: "Day off".into(),
+                },
+            )
+            .await
+            .unwrap();
+
+        let (text, buttons) = page(
+            render(
+                &store,
+                &clock,
+                CHAT,
+                CHAT,
+                Language::En,
+                View::today(Span::Week),
+            )
+            .await
+            .unwrap(),
+        );
+        assert!(text.contains("Week of 09\\.10\\.2023"));
+        assert!(text.contains("Mon 09\\.10*\n1️⃣ 08:40–10:15 Test title"));
+        assert!(text.contains("Wed 11\\.10*\nNo classes: Day off\\."));
+        assert!(text.contains("Fri 13\\.10*\nNo classes\\."));
+        assert_eq!(buttons[0], "nav:w:20231003:0:K-25");
+        assert_eq!(buttons[3], "nav:d:20231010:0:K-25");
+    }
+
+    #[tokio::test]
+    async fn steps_stop_at_the_end_of_the_calendar() {
+        let (store, clock) = setup().await;
+        for (span, date) in [(Span::Day, NaiveDate::MAX), (Span::Week, NaiveDate::MIN)] {
+            let view = View {
+                span,
+                date: Some(date),
+                group: None,
+            };
+            let (_, buttons) = page(
+                render(&store, &clock, CHAT, CHAT, Language::En, view)
+                    .await
+                    .unwrap(),
+            );
+            assert_eq!(buttons.len(), 3, "{:?}", buttons);
+        }
+    }
+
+    #[tokio::test]
+    async fn render_drops_unknown_groups() {
+        let (store, clock) = setup().await;
+        for group in [(0, Group::try_from("K-99").unwrap()), (5, k25())] {
+            let view = View {
+                span: Span::Day,
+                date: None,
+                group: Some(group),
+            };
+            assert_eq!(
+                render(&store, &clock, CHAT, CHAT, Language::En, view)
+                    .await
+                    .unwrap(),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn view_roundtrip() {
+        let views = [
+            View::today(Span::Day),
+            View {
+                span: Span::Week,
+                date: NaiveDate::from_ymd_opt(2023, 10, 9),
+                group: Some((3, k25())),
+            },
+        ];
+        for view in views {
+            let data = String::from(&view);
+            assert!(data.starts_with(PREFIX));
+            assert_eq!(View::try_from(data.as_str()).unwrap(), view);
+        }
+        assert_eq!(
+            String::from(&View {
+                span: Span::Day,
+                date: NaiveDate::from_ymd_opt(2023, 10, 9),
+                group: Some((0, k25())),
+            }),
+            "nav:d:20231009:0:K-25"
+        );
+    }
+
+    #[test]
+    fn view_leaves_out_long_groups() {
+        let group = Group::try_from("ДуженДовгаНазваГрупиФакультету-1").unwrap();
+        let view = View {
+            span: Span::Day,
+            date: NaiveDate::from_ymd_opt(2023, 10, 9),
+            group: Some((0, group)),
+        };
+        assert_eq!(String::from(&view), "nav:d:20231009");
+    }
+
+    #[test]
+    fn view_rejects_tampered_data() {
+        for data in [
+            "d:20231009",
+            "nav:x:20231009",
+            "nav:d:20231350",
+            "nav:d:20231009:0",
+            "nav:d:20231009:zero:K-25",
+            "nav:d:20231009:0:K 25",
+            "nav:d:-2621430101",
+            "nav:w:+2621421231",
+            "nav:d:00001231",
+        ] {
+            assert!(View::try_from(data).is_err(), "{}", data);
+        }
+    }
+}
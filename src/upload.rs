@@ -0,0 +1,311 @@
+use crate::bot::{failed, Error, Reply};
+use crate::data::{Institution, Subject};
+use crate::editor;
+use crate::i18n::{Language, Msg};
+use crate::import::{self, Import};
+use crate::store::{ScheduleStore, Upload};
+use teloxide::types::{ChatId, InlineKeyboardButton, InlineKeyboardMarkup};
+
+/// Callback data prefix of the buttons under a preview.
+pub const PREFIX: &str = "up:";
+
+/// Largest file taken, in bytes.
+pub const MAX_SIZE: u32 = 1 << 20;
+
+/// How many changes a preview lists before cutting them short.
+const PREVIEW_LENGTH: usize = 30;
+
+/// Reads timetable file `name` that `sender` sent to `chat_id`, and shows
+/// what importing it would change, keeping it until they apply or cancel it.
+pub async fn preview<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+    sender: ChatId,
+    language: Language,
+    name: &str,
+    text: &str,
+) -> Result<Reply, Error> {
+    let (import, institution, subjects) = match read(store, chat_id, sender, name, text).await? {
+        Ok(read) => read,
+        Err(problems) => {
+            let problems = list(language, problems);
+            let reply = language.tr(Msg::CantImport {
+                name,
+                problems: &problems,
+            });
+            return Ok(Reply::Text(reply));
+        }
+    };
+    let schedule = store
+        .get_schedule(institution.id)
+        .await
+        .map_err(failed(chat_id, "get schedule"))?;
+    let exams = store
+        .get_exams(institution.id)
+        .await
+        .map_err(failed(chat_id, "get exams"))?;
+    let changes = import::diff(&import, &subjects, &schedule, &exams);
+    if changes.is_empty() {
+        return Ok(Reply::Text(language.tr(Msg::NothingToImport(name))));
+    }
+
+    let upload = Upload {
+        chat_id,
+        name: name.into(),
+        text: text.into(),
+    };
+    store
+        .set_upload(&upload)
+        .await
+        .map_err(failed(chat_id, "set upload"))?;
+    let changes = list(language, changes);
+    let text = language.tr(Msg::ImportPreview {
+        name,
+        changes: &changes,
+    });
+    let keyboard = InlineKeyboardMarkup::new([[
+        InlineKeyboardButton::callback(language.tr(Msg::ApplyImport), format!("{}apply", PREFIX)),
+        InlineKeyboardButton::callback(language.tr(Msg::CancelImport), format!("{}cancel", PREFIX)),
+    ]]);
+    Ok(Reply::Menu(text, keyboard))
+}
+
+/// Imports the file kept for `chat_id` when `data` is the apply button, or
+/// forgets it otherwise.
+///
+/// The file is read anew, so it is checked against the timetable and roles as
+/// they are now rather than when it was previewed.
+pub async fn apply<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+    sender: ChatId,
+    language: Language,
+    data: &str,
+) -> Result<Reply, Error> {
+    let upload = store
+        .get_upload(&chat_id)
+        .await
+        .map_err(failed(chat_id, "get upload"))?;
+    let Some(Upload { name, text, .. }) = upload else {
+        return Ok(Reply::Text(language.tr(Msg::UploadExpired)));
+    };
+    let reply = match data.strip_prefix(PREFIX) {
+        Some("apply") => match read(store, chat_id, sender, &name, &text).await? {
+            Ok((import, institution, _)) => {
+                store
+                    .import(institution.id, &import)
+                    .await
+                    .map_err(failed(chat_id, "import"))?;
+                log::trace!("Imported {} into {}", &name, institution.code);
+                language.tr(Msg::Imported {
+                    name: &name,
+                    subjects: &import.subjects.len().to_string(),
+                    classes: &import.schedule.len().to_string(),
+                    exams: &import.exams.len().to_string(),
+                })
+            }
+            Err(problems) => {
+                let problems = list(language, problems);
+                language.tr(Msg::CantImport {
+                    name: &name,
+                    problems: &problems,
+                })
+            }
+        },
+        _ => language.tr(Msg::ImportCancelled(&name)),
+    };
+    store
+        .remove_upload(&chat_id)
+        .await
+        .map_err(failed(chat_id, "remove upload"))?;
+    Ok(Reply::Text(reply))
+}
+
+/// The import in file `name` along with the institution and its subjects, as
+/// long as `sender` may edit every group it touches, or else what is wrong
+/// with the file.
+async fn read<S: ScheduleStore>(
+    store: &S,
+    chat_id: ChatId,
+    sender: ChatId,
+    name: &str,
+    text: &str,
+) -> Result<Result<(Import, Institution, Vec<Subject>), Vec<String>>, Error> {
+    let (user, institution) = editor::editable(store, chat_id, sender).await?;
+    let subjects = store
+        .get_all_subjects(institution.id)
+        .await
+        .map_err(failed(chat_id, "get subjects"))?;
+    let import = match import::read(name, text, &institution, &user.group, &subjects) {
+        Ok(import) => import,
+        Err(problems) => return Ok(Err(problems)),
+    };
+
+    // Subjects moved to another group count for both of them
+    let (scheduled, examined) = (import.scheduled(), import.examined());
+    let replaced = subjects.iter().filter(|s| {
+        scheduled.contains(&s.id)
+            || examined.contains(&s.id)
+            || import.subjects.iter().any(|i| i.id == s.id)
+    });
+    let grants = store
+        .get_grants(&sender)
+        .await
+        .map_err(failed(chat_id, "get grants"))?;
+    let covered = import
+        .subjects
+        .iter()
+        .chain(replaced)
+        .all(|s| grants.iter().any(|g| g.covers(institution.id, &s.group)));
+    if !covered {
+        return Err(Error::NotEditor(chat_id));
+    }
+    Ok(Ok((import, institution, subjects)))
+}
+
+/// `lines` one per line, cut short to fit a message.
+fn list(language: Language, mut lines: Vec<String>) -> String {
+    if lines.len() > PREVIEW_LENGTH {
+        let more = lines.len() - PREVIEW_LENGTH;
+        lines.truncate(PREVIEW_LENGTH);
+        lines.push(language.tr(Msg::AndMore(&more.to_string())));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Day, Grant, Group, Repeat, Role, Schedule, Slot, User};
+    use crate::store::MemoryStore;
+
+    const HEADMAN: ChatId = ChatId(7);
+    const STUDENT: ChatId = ChatId(8);
+
+    fn k25() -> Group {
+        Group::try_from("K-25").unwrap()
+    }
+
+    /// K-25 with Algebra on Mondays, a headman who may edit it and a student
+    /// who may not, both in K-25.
+    async fn store() -> MemoryStore {
+        let store = MemoryStore::new();
+        store.add_group(0, &k25()).await.unwrap();
+        let algebra = Subject {
+            id: 0,
+            title: "Algebra".into(),
+            group: k25(),
+            optional: false,
+        };
+        store.add_subject(0, &algebra).await.unwrap();
+        let class = Schedule {
+            subject_id: 0,
+            day: Day::Mon,
+            repeat: Repeat::Both,
+            slot: Slot::I,
+        };
+        store.add_schedule(0, &class).await.unwrap();
+        let user = User {
+            institution: 0,
+            group: k25(),
+        };
+        for id in [HEADMAN, STUDENT] {
+            store.add_user(&id, &user).await.unwrap();
+        }
+        let grant = Grant {
+            institution: 0,
+            group: Some(k25()),
+            role: Role::Editor,
+        };
+        store.add_grant(&HEADMAN, &grant).await.unwrap();
+        store
+    }
+
+    async fn preview(store: &MemoryStore, sender: ChatId, text: &str) -> Result<Reply, Error> {
+        super::preview(store, sender, sender, Language::En, "k25.csv", text).await
+    }
+
+    fn text(reply: Reply) -> String {
+        match reply {
+            Reply::Text(text) | Reply::Menu(text, _) => text,
+            other => panic!("Unexpected reply {:?}", other),
+        }
+    }
+
+    const CSV: &str = "id,title,group,day,slot\n0,Algebra,K-25,Tue,2\n1,Logic,K-25,Wed,3\n";
+
+    #[tokio::test]
+    async fn preview_then_apply() {
+        let store = store().await;
+        let reply = preview(&store, HEADMAN, CSV).await.unwrap();
+        assert!(matches!(reply, Reply::Menu(..)));
+        assert_eq!(
+            text(reply),
+            "Changes from k25.csv:\n\
+             + 1 Logic (K-25)\n\
+             − Algebra: Mon slot 1, Both\n\
+             + Algebra: Tue slot 2, Both\n\
+             + Logic: Wed slot 3, Both\n\n\
+             Apply them?"
+        );
+        assert_eq!(store.get_schedule(0).await.unwrap().len(), 1);
+
+        let reply = apply(&store, HEADMAN, HEADMAN, Language::En, "up:apply").await;
+        assert_eq!(
+            text(reply.unwrap()),
+            "Imported k25.csv: 2 subjects, 2 classes, 0 exams."
+        );
+        assert_eq!(store.get_all_subjects(0).await.unwrap().len(), 2);
+        assert_eq!(store.get_schedule(0).await.unwrap().len(), 2);
+
+        let reply = apply(&store, HEADMAN, HEADMAN, Language::En, "up:apply").await;
+        assert_eq!(
+            text(reply.unwrap()),
+            "This upload has expired, please send the file again."
+        );
+        let reply = preview(&store, HEADMAN, CSV).await.unwrap();
+        assert_eq!(
+            text(reply),
+            "k25.csv matches the timetable, there is nothing to change."
+        );
+    }
+
+    #[tokio::test]
+    async fn only_editors_of_every_group() {
+        let store = store().await;
+        assert!(matches!(
+            preview(&store, STUDENT, CSV).await,
+            Err(Error::NotEditor(_))
+        ));
+
+        let other = "id,title,group,day,slot\n5,Drawing,K-26,Mon,1\n";
+        assert!(matches!(
+            preview(&store, HEADMAN, other).await,
+            Err(Error::NotEditor(_))
+        ));
+
+        let broken = "id,title,group,day,slot\n1,Logic,K-25,Sun,1\n";
+        assert_eq!(
+            text(preview(&store, HEADMAN, broken).await.unwrap()),
+            "Can't import k25.csv:\nLine 2: Not a day: Sun"
+        );
+    }
+
+    #[tokio::test]
+    async fn cancel_forgets_the_file() {
+        let store = store().await;
+        preview(&store, HEADMAN, CSV).await.unwrap();
+        let reply = apply(&store, HEADMAN, HEADMAN, Language::En, "up:cancel").await;
+        assert_eq!(text(reply.unwrap()), "Didn't import k25.csv.");
+        assert_eq!(store.get_upload(&HEADMAN).await.unwrap(), None);
+        assert_eq!(store.get_schedule(0).await.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn long_lists_are_cut() {
+        let lines = (0..40).map(|i| i.to_string()).collect();
+        let list = list(Language::En, lines);
+        assert_eq!(list.lines().count(), PREVIEW_LENGTH + 1);
+        assert!(list.ends_with("…and 10 more"));
+    }
+}
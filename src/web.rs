@@ -0,0 +1,433 @@
+use crate::api::{self, Api, Failure};
+use crate::calendar;
+use crate::data::{Group, Institution};
+use crate::i18n::{Language, Msg};
+use crate::store::ScheduleStore;
+use axum::extract::{Path, Query, State};
+use axum::http::header::{ACCEPT_LANGUAGE, CONTENT_TYPE};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use chrono::{Duration, NaiveDate};
+use serde::Deserialize;
+use std::sync::Arc;
+
+/// Fits phones and prints without the links.
+const STYLE: &str = "\
+body { font-family: sans-serif; max-width: 40em; margin: 0 auto; padding: 1em; }
+h1 { margin-bottom: 0; }
+nav a { margin-right: 1em; }
+section { padding: 0.25em 0.75em; margin: 0.5em 0; border-left: 4px solid transparent; }
+section.today { border-color: #2a7ae2; background: #eef4fd; }
+h2 { font-size: 1.1em; margin: 0.5em 0 0.25em; }
+ul { list-style: none; padding: 0; margin: 0; }
+li { margin: 0.2em 0; }
+.time { font-variant-numeric: tabular-nums; color: #555; margin-right: 0.5em; }
+.room { color: #555; }
+@media print {
+  nav, footer { display: none; }
+  section.today { background: none; }
+}";
+
+/// `?in=`, the code of the institution when a group name isn't enough.
+#[derive(Deserialize)]
+pub struct In {
+    #[serde(rename = "in")]
+    pub institution: Option<String>,
+}
+
+/// A [`Failure`] shown as plain text to whoever opened the page.
+pub struct Error(Failure);
+
+impl<E: Into<Failure>> From<E> for Error {
+    fn from(err: E) -> Self {
+        Error(err.into())
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        match self.0 {
+            Failure::NotFound(message) => (StatusCode::NOT_FOUND, message).into_response(),
+            Failure::BadRequest(message) => (StatusCode::BAD_REQUEST, message).into_response(),
+            Failure::Internal => {
+                (StatusCode::INTERNAL_SERVER_ERROR, "Something went wrong").into_response()
+            }
+        }
+    }
+}
+
+/// Routes of the timetable pages, all under `/g`.
+pub fn router<S: ScheduleStore>(api: Arc<Api<S>>) -> Router {
+    Router::new()
+        .route("/g/:group", get(this_week::<S>))
+        .route("/g/:group/week/:which", get(other_week::<S>))
+        .route("/g/:group/calendar.ics", get(subscribe::<S>))
+        .with_state(api)
+}
+
+/// The group named `name` in the institution of `code`, or in whichever
+/// institution has it when there's one.
+async fn find<S: ScheduleStore>(
+    store: &S,
+    name: &str,
+    code: Option<&str>,
+) -> Result<(Institution, Group), Failure> {
+    if let Some(code) = code {
+        return api::group(store, code, name).await;
+    }
+    let unknown = || Failure::NotFound(format!("Unknown group: {}", name));
+    let group = Group::try_from(name).map_err(|_| unknown())?;
+    let mut found = vec![];
+    for institution in store.get_institutions().await? {
+        if store.get_groups(institution.id).await?.contains(&group) {
+            found.push(institution);
+        }
+    }
+    match found.len() {
+        0 => Err(unknown()),
+        1 => Ok((found.remove(0), group)),
+        _ => {
+            let codes: Vec<&str> = found.iter().map(|i| i.code.as_str()).collect();
+            Err(Failure::BadRequest(format!(
+                "{} exists in several institutions, pick one with ?in={}",
+                name,
+                codes.join("|")
+            )))
+        }
+    }
+}
+
+/// Language of the first tag of `Accept-Language`, e.g. `uk-UA,uk;q=0.9`.
+fn language(headers: &HeaderMap) -> Language {
+    let tag = headers
+        .get(ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split([',', ';']).next())
+        .map(str::trim);
+    Language::from_telegram(tag)
+}
+
+async fn this_week<S: ScheduleStore>(
+    State(api): State<Arc<Api<S>>>,
+    Path(name): Path<String>,
+    Query(at): Query<In>,
+    headers: HeaderMap,
+) -> Result<Html<String>, Error> {
+    page(&api, &name, at, language(&headers), None).await
+}
+
+/// `next`, `previous` or the week of a date, as the API takes it.
+async fn other_week<S: ScheduleStore>(
+    State(api): State<Arc<Api<S>>>,
+    Path((name, which)): Path<(String, String)>,
+    Query(at): Query<In>,
+    headers: HeaderMap,
+) -> Result<Html<String>, Error> {
+    page(&api, &name, at, language(&headers), Some(&which)).await
+}
+
+async fn page<S: ScheduleStore>(
+    api: &Api<S>,
+    name: &str,
+    at: In,
+    language: Language,
+    which: Option<&str>,
+) -> Result<Html<String>, Error> {
+    let (institution, group) = find(&api.store, name, at.institution.as_deref()).await?;
+    let today = api::today(api.clock.as_ref(), &institution);
+    let value = match which {
+        Some("next") => Some("+7"),
+        Some("previous") => Some("-7"),
+        other => other,
+    };
+    let date = api::resolve(value, api.clock.as_ref(), &institution)?;
+    let week = api::week(&api.store, &institution, &group, date).await?;
+    let links = Links::new(&group, at.institution.as_deref());
+    Ok(Html(render(
+        language,
+        &group,
+        api::monday(date),
+        &week,
+        today,
+        &links,
+    )))
+}
+
+async fn subscribe<S: ScheduleStore>(
+    State(api): State<Arc<Api<S>>>,
+    Path(name): Path<String>,
+    Query(at): Query<In>,
+) -> Result<Response, Error> {
+    let (institution, group) = find(&api.store, &name, at.institution.as_deref()).await?;
+    let text = calendar::of(&api.store, &institution, &group, api.clock.now()).await?;
+    Ok(([(CONTENT_TYPE, "text/calendar; charset=utf-8")], text).into_response())
+}
+
+/// Where the links of a page lead, keeping `?in=` when it was given.
+struct Links {
+    base: String,
+    query: String,
+}
+
+impl Links {
+    fn new(group: &Group, code: Option<&str>) -> Links {
+        let encode = |value: &str| -> String {
+            url::form_urlencoded::byte_serialize(value.as_bytes()).collect()
+        };
+        Links {
+            base: format!("/g/{}", encode(group.as_str())),
+            query: code.map_or(String::new(), |code| format!("?in={}", encode(code))),
+        }
+    }
+
+    fn today(&self) -> String {
+        format!("{}{}", self.base, self.query)
+    }
+
+    fn week(&self, monday: NaiveDate) -> String {
+        format!("{}/week/{}{}", self.base, monday, self.query)
+    }
+
+    fn calendar(&self) -> String {
+        format!("{}/calendar.ics{}", self.base, self.query)
+    }
+}
+
+/// `value` safe to put in HTML text and attributes.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The page of `week`, from `monday` on, with `today` highlighted.
+fn render(
+    language: Language,
+    group: &Group,
+    monday: NaiveDate,
+    week: &api::Week,
+    today: NaiveDate,
+    links: &Links,
+) -> String {
+    let parity = match week.week {
+        "odd" => Msg::OddWeek,
+        _ => Msg::EvenWeek,
+    };
+    let title = format!(
+        "{} · {}",
+        language.tr(Msg::WeekOf(&monday.format("%d.%m.%Y").to_string())),
+        language.tr(parity)
+    );
+
+    let mut days = String::new();
+    for (offset, day) in (0..).zip(&week.days) {
+        let Some(date) = monday.checked_add_signed(Duration::days(offset)) else {
+            break;
+        };
+        let class = if date == today {
+            " class=\"today\""
+        } else {
+            ""
+        };
+        days.push_str(&format!(
+            "<section{}>\n<h2>{}</h2>\n",
+            class,
+            escape(&language.day(date, "%d.%m"))
+        ));
+        if let Some(holiday) = &day.holiday {
+            let text = language.tr(Msg::NoClassesBecause(holiday));
+            days.push_str(&format!("<p>{}</p>\n", escape(&text)));
+        } else if day.classes.is_empty() {
+            days.push_str(&format!(
+                "<p>{}</p>\n",
+                escape(&language.tr(Msg::NoClasses))
+            ));
+        } else {
+            days.push_str("<ul>\n");
+            for class in &day.classes {
+                for subject in &class.subjects {
+                    let room = subject.room.as_ref().map_or(String::new(), |room| {
+                        format!(" <span class=\"room\">· {}</span>", escape(room))
+                    });
+                    days.push_str(&format!(
+                        "<li><span class=\"time\">{}–{}</span>{}{}</li>\n",
+                        class.starts,
+                        class.ends,
+                        escape(&subject.title),
+                        room
+                    ));
+                }
+            }
+            days.push_str("</ul>\n");
+        }
+        days.push_str("</section>\n");
+    }
+
+    // Weeks the API would turn down get no link
+    let step = Duration::days(7);
+    let link = |monday: Option<NaiveDate>, msg| {
+        monday
+            .filter(|monday| api::within_reach(*monday, today))
+            .map_or(String::new(), |monday| {
+                format!(
+                    "<a href=\"{}\">{}</a>",
+                    escape(&links.week(monday)),
+                    escape(&language.tr(msg))
+                )
+            })
+    };
+    format!(
+        "<!DOCTYPE html>
+<html lang=\"{lang}\">
+<head>
+<meta charset=\"utf-8\">
+<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">
+<title>{group} · {title}</title>
+<style>
+{style}
+</style>
+</head>
+<body>
+<h1>{group}</h1>
+<p>{title}</p>
+<nav>{prev}<a href=\"{today}\">{today_text}</a>{next}</nav>
+{days}<footer><p><a href=\"{calendar}\">{subscribe}</a></p></footer>
+</body>
+</html>
+",
+        lang = language.code(),
+        group = escape(group.as_str()),
+        title = escape(&title),
+        style = STYLE,
+        prev = link(monday.checked_sub_signed(step), Msg::Prev),
+        today = escape(&links.today()),
+        today_text = escape(&language.tr(Msg::Today)),
+        next = link(monday.checked_add_signed(step), Msg::Next),
+        days = days,
+        calendar = escape(&links.calendar()),
+        subscribe = escape(&language.tr(Msg::Subscribe)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::TestClock;
+    use crate::data::{Day, Holiday, Repeat, Schedule, Slot, Subject};
+    use crate::store::MemoryStore;
+    use axum::body::Body;
+    use axum::http::Request;
+    use chrono::{TimeZone, Utc};
+    use tower::ServiceExt;
+
+    /// K-25 with Algebra first thing on Mondays of odd weeks and <Logic> in
+    /// room 204 on Wednesdays, a holiday on 23 Oct, on Monday, 9 Oct 2023.
+    async fn setup() -> Router {
+        let store = MemoryStore::new();
+        let group = Group::try_from("K-25").unwrap();
+        store.add_group(0, &group).await.unwrap();
+        let classes = [
+            (0, "Algebra", Day::Mon, Repeat::Odd),
+            (1, "<Logic>", Day::Wed, Repeat::Both),
+        ];
+        for (id, title, day, repeat) in classes {
+            let subject = Subject {
+                id,
+                title: title.into(),
+                group: group.clone(),
+                optional: false,
+            };
+            store.add_subject(0, &subject).await.unwrap();
+            let class = Schedule {
+                subject_id: id,
+                day,
+                repeat,
+                slot: Slot::I,
+            };
+            store.add_schedule(0, &class).await.unwrap();
+        }
+        store.set_room(0, 1, "204").await.unwrap();
+        let holiday = Holiday {
+            date: NaiveDate::from_ymd_opt(2023, 10, 23).unwrap(),
+            title: "Day off".into(),
+        };
+        store.add_holiday(0, &holiday).await.unwrap();
+        let clock = TestClock::new(Utc.with_ymd_and_hms(2023, 10, 9, 6, 0, 0).unwrap());
+        router(Arc::new(Api {
+            store,
+            clock: Arc::new(clock),
+        }))
+    }
+
+    async fn fetch(router: &Router, uri: &str, language: &str) -> (StatusCode, String) {
+        let request = Request::get(uri)
+            .header(ACCEPT_LANGUAGE, language)
+            .body(Body::empty())
+            .unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn weeks_of_a_group() {
+        let router = setup().await;
+        let (status, page) = fetch(&router, "/g/K-25", "en-US,en;q=0.9").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(page.contains("<p>Week of 09.10.2023 · odd week</p>"));
+        assert!(page.contains("<section class=\"today\">\n<h2>Mon 09.10</h2>"));
+        assert!(page.contains("<li><span class=\"time\">08:40–10:15</span>Algebra</li>"));
+        assert!(page.contains("&lt;Logic&gt; <span class=\"room\">· 204</span>"));
+        assert!(page.contains("<a href=\"/g/K-25/week/2023-10-16\">"));
+
+        let (_, page) = fetch(&router, "/g/K-25/week/next?in=default", "uk").await;
+        assert!(page.contains("<p>Тиждень з 16.10.2023 · парний тиждень</p>"));
+        assert!(!page.contains("Algebra"));
+        assert!(!page.contains("class=\"today\""));
+        assert!(page.contains("<a href=\"/g/K-25/week/2023-10-23?in=default\">"));
+
+        let (_, page) = fetch(&router, "/g/K-25/week/2023-10-25", "en").await;
+        assert!(page.contains("<p>No classes: Day off.</p>"));
+
+        // A hundred years on is as far as it goes
+        let (status, page) = fetch(&router, "/g/K-25/week/2123-10-08", "en").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(page.contains("<a href=\"/g/K-25/week/2123-09-27\">"));
+        assert!(!page.contains("/g/K-25/week/2123-10-11"));
+
+        let (status, calendar) = fetch(&router, "/g/K-25/calendar.ics", "en").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(calendar.starts_with("BEGIN:VCALENDAR"));
+        assert!(calendar.contains("SUMMARY:Algebra"));
+    }
+
+    #[tokio::test]
+    async fn unknown_groups_are_not_found() {
+        let router = setup().await;
+        for uri in [
+            "/g/K-99",
+            "/g/K-25?in=nowhere",
+            "/g/K-25/week/someday",
+            "/g/K-25/week/+200000000000000",
+            "/g/K-25/week/-262143-01-01",
+        ] {
+            let (status, _) = fetch(&router, uri, "en").await;
+            assert_ne!(status, StatusCode::OK, "{}", uri);
+        }
+        let (status, message) = fetch(&router, "/g/K-99", "en").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(message, "Unknown group: K-99");
+    }
+}